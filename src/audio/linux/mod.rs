@@ -0,0 +1,156 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use pipewire as pw;
+use pw::{
+    core::Core,
+    main_loop::MainLoop,
+    metadata::{Metadata, MetadataListener},
+    types::ObjectType,
+};
+
+use super::{AudioDevice, AudioDeviceKind};
+
+// Runs the loop until the server has processed everything sent so far
+fn roundtrip(mainloop: &MainLoop, core: &Core) -> Result<(), pw::Error> {
+    let done = Rc::new(Cell::new(false));
+    let pending = core.sync(0)?;
+
+    let _listener = core
+        .add_listener_local()
+        .done({
+            let done = done.clone();
+            let mainloop = mainloop.clone();
+            move |id, seq| {
+                if id == pw::core::PW_ID_CORE && seq == pending {
+                    done.set(true);
+                    mainloop.quit();
+                }
+            }
+        })
+        .register();
+
+    while !done.get() {
+        mainloop.run();
+    }
+    Ok(())
+}
+
+// The node name in a default metadata value like {"name":"alsa_output.pci"}
+fn get_default_name(value: &str) -> Option<&str> {
+    let value = value.split_once("\"name\"")?.1;
+    let value = value.trim_start().strip_prefix(':')?.trim_start();
+    value.strip_prefix('"')?.split('"').next()
+}
+
+fn get_audio_devices() -> Result<Vec<AudioDevice>, pw::Error> {
+    pw::init();
+
+    let mainloop = MainLoop::new(None)?;
+    let context = pw::context::Context::new(&mainloop)?;
+    let core = context.connect(None)?;
+    let registry = Rc::new(core.get_registry()?);
+
+    let devices = Rc::new(RefCell::new(Vec::new()));
+    let defaults = Rc::new(RefCell::new(Vec::<String>::new()));
+    let metadata = Rc::new(RefCell::new(Vec::<(Metadata, MetadataListener)>::new()));
+
+    let _listener = registry
+        .add_listener_local()
+        .global({
+            let (registry, devices) = (registry.clone(), devices.clone());
+            let (defaults, metadata) = (defaults.clone(), metadata.clone());
+            move |global| {
+                let Some(props) = global.props else {
+                    return;
+                };
+
+                match global.type_ {
+                    ObjectType::Node => {
+                        let kind = match props.get(*pw::keys::MEDIA_CLASS) {
+                            Some("Audio/Sink") => AudioDeviceKind::Output,
+                            Some("Audio/Source" | "Audio/Source/Virtual") => AudioDeviceKind::Input,
+                            _ => return,
+                        };
+                        let Some(id) = props.get(*pw::keys::NODE_NAME) else {
+                            return;
+                        };
+                        let name = props
+                            .get(*pw::keys::NODE_DESCRIPTION)
+                            .or_else(|| props.get(*pw::keys::NODE_NICK))
+                            .unwrap_or(id);
+
+                        devices.borrow_mut().push(AudioDevice {
+                            id: id.to_string(),
+                            name: name.to_string(),
+                            kind,
+                            is_default: false,
+                        });
+                    }
+                    // The session manager publishes the default devices here
+                    ObjectType::Metadata if props.get("metadata.name") == Some("default") => {
+                        let Ok(proxy) = registry.bind::<Metadata, _>(global) else {
+                            return;
+                        };
+                        let listener = proxy
+                            .add_listener_local()
+                            .property({
+                                let defaults = defaults.clone();
+                                move |_, key, _, value| {
+                                    if let (
+                                        Some("default.audio.sink" | "default.audio.source"),
+                                        Some(value),
+                                    ) = (key, value)
+                                    {
+                                        if let Some(name) = get_default_name(value) {
+                                            defaults.borrow_mut().push(name.to_string());
+                                        }
+                                    }
+                                    0
+                                }
+                            })
+                            .register();
+                        metadata.borrow_mut().push((proxy, listener));
+                    }
+                    _ => {}
+                }
+            }
+        })
+        .register();
+
+    // The first round trip lists the globals, the second delivers the
+    // properties of the metadata bound during the first
+    roundtrip(&mainloop, &core)?;
+    roundtrip(&mainloop, &core)?;
+
+    let defaults = defaults.borrow();
+    let mut devices = devices.take();
+    for device in &mut devices {
+        device.is_default = defaults.contains(&device.id);
+    }
+    Ok(devices)
+}
+
+pub fn audio_devices() -> Vec<AudioDevice> {
+    get_audio_devices().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_name() {
+        assert_eq!(
+            get_default_name(r#"{"name":"alsa_output.pci-0000_00_1f.3.analog-stereo"}"#),
+            Some("alsa_output.pci-0000_00_1f.3.analog-stereo")
+        );
+        assert_eq!(
+            get_default_name(r#"{ "name": "bluez_input.00:1B:66" }"#),
+            Some("bluez_input.00:1B:66")
+        );
+        assert_eq!(get_default_name("{}"), None);
+    }
+}
@@ -0,0 +1,183 @@
+#![allow(non_upper_case_globals)]
+
+use std::{
+    ffi::{c_void, CStr},
+    mem,
+    os::raw::c_char,
+    ptr,
+};
+
+use objc::{msg_send, runtime::Object, sel, sel_impl};
+
+use super::{AudioDevice, AudioDeviceKind};
+
+type AudioObjectID = u32;
+type OSStatus = i32;
+
+#[repr(C)]
+struct AudioObjectPropertyAddress {
+    selector: u32,
+    scope: u32,
+    element: u32,
+}
+
+#[link(name = "CoreAudio", kind = "framework")]
+extern "C" {
+    fn AudioObjectGetPropertyDataSize(
+        object: AudioObjectID,
+        address: *const AudioObjectPropertyAddress,
+        qualifier_size: u32,
+        qualifier: *const c_void,
+        size: *mut u32,
+    ) -> OSStatus;
+    fn AudioObjectGetPropertyData(
+        object: AudioObjectID,
+        address: *const AudioObjectPropertyAddress,
+        qualifier_size: u32,
+        qualifier: *const c_void,
+        size: *mut u32,
+        data: *mut c_void,
+    ) -> OSStatus;
+}
+
+extern "C" {
+    fn CFRelease(cf: *const c_void);
+}
+
+const kAudioObjectSystemObject: AudioObjectID = 1;
+const kAudioObjectPropertyElementMain: u32 = 0;
+const kAudioObjectPropertyScopeGlobal: u32 = u32::from_be_bytes(*b"glob");
+const kAudioObjectPropertyScopeInput: u32 = u32::from_be_bytes(*b"inpt");
+const kAudioObjectPropertyScopeOutput: u32 = u32::from_be_bytes(*b"outp");
+const kAudioHardwarePropertyDevices: u32 = u32::from_be_bytes(*b"dev#");
+const kAudioHardwarePropertyDefaultInputDevice: u32 = u32::from_be_bytes(*b"dIn ");
+const kAudioHardwarePropertyDefaultOutputDevice: u32 = u32::from_be_bytes(*b"dOut");
+const kAudioObjectPropertyName: u32 = u32::from_be_bytes(*b"lnam");
+const kAudioDevicePropertyDeviceUID: u32 = u32::from_be_bytes(*b"uid ");
+const kAudioDevicePropertyStreams: u32 = u32::from_be_bytes(*b"stm#");
+
+fn address(selector: u32, scope: u32) -> AudioObjectPropertyAddress {
+    AudioObjectPropertyAddress {
+        selector,
+        scope,
+        element: kAudioObjectPropertyElementMain,
+    }
+}
+
+fn get_property_size(object: AudioObjectID, selector: u32, scope: u32) -> Option<usize> {
+    let mut size = 0;
+    let status = unsafe {
+        AudioObjectGetPropertyDataSize(object, &address(selector, scope), 0, ptr::null(), &mut size)
+    };
+    (status == 0).then_some(size as usize)
+}
+
+// A property holding an array of plain values
+fn get_property<T: Copy + Default>(object: AudioObjectID, selector: u32, scope: u32) -> Vec<T> {
+    let Some(size) = get_property_size(object, selector, scope) else {
+        return vec![];
+    };
+
+    let mut values = vec![T::default(); size / mem::size_of::<T>()];
+    let mut size = (values.len() * mem::size_of::<T>()) as u32;
+    let status = unsafe {
+        AudioObjectGetPropertyData(
+            object,
+            &address(selector, scope),
+            0,
+            ptr::null(),
+            &mut size,
+            values.as_mut_ptr() as *mut c_void,
+        )
+    };
+    if status != 0 {
+        return vec![];
+    }
+
+    values.truncate(size as usize / mem::size_of::<T>());
+    values
+}
+
+// A CFString property, which the caller owns
+fn get_string_property(object: AudioObjectID, selector: u32) -> Option<String> {
+    let mut string: *const c_void = ptr::null();
+    let mut size = mem::size_of::<*const c_void>() as u32;
+    let status = unsafe {
+        AudioObjectGetPropertyData(
+            object,
+            &address(selector, kAudioObjectPropertyScopeGlobal),
+            0,
+            ptr::null(),
+            &mut size,
+            &mut string as *mut _ as *mut c_void,
+        )
+    };
+    if status != 0 || string.is_null() {
+        return None;
+    }
+
+    // CFString is toll-free bridged to NSString
+    unsafe {
+        let utf8: *const c_char = msg_send![string as *mut Object, UTF8String];
+        let result = (!utf8.is_null()).then(|| CStr::from_ptr(utf8).to_string_lossy().into_owned());
+        CFRelease(string);
+        result
+    }
+}
+
+pub fn audio_devices() -> Vec<AudioDevice> {
+    let defaults = [
+        (
+            AudioDeviceKind::Output,
+            kAudioHardwarePropertyDefaultOutputDevice,
+        ),
+        (
+            AudioDeviceKind::Input,
+            kAudioHardwarePropertyDefaultInputDevice,
+        ),
+    ]
+    .map(|(kind, selector)| {
+        let default = get_property::<AudioObjectID>(
+            kAudioObjectSystemObject,
+            selector,
+            kAudioObjectPropertyScopeGlobal,
+        );
+        (kind, default.first().copied())
+    });
+
+    let devices = get_property::<AudioObjectID>(
+        kAudioObjectSystemObject,
+        kAudioHardwarePropertyDevices,
+        kAudioObjectPropertyScopeGlobal,
+    );
+
+    let mut result = Vec::new();
+    for device in devices {
+        let (Some(id), Some(name)) = (
+            get_string_property(device, kAudioDevicePropertyDeviceUID),
+            get_string_property(device, kAudioObjectPropertyName),
+        ) else {
+            continue;
+        };
+
+        // A device plays or records sound if it has streams in that direction
+        for (kind, default) in defaults {
+            let scope = match kind {
+                AudioDeviceKind::Output => kAudioObjectPropertyScopeOutput,
+                AudioDeviceKind::Input => kAudioObjectPropertyScopeInput,
+            };
+            if get_property_size(device, kAudioDevicePropertyStreams, scope).unwrap_or(0) == 0 {
+                continue;
+            }
+
+            result.push(AudioDevice {
+                id: id.clone(),
+                name: name.clone(),
+                kind,
+                is_default: default == Some(device),
+            });
+        }
+    }
+
+    result
+}
@@ -0,0 +1,47 @@
+#[cfg(target_os = "macos")]
+mod mac;
+
+#[cfg(target_os = "windows")]
+mod win;
+
+#[cfg(target_os = "linux")]
+mod linux;
+
+/// Whether an audio device plays or records sound
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioDeviceKind {
+    /// Speakers, headphones and other playback devices. Their output is what
+    /// loopback capture records.
+    Output,
+    /// Microphones and other recording devices
+    Input,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioDevice {
+    /// Identifies the device across calls and restarts: the endpoint id on
+    /// Windows, the device UID on macOS and the node name on Linux
+    pub id: String,
+    /// The name to show users
+    pub name: String,
+    pub kind: AudioDeviceKind,
+    /// The system's default device of this kind
+    pub is_default: bool,
+}
+
+/// Returns the connected audio devices. Devices that both play and record
+/// sound are listed once for each kind, with the same id.
+///
+/// Devices are enumerated on every call, so calling it again picks up devices
+/// plugged in or removed since. Returns an empty list when the audio system
+/// can't be reached.
+pub fn audio_devices() -> Vec<AudioDevice> {
+    #[cfg(target_os = "macos")]
+    return mac::audio_devices();
+
+    #[cfg(target_os = "windows")]
+    return win::audio_devices();
+
+    #[cfg(target_os = "linux")]
+    return linux::audio_devices();
+}
@@ -0,0 +1,83 @@
+use windows::{
+    core::Result,
+    Win32::{
+        Devices::FunctionDiscovery::PKEY_Device_FriendlyName,
+        Media::Audio::{
+            eCapture, eConsole, eRender, IMMDevice, IMMDeviceEnumerator, MMDeviceEnumerator,
+            DEVICE_STATE_ACTIVE,
+        },
+        System::Com::{
+            CoCreateInstance, CoInitializeEx, CoTaskMemFree, CoUninitialize, CLSCTX_ALL,
+            COINIT_MULTITHREADED, STGM_READ,
+        },
+    },
+};
+
+use super::{AudioDevice, AudioDeviceKind};
+
+fn get_device_id(device: &IMMDevice) -> Result<String> {
+    unsafe {
+        let id = device.GetId()?;
+        let result = String::from_utf16_lossy(id.as_wide());
+        CoTaskMemFree(Some(id.0 as *const _));
+        Ok(result)
+    }
+}
+
+fn get_device(
+    device: &IMMDevice,
+    kind: AudioDeviceKind,
+    default: Option<&str>,
+) -> Result<AudioDevice> {
+    let id = get_device_id(device)?;
+    let name = unsafe {
+        device
+            .OpenPropertyStore(STGM_READ)?
+            .GetValue(&PKEY_Device_FriendlyName)?
+    };
+
+    Ok(AudioDevice {
+        is_default: default == Some(id.as_str()),
+        id,
+        name: name.to_string(),
+        kind,
+    })
+}
+
+fn get_audio_devices() -> Result<Vec<AudioDevice>> {
+    let enumerator: IMMDeviceEnumerator =
+        unsafe { CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)? };
+
+    let mut devices = Vec::new();
+    for (flow, kind) in [
+        (eRender, AudioDeviceKind::Output),
+        (eCapture, AudioDeviceKind::Input),
+    ] {
+        // There's no default while no device of the kind is connected
+        let default = unsafe { enumerator.GetDefaultAudioEndpoint(flow, eConsole) }
+            .and_then(|device| get_device_id(&device))
+            .ok();
+
+        let collection = unsafe { enumerator.EnumAudioEndpoints(flow, DEVICE_STATE_ACTIVE)? };
+        for i in 0..unsafe { collection.GetCount()? } {
+            // Devices removed while enumerating fail and are left out
+            let device = unsafe { collection.Item(i) }
+                .and_then(|device| get_device(&device, kind, default.as_deref()));
+            if let Ok(device) = device {
+                devices.push(device);
+            }
+        }
+    }
+
+    Ok(devices)
+}
+
+pub fn audio_devices() -> Vec<AudioDevice> {
+    // COM may already be initialized on this thread, in either apartment
+    let initialized = unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }.is_ok();
+    let devices = get_audio_devices().unwrap_or_default();
+    if initialized {
+        unsafe { CoUninitialize() };
+    }
+    devices
+}
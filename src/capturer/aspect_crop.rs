@@ -0,0 +1,100 @@
+use super::Point;
+#[cfg(not(target_os = "linux"))]
+use super::{Area, Size};
+
+/// Crops the captured area to an aspect ratio before it's scaled, e.g. a
+/// 9:16 vertical video of a 16:9 display, see
+/// [Options::aspect_crop](super::Options::aspect_crop)
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AspectCrop {
+    /// Width and height of the ratio, e.g. `[9, 16]` or `[1, 1]`
+    pub ratio: [u32; 2],
+    /// Where the crop sits in the area it's cut from, as the part of the
+    /// leftover space left of and above it from 0.0 to 1.0. `{ x: 0.5, y:
+    /// 0.5 }` centers it, `{ x: 0.0, y: 0.0 }` keeps the top left corner.
+    pub anchor: Point,
+}
+
+impl Default for AspectCrop {
+    fn default() -> Self {
+        AspectCrop {
+            ratio: [9, 16],
+            anchor: Point { x: 0.5, y: 0.5 },
+        }
+    }
+}
+
+// The largest part of `area` with the crop's aspect ratio, in whole units
+// and placed at its anchor. `area` itself where the ratio is empty.
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn fit_aspect_ratio(area: &Area, crop: &AspectCrop) -> Area {
+    let [ratio_width, ratio_height] = crop.ratio;
+    if ratio_width == 0 || ratio_height == 0 {
+        return area.clone();
+    }
+    let ratio = ratio_width as f64 / ratio_height as f64;
+    let (width, height) = match area.size.width / area.size.height > ratio {
+        true => (area.size.height * ratio, area.size.height),
+        false => (area.size.width, area.size.width / ratio),
+    };
+    let (width, height) = (width.floor(), height.floor());
+
+    let offset = |leftover: f64, anchor: f64| (leftover * anchor.clamp(0.0, 1.0)).round();
+    Area {
+        origin: Point {
+            x: area.origin.x + offset(area.size.width - width, crop.anchor.x),
+            y: area.origin.y + offset(area.size.height - height, crop.anchor.y),
+        },
+        size: Size { width, height },
+    }
+}
+
+#[cfg(all(test, not(target_os = "linux")))]
+mod tests {
+    use super::*;
+
+    fn area(x: f64, y: f64, width: f64, height: f64) -> Area {
+        Area {
+            origin: Point { x, y },
+            size: Size { width, height },
+        }
+    }
+
+    #[test]
+    fn test_fit_aspect_ratio() {
+        let display = area(0.0, 0.0, 1920.0, 1080.0);
+        let vertical = AspectCrop::default();
+        assert_eq!(
+            fit_aspect_ratio(&display, &vertical),
+            area(657.0, 0.0, 607.0, 1080.0)
+        );
+
+        // Cut from the crop area, kept in its top left corner
+        let square = AspectCrop {
+            ratio: [1, 1],
+            anchor: Point { x: 0.0, y: 0.0 },
+        };
+        let crop = area(100.0, 50.0, 400.0, 600.0);
+        assert_eq!(
+            fit_aspect_ratio(&crop, &square),
+            area(100.0, 50.0, 400.0, 400.0)
+        );
+
+        // Anchors past the edges are clamped
+        let wide = AspectCrop {
+            ratio: [16, 9],
+            anchor: Point { x: 0.5, y: 2.0 },
+        };
+        assert_eq!(
+            fit_aspect_ratio(&crop, &wide),
+            area(100.0, 425.0, 400.0, 225.0)
+        );
+
+        let empty = AspectCrop {
+            ratio: [0, 1],
+            ..Default::default()
+        };
+        assert_eq!(fit_aspect_ratio(&crop, &empty), crop);
+    }
+}
@@ -0,0 +1,80 @@
+use std::{
+    sync::mpsc::RecvTimeoutError,
+    time::{Duration, Instant},
+};
+
+use super::{Capturer, CapturerBuildError, DroppedFrames, Options};
+use crate::frame::Frame;
+
+/// Frames captured back to back by [capture_burst]
+#[derive(Debug, Clone)]
+pub struct Burst {
+    /// In the order they were captured, markers left out
+    pub frames: Vec<Frame>,
+    /// When each frame was received, relative to when capture started. Their
+    /// display times are the OS's presentation times.
+    pub received: Vec<Duration>,
+    /// Frames the OS didn't produce during the burst, see
+    /// [CaptureStats::compositor_drops](super::CaptureStats::compositor_drops)
+    pub compositor_drops: Option<u64>,
+    /// Frames received but not returned, see [DroppedFrames]
+    pub dropped: DroppedFrames,
+}
+
+/// Capture `count` consecutive frames of the target in `options` as fast as
+/// the OS produces them, for measuring frame timing and latency. Capture is
+/// started and stopped for it.
+///
+/// `fps` is set to 0 and nothing skips, repeats or replaces frames: delta
+/// and scanline encoding, low latency capture, a constant or minimum frame
+/// rate, activity summaries, external triggers and the frame ring are
+/// turned off. Frames queue up unbounded while the burst runs, so none are
+/// dropped for a slow caller, but all of them are kept in memory. Fewer frames are returned if `timeout` passes or capture
+/// stops first.
+pub fn capture_burst(
+    options: Options,
+    count: usize,
+    timeout: Duration,
+) -> Result<Burst, CapturerBuildError> {
+    let options = Options {
+        fps: 0,
+        delta: None,
+        scanline_patches: None,
+        latency: Default::default(),
+        frame_ring: None,
+        constant_frame_rate: None,
+        min_frame_rate: None,
+        activity: None,
+        external_trigger: false,
+        ..options
+    };
+    let mut capturer = Capturer::build(options)?;
+    capturer.try_start_capture()?;
+
+    let started = Instant::now();
+    let deadline = started + timeout;
+    let mut frames = Vec::with_capacity(count);
+    let mut received = Vec::with_capacity(count);
+    while frames.len() < count {
+        match capturer.next_frame(Some(deadline)) {
+            Ok(Frame::Marker(_)) => {}
+            Ok(frame) => {
+                received.push(started.elapsed());
+                frames.push(frame);
+            }
+            Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    let compositor_drops = capturer.stats().compositor_drops;
+    capturer.stop_capture();
+    let dropped = capturer
+        .take_summary()
+        .map_or_else(DroppedFrames::default, |summary| summary.frames_dropped);
+    Ok(Burst {
+        frames,
+        received,
+        compositor_drops,
+        dropped,
+    })
+}
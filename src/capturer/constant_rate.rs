@@ -0,0 +1,120 @@
+use std::time::{Duration, Instant};
+
+use crate::frame::{rescale_to_timebase, Frame};
+
+// Turns frames arriving whenever the compositor sends them into frames at
+// evenly spaced ticks, see Options::constant_frame_rate. Every tick gets
+// the latest frame that arrived before it, or the one before again.
+#[derive(Debug)]
+pub(crate) struct ConstantRate {
+    interval: Duration,
+    timebase: Option<u32>,
+    // The next tick, None before the first frame
+    next: Option<Instant>,
+    ticks: u64,
+    // Display time of the first frame, which the ticks count from
+    start_time: u64,
+    latest: Option<Frame>,
+    // Whether the latest frame arrived since the last tick
+    fresh: bool,
+    repeats: u64,
+}
+
+impl ConstantRate {
+    pub fn new(fps: u32, timebase: Option<u32>) -> Self {
+        ConstantRate {
+            interval: Duration::from_secs(1) / fps.max(1),
+            timebase,
+            next: None,
+            ticks: 0,
+            start_time: 0,
+            latest: None,
+            fresh: false,
+            repeats: 0,
+        }
+    }
+
+    // Starts over for a new capture session
+    pub fn reset(&mut self) {
+        self.next = None;
+        self.ticks = 0;
+        self.latest = None;
+        self.fresh = false;
+        self.repeats = 0;
+    }
+
+    // When the next tick is due, None until a frame arrived
+    pub fn deadline(&self) -> Option<Instant> {
+        self.next
+    }
+
+    // Outputs that repeated the frame before, since none arrived in time
+    pub fn repeats(&self) -> u64 {
+        self.repeats
+    }
+
+    // Takes a frame that arrived at `now`. The first starts the ticks.
+    pub fn push(&mut self, frame: Frame, now: Instant) {
+        if self.next.is_none() {
+            self.next = Some(now);
+            self.start_time = frame.display_time();
+        }
+        self.latest = Some(frame);
+        self.fresh = true;
+    }
+
+    // The frame of the tick due at `now`, if one is. Ticks are never
+    // skipped, a consumer that fell behind gets the missed ones at once.
+    pub fn tick(&mut self, now: Instant) -> Option<Frame> {
+        let next = self.next.filter(|next| now >= *next)?;
+        let mut frame = self.latest.clone()?;
+        if !self.fresh {
+            self.repeats += 1;
+        }
+        self.fresh = false;
+
+        // Stamped from the tick count, so rounding doesn't add up
+        let offset = self.interval.as_nanos() as u64 * self.ticks;
+        *frame.display_time_mut() = self.start_time + rescale_to_timebase(offset, self.timebase);
+        self.ticks += 1;
+        self.next = Some(next + self.interval);
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capturer::fixtures::{frame, output};
+
+    #[test]
+    fn test_even_ticks_and_repeats() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut rate = ConstantRate::new(10, None);
+        assert!(rate.deadline().is_none());
+        assert!(rate.tick(at(0)).is_none());
+
+        rate.push(frame(1_000, 1), at(0));
+        assert_eq!(output(rate.tick(at(0))), (1_000, 1));
+        assert_eq!(rate.deadline(), Some(at(100)));
+        assert!(rate.tick(at(50)).is_none());
+
+        // Two frames before a tick, the newest is taken
+        rate.push(frame(2_000, 2), at(60));
+        rate.push(frame(3_000, 3), at(80));
+        assert_eq!(output(rate.tick(at(100))), (100_001_000, 3));
+
+        // None arrived, the last is repeated, also for ticks missed
+        assert_eq!(output(rate.tick(at(200))), (200_001_000, 3));
+        assert_eq!(output(rate.tick(at(450))), (300_001_000, 3));
+        assert_eq!(output(rate.tick(at(450))), (400_001_000, 3));
+        assert!(rate.tick(at(450)).is_none());
+        assert_eq!(rate.repeats(), 3);
+
+        let mut rate = ConstantRate::new(1, Some(90_000));
+        rate.push(frame(5, 1), at(0));
+        rate.tick(at(0));
+        assert_eq!(output(rate.tick(at(1_000))).0, 90_005);
+    }
+}
@@ -0,0 +1,273 @@
+use std::{
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use super::{Area, CapturerEvent, Point, Size};
+use crate::frame::{detect_black_bars, detect_uniform_border, Frame};
+
+/// Pixels removed from each edge of the frames, see [EdgeTrim]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Edges {
+    pub left: u32,
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+}
+
+/// Removes the thin borders of black or garbage pixels some displays and
+/// drivers leave at the edges of the frames, see
+/// [Options::edge_trim](super::Options::edge_trim)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EdgeTrim {
+    /// Always trims these edges
+    Fixed(Edges),
+    /// Trims the rows and columns at each edge that are one uniform color,
+    /// up to `max` pixels per edge, allowing each color channel to vary by
+    /// `tolerance`. Detected on the first frame that isn't black each time
+    /// capture starts, so the frames keep their size.
+    Auto { max: u32, tolerance: u8 },
+    /// Crops the black bars of letterboxed or pillarboxed content, like a
+    /// video player showing a film of another aspect ratio, down to the
+    /// active picture, for archiving or re-encoding just the video. Rows
+    /// and columns at the edges are bars while no color channel of any of
+    /// their pixels is above `threshold`. Detected on the first frame that
+    /// isn't black and again on the first one after every `redetect`, so
+    /// the frames change size when the content does.
+    Letterbox { threshold: u8, redetect: Duration },
+}
+
+impl Default for EdgeTrim {
+    fn default() -> Self {
+        EdgeTrim::Auto {
+            max: 8,
+            tolerance: 4,
+        }
+    }
+}
+
+// Trims the edges of every frame and reports what was trimmed
+#[derive(Debug)]
+pub(crate) struct EdgeTrimmer {
+    trim: EdgeTrim,
+    // The detected border, None until a frame that isn't black arrives
+    edges: Option<Edges>,
+    // When the letterbox was last detected
+    detected: Option<Instant>,
+    // The last trimmed edges and resulting size reported
+    reported: Option<(Edges, u32, u32)>,
+}
+
+impl EdgeTrimmer {
+    pub fn new(trim: EdgeTrim) -> Self {
+        EdgeTrimmer {
+            trim,
+            edges: match trim {
+                EdgeTrim::Fixed(edges) => Some(edges),
+                EdgeTrim::Auto { .. } | EdgeTrim::Letterbox { .. } => None,
+            },
+            detected: None,
+            reported: None,
+        }
+    }
+
+    // Detects the border again when capture starts
+    pub fn reset(&mut self) {
+        *self = EdgeTrimmer::new(self.trim);
+    }
+
+    // The frame arriving at `now` without its edges. Frames that would have
+    // no pixels left and YUV frames, which can't be cropped, are delivered
+    // whole.
+    pub fn trim(
+        &mut self,
+        frame: Frame,
+        events: &mpsc::Sender<CapturerEvent>,
+        now: Instant,
+    ) -> Frame {
+        let border = match self.trim {
+            EdgeTrim::Fixed(_) => None,
+            EdgeTrim::Auto { max, tolerance } => (self.edges.is_none()
+                && !frame.is_black(tolerance))
+            .then(|| detect_uniform_border(&frame, max, tolerance)),
+            // Black frames, like fades, aren't judged and keep the last one
+            EdgeTrim::Letterbox {
+                threshold,
+                redetect,
+            } => {
+                let due = self.detected.map_or(true, |detected| {
+                    now.saturating_duration_since(detected) >= redetect
+                });
+                (due && !frame.is_black(threshold)).then(|| {
+                    self.detected = Some(now);
+                    detect_black_bars(&frame, threshold)
+                })
+            }
+        };
+        if let Some(border) = border {
+            self.edges = Some(
+                border.map_or(Edges::default(), |[left, top, right, bottom]| Edges {
+                    left,
+                    top,
+                    right,
+                    bottom,
+                }),
+            );
+        }
+        let Some(edges) = self.edges else {
+            return frame;
+        };
+
+        let (width, height) = frame.size();
+        let (width, height) = (width.max(0) as u32, height.max(0) as u32);
+        let horizontal = edges.left.saturating_add(edges.right);
+        let vertical = edges.top.saturating_add(edges.bottom);
+        let trimmed = match horizontal < width && vertical < height {
+            true if edges != Edges::default() => frame.cropped(&Area {
+                origin: Point {
+                    x: edges.left as f64,
+                    y: edges.top as f64,
+                },
+                size: Size {
+                    width: (width - horizontal) as f64,
+                    height: (height - vertical) as f64,
+                },
+            }),
+            _ => None,
+        };
+        let (edges, frame) = match trimmed {
+            Some(trimmed) => (edges, trimmed),
+            None => (Edges::default(), frame),
+        };
+
+        let (width, height) = frame.size();
+        let report = (edges, width.max(0) as u32, height.max(0) as u32);
+        if self.reported != Some(report) {
+            let _ = events.send(CapturerEvent::EdgesTrimmed {
+                edges,
+                width: report.1,
+                height: report.2,
+            });
+            self.reported = Some(report);
+        }
+        frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{ColorSpace, Gray8Frame, RowOrder};
+
+    // A 4x3 gradient down from `value` with a black column on the left
+    fn frame(value: u8) -> Frame {
+        let data = (0..12)
+            .map(|i| {
+                if i % 4 == 0 {
+                    0
+                } else {
+                    value.saturating_sub(i)
+                }
+            })
+            .collect();
+        Frame::Gray8(Gray8Frame {
+            display_time: 0,
+            width: 4,
+            height: 3,
+            data,
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::SRGB,
+        })
+    }
+
+    fn reported(events: &mpsc::Receiver<CapturerEvent>) -> Option<(Edges, u32, u32)> {
+        match events.try_recv() {
+            Ok(CapturerEvent::EdgesTrimmed {
+                edges,
+                width,
+                height,
+            }) => Some((edges, width, height)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_trims_edges() {
+        let (tx, rx) = mpsc::channel();
+        let mut trimmer = EdgeTrimmer::new(EdgeTrim::Auto {
+            max: 2,
+            tolerance: 0,
+        });
+
+        let now = Instant::now();
+        // Black frames are delivered as they are until one can be judged
+        assert_eq!(trimmer.trim(frame(0), &tx, now).size(), (4, 3));
+        assert_eq!(reported(&rx), None);
+
+        let left = Edges {
+            left: 1,
+            ..Default::default()
+        };
+        assert_eq!(trimmer.trim(frame(100), &tx, now).size(), (3, 3));
+        assert_eq!(reported(&rx), Some((left, 3, 3)));
+        // The border stays, and is only reported again when it changes
+        match trimmer.trim(frame(0), &tx, now) {
+            Frame::Gray8(f) => assert_eq!(f.data, [0; 9]),
+            _ => panic!("expected a gray frame"),
+        }
+        assert_eq!(reported(&rx), None);
+
+        let mut fixed = EdgeTrimmer::new(EdgeTrim::Fixed(Edges {
+            top: 1,
+            bottom: 2,
+            ..Default::default()
+        }));
+        // Nothing would be left, so the frame is delivered whole
+        assert_eq!(fixed.trim(frame(100), &tx, now).size(), (4, 3));
+        assert_eq!(reported(&rx), Some((Edges::default(), 4, 3)));
+    }
+
+    #[test]
+    fn test_letterbox() {
+        let (tx, rx) = mpsc::channel();
+        let mut trimmer = EdgeTrimmer::new(EdgeTrim::Letterbox {
+            threshold: 8,
+            redetect: Duration::from_secs(1),
+        });
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        // Pillarboxed: black columns of any width on both sides
+        let picture = |bars: usize| {
+            let data = (0..60)
+                .map(|i| match i % 10 {
+                    x if x < bars || x >= 10 - bars => 0,
+                    _ => 100,
+                })
+                .collect();
+            Frame::Gray8(Gray8Frame {
+                display_time: 0,
+                width: 10,
+                height: 6,
+                data,
+                origin: RowOrder::TopDown,
+                color_space: ColorSpace::SRGB,
+            })
+        };
+        let bars = |width: u32| Edges {
+            left: width,
+            right: width,
+            ..Default::default()
+        };
+
+        assert_eq!(trimmer.trim(picture(3), &tx, at(0)).size(), (4, 6));
+        assert_eq!(reported(&rx), Some((bars(3), 4, 6)));
+        // Kept until it's due again, black frames don't count
+        assert_eq!(trimmer.trim(picture(1), &tx, at(500)).size(), (4, 6));
+        assert_eq!(trimmer.trim(picture(5), &tx, at(1500)).size(), (4, 6));
+        assert_eq!(reported(&rx), None);
+        assert_eq!(trimmer.trim(picture(1), &tx, at(1600)).size(), (8, 6));
+        assert_eq!(reported(&rx), Some((bars(1), 8, 6)));
+    }
+}
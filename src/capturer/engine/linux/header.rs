@@ -0,0 +1,116 @@
+// From spa/buffer/meta.h, set on buffers the producer didn't finish writing
+pub const SPA_META_HEADER_FLAG_CORRUPTED: u32 = 1 << 1;
+
+// The part of struct spa_meta_header that's read: flags, an offset and pts
+const HEADER_SIZE: usize = 16;
+
+// The header metadata of a buffer, zero for buffers without one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BufferHeader {
+    pub flags: u32,
+    // Presentation time in nanoseconds
+    pub pts: i64,
+}
+
+impl BufferHeader {
+    // From a buffer's metadata as its type and data, the first one of
+    // `header_type`. Headers too short to hold the pts are ignored.
+    pub fn parse<'a>(metas: impl IntoIterator<Item = (u32, &'a [u8])>, header_type: u32) -> Self {
+        metas
+            .into_iter()
+            .find(|(type_, _)| *type_ == header_type)
+            .and_then(|(_, data)| {
+                let flags = data.get(0..4)?.try_into().ok()?;
+                let pts = data.get(8..HEADER_SIZE)?.try_into().ok()?;
+                Some(BufferHeader {
+                    flags: u32::from_ne_bytes(flags),
+                    pts: i64::from_ne_bytes(pts),
+                })
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn is_corrupted(&self) -> bool {
+        self.flags & SPA_META_HEADER_FLAG_CORRUPTED != 0
+    }
+}
+
+// The frame `read` makes of a dequeued buffer, None if it's dropped for
+// Options::tear_free or holds none. The buffer is queued back with `queue`
+// either way, or the stream runs out of them.
+pub fn take_frame<B, F>(
+    buffer: B,
+    header: BufferHeader,
+    tear_free: bool,
+    read: impl FnOnce(&B, i64) -> Option<F>,
+    queue: impl FnOnce(B),
+) -> Option<F> {
+    let frame = match tear_free && header.is_corrupted() {
+        true => None,
+        false => read(&buffer, header.pts),
+    };
+    queue(buffer);
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: u32 = 1;
+
+    fn header(flags: u32, pts: i64) -> Vec<u8> {
+        let mut data = flags.to_ne_bytes().to_vec();
+        data.extend(0u32.to_ne_bytes());
+        data.extend(pts.to_ne_bytes());
+        // dts_offset and seq
+        data.extend([0; 16]);
+        data
+    }
+
+    #[test]
+    fn test_parse_header() {
+        let data = header(SPA_META_HEADER_FLAG_CORRUPTED, 16_666_667);
+        let other = [0xff; 32];
+        let parsed = BufferHeader::parse([(3, &other[..]), (HEADER, &data[..])], HEADER);
+        assert_eq!(parsed.pts, 16_666_667);
+        assert!(parsed.is_corrupted());
+
+        assert_eq!(
+            BufferHeader::parse([(3, &other[..])], HEADER),
+            BufferHeader::default()
+        );
+        assert_eq!(
+            BufferHeader::parse([(HEADER, &data[..12])], HEADER),
+            BufferHeader::default()
+        );
+        assert!(!BufferHeader::parse([(HEADER, &header(0, 1)[..])], HEADER).is_corrupted());
+    }
+
+    #[test]
+    fn test_take_frame() {
+        let corrupted = BufferHeader {
+            flags: SPA_META_HEADER_FLAG_CORRUPTED,
+            pts: 5,
+        };
+        let mut queued = Vec::new();
+
+        // Corrupted buffers are skipped for tear free capture, and only then
+        let frame = take_frame(1, corrupted, true, |_, pts| Some(pts), |b| queued.push(b));
+        assert_eq!(frame, None);
+        let frame = take_frame(2, corrupted, false, |_, pts| Some(pts), |b| queued.push(b));
+        assert_eq!(frame, Some(5));
+
+        // As are buffers without data
+        let frame = take_frame(
+            3,
+            BufferHeader::default(),
+            true,
+            |_, _| None::<i64>,
+            |b| queued.push(b),
+        );
+        assert_eq!(frame, None);
+
+        assert_eq!(queued, [1, 2, 3]);
+    }
+}
@@ -2,7 +2,7 @@ use std::{
     mem::size_of,
     sync::{
         atomic::{AtomicBool, AtomicU8, Ordering},
-        mpsc::{self, sync_channel, SyncSender},
+        mpsc::{sync_channel, SyncSender},
     },
     thread::JoinHandle,
     time::Duration,
@@ -21,9 +21,7 @@ use pw::{
             ParamType,
         },
         pod::{Pod, Property},
-        sys::{
-            spa_buffer, spa_meta_header, SPA_META_Header, SPA_PARAM_META_size, SPA_PARAM_META_type,
-        },
+        sys::{spa_buffer, spa_meta, SPA_META_Header, SPA_PARAM_META_size, SPA_PARAM_META_type},
         utils::{Direction, SpaTypes},
     },
     stream::{StreamRef, StreamState},
@@ -31,12 +29,18 @@ use pw::{
 
 use crate::{
     capturer::Options,
-    frame::{BGRxFrame, Frame, RGBFrame, RGBxFrame, XBGRFrame},
+    frame::{BGRxFrame, ColorSpace, Frame, RGBFrame, RGBxFrame, RowOrder, XBGRFrame},
 };
 
-use self::{error::LinCapError, portal::ScreenCastPortal};
+use self::{
+    error::LinCapError,
+    header::{take_frame, BufferHeader},
+    portal::ScreenCastPortal,
+};
+use super::FrameSender;
 
 mod error;
+mod header;
 mod portal;
 
 static CAPTURER_STATE: AtomicU8 = AtomicU8::new(0);
@@ -44,8 +48,10 @@ static STREAM_STATE_CHANGED_TO_ERROR: AtomicBool = AtomicBool::new(false);
 
 #[derive(Clone)]
 struct ListenerUserData {
-    pub tx: mpsc::Sender<Frame>,
+    pub tx: FrameSender,
     pub format: spa::param::video::VideoInfoRaw,
+    // Whether buffers flagged as corrupted are dropped, see Options::tear_free
+    pub tear_free: bool,
 }
 
 fn param_changed_callback(
@@ -91,89 +97,111 @@ fn state_changed_callback(
     }
 }
 
-unsafe fn get_timestamp(buffer: *mut spa_buffer) -> i64 {
-    let n_metas = (*buffer).n_metas;
-    if n_metas > 0 {
-        let mut meta_ptr = (*buffer).metas;
-        let metas_end = (*buffer).metas.wrapping_add(n_metas as usize);
-        while meta_ptr != metas_end {
-            if (*meta_ptr).type_ == SPA_META_Header {
-                let meta_header: &mut spa_meta_header =
-                    &mut *((*meta_ptr).data as *mut spa_meta_header);
-                return meta_header.pts;
-            }
-            meta_ptr = meta_ptr.wrapping_add(1);
-        }
-        0
-    } else {
-        0
+// The header metadata of a buffer
+unsafe fn get_header(buffer: *mut spa_buffer) -> BufferHeader {
+    let metas: &[spa_meta] = match (*buffer).n_metas {
+        0 => &[],
+        n_metas => std::slice::from_raw_parts((*buffer).metas, n_metas as usize),
+    };
+    let metas = metas
+        .iter()
+        .filter(|meta| !meta.data.is_null())
+        .map(|meta| {
+            let data = std::slice::from_raw_parts(meta.data as *const u8, meta.size as usize);
+            (meta.type_, data)
+        });
+    BufferHeader::parse(metas, SPA_META_Header)
+}
+
+// The frame in a buffer's first data block, None if it has none
+unsafe fn read_frame(
+    buffer: *mut spa_buffer,
+    pts: i64,
+    format: &spa::param::video::VideoInfoRaw,
+) -> Option<Frame> {
+    if (*buffer).n_datas < 1 || (*(*buffer).datas).data.is_null() {
+        return None;
     }
+    let frame_size = format.size();
+    let frame_data: Vec<u8> = std::slice::from_raw_parts(
+        (*(*buffer).datas).data as *mut u8,
+        (*(*buffer).datas).maxsize as usize,
+    )
+    .to_vec();
+
+    // PipeWire carries no color metadata for these formats
+    Some(match format.format() {
+        VideoFormat::RGBx => Frame::RGBx(RGBxFrame {
+            display_time: pts as u64,
+            width: frame_size.width as i32,
+            height: frame_size.height as i32,
+            data: frame_data,
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::Unknown,
+        }),
+        VideoFormat::RGB => Frame::RGB(RGBFrame {
+            display_time: pts as u64,
+            width: frame_size.width as i32,
+            height: frame_size.height as i32,
+            data: frame_data,
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::Unknown,
+        }),
+        VideoFormat::xBGR => Frame::XBGR(XBGRFrame {
+            display_time: pts as u64,
+            width: frame_size.width as i32,
+            height: frame_size.height as i32,
+            data: frame_data,
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::Unknown,
+        }),
+        VideoFormat::BGRx => Frame::BGRx(BGRxFrame {
+            display_time: pts as u64,
+            width: frame_size.width as i32,
+            height: frame_size.height as i32,
+            data: frame_data,
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::Unknown,
+        }),
+        _ => panic!("Unsupported frame format received"),
+    })
 }
 
 fn process_callback(stream: &StreamRef, user_data: &mut ListenerUserData) {
     let buffer = unsafe { stream.dequeue_raw_buffer() };
-    if !buffer.is_null() {
-        'outside: {
-            let buffer = unsafe { (*buffer).buffer };
-            if buffer.is_null() {
-                break 'outside;
-            }
-            let timestamp = unsafe { get_timestamp(buffer) };
-
-            let n_datas = unsafe { (*buffer).n_datas };
-            if n_datas < 1 {
-                return;
-            }
-            let frame_size = user_data.format.size();
-            let frame_data: Vec<u8> = unsafe {
-                std::slice::from_raw_parts(
-                    (*(*buffer).datas).data as *mut u8,
-                    (*(*buffer).datas).maxsize as usize,
-                )
-                .to_vec()
-            };
-
-            if let Err(e) = match user_data.format.format() {
-                VideoFormat::RGBx => user_data.tx.send(Frame::RGBx(RGBxFrame {
-                    display_time: timestamp as u64,
-                    width: frame_size.width as i32,
-                    height: frame_size.height as i32,
-                    data: frame_data,
-                })),
-                VideoFormat::RGB => user_data.tx.send(Frame::RGB(RGBFrame {
-                    display_time: timestamp as u64,
-                    width: frame_size.width as i32,
-                    height: frame_size.height as i32,
-                    data: frame_data,
-                })),
-                VideoFormat::xBGR => user_data.tx.send(Frame::XBGR(XBGRFrame {
-                    display_time: timestamp as u64,
-                    width: frame_size.width as i32,
-                    height: frame_size.height as i32,
-                    data: frame_data,
-                })),
-                VideoFormat::BGRx => user_data.tx.send(Frame::BGRx(BGRxFrame {
-                    display_time: timestamp as u64,
-                    width: frame_size.width as i32,
-                    height: frame_size.height as i32,
-                    data: frame_data,
-                })),
-                _ => panic!("Unsupported frame format received"),
-            } {
-                eprintln!("{e}");
-            }
-        }
-    } else {
+    if buffer.is_null() {
         eprintln!("Out of buffers");
+        return;
     }
+    let spa_buffer = unsafe { (*buffer).buffer };
+    let header = match spa_buffer.is_null() {
+        true => BufferHeader::default(),
+        false => unsafe { get_header(spa_buffer) },
+    };
+    let frame = take_frame(
+        buffer,
+        header,
+        user_data.tear_free,
+        |_, pts| match spa_buffer.is_null() {
+            true => None,
+            false => unsafe { read_frame(spa_buffer, pts, &user_data.format) },
+        },
+        |buffer| unsafe { stream.queue_raw_buffer(buffer) },
+    );
 
-    unsafe { stream.queue_raw_buffer(buffer) };
+    // The clock of the timestamp can't be read, so the time it took to
+    // acquire the frame isn't known
+    if let Some(frame) = frame {
+        if let Err(e) = user_data.tx.send(frame, None) {
+            eprintln!("{e}");
+        }
+    }
 }
 
 // TODO: Format negotiation
 fn pipewire_capturer(
     options: Options,
-    tx: mpsc::Sender<Frame>,
+    tx: FrameSender,
     ready_sender: &SyncSender<bool>,
     stream_id: u32,
 ) -> Result<(), LinCapError> {
@@ -186,6 +214,7 @@ fn pipewire_capturer(
     let user_data = ListenerUserData {
         tx,
         format: Default::default(),
+        tear_free: options.tear_free,
     };
 
     let stream = pw::stream::Stream::new(
@@ -318,13 +347,13 @@ fn pipewire_capturer(
 pub struct LinuxCapturer {
     capturer_join_handle: Option<JoinHandle<Result<(), LinCapError>>>,
     // The pipewire stream is deleted when the connection is dropped.
-    // That's why we keep it alive
-    _connection: dbus::blocking::Connection,
+    // That's why we keep it alive. None for detached capturers.
+    _connection: Option<dbus::blocking::Connection>,
 }
 
 impl LinuxCapturer {
     // TODO: Error handling
-    pub fn new(options: &Options, tx: mpsc::Sender<Frame>) -> Self {
+    pub fn new(options: &Options, tx: FrameSender) -> Self {
         let connection =
             dbus::blocking::Connection::new_session().expect("Failed to create dbus connection");
         let stream_id = ScreenCastPortal::new(&connection)
@@ -351,7 +380,17 @@ impl LinuxCapturer {
 
         Self {
             capturer_join_handle: Some(capturer_join_handle),
-            _connection: connection,
+            _connection: Some(connection),
+        }
+    }
+
+    // A capturer without a portal session or stream, for tests that send
+    // frames through the engine themselves
+    #[cfg(test)]
+    pub fn detached() -> Self {
+        Self {
+            capturer_join_handle: None,
+            _connection: None,
         }
     }
 
@@ -371,6 +410,7 @@ impl LinuxCapturer {
     }
 }
 
-pub fn create_capturer(options: &Options, tx: mpsc::Sender<Frame>) -> LinuxCapturer {
+#[cfg_attr(test, allow(dead_code))]
+pub fn create_capturer(options: &Options, tx: FrameSender) -> LinuxCapturer {
     LinuxCapturer::new(options, tx)
 }
@@ -28,6 +28,7 @@ pub type id = *mut objc::runtime::Object;
 pub struct NSString(pub id);
 
 pub type CFNumberRef = *const __CFNumber;
+pub type CMClockRef = *const ::std::os::raw::c_void;
 pub type SCStreamFrameInfo = NSString;
 extern "C" {
     pub fn CFDictionaryGetValue(
@@ -40,9 +41,20 @@ extern "C" {
         valuePtr: *mut ::std::os::raw::c_void,
     ) -> Boolean;
     pub fn CMTimeGetSeconds(time: CMTime) -> Float64;
+    pub fn CMClockGetHostTimeClock() -> CMClockRef;
+    pub fn CMClockGetTime(clock: CMClockRef) -> CMTime;
+    pub fn CFEqual(cf1: CFTypeRef, cf2: CFTypeRef) -> Boolean;
     pub static SCStreamFrameInfoStatus: SCStreamFrameInfo;
+    pub static kCVImageBufferColorPrimariesKey: CFTypeRef;
+    pub static kCVImageBufferColorPrimaries_ITU_R_709_2: CFTypeRef;
+    pub static kCVImageBufferColorPrimaries_P3_D65: CFTypeRef;
+    pub fn CGRectMakeWithDictionaryRepresentation(
+        dict: CFDictionaryRef,
+        rect: *mut core_graphics_helmer_fork::geometry::CGRect,
+    ) -> Boolean;
 }
 pub const CFNumberType_kCFNumberSInt64Type: CFNumberType = 4;
+pub const CFNumberType_kCFNumberFloat64Type: CFNumberType = 6;
 pub type NSInteger = ::std::os::raw::c_long;
 pub type SCFrameStatus = NSInteger;
 pub const SCFrameStatus_SCFrameStatusComplete: SCFrameStatus = 0;
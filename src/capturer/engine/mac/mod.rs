@@ -1,7 +1,12 @@
 use std::sync::atomic::AtomicBool;
-use std::sync::mpsc;
+use std::time::Duration;
 use std::{cmp, sync::Arc};
 
+use core_graphics_helmer_fork::{
+    display::CGDisplay,
+    event::CGEvent,
+    event_source::{CGEventSource, CGEventSourceStateID},
+};
 use pixelformat::get_pts_in_nanoseconds;
 use screencapturekit::{
     cm_sample_buffer::CMSampleBuffer,
@@ -12,25 +17,38 @@ use screencapturekit::{
     sc_stream::SCStream,
     sc_stream_configuration::{PixelFormat, SCStreamConfiguration},
     sc_types::SCFrameStatus,
+    sc_window::SCWindow,
 };
 use screencapturekit_sys::os_types::base::{CMTime, CMTimeScale};
 use screencapturekit_sys::os_types::geometry::{CGPoint, CGRect, CGSize};
 
-use crate::frame::{Frame, FrameType};
+use crate::capturer::aspect_crop::fit_aspect_ratio;
+use crate::frame::{
+    convert_bgra_to_yuv, ChromaSubsampling, ColorMatrix, ColorRange, Frame, FrameType,
+};
 use crate::targets::Target;
 use crate::{
-    capturer::{Area, Options, Point, Resolution, Size},
-    frame::BGRAFrame,
+    capturer::{
+        shift_area, Area, CapturerBuildError, CropOverflow, CursorStyle, Latency, Options, Point,
+        Resolution, Size,
+    },
+    frame::{BGRAFrame, ColorSpace, RowOrder},
     targets,
 };
 
-use super::ChannelItem;
+use super::FrameSender;
 
-mod apple_sys;
+pub(crate) mod apple_sys;
 mod pixel_buffer;
 mod pixelformat;
+mod window_tracker;
+mod window_watcher;
 
 pub use pixel_buffer::PixelBuffer;
+pub use window_tracker::WindowTracker;
+pub(crate) use window_tracker::{get_description_bounds, get_window_display};
+pub use window_watcher::WindowWatcher;
+pub(crate) use window_watcher::{get_host_time, get_window_title};
 
 struct ErrorHandler {
     error_flag: Arc<AtomicBool>,
@@ -45,26 +63,39 @@ impl StreamErrorHandler for ErrorHandler {
 }
 
 pub struct Capturer {
-    pub tx: mpsc::Sender<ChannelItem>,
+    pub tx: FrameSender,
 }
 
 impl Capturer {
-    pub fn new(tx: mpsc::Sender<ChannelItem>) -> Self {
+    pub fn new(tx: FrameSender) -> Self {
         Capturer { tx }
     }
 }
 
 impl StreamOutput for Capturer {
     fn did_output_sample_buffer(&self, sample: CMSampleBuffer, of_type: SCStreamOutputType) {
-        self.tx.send((sample, of_type)).unwrap_or(());
+        let latency = get_frame_age(&sample, &of_type);
+        self.tx.send((sample, of_type), latency).unwrap_or(());
     }
 }
 
+// Whether the system cursor is left out for a custom one that scap draws
+pub fn hides_system_cursor(options: &Options) -> bool {
+    matches!(
+        (&options.cursor_style, &options.target, options.output_type),
+        (
+            CursorStyle::Custom(_),
+            None | Some(Target::Display(_)),
+            FrameType::BGRAFrame
+        )
+    )
+}
+
 pub fn create_capturer(
     options: &Options,
-    tx: mpsc::Sender<ChannelItem>,
+    tx: FrameSender,
     error_flag: Arc<AtomicBool>,
-) -> SCStream {
+) -> Result<SCStream, CapturerBuildError> {
     // If no target is specified, capture the main display
     let target = options
         .target
@@ -80,7 +111,9 @@ pub fn create_capturer(
                 .windows
                 .into_iter()
                 .find(|sc_win| sc_win.window_id == window.id)
-                .unwrap();
+                .ok_or_else(|| {
+                    CapturerBuildError::InvalidTarget(format!("window {} is closed", window.id))
+                })?;
 
             // Return a DesktopIndependentWindow
             // https://developer.apple.com/documentation/screencapturekit/sccontentfilter/3919804-init
@@ -92,22 +125,54 @@ pub fn create_capturer(
                 .displays
                 .into_iter()
                 .find(|sc_dis| sc_dis.display_id == display.id)
-                .unwrap();
-
-            match &options.excluded_targets {
-                None => InitParams::Display(sc_display),
-                Some(excluded_targets) => {
+                .ok_or_else(|| {
+                    // Also the case for the main display of a headless Mac
+                    CapturerBuildError::InvalidTarget(format!(
+                        "display {} isn't connected",
+                        display.id
+                    ))
+                })?;
+
+            let excluded_targets = options.excluded_targets.as_deref().unwrap_or_default();
+            let current_process = std::process::id() as i32;
+            let is_current_process = |window: &SCWindow| {
+                window
+                    .owning_application
+                    .as_ref()
+                    .is_some_and(|app| app.process_id == current_process)
+            };
+
+            let current_app = sc_shareable_content
+                .applications
+                .into_iter()
+                .find(|app| app.process_id == current_process);
+
+            match current_app {
+                // Excluding the application also covers windows it opens during capture
+                Some(app) if options.exclude_current_process && excluded_targets.is_empty() => {
+                    InitParams::DisplayExcludingApplicationsExceptingWindows(
+                        sc_display,
+                        vec![app],
+                        vec![],
+                    )
+                }
+                _ if !options.exclude_current_process && excluded_targets.is_empty() => {
+                    InitParams::Display(sc_display)
+                }
+                // A filter can't mix both, so the process's windows are resolved once
+                _ => {
                     let excluded_windows = sc_shareable_content
                         .windows
                         .into_iter()
                         .filter(|window| {
-                            excluded_targets
-                                .iter()
-                                .any(|excluded_target| match excluded_target {
-                                    Target::Window(excluded_window) => {
-                                        excluded_window.id == window.window_id
+                            (options.exclude_current_process && is_current_process(window))
+                                || excluded_targets.iter().any(|excluded_target| {
+                                    match excluded_target {
+                                        Target::Window(excluded_window) => {
+                                            excluded_window.id == window.window_id
+                                        }
+                                        _ => false,
                                     }
-                                    _ => false,
                                 })
                         })
                         .collect();
@@ -134,25 +199,41 @@ pub fn create_capturer(
     };
 
     let pixel_format = match options.output_type {
-        FrameType::YUVFrame => PixelFormat::YCbCr420v,
+        // The deepest format ScreenCaptureKit delivers
+        _ if options.native_format => PixelFormat::ARGB2101010,
+        FrameType::YUVFrame if is_native_yuv(&options.color_matrix, options.chroma_subsampling) => {
+            PixelFormat::YCbCr420v
+        }
+        // Other matrices and subsamplings are converted from BGRA
+        FrameType::YUVFrame => PixelFormat::ARGB8888,
         FrameType::BGR0 => PixelFormat::ARGB8888,
         FrameType::RGB => PixelFormat::ARGB8888,
         FrameType::BGRAFrame => PixelFormat::ARGB8888,
     };
 
-    let [width, height] = get_output_frame_size(options);
+    let [width, height] = super::fit_max_output_size(options, get_output_frame_size(options));
 
     let stream_config = SCStreamConfiguration {
         width,
         height,
         source_rect,
         pixel_format,
-        shows_cursor: options.show_cursor,
-        minimum_frame_interval: CMTime {
-            value: 1,
-            timescale: options.fps as CMTimeScale,
-            epoch: 0,
-            flags: 1,
+        // Custom cursors replace the system one where scap can draw them
+        shows_cursor: options.show_cursor && !hides_system_cursor(options),
+        // Let ScreenCaptureKit throttle capture itself, a zero interval is the display rate
+        minimum_frame_interval: match options.fps {
+            0 => CMTime::default(),
+            fps => CMTime {
+                value: 1,
+                timescale: fps as CMTimeScale,
+                epoch: 0,
+                flags: 1,
+            },
+        },
+        // The fewest buffers ScreenCaptureKit accepts
+        queue_depth: match options.latency {
+            Latency::LowLatency => 3,
+            Latency::Smooth => Default::default(),
         },
         ..Default::default()
     };
@@ -160,10 +241,12 @@ pub fn create_capturer(
     let mut stream = SCStream::new(filter, stream_config, ErrorHandler { error_flag });
     stream.add_output(Capturer::new(tx), SCStreamOutputType::Screen);
 
-    stream
+    Ok(stream)
 }
 
-pub fn get_output_frame_size(options: &Options) -> [u32; 2] {
+// The size of the captured area in pixels, the points of the crop area
+// multiplied by the scale factor
+pub fn get_captured_size(options: &Options) -> [u32; 2] {
     let target = options
         .target
         .clone()
@@ -171,11 +254,18 @@ pub fn get_output_frame_size(options: &Options) -> [u32; 2] {
 
     let scale_factor = targets::get_scale_factor(&target);
     let source_rect = get_crop_area(options);
+    [
+        (source_rect.size.width as u32) * (scale_factor as u32),
+        (source_rect.size.height as u32) * (scale_factor as u32),
+    ]
+}
+
+pub fn get_output_frame_size(options: &Options) -> [u32; 2] {
+    let source_rect = get_crop_area(options);
 
     // Calculate the output height & width based on the required resolution
-    // Output width and height need to be multiplied by scale (or dpi)
-    let mut output_width = (source_rect.size.width as u32) * (scale_factor as u32);
-    let mut output_height = (source_rect.size.height as u32) * (scale_factor as u32);
+    let [mut output_width, mut output_height] =
+        super::fit_max_dimension(options, get_captured_size(options));
     // 1200x800
     match options.output_resolution {
         Resolution::Captured => {}
@@ -203,7 +293,7 @@ pub fn get_crop_area(options: &Options) -> Area {
 
     let (width, height) = targets::get_target_dimensions(&target);
 
-    options
+    let area = options
         .crop_area
         .as_ref()
         .map(|val| {
@@ -227,13 +317,34 @@ pub fn get_crop_area(options: &Options) -> Area {
                 width: width as f64,
                 height: height as f64,
             },
-        })
+        });
+    let area = match &options.aspect_crop {
+        Some(crop) => fit_aspect_ratio(&area, crop),
+        None => area,
+    };
+    match options.crop_overflow {
+        CropOverflow::Shift => shift_area(
+            &area,
+            &Size {
+                width: width as f64,
+                height: height as f64,
+            },
+        ),
+        _ => area,
+    }
+}
+
+// Whether ScreenCaptureKit encodes the YUV frames itself, as NV12 with BT.709
+fn is_native_yuv(color_matrix: &ColorMatrix, subsampling: ChromaSubsampling) -> bool {
+    *color_matrix == ColorMatrix::BT709 && subsampling == ChromaSubsampling::Yuv420
 }
 
 pub fn process_sample_buffer(
     sample: CMSampleBuffer,
     of_type: SCStreamOutputType,
     output_type: FrameType,
+    color_matrix: &ColorMatrix,
+    subsampling: ChromaSubsampling,
 ) -> Option<Frame> {
     if let SCStreamOutputType::Screen = of_type {
         let frame_status = &sample.frame_status;
@@ -241,10 +352,21 @@ pub fn process_sample_buffer(
         match frame_status {
             SCFrameStatus::Complete | SCFrameStatus::Started => unsafe {
                 return Some(match output_type {
-                    FrameType::YUVFrame => {
+                    FrameType::YUVFrame if is_native_yuv(color_matrix, subsampling) => {
                         let yuvframe = pixelformat::create_yuv_frame(sample).unwrap();
                         Frame::YUVFrame(yuvframe)
                     }
+                    // In the video range ScreenCaptureKit's own YUV frames use
+                    FrameType::YUVFrame => {
+                        let bgraframe = pixelformat::create_bgra_frame(sample).unwrap();
+                        let yuvframe = convert_bgra_to_yuv(
+                            &bgraframe,
+                            color_matrix.clone(),
+                            ColorRange::Limited,
+                            subsampling,
+                        );
+                        Frame::YUVFrame(yuvframe)
+                    }
                     FrameType::RGB => {
                         let rgbframe = pixelformat::create_rgb_frame(sample).unwrap();
                         Frame::RGB(rgbframe)
@@ -267,6 +389,8 @@ pub fn process_sample_buffer(
                         width: 0,
                         height: 0,
                         data: vec![],
+                        origin: RowOrder::TopDown,
+                        color_space: ColorSpace::Unknown,
                     }));
                 }
             }
@@ -276,3 +400,79 @@ pub fn process_sample_buffer(
 
     None
 }
+
+// The sample as ScreenCaptureKit delivered it, for Options::native_format
+pub fn process_raw_sample_buffer(
+    sample: CMSampleBuffer,
+    of_type: SCStreamOutputType,
+) -> Option<Frame> {
+    match (of_type, &sample.frame_status) {
+        (SCStreamOutputType::Screen, SCFrameStatus::Complete | SCFrameStatus::Started) => unsafe {
+            pixelformat::create_raw_frame(sample).map(Frame::Raw)
+        },
+        _ => None,
+    }
+}
+
+// The refresh rate of the display the target is on. Some displays, like
+// older built-in panels, report 0.
+// The time between samples ScreenCaptureKit sends, at the requested rate
+// or the display's
+pub fn get_frame_interval(options: &Options) -> u64 {
+    let refresh_rate = get_refresh_rate(options).unwrap_or(60);
+    let rate = match options.fps {
+        0 => refresh_rate,
+        fps => fps.min(refresh_rate),
+    };
+    1_000_000_000 / rate as u64
+}
+
+// When a screen sample was presented, for complete and idle frames alike
+pub fn get_presentation_time(sample: &CMSampleBuffer, of_type: &SCStreamOutputType) -> Option<u64> {
+    match (of_type, &sample.frame_status) {
+        (
+            SCStreamOutputType::Screen,
+            SCFrameStatus::Complete | SCFrameStatus::Started | SCFrameStatus::Idle,
+        ) => Some(get_pts_in_nanoseconds(sample)),
+        _ => None,
+    }
+}
+
+// How long ago a screen sample was presented
+pub fn get_frame_age(sample: &CMSampleBuffer, of_type: &SCStreamOutputType) -> Option<Duration> {
+    let time = get_presentation_time(sample, of_type)?;
+    Some(Duration::from_nanos(get_host_time().saturating_sub(time)))
+}
+
+pub fn get_refresh_rate(options: &Options) -> Option<u32> {
+    let display = match &options.target {
+        Some(Target::Window(window)) => get_window_display(window.id)?,
+        Some(Target::Display(display)) => display.raw_handle,
+        None => targets::get_main_display().raw_handle,
+    };
+
+    let rate = display.display_mode()?.refresh_rate().round() as u32;
+    (rate > 0).then_some(rate)
+}
+
+pub fn get_cursor_position(options: &Options) -> Option<Point> {
+    let target = options
+        .target
+        .clone()
+        .unwrap_or_else(|| Target::Display(targets::get_main_display()));
+
+    // Window capture follows the window's content, not its place on screen
+    let Target::Display(display) = target else {
+        return None;
+    };
+
+    let source = CGEventSource::new(CGEventSourceStateID::CombinedSessionState).ok()?;
+    let location = CGEvent::new(source).ok()?.location();
+    let bounds = CGDisplay::new(display.id).bounds();
+    let crop_area = get_crop_area(options);
+
+    Some(Point {
+        x: (location.x - bounds.origin.x - crop_area.origin.x) / crop_area.size.width,
+        y: (location.y - bounds.origin.y - crop_area.origin.y) / crop_area.size.height,
+    })
+}
@@ -1,15 +1,23 @@
 use core::slice;
 use core_video_sys::{
-    CVPixelBufferGetBaseAddress, CVPixelBufferGetBaseAddressOfPlane, CVPixelBufferGetBytesPerRow,
-    CVPixelBufferGetBytesPerRowOfPlane, CVPixelBufferGetHeight, CVPixelBufferGetHeightOfPlane,
-    CVPixelBufferGetPlaneCount, CVPixelBufferGetWidth, CVPixelBufferGetWidthOfPlane,
-    CVPixelBufferLockBaseAddress, CVPixelBufferRef, CVPixelBufferUnlockBaseAddress,
+    CVBufferGetAttachment, CVPixelBufferGetBaseAddress, CVPixelBufferGetBaseAddressOfPlane,
+    CVPixelBufferGetBytesPerRow, CVPixelBufferGetBytesPerRowOfPlane, CVPixelBufferGetHeight,
+    CVPixelBufferGetHeightOfPlane, CVPixelBufferGetPlaneCount, CVPixelBufferGetWidth,
+    CVPixelBufferGetWidthOfPlane, CVPixelBufferLockBaseAddress, CVPixelBufferRef,
+    CVPixelBufferUnlockBaseAddress,
 };
 use screencapturekit::{cm_sample_buffer::CMSampleBuffer, sc_types::SCFrameStatus};
 use screencapturekit_sys::cm_sample_buffer_ref::CMSampleBufferGetImageBuffer;
 use std::{ops::Deref, sync::mpsc};
 
-use crate::capturer::{engine::ChannelItem, RawCapturer};
+use super::apple_sys::{
+    kCVImageBufferColorPrimariesKey, kCVImageBufferColorPrimaries_ITU_R_709_2,
+    kCVImageBufferColorPrimaries_P3_D65, CFEqual,
+};
+use crate::{
+    capturer::{engine::ChannelItem, RawCapturer},
+    frame::ColorSpace,
+};
 
 pub struct PixelBuffer {
     display_time: u64,
@@ -185,7 +193,7 @@ impl RawCapturer<'_> {
                 Err(mpsc::RecvTimeoutError::Disconnected) => Err(mpsc::RecvError),
             }?;
 
-            if let Some(frame) = PixelBuffer::new(res) {
+            if let Some(frame) = PixelBuffer::new(res.item) {
                 return Ok(frame);
             }
         }
@@ -212,3 +220,24 @@ pub unsafe fn pixel_buffer_bytes_per_row(pixel_buffer: CVPixelBufferRef) -> usiz
 pub unsafe fn pixel_buffer_display_time(sample_buffer: &CMSampleBuffer) -> u64 {
     sample_buffer.sys_ref.get_presentation_timestamp().value as u64
 }
+
+// ScreenCaptureKit tags buffers with the primaries of the captured display
+pub unsafe fn pixel_buffer_color_space(pixel_buffer: CVPixelBufferRef) -> ColorSpace {
+    let primaries = CVBufferGetAttachment(
+        pixel_buffer as _,
+        kCVImageBufferColorPrimariesKey as _,
+        std::ptr::null_mut(),
+    );
+    if primaries.is_null() {
+        return ColorSpace::Unknown;
+    }
+
+    if CFEqual(primaries as _, kCVImageBufferColorPrimaries_P3_D65) != 0 {
+        ColorSpace::DisplayP3
+    } else if CFEqual(primaries as _, kCVImageBufferColorPrimaries_ITU_R_709_2) != 0 {
+        // sRGB shares the BT.709 primaries
+        ColorSpace::SRGB
+    } else {
+        ColorSpace::Unknown
+    }
+}
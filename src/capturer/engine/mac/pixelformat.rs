@@ -5,10 +5,11 @@ use screencapturekit_sys::cm_sample_buffer_ref::CMSampleBufferGetSampleAttachmen
 
 use super::{
     apple_sys::*,
-    pixel_buffer::{pixel_buffer_bounds, sample_buffer_to_pixel_buffer},
+    pixel_buffer::{pixel_buffer_bounds, pixel_buffer_color_space, sample_buffer_to_pixel_buffer},
 };
 use crate::frame::{
-    convert_bgra_to_rgb, get_cropped_data, remove_alpha_channel, BGRAFrame, BGRFrame, RGBFrame,
+    convert_bgra_to_rgb, get_cropped_data, remove_alpha_channel, BGRAFrame, BGRFrame,
+    ChromaSubsampling, ColorMatrix, ColorRange, RGBFrame, RawFrame, RawPixelFormat, RowOrder,
     YUVFrame,
 };
 use core_graphics_helmer_fork::display::{CFArrayGetCount, CFArrayGetValueAtIndex, CFArrayRef};
@@ -94,6 +95,12 @@ pub unsafe fn create_yuv_frame(sample_buffer: CMSampleBuffer) -> Option<YUVFrame
         luminance_stride: luminance_stride as i32,
         chrominance_bytes,
         chrominance_stride: chrominance_stride as i32,
+        // 420v is video range, and ScreenCaptureKit defaults to the BT.709 matrix
+        color_matrix: ColorMatrix::BT709,
+        color_range: ColorRange::Limited,
+        chroma_subsampling: ChromaSubsampling::Yuv420,
+        origin: RowOrder::TopDown,
+        color_space: pixel_buffer_color_space(pixel_buffer),
     }
     .into()
 }
@@ -128,6 +135,8 @@ pub unsafe fn create_bgr_frame(sample_buffer: CMSampleBuffer) -> Option<BGRFrame
         width: width as i32, // width does not give accurate results - https://stackoverflow.com/questions/19587185/cvpixelbuffergetbytesperrow-for-cvimagebufferref-returns-unexpected-wrong-valu
         height: height as i32,
         data: remove_alpha_channel(cropped_data),
+        origin: RowOrder::TopDown,
+        color_space: pixel_buffer_color_space(pixel_buffer),
     })
 }
 
@@ -159,6 +168,36 @@ pub unsafe fn create_bgra_frame(sample_buffer: CMSampleBuffer) -> Option<BGRAFra
         width: width as i32, // width does not give accurate results - https://stackoverflow.com/questions/19587185/cvpixelbuffergetbytesperrow-for-cvimagebufferref-returns-unexpected-wrong-valu
         height: height as i32,
         data,
+        origin: RowOrder::TopDown,
+        color_space: pixel_buffer_color_space(pixel_buffer),
+    })
+}
+
+// The 'l10r' pixels as they are, rows padded to the buffer's alignment
+pub unsafe fn create_raw_frame(sample_buffer: CMSampleBuffer) -> Option<RawFrame> {
+    let pixel_buffer = sample_buffer_to_pixel_buffer(&sample_buffer);
+    let display_time = get_pts_in_nanoseconds(&sample_buffer);
+
+    CVPixelBufferLockBaseAddress(pixel_buffer, 0);
+
+    let (width, height) = pixel_buffer_bounds(pixel_buffer);
+    if width == 0 || height == 0 {
+        return None;
+    }
+
+    let base_address = CVPixelBufferGetBaseAddress(pixel_buffer);
+    let bytes_per_row = CVPixelBufferGetBytesPerRow(pixel_buffer);
+    let data = slice::from_raw_parts(base_address as *mut u8, bytes_per_row * height).to_vec();
+
+    CVPixelBufferUnlockBaseAddress(pixel_buffer, 0);
+
+    Some(RawFrame {
+        display_time,
+        width: width as i32,
+        height: height as i32,
+        format: RawPixelFormat::Argb2101010,
+        data,
+        color_space: pixel_buffer_color_space(pixel_buffer),
     })
 }
 
@@ -192,5 +231,7 @@ pub unsafe fn create_rgb_frame(sample_buffer: CMSampleBuffer) -> Option<RGBFrame
         width: width as i32, // width does not give accurate results - https://stackoverflow.com/questions/19587185/cvpixelbuffergetbytesperrow-for-cvimagebufferref-returns-unexpected-wrong-valu
         height: height as i32,
         data: convert_bgra_to_rgb(cropped_data),
+        origin: RowOrder::TopDown,
+        color_space: pixel_buffer_color_space(pixel_buffer),
     })
 }
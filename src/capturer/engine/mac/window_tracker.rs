@@ -0,0 +1,144 @@
+use std::sync::mpsc;
+
+use core_graphics_helmer_fork::{
+    display::CGDisplay,
+    geometry::{CGPoint, CGRect},
+    window::{copy_window_info, kCGWindowBounds, kCGWindowListOptionIncludingWindow, CGWindowID},
+};
+
+use super::apple_sys::{
+    CFDictionaryGetValue, CFDictionaryRef, CGRectMakeWithDictionaryRepresentation,
+};
+use crate::{
+    capturer::{
+        shift_area, Area, CapturerEvent, CropOverflow, CursorStyle, Options, Point, Size,
+        WindowContentMode,
+    },
+    frame::Frame,
+    targets::{Display, Target},
+};
+
+// The bounds in a window list entry, in global display points
+pub(crate) fn get_description_bounds(description: CFDictionaryRef) -> Option<CGRect> {
+    unsafe {
+        let bounds = CFDictionaryGetValue(description, kCGWindowBounds as *const _);
+        if bounds.is_null() {
+            return None;
+        }
+
+        let mut rect = CGRect::default();
+        (CGRectMakeWithDictionaryRepresentation(bounds as CFDictionaryRef, &mut rect) != 0)
+            .then_some(rect)
+    }
+}
+
+// Where a window is on screen, in global display points
+fn get_window_bounds(window: CGWindowID) -> Option<CGRect> {
+    let info = copy_window_info(kCGWindowListOptionIncludingWindow, window)?;
+    get_description_bounds(*info.get_all_values().first()? as CFDictionaryRef)
+}
+
+// The display holding a window's center
+pub(crate) fn get_window_display(window: CGWindowID) -> Option<CGDisplay> {
+    let bounds = get_window_bounds(window)?;
+    let center = CGPoint::new(
+        bounds.origin.x + bounds.size.width / 2.0,
+        bounds.origin.y + bounds.size.height / 2.0,
+    );
+    CGDisplay::active_displays()
+        .ok()?
+        .into_iter()
+        .map(CGDisplay::new)
+        .find(|display| display.bounds().contains(&center))
+}
+
+/// Follows a window for [WindowContentMode::ScreenRegion] by capturing the
+/// display it's on and cropping every frame to where the window is
+pub struct WindowTracker {
+    window: CGWindowID,
+    // Bounds of the captured display in global points
+    display: CGRect,
+    bounds: Option<Area>,
+    // Whether crops past the display are moved back onto it, for
+    // CropOverflow::Shift
+    shift: bool,
+}
+
+impl WindowTracker {
+    /// Returns the tracker and the options to capture the window's display
+    /// with, or None if `options` don't ask for a tracked window
+    pub fn new(options: &Options) -> Option<(WindowTracker, Options)> {
+        let Some(Target::Window(window)) = &options.target else {
+            return None;
+        };
+        if options.window_content_mode != WindowContentMode::ScreenRegion {
+            return None;
+        }
+
+        // The capture stays on the window's display
+        let display = get_window_display(window.id)?;
+
+        let mut display_options = options.clone();
+        display_options.target = Some(Target::Display(Display {
+            id: display.id,
+            title: window.title.clone(),
+            raw_handle: display,
+            is_virtual: false,
+            span: None,
+        }));
+        display_options.crop_area = None;
+        // Custom cursors are positioned relative to the window, which the
+        // display capture knows nothing about
+        display_options.cursor_style = CursorStyle::System;
+
+        let tracker = WindowTracker {
+            window: window.id,
+            display: display.bounds(),
+            bounds: None,
+            shift: options.crop_overflow == CropOverflow::Shift,
+        };
+        Some((tracker, display_options))
+    }
+
+    /// Crops a frame of the display to the window. Returns None while the
+    /// window is closed or off the display. YUV frames can't be cropped and
+    /// are delivered whole.
+    pub fn crop(&mut self, frame: Frame, events: &mpsc::Sender<CapturerEvent>) -> Option<Frame> {
+        let window = get_window_bounds(self.window)?;
+
+        // Frames are scaled to the output size, so map points through it
+        let (width, height) = frame.size();
+        let scale_x = width as f64 / self.display.size.width;
+        let scale_y = height as f64 / self.display.size.height;
+        let bounds = Area {
+            origin: Point {
+                x: (window.origin.x - self.display.origin.x) * scale_x,
+                y: (window.origin.y - self.display.origin.y) * scale_y,
+            },
+            size: Size {
+                width: window.size.width * scale_x,
+                height: window.size.height * scale_y,
+            },
+        };
+
+        if self.bounds.as_ref() != Some(&bounds) {
+            let _ = events.send(CapturerEvent::WindowBoundsChanged(bounds.clone()));
+            self.bounds = Some(bounds.clone());
+        }
+
+        let crop = match self.shift {
+            true => shift_area(
+                &bounds,
+                &Size {
+                    width: width as f64,
+                    height: height as f64,
+                },
+            ),
+            false => bounds,
+        };
+        match frame {
+            Frame::YUVFrame(_) => Some(frame),
+            _ => frame.cropped(&crop),
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::{
+    ffi::CStr,
+    os::raw::c_char,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use core_graphics_helmer_fork::{
+    display::CGDisplay,
+    window::{
+        copy_window_info, kCGWindowIsOnscreen, kCGWindowListOptionIncludingWindow, kCGWindowName,
+        CGWindowID,
+    },
+};
+use objc::{msg_send, sel, sel_impl};
+
+use super::{
+    apple_sys::{
+        id, CFDictionaryGetValue, CFDictionaryRef, CMClockGetHostTimeClock, CMClockGetTime,
+        CMTimeGetSeconds,
+    },
+    get_description_bounds,
+};
+use crate::{
+    capturer::{
+        engine::window_watch::WatchedWindow, Area, CapturerEvent, Point, Size, WindowState,
+    },
+    targets::Window,
+};
+
+// The window list has no change notifications, but querying a single window
+// is cheap enough to do this often
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// The same clock as the frames' presentation timestamps
+pub(crate) fn get_host_time() -> u64 {
+    unsafe {
+        (CMTimeGetSeconds(CMClockGetTime(CMClockGetHostTimeClock())) * 1_000_000_000.).trunc()
+            as u64
+    }
+}
+
+// A window's current title, or None once it's closed
+pub(crate) fn get_window_title(window: CGWindowID) -> Option<String> {
+    get_window_snapshot(window).map(|(title, _, _)| title)
+}
+
+// A window's title, bounds in global points and state, or None once it's closed
+fn get_window_snapshot(window: CGWindowID) -> Option<(String, Area, WindowState)> {
+    let info = copy_window_info(kCGWindowListOptionIncludingWindow, window)?;
+    let description = *info.get_all_values().first()? as CFDictionaryRef;
+    let bounds = get_description_bounds(description)?;
+
+    // Strings and booleans are toll-free bridged to NSString and NSNumber
+    let (title, on_screen) = unsafe {
+        let name = CFDictionaryGetValue(description, kCGWindowName as *const _) as id;
+        let title = match name.is_null() {
+            true => String::new(),
+            false => {
+                let utf8: *const c_char = msg_send![name, UTF8String];
+                CStr::from_ptr(utf8).to_string_lossy().into_owned()
+            }
+        };
+        let on_screen = CFDictionaryGetValue(description, kCGWindowIsOnscreen as *const _) as id;
+        let on_screen: bool = !on_screen.is_null() && msg_send![on_screen, boolValue];
+        (title, on_screen)
+    };
+
+    // Windows on other spaces or of hidden apps are off screen too
+    let fullscreen = || {
+        CGDisplay::active_displays()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|display| CGDisplay::new(display).bounds())
+            .any(|display| {
+                (
+                    display.origin.x,
+                    display.origin.y,
+                    display.size.width,
+                    display.size.height,
+                ) == (
+                    bounds.origin.x,
+                    bounds.origin.y,
+                    bounds.size.width,
+                    bounds.size.height,
+                )
+            })
+    };
+    let state = match on_screen {
+        false => WindowState::Minimized,
+        true if fullscreen() => WindowState::Fullscreen,
+        true => WindowState::Normal,
+    };
+
+    let bounds = Area {
+        origin: Point {
+            x: bounds.origin.x,
+            y: bounds.origin.y,
+        },
+        size: Size {
+            width: bounds.size.width,
+            height: bounds.size.height,
+        },
+    };
+    Some((title, bounds, state))
+}
+
+/// Reports title, bounds and state changes of a window until dropped, see
+/// [Options::watch_window](crate::capturer::Options::watch_window)
+pub struct WindowWatcher {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl WindowWatcher {
+    pub fn new(window: &Window, events: mpsc::Sender<CapturerEvent>) -> Self {
+        let (target_id, window) = (window.id, window.raw_handle);
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = thread::spawn({
+            let stop = stop.clone();
+            move || {
+                let mut watched = WatchedWindow::new(target_id);
+                while !stop.load(Ordering::Relaxed) {
+                    if let Some((title, bounds, state)) = get_window_snapshot(window) {
+                        for event in watched.update(title, bounds, state, None, get_host_time()) {
+                            let _ = events.send(event);
+                        }
+                    }
+                    thread::park_timeout(POLL_INTERVAL);
+                }
+            }
+        });
+
+        WindowWatcher {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for WindowWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
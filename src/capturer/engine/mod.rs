@@ -1,7 +1,38 @@
-use std::sync::mpsc;
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime},
+};
 
-use super::Options;
-use crate::frame::Frame;
+#[cfg(target_os = "macos")]
+use super::stats::PresentationGaps;
+use super::{
+    edge_trim::EdgeTrimmer,
+    follow::ActionFollower,
+    handoff,
+    negotiated::{
+        get_exclusion_downgrade, negotiate, pick_output_type, Downgrade, NegotiatedConfig,
+    },
+    schedule::Scheduler,
+    stats::CaptureStats,
+    thumbnail::{get_thumbnail_size, Thumbnail, ThumbnailStream},
+    transform::TransformChain,
+    trigger::Trigger,
+    warmup::Warmup,
+    Area, CapturerBuildError, CapturerEvent, CursorStyle, FrameRateCap, GpuReplayUsage, Latency,
+    Options, OutputColorSpace, Point, ProcessExclusion, WindowSubregion,
+};
+#[cfg(not(target_os = "windows"))]
+use super::{CallbackPanic, Size};
+use crate::frame::{
+    composite_cursor, convert_p3_to_srgb, draw_cursor_highlight, embed_watermark, extract_regions,
+    frame_checksum, get_fitted_size, BufferPool, DeltaEncoder, Frame, FrameChecksum, FrameMotion,
+    FrameType, MotionEstimator, ScanlineEncoder, Watermark,
+};
+#[cfg(not(target_os = "windows"))]
+use crate::targets::Target;
 
 #[cfg(target_os = "macos")]
 pub mod mac;
@@ -12,6 +43,9 @@ mod win;
 #[cfg(target_os = "linux")]
 mod linux;
 
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+mod window_watch;
+
 #[cfg(target_os = "macos")]
 pub type ChannelItem = (
     screencapturekit::cm_sample_buffer::CMSampleBuffer,
@@ -20,7 +54,158 @@ pub type ChannelItem = (
 #[cfg(not(target_os = "macos"))]
 pub type ChannelItem = Frame;
 
+// A captured item and how long after the compositor composed it scap
+// acquired it, None where the platform doesn't tell when that was. See
+// Capturer::last_acquisition_latency.
+pub struct Acquired {
+    pub item: ChannelItem,
+    pub latency: Option<Duration>,
+}
+
+// Whether captured items go on to the capturer. Warmed up sessions capture
+// with the gate closed until they're started, and past their idle deadline
+// the platform capture is expected to stop.
+#[derive(Debug)]
+struct Gate {
+    open: AtomicBool,
+    idle_deadline: Mutex<Option<Instant>>,
+    // Items let through since the engine was created
+    sent: AtomicU64,
+}
+
+/// Sends captured items to the capturer, see [Engine::warm_up]
+#[derive(Debug, Clone)]
+pub struct FrameSender {
+    tx: handoff::Sender<Acquired>,
+    gate: Arc<Gate>,
+}
+
+impl FrameSender {
+    pub(crate) fn new(tx: handoff::Sender<Acquired>) -> Self {
+        FrameSender {
+            tx,
+            gate: Arc::new(Gate {
+                open: AtomicBool::new(true),
+                idle_deadline: Mutex::new(None),
+                sent: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    // Items sent while a warmed up session waits to be started are dropped
+    pub fn send(
+        &self,
+        item: ChannelItem,
+        latency: Option<Duration>,
+    ) -> Result<(), mpsc::SendError<Acquired>> {
+        if !self.gate.open.load(Ordering::Acquire) {
+            return Ok(());
+        }
+        // Counted first, so an item is never on the channel uncounted
+        self.gate.sent.fetch_add(1, Ordering::AcqRel);
+        self.tx.send(Acquired { item, latency })
+    }
+
+    fn sent(&self) -> u64 {
+        self.gate.sent.load(Ordering::Acquire)
+    }
+
+    // Whether a warmed up session was left idle for too long
+    pub fn is_expired(&self) -> bool {
+        !self.gate.open.load(Ordering::Acquire)
+            && self
+                .gate
+                .idle_deadline
+                .lock()
+                .unwrap()
+                .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    fn close(&self, idle_deadline: Instant) {
+        *self.gate.idle_deadline.lock().unwrap() = Some(idle_deadline);
+        self.gate.open.store(false, Ordering::Release);
+    }
+
+    fn open(&self) {
+        *self.gate.idle_deadline.lock().unwrap() = None;
+        self.gate.open.store(true, Ordering::Release);
+    }
+}
+
+// Named regions delivered instead of whole frames, see Capturer::set_regions
+struct RegionsOutput {
+    regions: Vec<(String, Area)>,
+    keep_source: bool,
+}
+
+// The refresh rate of the display of the target in `options`, None where
+// it's unknown
+pub(crate) fn get_refresh_rate(options: &Options) -> Option<u32> {
+    #[cfg(target_os = "macos")]
+    return mac::get_refresh_rate(options);
+
+    #[cfg(target_os = "windows")]
+    return win::get_refresh_rate(options);
+
+    #[cfg(target_os = "linux")]
+    return {
+        let _ = options;
+        None
+    };
+}
+
 pub fn get_output_frame_size(options: &Options) -> [u32; 2] {
+    if let Some(grayscale) = &options.grayscale {
+        return [grayscale.width, grayscale.height];
+    }
+    fit_max_output_size(options, get_unlimited_output_size(options))
+}
+
+// Fits `size` in Options::max_output_size, keeping its aspect ratio
+pub(crate) fn fit_max_output_size(options: &Options, size: [u32; 2]) -> [u32; 2] {
+    match options.max_output_size {
+        Some([max_width, max_height]) if size[0] > 0 && size[1] > 0 => {
+            let (width, height) =
+                get_fitted_size(size[0] as usize, size[1] as usize, max_width, max_height);
+            [width as u32, height as u32]
+        }
+        _ => size,
+    }
+}
+
+// Caps the longest side of the captured `size` at Options::max_dimension,
+// keeping its aspect ratio
+pub(crate) fn fit_max_dimension(options: &Options, size: [u32; 2]) -> [u32; 2] {
+    match options.max_dimension {
+        Some(max) if size[0] > 0 && size[1] > 0 => {
+            let (width, height) = get_fitted_size(size[0] as usize, size[1] as usize, max, max);
+            [width as u32, height as u32]
+        }
+        _ => size,
+    }
+}
+
+// The size of the captured area in pixels, before any scaling
+fn get_captured_size(options: &Options) -> [u32; 2] {
+    #[cfg(target_os = "macos")]
+    {
+        mac::get_captured_size(options)
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        win::get_captured_size(options)
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let _ = options;
+        return [0, 0];
+    }
+}
+
+// The output size before Options::max_output_size is applied
+fn get_unlimited_output_size(options: &Options) -> [u32; 2] {
     #[cfg(target_os = "macos")]
     {
         mac::get_output_frame_size(options)
@@ -38,8 +223,125 @@ pub fn get_output_frame_size(options: &Options) -> [u32; 2] {
     }
 }
 
+// Where the cursor is as a fraction of the captured area, or None if that
+// can't be determined for this target
+fn get_cursor_position(options: &Options) -> Option<Point> {
+    #[cfg(target_os = "macos")]
+    {
+        mac::get_cursor_position(options)
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        win::get_cursor_position(options)
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        // The portal doesn't expose the cursor position
+        let _ = options;
+        return None;
+    }
+}
+
+// Whether the OS leaves out the cursor for a custom one drawn here
+fn hides_system_cursor(options: &Options) -> bool {
+    #[cfg(target_os = "macos")]
+    {
+        mac::hides_system_cursor(options)
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        win::hides_system_cursor(options)
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let _ = options;
+        return false;
+    }
+}
+
+// Whether the effective `old` options can change to `new` ones, which differ
+// at most in what Capturer::reconfigure changes, while capture runs. Cursor
+// styles are drawn here unless the OS has to stop drawing its own, and
+// Windows paces frames itself and only reports the output resolution.
+fn is_live_change(old: &Options, new: &Options) -> bool {
+    let same_cursor =
+        old.show_cursor == new.show_cursor && hides_system_cursor(old) == hides_system_cursor(new);
+    let same_rate = cfg!(target_os = "windows")
+        || (old.fps == new.fps && old.output_resolution == new.output_resolution);
+    same_cursor && same_rate && old.crop_area == new.crop_area
+}
+
+// The options the platform engines actually run with. Low latency capture
+// drops pacing and everything that processes frames after capture, grayscale
+// frames skip what doesn't survive the conversion.
+fn effective_options(options: &Options) -> Options {
+    let mut options = options.clone();
+    if let Some(output_type) = pick_output_type(&options) {
+        options.output_type = output_type;
+    }
+    if options.latency == Latency::LowLatency {
+        options.fps = 0;
+        options.color_space = OutputColorSpace::Native;
+        options.cursor_style = CursorStyle::System;
+    }
+    if options.grayscale.is_some() {
+        options.color_space = OutputColorSpace::Native;
+        options.cursor_style = CursorStyle::System;
+    }
+    if !matches!(options.window_subregion, WindowSubregion::Whole) {
+        options.crop_area = None;
+    }
+    options
+}
+
 pub struct Engine {
     options: Options,
+    // As passed in, before effective_options
+    requested: Options,
+    tx: FrameSender,
+    // Whether the platform capture runs for a warm up
+    warm: bool,
+    delta: Option<Mutex<DeltaEncoder>>,
+    scanlines: Option<Mutex<ScanlineEncoder>>,
+    trigger: Option<Mutex<Trigger>>,
+    warmup: Mutex<Warmup>,
+    edge_trim: Option<Mutex<EdgeTrimmer>>,
+    follow: Option<Mutex<ActionFollower>>,
+    motion: Option<Mutex<MotionEstimator>>,
+    schedule: Option<Mutex<Scheduler>>,
+    thumbnails: Option<Mutex<ThumbnailStream>>,
+    transforms: Option<TransformChain>,
+    regions: Mutex<Option<RegionsOutput>>,
+    // Of the last delivered frame, for Options::checksum
+    last_checksum: Mutex<Option<FrameChecksum>>,
+    // Buffers returned by PooledFrames, see Options::buffer_pool
+    pool: BufferPool,
+    // Frames returned since capture started
+    sequence: AtomicU64,
+    // What the requested options come down to before capture starts
+    negotiated: NegotiatedConfig,
+    events: mpsc::Sender<CapturerEvent>,
+
+    // Windows resolves subregions in its capture handler, elsewhere frames
+    // are cropped after capture
+    // The last reported subregion, None until the first frame
+    #[cfg(not(target_os = "windows"))]
+    subregion: Mutex<Option<Option<Area>>>,
+    // Set once the subregion callback panicked with CallbackPanic::Stop
+    #[cfg(not(target_os = "windows"))]
+    callback_stopped: std::sync::atomic::AtomicBool,
+
+    #[cfg(target_os = "macos")]
+    window_tracker: Option<Mutex<mac::WindowTracker>>,
+    #[cfg(target_os = "macos")]
+    window_watcher: Option<mac::WindowWatcher>,
+    // Presentation times of every sample, idle ones included
+    #[cfg(target_os = "macos")]
+    presentation: Mutex<PresentationGaps>,
 
     #[cfg(target_os = "macos")]
     mac: screencapturekit::sc_stream::SCStream,
@@ -54,59 +356,267 @@ pub struct Engine {
 }
 
 impl Engine {
-    pub fn new(options: &Options, tx: mpsc::Sender<ChannelItem>) -> Engine {
+    pub(crate) fn new(
+        options: &Options,
+        tx: handoff::Sender<Acquired>,
+        events: mpsc::Sender<CapturerEvent>,
+    ) -> Result<Engine, CapturerBuildError> {
+        Self::create(options, FrameSender::new(tx), events)
+    }
+
+    fn create(
+        options: &Options,
+        tx: FrameSender,
+        events: mpsc::Sender<CapturerEvent>,
+    ) -> Result<Engine, CapturerBuildError> {
+        let negotiated = negotiate(options);
+        let requested = options.clone();
+        let options = &effective_options(options);
+        let delta = options.delta.map(|delta| {
+            Mutex::new(DeltaEncoder::new(
+                delta.tile_size,
+                delta.keyframe_interval,
+                delta.reference,
+            ))
+        });
+        let scanlines = match options.delta {
+            Some(_) => None,
+            None => options.scanline_patches.map(|scanlines| {
+                Mutex::new(ScanlineEncoder::new(
+                    scanlines.max_changed_fraction,
+                    scanlines.full_frame_interval,
+                ))
+            }),
+        };
+        let trigger = options
+            .trigger
+            .clone()
+            .map(|trigger| Mutex::new(Trigger::new(trigger)));
+        let warmup = Mutex::new(Warmup::new(options.warmup));
+        let edge_trim = options
+            .edge_trim
+            .map(|trim| Mutex::new(EdgeTrimmer::new(trim)));
+        let follow = options
+            .follow_action
+            .map(|follow| Mutex::new(ActionFollower::new(follow)));
+        let motion = options
+            .estimate_motion
+            .then(|| Mutex::new(MotionEstimator::default()));
+        let schedule = options
+            .schedule
+            .clone()
+            .map(|schedule| Mutex::new(Scheduler::new(schedule, options.callback_panic)));
+        let thumbnails = options
+            .thumbnails
+            .map(|thumbnails| Mutex::new(ThumbnailStream::new(thumbnails)));
+        let transforms = TransformChain::new(&options.transforms, options.callback_panic);
+        let pool = BufferPool::new(options.buffer_pool.unwrap_or(0));
+
         #[cfg(target_os = "macos")]
         {
             let error_flag = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
-            let mac = mac::create_capturer(options, tx, error_flag.clone());
+            let (window_tracker, capture_options) = match mac::WindowTracker::new(options) {
+                Some((tracker, display_options)) => (Some(Mutex::new(tracker)), display_options),
+                None => (None, options.clone()),
+            };
+            let mac = mac::create_capturer(&capture_options, tx.clone(), error_flag.clone())?;
 
-            Engine {
+            Ok(Engine {
                 mac,
                 error_flag,
                 options: (*options).clone(),
-            }
+                requested,
+                tx,
+                warm: false,
+                delta,
+                scanlines,
+                trigger,
+                warmup,
+                edge_trim,
+                follow,
+                motion,
+                schedule,
+                thumbnails,
+                transforms,
+                regions: Mutex::new(None),
+                last_checksum: Mutex::new(None),
+                pool,
+                sequence: AtomicU64::new(0),
+                negotiated,
+                events,
+                subregion: Mutex::new(None),
+                callback_stopped: Default::default(),
+                window_tracker,
+                window_watcher: None,
+                presentation: Mutex::new(PresentationGaps::new(mac::get_frame_interval(options))),
+            })
         }
 
         #[cfg(target_os = "windows")]
         {
-            let win = win::create_capturer(&options, tx);
-            return Engine {
+            let win = win::create_capturer(&options, tx.clone(), events.clone(), pool.clone())?;
+            return Ok(Engine {
                 win,
                 options: (*options).clone(),
-            };
+                requested,
+                tx,
+                warm: false,
+                delta,
+                scanlines,
+                trigger,
+                warmup,
+                edge_trim,
+                follow,
+                motion,
+                schedule,
+                thumbnails,
+                transforms,
+                regions: Mutex::new(None),
+                last_checksum: Mutex::new(None),
+                pool,
+                sequence: AtomicU64::new(0),
+                negotiated,
+                events,
+            });
         }
 
         #[cfg(target_os = "linux")]
         {
-            let linux = linux::create_capturer(&options, tx);
-            return Engine {
+            #[cfg(not(test))]
+            let linux = linux::create_capturer(&options, tx.clone());
+            // Tests send the frames through the engine's sender
+            #[cfg(test)]
+            let linux = linux::LinuxCapturer::detached();
+            return Ok(Engine {
                 linux,
                 options: (*options).clone(),
-            };
+                requested,
+                tx,
+                warm: false,
+                delta,
+                scanlines,
+                trigger,
+                warmup,
+                edge_trim,
+                follow,
+                motion,
+                schedule,
+                thumbnails,
+                transforms,
+                regions: Mutex::new(None),
+                last_checksum: Mutex::new(None),
+                pool,
+                sequence: AtomicU64::new(0),
+                negotiated,
+                events,
+                subregion: Mutex::new(None),
+                callback_stopped: Default::default(),
+            });
         }
     }
 
-    pub fn start(&mut self) {
+    // Returns whether the session was warmed up. Sessions left idle past
+    // their timeout are restarted.
+    pub fn start(&mut self) -> Result<bool, CapturerBuildError> {
+        let warm = std::mem::take(&mut self.warm);
+        let warm_start = match warm && self.tx.is_expired() {
+            true => {
+                self.stop_session();
+                false
+            }
+            false => warm,
+        };
+
+        self.tx.open();
+        if !warm_start {
+            self.start_session()?;
+        }
+        if let Some(thumbnails) = &self.thumbnails {
+            thumbnails
+                .lock()
+                .unwrap()
+                .restart(Instant::now(), warm_start);
+        }
+        self.sequence.store(0, Ordering::Relaxed);
+        *self.last_checksum.lock().unwrap() = None;
+        self.warmup.lock().unwrap().reset();
+        if let Some(edge_trim) = &self.edge_trim {
+            edge_trim.lock().unwrap().reset();
+        }
+        if let Some(follow) = &self.follow {
+            follow.lock().unwrap().reset();
+        }
+        if let Some(motion) = &self.motion {
+            motion.lock().unwrap().reset();
+        }
+        #[cfg(target_os = "macos")]
+        {
+            let interval = mac::get_frame_interval(&self.options);
+            *self.presentation.lock().unwrap() = PresentationGaps::new(interval);
+        }
+
+        let downgrades = self.get_negotiated_config().downgrades;
+        if !downgrades.is_empty() {
+            let _ = self
+                .events
+                .send(CapturerEvent::ConfigDowngraded(downgrades));
+        }
+        Ok(warm_start)
+    }
+
+    /// Start the platform capture without delivering frames, so starting
+    /// later is quick. It's stopped once idle for `idle_timeout`, right away
+    /// on Windows and at the next start elsewhere.
+    pub fn warm_up(&mut self, idle_timeout: Duration) -> Result<(), CapturerBuildError> {
+        self.tx.close(Instant::now() + idle_timeout);
+        if !self.warm {
+            self.start_session()?;
+            self.warm = true;
+        }
+        Ok(())
+    }
+
+    pub fn cancel_warm_up(&mut self) {
+        if std::mem::take(&mut self.warm) {
+            self.stop_session();
+            self.tx.open();
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.warm = false;
+        self.stop_session();
+    }
+
+    fn start_session(&mut self) -> Result<(), CapturerBuildError> {
         #[cfg(target_os = "macos")]
         {
             // self.mac.add_output(Capturer::new(tx));
             self.mac.start_capture().expect("Failed to start capture");
+            if let (true, Some(Target::Window(window))) =
+                (self.options.watch_window, &self.options.target)
+            {
+                self.window_watcher = Some(mac::WindowWatcher::new(window, self.events.clone()));
+            }
+            Ok(())
         }
 
         #[cfg(target_os = "windows")]
         {
-            self.win.start_capture();
+            self.win.start_capture()
         }
 
         #[cfg(target_os = "linux")]
         {
             self.linux.start_capture();
+            Ok(())
         }
     }
 
-    pub fn stop(&mut self) {
+    fn stop_session(&mut self) {
         #[cfg(target_os = "macos")]
         {
+            self.window_watcher = None;
             self.mac.stop_capture().expect("Failed to stop capture");
         }
 
@@ -121,16 +631,581 @@ impl Engine {
         }
     }
 
+    /// Applies `options`, which differ from the ones in use at most in what
+    /// [Capturer::reconfigure](super::Capturer::reconfigure) changes. Returns
+    /// whether that was done live, otherwise the platform capture is
+    /// recreated and restarted if it ran. Frame processing, like the delta
+    /// encoder and the trigger, carries on either way.
+    pub fn reconfigure(
+        &mut self,
+        options: &Options,
+        capturing: bool,
+    ) -> Result<bool, CapturerBuildError> {
+        let size = get_output_frame_size(&self.options);
+        let effective = effective_options(options);
+        let live = is_live_change(&self.options, &effective);
+
+        if live {
+            #[cfg(target_os = "windows")]
+            self.win.set_fps(&effective);
+            self.options = effective;
+            self.requested = options.clone();
+            self.negotiated = negotiate(options);
+        } else {
+            let engine = Self::create(options, self.tx.clone(), self.events.clone())?;
+            let running = capturing || self.warm;
+            if running {
+                self.stop_session();
+            }
+            let old = std::mem::replace(self, engine);
+            self.warm = old.warm;
+            self.delta = old.delta;
+            self.scanlines = old.scanlines;
+            self.trigger = old.trigger;
+            self.motion = old.motion;
+            self.schedule = old.schedule;
+            self.thumbnails = old.thumbnails;
+            self.regions = old.regions;
+            self.sequence = old.sequence;
+            if running {
+                self.start_session()?;
+            }
+        }
+
+        let [width, height] = get_output_frame_size(&self.options);
+        if [width, height] != size {
+            let _ = self
+                .events
+                .send(CapturerEvent::FormatChanged { width, height });
+        }
+        Ok(live)
+    }
+
+    // The options as passed in, see Capturer::reconfigure
+    pub fn get_requested_options(&self) -> &Options {
+        &self.requested
+    }
+
+    // Sends to the capturer as the platform capture does
+    #[cfg(all(test, target_os = "linux"))]
+    pub fn get_sender(&self) -> FrameSender {
+        self.tx.clone()
+    }
+
     pub fn get_output_frame_size(&mut self) -> [u32; 2] {
         get_output_frame_size(&self.options)
     }
 
+    pub fn get_output_type(&self) -> FrameType {
+        self.options.output_type
+    }
+
+    pub fn get_frame_rate_cap(&self) -> FrameRateCap {
+        if self.options.fps == 0 {
+            return FrameRateCap::Unlimited;
+        }
+
+        #[cfg(target_os = "windows")]
+        return FrameRateCap::Client(self.options.fps);
+
+        #[cfg(not(target_os = "windows"))]
+        return FrameRateCap::Os(self.options.fps);
+    }
+
+    pub fn get_max_frame_rate(&self) -> Option<u32> {
+        get_refresh_rate(&self.options)
+    }
+
+    pub fn get_compositor_drops(&self) -> Option<u64> {
+        #[cfg(target_os = "macos")]
+        return Some(self.presentation.lock().unwrap().missed());
+
+        #[cfg(not(target_os = "macos"))]
+        return None;
+    }
+
+    pub fn get_process_exclusion(&self) -> ProcessExclusion {
+        if !self.options.exclude_current_process {
+            return ProcessExclusion::Off;
+        }
+
+        #[cfg(target_os = "macos")]
+        return ProcessExclusion::Excluded;
+
+        #[cfg(target_os = "windows")]
+        return self.win.get_process_exclusion();
+
+        #[cfg(target_os = "linux")]
+        return ProcessExclusion::Unsupported;
+    }
+
+    pub fn read_replay(&self, duration: Duration) -> Vec<Frame> {
+        #[cfg(target_os = "windows")]
+        let mut frames = self.win.read_replay(duration);
+
+        #[cfg(not(target_os = "windows"))]
+        let mut frames: Vec<Frame> = {
+            let _ = duration;
+            Vec::new()
+        };
+
+        if let Some(timebase) = self.options.timestamp_base {
+            for frame in &mut frames {
+                frame.rescale_display_time(timebase);
+            }
+        }
+        frames
+    }
+
+    pub fn get_last_motion(&self) -> Option<FrameMotion> {
+        self.motion
+            .as_ref()
+            .and_then(|motion| motion.lock().unwrap().last())
+    }
+
+    pub fn get_last_checksum(&self) -> Option<FrameChecksum> {
+        *self.last_checksum.lock().unwrap()
+    }
+
+    pub fn get_followed_area(&self) -> Option<Area> {
+        self.follow
+            .as_ref()
+            .and_then(|follow| follow.lock().unwrap().last())
+    }
+
+    // Where the focused window is in the frames, for Options::follow_action
+    fn get_focused_bounds(&self) -> Option<Area> {
+        #[cfg(target_os = "windows")]
+        return self.win.focused_bounds();
+
+        #[cfg(not(target_os = "windows"))]
+        return None;
+    }
+
+    pub fn get_replay_usage(&self) -> Option<GpuReplayUsage> {
+        #[cfg(target_os = "windows")]
+        return self.win.replay_usage();
+
+        #[cfg(not(target_os = "windows"))]
+        return None;
+    }
+
+    pub fn get_effective_source_rect(&self) -> Option<Area> {
+        #[cfg(target_os = "macos")]
+        return Some(mac::get_crop_area(&self.options));
+
+        #[cfg(target_os = "windows")]
+        return self.win.effective_source_rect();
+
+        #[cfg(target_os = "linux")]
+        return None;
+    }
+
+    pub fn get_target_title(&self) -> Option<String> {
+        #[cfg(not(target_os = "linux"))]
+        return match &self.options.target {
+            Some(target) => crate::targets::get_target_title(target),
+            None => Some(crate::targets::get_main_display().title),
+        };
+
+        // The portal doesn't tell what the user picked
+        #[cfg(target_os = "linux")]
+        return None;
+    }
+
+    pub fn get_negotiated_config(&self) -> NegotiatedConfig {
+        let mut config = self.negotiated.clone();
+        config.frame_size = get_output_frame_size(&self.options);
+        let [width, height] = get_unlimited_output_size(&self.options);
+        if self.options.grayscale.is_none() && config.frame_size != [width, height] {
+            config.downgrades.push(Downgrade {
+                option: "output_resolution".to_string(),
+                reason: format!("downscaled from {width}x{height} to fit max_output_size"),
+            });
+        }
+        let captured = get_captured_size(&self.options);
+        let capped = fit_max_dimension(&self.options, captured);
+        if capped != captured {
+            config.capture_scale = capped[0] as f64 / captured[0] as f64;
+        }
+        config.frame_rate_cap = self.get_frame_rate_cap();
+        config.max_frame_rate = self.get_max_frame_rate();
+        config.process_exclusion = self.get_process_exclusion();
+        config
+            .downgrades
+            .extend(get_exclusion_downgrade(config.process_exclusion));
+
+        #[cfg(target_os = "windows")]
+        self.win.negotiate(&mut config);
+        config
+    }
+
+    // Whether a transform stopped capture by panicking, see
+    // Options::transforms
+    pub fn is_stopped(&self) -> bool {
+        self.transforms
+            .as_ref()
+            .is_some_and(|transforms| transforms.stopped())
+    }
+
+    // How long ago an item was captured, None where the frames' clock can't
+    // be read or the item isn't a frame
+    pub fn get_frame_age(&self, item: &ChannelItem) -> Option<Duration> {
+        #[cfg(target_os = "macos")]
+        return mac::get_frame_age(&item.0, &item.1);
+
+        // Frames are timed with the system clock when they arrive
+        #[cfg(target_os = "windows")]
+        return Some(Duration::from_nanos(
+            win::get_current_time().saturating_sub(item.display_time()),
+        ));
+
+        #[cfg(target_os = "linux")]
+        return {
+            let _ = item;
+            None
+        };
+    }
+
+    // The current time on the clock frames are timed with, in nanoseconds
+    pub fn get_frame_clock_time(&self) -> Option<u64> {
+        #[cfg(target_os = "macos")]
+        return Some(mac::get_host_time());
+
+        #[cfg(target_os = "windows")]
+        return Some(win::get_current_time());
+
+        // PipeWire's clock isn't readable outside of its stream
+        #[cfg(target_os = "linux")]
+        return None;
+    }
+
     pub fn process_channel_item(&self, data: ChannelItem) -> Option<Frame> {
         #[cfg(target_os = "macos")]
-        {
-            mac::process_sample_buffer(data.0, data.1, self.options.output_type)
+        if let Some(time) = mac::get_presentation_time(&data.0, &data.1) {
+            self.presentation.lock().unwrap().record(time);
+        }
+        #[cfg(target_os = "macos")]
+        let mut frame = match self.options.native_format {
+            true => mac::process_raw_sample_buffer(data.0, data.1)?,
+            false => mac::process_sample_buffer(
+                data.0,
+                data.1,
+                self.options.output_type,
+                &self.options.color_matrix,
+                self.options.chroma_subsampling,
+            )?,
+        };
+        // Idle updates carry no new content, see Options::content_aligned
+        #[cfg(target_os = "macos")]
+        if self.negotiated.content_aligned && frame.size() == (0, 0) {
+            return None;
+        }
+        #[cfg(target_os = "macos")]
+        if let Some(window_tracker) = &self.window_tracker {
+            frame = window_tracker.lock().unwrap().crop(frame, &self.events)?;
+        }
+        #[cfg(not(target_os = "macos"))]
+        let mut frame = data;
+
+        if let Some(schedule) = &self.schedule {
+            let time = frame.display_time();
+            if !schedule
+                .lock()
+                .unwrap()
+                .check(SystemTime::now(), time, &self.events)
+            {
+                return None;
+            }
+        }
+
+        #[cfg(not(target_os = "windows"))]
+        if let Some(Target::Window(_)) = self.options.target {
+            frame = self.crop_subregion(frame)?;
+        }
+
+        if let Some(edge_trim) = &self.edge_trim {
+            frame = edge_trim
+                .lock()
+                .unwrap()
+                .trim(frame, &self.events, Instant::now());
         }
+
+        if let Some(follow) = &self.follow {
+            let cursor = get_cursor_position(&self.options);
+            let focus = self.get_focused_bounds();
+            frame = follow
+                .lock()
+                .unwrap()
+                .crop(frame, cursor, focus, Instant::now());
+        }
+
+        // macOS scales to the limits itself, grayscale frames have their own
+        // size and raw frames can't be scaled
         #[cfg(not(target_os = "macos"))]
-        return Some(data);
+        if self.options.grayscale.is_none() && !self.options.native_format {
+            let (width, height) = frame.size();
+            let size = [width as u32, height as u32];
+            let [max_width, max_height] =
+                fit_max_output_size(&self.options, fit_max_dimension(&self.options, size));
+            if [max_width, max_height] != size {
+                frame = frame.downscaled(max_width, max_height)?;
+            }
+        }
+
+        // Windows converts on its capture thread, raw frames aren't converted
+        #[cfg(not(target_os = "windows"))]
+        if let (Some(grayscale), false) = (&self.options.grayscale, self.options.native_format) {
+            frame = Frame::Gray8(frame.to_gray8(
+                grayscale.width,
+                grayscale.height,
+                grayscale.weights,
+            )?);
+        }
+
+        if self.options.color_space == OutputColorSpace::SRGB {
+            convert_p3_to_srgb(&mut frame);
+        }
+
+        if self.options.show_cursor {
+            self.draw_cursor(&mut frame);
+        }
+
+        if let Some(transforms) = &self.transforms {
+            let transformed = transforms.apply(frame, &self.events);
+            if transforms.stopped() {
+                // Nothing is sent anymore, and Windows ends its session
+                self.tx.close(Instant::now());
+            }
+            frame = transformed?;
+        }
+
+        let (deliver, discarded) = self.warmup.lock().unwrap().check(&frame);
+        if !deliver {
+            return None;
+        }
+        if let Some(discarded) = discarded {
+            let time = frame.display_time();
+            let _ = self
+                .events
+                .send(CapturerEvent::WarmupCompleted { discarded, time });
+        }
+
+        if let Some(trigger) = &self.trigger {
+            let mut trigger = trigger.lock().unwrap();
+            let fired = trigger.fired();
+            if !trigger.check(&frame) {
+                return None;
+            }
+            if !fired {
+                let time = frame.display_time();
+                let _ = self.events.send(CapturerEvent::Triggered { time });
+            }
+        }
+
+        if let Some(motion) = &self.motion {
+            motion.lock().unwrap().estimate(&frame, Instant::now());
+        }
+
+        if let Some(timebase) = self.options.timestamp_base {
+            frame.rescale_display_time(timebase);
+        }
+
+        let sequence = self.sequence.load(Ordering::Relaxed);
+        if let Some(options) = &self.options.watermark {
+            let watermark = Watermark {
+                sequence,
+                display_time: frame.display_time(),
+            };
+            embed_watermark(&mut frame, &watermark, options);
+        }
+
+        let now = Instant::now();
+        let mut thumbnails = self.thumbnails.as_ref().map(|t| t.lock().unwrap());
+        let thumbnail = thumbnails.as_mut().and_then(|t| t.downscale(&frame, now));
+        let frame = match &*self.regions.lock().unwrap() {
+            Some(output) => extract_regions(frame, &output.regions, sequence, output.keep_source),
+            None => self.encode(frame)?,
+        };
+        if let Some(thumbnails) = &mut thumbnails {
+            thumbnails.deliver(thumbnail, sequence, now);
+        }
+        if let Some(algorithm) = self.options.checksum {
+            *self.last_checksum.lock().unwrap() = frame_checksum(&frame, algorithm);
+        }
+        self.sequence.store(sequence + 1, Ordering::Relaxed);
+
+        Some(frame)
+    }
+
+    pub(crate) fn get_buffer_pool(&self) -> BufferPool {
+        self.pool.clone()
+    }
+
+    // Items sent to the capturer so far, see Capturer::insert_marker
+    pub fn get_items_sent(&self) -> u64 {
+        self.tx.sent()
+    }
+
+    pub fn set_regions(&self, regions: Vec<(String, Area)>, keep_source: bool) {
+        *self.regions.lock().unwrap() = match regions.is_empty() {
+            true => None,
+            false => Some(RegionsOutput {
+                regions,
+                keep_source,
+            }),
+        };
+    }
+
+    // Encodes BGRA frames as deltas or scanline patches, when asked to
+    fn encode(&self, frame: Frame) -> Option<Frame> {
+        if let (Some(delta), Frame::BGRA(bgra)) = (&self.delta, &frame) {
+            return delta.lock().unwrap().encode(bgra).map(Frame::Delta);
+        }
+        if let (Some(scanlines), Frame::BGRA(bgra)) = (&self.scanlines, &frame) {
+            return scanlines
+                .lock()
+                .unwrap()
+                .encode(bgra)
+                .map(Frame::ScanlinePatch);
+        }
+
+        Some(frame)
+    }
+
+    pub fn take_thumbnail_receiver(&self) -> Option<mpsc::Receiver<Thumbnail>> {
+        self.thumbnails.as_ref()?.lock().unwrap().take_receiver()
+    }
+
+    pub fn get_thumbnail_stats(&self) -> Option<CaptureStats> {
+        Some(self.thumbnails.as_ref()?.lock().unwrap().stats())
+    }
+
+    pub fn get_thumbnail_size(&mut self) -> Option<[u32; 2]> {
+        let thumbnails = self.options.thumbnails?;
+        Some(get_thumbnail_size(
+            &thumbnails,
+            self.get_output_frame_size(),
+        ))
+    }
+
+    pub fn force_keyframe(&self) {
+        if let Some(delta) = &self.delta {
+            delta.lock().unwrap().force_keyframe();
+        }
+        if let Some(scanlines) = &self.scanlines {
+            scanlines.lock().unwrap().force_full();
+        }
+    }
+
+    // None once the subregion callback stopped capture by panicking
+    #[cfg(not(target_os = "windows"))]
+    fn crop_subregion(&self, frame: Frame) -> Option<Frame> {
+        use std::sync::atomic::Ordering;
+
+        if let WindowSubregion::Whole = self.options.window_subregion {
+            return Some(frame);
+        }
+        if self.callback_stopped.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        let (width, height) = frame.size();
+        let size = Size {
+            width: width as f64,
+            height: height as f64,
+        };
+        let area = match self.options.window_subregion.resolve(&size) {
+            Ok(area) => area,
+            Err(message) => {
+                let _ = self
+                    .events
+                    .send(CapturerEvent::CallbackPanicked { message });
+                if self.options.callback_panic == CallbackPanic::Stop {
+                    self.callback_stopped.store(true, Ordering::Relaxed);
+                    return None;
+                }
+                None
+            }
+        };
+
+        let mut last = self.subregion.lock().unwrap();
+        if last.as_ref() != Some(&area) {
+            let _ = self
+                .events
+                .send(CapturerEvent::SubregionChanged(area.clone()));
+            *last = Some(area.clone());
+        }
+
+        // YUV frames can't be cropped and are delivered whole
+        Some(area.and_then(|area| frame.cropped(&area)).unwrap_or(frame))
+    }
+
+    // Custom cursors and highlights are drawn at the position the cursor has
+    // when the frame is processed, which can trail the frame by a few milliseconds
+    fn draw_cursor(&self, frame: &mut Frame) {
+        if let CursorStyle::System = self.options.cursor_style {
+            return;
+        }
+
+        let Some(mut position) = get_cursor_position(&self.options) else {
+            return;
+        };
+        if let Some(follow) = &self.follow {
+            position = follow.lock().unwrap().map_point(position);
+        }
+        let (width, height) = frame.size();
+        let position = (position.x * width as f64, position.y * height as f64);
+
+        match &self.options.cursor_style {
+            CursorStyle::System => {}
+            CursorStyle::Custom(image) => composite_cursor(frame, image, position),
+            CursorStyle::Highlighted => draw_cursor_highlight(frame, position),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_max_dimension() {
+        // A virtual desktop of four 4K displays side by side
+        let options = Options {
+            max_dimension: Some(8192),
+            max_output_size: Some([4096, 4096]),
+            ..Default::default()
+        };
+        assert_eq!(fit_max_dimension(&options, [15360, 2160]), [8192, 1152]);
+        assert_eq!(fit_max_output_size(&options, [8192, 1152]), [4096, 576]);
+        // Within the limit, or not known yet
+        assert_eq!(fit_max_dimension(&options, [3840, 2160]), [3840, 2160]);
+        assert_eq!(fit_max_dimension(&options, [0, 0]), [0, 0]);
+    }
+    #[test]
+    fn test_live_changes() {
+        let options = Options::default();
+        let highlighted = Options {
+            cursor_style: CursorStyle::Highlighted,
+            ..options.clone()
+        };
+        assert!(is_live_change(&options, &highlighted));
+
+        let cropped = Options {
+            crop_area: Some(Area::default()),
+            ..options.clone()
+        };
+        assert!(!is_live_change(&options, &cropped));
+
+        // Only Windows paces frames itself
+        let faster = Options {
+            fps: options.fps + 30,
+            ..options.clone()
+        };
+        assert_eq!(
+            is_live_change(&options, &faster),
+            cfg!(target_os = "windows")
+        );
     }
 }
@@ -0,0 +1,153 @@
+use std::{
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use windows::Win32::{
+    Foundation::{BOOL, HWND, LPARAM},
+    UI::WindowsAndMessaging::{
+        EnumWindows, GetWindowDisplayAffinity, GetWindowThreadProcessId, IsWindowVisible,
+        SetWindowDisplayAffinity, WDA_EXCLUDEFROMCAPTURE, WDA_MONITOR, WDA_NONE,
+        WINDOW_DISPLAY_AFFINITY,
+    },
+};
+
+use crate::capturer::ProcessExclusion;
+
+// How often windows opened during capture are picked up. Enumerating top
+// level windows takes well under a millisecond.
+const RESCAN_INTERVAL: Duration = Duration::from_millis(250);
+
+// Windows this process owns whose display affinity scap changed
+struct ExcludedWindows {
+    // Raw handles, which unlike HWND can be sent to the rescan thread
+    windows: HashSet<isize>,
+    // Windows older than 10 2004 can't exclude windows, only black them out
+    affinity: WINDOW_DISPLAY_AFFINITY,
+    masked: Arc<AtomicBool>,
+    skip: Option<isize>,
+}
+
+impl ExcludedWindows {
+    fn scan(&mut self) {
+        for window in get_process_windows() {
+            if Some(window) == self.skip || self.windows.contains(&window) {
+                continue;
+            }
+
+            let hwnd = HWND(window as _);
+            unsafe {
+                // Leave windows the app protects itself alone
+                let mut current = 0;
+                if GetWindowDisplayAffinity(hwnd, &mut current).is_err() || current != WDA_NONE.0 {
+                    continue;
+                }
+
+                if SetWindowDisplayAffinity(hwnd, self.affinity).is_err() {
+                    if self.affinity != WDA_EXCLUDEFROMCAPTURE
+                        || SetWindowDisplayAffinity(hwnd, WDA_MONITOR).is_err()
+                    {
+                        continue;
+                    }
+                    self.affinity = WDA_MONITOR;
+                    self.masked.store(true, Ordering::Relaxed);
+                }
+            }
+            self.windows.insert(window);
+        }
+    }
+
+    fn restore(&mut self) {
+        for window in self.windows.drain() {
+            // Fails harmlessly for windows that were closed in the meantime
+            let _ = unsafe { SetWindowDisplayAffinity(HWND(window as _), WDA_NONE) };
+        }
+    }
+}
+
+/// Keeps the windows of the current process out of captures, including the
+/// ones it opens later, until dropped.
+///
+/// This uses window display affinity, which hides the windows from every
+/// capture on the system while it's active, not just this one.
+pub struct ProcessWindowExcluder {
+    masked: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ProcessWindowExcluder {
+    /// Starts excluding, except for `skip`, the window being captured
+    pub fn new(skip: Option<HWND>) -> Self {
+        let masked = Arc::new(AtomicBool::new(false));
+        let mut excluded = ExcludedWindows {
+            windows: HashSet::new(),
+            affinity: WDA_EXCLUDEFROMCAPTURE,
+            masked: masked.clone(),
+            skip: skip.map(|hwnd| hwnd.0 as isize),
+        };
+        excluded.scan();
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = thread::spawn({
+            let stop = stop.clone();
+            move || {
+                while !stop.load(Ordering::Relaxed) {
+                    excluded.scan();
+                    thread::park_timeout(RESCAN_INTERVAL);
+                }
+                excluded.restore();
+            }
+        });
+
+        ProcessWindowExcluder {
+            masked,
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Known once the first window is excluded, the OS version decides it
+    pub fn mode(&self) -> ProcessExclusion {
+        match self.masked.load(Ordering::Relaxed) {
+            true => ProcessExclusion::Masked,
+            false => ProcessExclusion::Excluded,
+        }
+    }
+}
+
+impl Drop for ProcessWindowExcluder {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+// The visible top level windows owned by this process
+fn get_process_windows() -> Vec<isize> {
+    unsafe extern "system" fn collect(hwnd: HWND, windows: LPARAM) -> BOOL {
+        let windows = &mut *(windows.0 as *mut Vec<isize>);
+
+        let mut process_id = 0;
+        GetWindowThreadProcessId(hwnd, Some(&mut process_id));
+        if process_id == std::process::id() && IsWindowVisible(hwnd).as_bool() {
+            windows.push(hwnd.0 as isize);
+        }
+
+        true.into()
+    }
+
+    let mut windows: Vec<isize> = Vec::new();
+    unsafe {
+        let _ = EnumWindows(Some(collect), LPARAM(&mut windows as *mut _ as isize));
+    }
+    windows
+}
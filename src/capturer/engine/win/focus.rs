@@ -0,0 +1,288 @@
+use std::{
+    cell::RefCell,
+    sync::{mpsc, Arc, Mutex},
+    thread::{self, JoinHandle},
+};
+
+use windows::Win32::{
+    Foundation::{HMODULE, HWND, LPARAM, RECT, WPARAM},
+    Graphics::{
+        Dwm::{DwmGetWindowAttribute, DWMWA_EXTENDED_FRAME_BOUNDS},
+        Gdi::{GetMonitorInfoW, HMONITOR, MONITORINFO},
+    },
+    System::Threading::GetCurrentThreadId,
+    UI::{
+        Accessibility::{SetWinEventHook, UnhookWinEvent, HWINEVENTHOOK},
+        WindowsAndMessaging::{
+            DispatchMessageW, GetForegroundWindow, GetMessageW, GetWindowRect, PeekMessageW,
+            PostThreadMessageW, CHILDID_SELF, EVENT_SYSTEM_FOREGROUND, EVENT_SYSTEM_MINIMIZEEND,
+            EVENT_SYSTEM_MOVESIZEEND, MSG, OBJID_WINDOW, PM_NOREMOVE, WINEVENT_OUTOFCONTEXT,
+            WM_QUIT, WM_USER,
+        },
+    },
+};
+use windows_capture::{monitor::Monitor as WCMonitor, window::Window as WCWindow};
+
+use super::get_current_time;
+use crate::capturer::{Area, CapturerEvent, Point, Size};
+
+// What the frames show, for placing the focused window in them. Raw handles
+// so it can be sent to the watcher thread.
+#[derive(Debug, Clone)]
+pub(crate) struct FocusRegion {
+    pub target: FocusTarget,
+    // The captured part of the target in physical pixels, None for all of it
+    pub source_rect: Option<Area>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum FocusTarget {
+    Display(isize),
+    Window(isize),
+}
+
+impl FocusRegion {
+    // Where `window` is in the frames, None if it's outside of them
+    fn locate(&self, window: HWND) -> Option<Area> {
+        let (bounds, origin, scale) = match self.target {
+            // Monitor and window rects share this process's coordinates,
+            // which are scaled down if it isn't DPI aware
+            FocusTarget::Display(monitor) => unsafe {
+                let mut monitor_info = MONITORINFO {
+                    cbSize: std::mem::size_of::<MONITORINFO>() as u32,
+                    ..Default::default()
+                };
+                if !GetMonitorInfoW(HMONITOR(monitor as _), &mut monitor_info).as_bool() {
+                    return None;
+                }
+                let rect = monitor_info.rcMonitor;
+                let width = WCMonitor::from_raw_hmonitor(monitor as _).width().ok()?;
+                let scale = width as f64 / (rect.right - rect.left).max(1) as f64;
+
+                let mut bounds = RECT::default();
+                GetWindowRect(window, &mut bounds).ok()?;
+                (bounds, (rect.left, rect.top), scale)
+            },
+            // Extended frame bounds are in physical pixels either way
+            FocusTarget::Window(target) => {
+                let rect = get_frame_bounds(HWND(target as _))?;
+                (get_frame_bounds(window)?, (rect.left, rect.top), 1.0)
+            }
+        };
+        get_relative_bounds(&bounds, origin, scale, self.source_rect.as_ref())
+    }
+}
+
+fn get_frame_bounds(window: HWND) -> Option<RECT> {
+    let mut rect = RECT::default();
+    unsafe {
+        DwmGetWindowAttribute(
+            window,
+            DWMWA_EXTENDED_FRAME_BOUNDS,
+            &mut rect as *mut _ as _,
+            std::mem::size_of::<RECT>() as u32,
+        )
+        .ok()?;
+    }
+    Some(rect)
+}
+
+// `bounds` at `origin` of the target, in units of `scale` physical pixels,
+// as frame pixels within `source_rect`. Clipped to it, None if outside.
+fn get_relative_bounds(
+    bounds: &RECT,
+    origin: (i32, i32),
+    scale: f64,
+    source_rect: Option<&Area>,
+) -> Option<Area> {
+    let (offset_x, offset_y) = source_rect.map_or((0.0, 0.0), |r| (r.origin.x, r.origin.y));
+    let position = |value: i32, origin: i32, offset: f64| (value - origin) as f64 * scale - offset;
+    let mut left = position(bounds.left, origin.0, offset_x);
+    let mut top = position(bounds.top, origin.1, offset_y);
+    let mut right = position(bounds.right, origin.0, offset_x);
+    let mut bottom = position(bounds.bottom, origin.1, offset_y);
+
+    if let Some(source_rect) = source_rect {
+        left = left.max(0.0);
+        top = top.max(0.0);
+        right = right.min(source_rect.size.width);
+        bottom = bottom.min(source_rect.size.height);
+    }
+    if right <= left || bottom <= top {
+        return None;
+    }
+
+    Some(Area {
+        origin: Point { x: left, y: top },
+        size: Size {
+            width: right - left,
+            height: bottom - top,
+        },
+    })
+}
+
+// WinEvent callbacks carry no context, but run on the thread that set the
+// hook, so each watcher thread keeps its region here
+thread_local! {
+    static FOCUS: RefCell<Option<Focus>> = const { RefCell::new(None) };
+}
+
+struct Focus {
+    region: FocusRegion,
+    events: mpsc::Sender<CapturerEvent>,
+    // The last report, so moves of other windows aren't reported again
+    last: Option<(isize, Option<Area>)>,
+    // The focused window's bounds, for Options::follow_action
+    bounds: Arc<Mutex<Option<Area>>>,
+}
+
+impl Focus {
+    fn report(&mut self) {
+        let window = unsafe { GetForegroundWindow() };
+        if window.is_invalid() {
+            return;
+        }
+        let bounds = self.region.locate(window);
+        let report = Some((window.0 as isize, bounds.clone()));
+        if self.last == report {
+            return;
+        }
+        self.last = report;
+        *self.bounds.lock().unwrap() = bounds.clone();
+
+        let time = get_current_time();
+        let title = WCWindow::from_raw_hwnd(window.0)
+            .title()
+            .unwrap_or_default();
+        let _ = self.events.send(CapturerEvent::FocusChanged {
+            title,
+            bounds,
+            time,
+        });
+    }
+}
+
+unsafe extern "system" fn on_event(
+    _hook: HWINEVENTHOOK,
+    _event: u32,
+    _hwnd: HWND,
+    object: i32,
+    child: i32,
+    _thread: u32,
+    _time: u32,
+) {
+    if object != OBJID_WINDOW.0 || child != CHILDID_SELF as i32 {
+        return;
+    }
+
+    FOCUS.with(|focus| {
+        if let Some(focus) = focus.borrow_mut().as_mut() {
+            focus.report();
+        }
+    });
+}
+
+/// Reports where the focused window is in the frames until dropped, see
+/// [Options::watch_focus](crate::capturer::Options::watch_focus)
+pub struct FocusWatcher {
+    thread_id: u32,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl FocusWatcher {
+    pub fn new(
+        region: FocusRegion,
+        events: mpsc::Sender<CapturerEvent>,
+        bounds: Arc<Mutex<Option<Area>>>,
+    ) -> Self {
+        let (ready_tx, ready_rx) = mpsc::channel();
+
+        let thread = thread::spawn(move || unsafe {
+            // Only focus switches and the end of moves and restores, which
+            // are rare enough to look the window up every time
+            let hooks = [
+                (EVENT_SYSTEM_FOREGROUND, EVENT_SYSTEM_FOREGROUND),
+                (EVENT_SYSTEM_MOVESIZEEND, EVENT_SYSTEM_MOVESIZEEND),
+                (EVENT_SYSTEM_MINIMIZEEND, EVENT_SYSTEM_MINIMIZEEND),
+            ]
+            .map(|(min, max)| {
+                SetWinEventHook(
+                    min,
+                    max,
+                    HMODULE::default(),
+                    Some(on_event),
+                    0,
+                    0,
+                    WINEVENT_OUTOFCONTEXT,
+                )
+            });
+
+            let mut focus = Focus {
+                region,
+                events,
+                last: None,
+                bounds,
+            };
+            focus.report();
+            FOCUS.with(|cell| *cell.borrow_mut() = Some(focus));
+
+            // Make sure the message queue exists before the quit message is posted
+            let mut message = MSG::default();
+            let _ = PeekMessageW(&mut message, None, WM_USER, WM_USER, PM_NOREMOVE);
+            let _ = ready_tx.send(GetCurrentThreadId());
+
+            while GetMessageW(&mut message, None, 0, 0).as_bool() {
+                DispatchMessageW(&message);
+            }
+
+            for hook in hooks {
+                let _ = UnhookWinEvent(hook);
+            }
+        });
+
+        FocusWatcher {
+            thread_id: ready_rx.recv().unwrap_or_default(),
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for FocusWatcher {
+    fn drop(&mut self) {
+        let _ = unsafe { PostThreadMessageW(self.thread_id, WM_QUIT, WPARAM(0), LPARAM(0)) };
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative_bounds() {
+        let window = RECT {
+            left: 150,
+            top: 100,
+            right: 350,
+            bottom: 300,
+        };
+        // A 2x display at (100, 0) seen by a process that isn't DPI aware
+        let bounds = get_relative_bounds(&window, (100, 0), 2.0, None).unwrap();
+        assert_eq!((bounds.origin.x, bounds.origin.y), (100.0, 200.0));
+        assert_eq!((bounds.size.width, bounds.size.height), (400.0, 400.0));
+
+        // Clipped to the crop area
+        let crop = Area {
+            origin: Point { x: 200.0, y: 0.0 },
+            size: Size {
+                width: 100.0,
+                height: 1000.0,
+            },
+        };
+        let bounds = get_relative_bounds(&window, (100, 0), 2.0, Some(&crop)).unwrap();
+        assert_eq!((bounds.origin.x, bounds.size.width), (0.0, 100.0));
+        assert!(get_relative_bounds(&window, (600, 0), 1.0, Some(&crop)).is_none());
+    }
+}
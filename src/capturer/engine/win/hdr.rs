@@ -0,0 +1,119 @@
+use windows::core::Interface;
+use windows::Win32::{
+    Devices::Display::{
+        DisplayConfigGetDeviceInfo, GetDisplayConfigBufferSizes, QueryDisplayConfig,
+        DISPLAYCONFIG_DEVICE_INFO_GET_SDR_WHITE_LEVEL, DISPLAYCONFIG_DEVICE_INFO_GET_SOURCE_NAME,
+        DISPLAYCONFIG_DEVICE_INFO_HEADER, DISPLAYCONFIG_MODE_INFO, DISPLAYCONFIG_PATH_INFO,
+        DISPLAYCONFIG_SDR_WHITE_LEVEL, DISPLAYCONFIG_SOURCE_DEVICE_NAME, QDC_ONLY_ACTIVE_PATHS,
+    },
+    Foundation::ERROR_SUCCESS,
+    Graphics::{
+        Dxgi::{
+            Common::DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020, CreateDXGIFactory1, IDXGIFactory1,
+            IDXGIOutput6,
+        },
+        Gdi::HMONITOR,
+    },
+};
+
+/// SDR white level Windows uses when it can't be queried
+const DEFAULT_SDR_WHITE_NITS: f32 = 80.0;
+
+/// Returns the SDR white level in nits if `monitor` is in HDR mode, or None
+/// if it's in SDR mode or its mode can't be determined.
+pub fn get_hdr_white_level(monitor: HMONITOR) -> Option<f32> {
+    let device_name = get_hdr_output_name(monitor)?;
+
+    Some(get_sdr_white_level(&device_name).unwrap_or(DEFAULT_SDR_WHITE_NITS))
+}
+
+// Finds the DXGI output of `monitor` and returns its GDI device name if it's
+// composited in HDR (HDR10, which the capture API hands out as scRGB)
+fn get_hdr_output_name(monitor: HMONITOR) -> Option<[u16; 32]> {
+    unsafe {
+        let factory: IDXGIFactory1 = CreateDXGIFactory1().ok()?;
+
+        let mut adapter_index = 0;
+        while let Ok(adapter) = factory.EnumAdapters1(adapter_index) {
+            adapter_index += 1;
+
+            let mut output_index = 0;
+            while let Ok(output) = adapter.EnumOutputs(output_index) {
+                output_index += 1;
+
+                // IDXGIOutput6 needs Windows 10 1803, which is older than HDR capture support
+                let Ok(desc) = output.cast::<IDXGIOutput6>().and_then(|o| o.GetDesc1()) else {
+                    continue;
+                };
+                if desc.Monitor == monitor {
+                    return (desc.ColorSpace == DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020)
+                        .then_some(desc.DeviceName);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+// Reads the "SDR content brightness" setting of the display with the GDI `device_name`
+fn get_sdr_white_level(device_name: &[u16; 32]) -> Option<f32> {
+    unsafe {
+        let (mut path_count, mut mode_count) = (0, 0);
+        if GetDisplayConfigBufferSizes(QDC_ONLY_ACTIVE_PATHS, &mut path_count, &mut mode_count)
+            != ERROR_SUCCESS
+        {
+            return None;
+        }
+
+        let mut paths = vec![DISPLAYCONFIG_PATH_INFO::default(); path_count as usize];
+        let mut modes = vec![DISPLAYCONFIG_MODE_INFO::default(); mode_count as usize];
+        if QueryDisplayConfig(
+            QDC_ONLY_ACTIVE_PATHS,
+            &mut path_count,
+            paths.as_mut_ptr(),
+            &mut mode_count,
+            modes.as_mut_ptr(),
+            None,
+        ) != ERROR_SUCCESS
+        {
+            return None;
+        }
+        paths.truncate(path_count as usize);
+
+        for path in paths {
+            let mut source = DISPLAYCONFIG_SOURCE_DEVICE_NAME {
+                header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
+                    r#type: DISPLAYCONFIG_DEVICE_INFO_GET_SOURCE_NAME,
+                    size: std::mem::size_of::<DISPLAYCONFIG_SOURCE_DEVICE_NAME>() as u32,
+                    adapterId: path.sourceInfo.adapterId,
+                    id: path.sourceInfo.id,
+                },
+                ..Default::default()
+            };
+            if DisplayConfigGetDeviceInfo(&mut source.header) != 0
+                || source.viewGdiDeviceName != *device_name
+            {
+                continue;
+            }
+
+            let mut white_level = DISPLAYCONFIG_SDR_WHITE_LEVEL {
+                header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
+                    r#type: DISPLAYCONFIG_DEVICE_INFO_GET_SDR_WHITE_LEVEL,
+                    size: std::mem::size_of::<DISPLAYCONFIG_SDR_WHITE_LEVEL>() as u32,
+                    adapterId: path.targetInfo.adapterId,
+                    id: path.targetInfo.id,
+                },
+                ..Default::default()
+            };
+            if DisplayConfigGetDeviceInfo(&mut white_level.header) != 0 {
+                return None;
+            }
+
+            // The level is a multiplier of 80 nits, scaled by 1000
+            return Some(white_level.SDRWhiteLevel as f32 / 1000.0 * DEFAULT_SDR_WHITE_NITS);
+        }
+
+        None
+    }
+}
@@ -1,26 +1,212 @@
+use super::FrameSender;
+use crate::capturer::aspect_crop::fit_aspect_ratio;
+use crate::capturer::pacer::FramePacer;
+use crate::capturer::secure_desktop::SecureDesktop;
+use crate::frame::{
+    get_clamped_bounds, pad_frame, remove_row_padding, BufferPool, ColorSpace, RGBFrame, RGBxFrame,
+    RawFrame, RawPixelFormat, RowOrder, ScRgbToneMapper,
+};
 use crate::{
-    capturer::{Area, Options, Point, Resolution, Size},
+    capturer::{
+        clamp_area, shift_area, Area, CallbackPanic, CapturerBuildError, CapturerEvent,
+        CropOverflow, CursorStyle, Downgrade, GpuReplayUsage, GrayscaleOptions, HdrHandling,
+        NegotiatedConfig, Options, Point, ProcessExclusion, Resolution, SecureDesktopPolicy, Size,
+        WindowContentMode, WindowSubregion,
+    },
     frame::{BGRAFrame, Frame, FrameType},
     targets::{self, get_scale_factor, Target},
 };
 use std::cmp;
-use std::sync::mpsc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    mpsc, Arc, Mutex,
+};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use windows::Foundation::TimeSpan;
+use windows::Graphics::Capture::GraphicsCaptureItem;
+use windows::Win32::{
+    Foundation::{HWND, POINT, RECT},
+    Graphics::{
+        Dwm::{DwmGetWindowAttribute, DWMWA_EXTENDED_FRAME_BOUNDS},
+        Gdi::{
+            GetMonitorInfoW, MonitorFromWindow, HMONITOR, MONITORINFO, MONITOR_DEFAULTTONEAREST,
+        },
+    },
+    System::Performance::{QueryPerformanceCounter, QueryPerformanceFrequency},
+    UI::WindowsAndMessaging::{
+        GetCursorInfo, GetPhysicalCursorPos, GetWindowRect, CURSORINFO, CURSOR_SHOWING,
+    },
+};
+use windows_capture::capture::Context;
 use windows_capture::{
-    capture::{CaptureControl, GraphicsCaptureApiHandler},
+    capture::{CaptureControl, GraphicsCaptureApiError, GraphicsCaptureApiHandler},
     frame::Frame as WCFrame,
-    graphics_capture_api::InternalCaptureControl,
+    graphics_capture_api::{Error as WCApiError, InternalCaptureControl},
     monitor::Monitor as WCMonitor,
     settings::{ColorFormat, CursorCaptureSettings, DrawBorderSettings, Settings as WCSettings},
     window::Window as WCWindow,
 };
-use windows_capture::capture::Context;
-use crate::frame::{RGBFrame, RGBxFrame};
+
+mod exclusion;
+mod focus;
+mod hdr;
+mod recovery;
+mod replay;
+mod secure_desktop;
+mod watcher;
 
 #[derive(Debug)]
 struct Capturer {
-    pub tx: mpsc::Sender<Frame>,
+    pub tx: FrameSender,
     pub crop: Option<Area>,
+    pub crop_overflow: CropOverflow,
+    pub tight_packing: bool,
+    // Windows.Graphics.Capture has no frame interval, so frames are paced here
+    pub pacer: FramePacer,
+    // The rate to pace to, which Capturer::reconfigure changes while capturing
+    pub fps: Arc<AtomicU32>,
+    pub paced_fps: u32,
+    // Shared with the replay, which converts the frames it reads back the same way
+    pub converter: Arc<FrameConverter>,
+    pub window_tracker: Option<WindowTracker>,
+    pub subregion: Option<Subregion>,
+    pub events: mpsc::Sender<CapturerEvent>,
+    pub secure_desktop: Arc<Mutex<SecureDesktop>>,
+    // Frames are copied out of the texture into buffers from here
+    pub pool: BufferPool,
+    pub replay: Option<replay::SharedGpuReplay>,
+}
+
+// Turns the captured pixels into the frames that are delivered
+#[derive(Debug)]
+struct FrameConverter {
+    // Set when HDR frames are captured as scRGB and mapped to `output_format`
+    tone_mapper: Option<ScRgbToneMapper>,
+    output_format: ColorFormat,
+    // Frames are reduced to grayscale here so only the small frames are sent
+    grayscale: Option<GrayscaleOptions>,
+    // Whether frames are sent as captured, for Options::native_format
+    native_format: bool,
+}
+
+// Where the captured part of a crop area that extends past the frame goes,
+// for CropOverflow::Fill
+#[derive(Debug, Clone, Copy)]
+struct Padding {
+    offset: [usize; 2],
+    size: [usize; 2],
+    color: [u8; 3],
+}
+
+// Where `child` is within `window`, both raw HWNDs
+fn get_child_area(window: isize, child: isize) -> Option<Area> {
+    let mut window_rect = RECT::default();
+    let mut child_rect = RECT::default();
+    unsafe {
+        GetWindowRect(HWND(window as _), &mut window_rect).ok()?;
+        GetWindowRect(HWND(child as _), &mut child_rect).ok()?;
+    }
+
+    Some(Area {
+        origin: Point {
+            x: (child_rect.left - window_rect.left) as f64,
+            y: (child_rect.top - window_rect.top) as f64,
+        },
+        size: Size {
+            width: (child_rect.right - child_rect.left) as f64,
+            height: (child_rect.bottom - child_rect.top) as f64,
+        },
+    })
+}
+
+// Resolves Options::window_subregion for every frame
+#[derive(Debug, Clone)]
+struct Subregion {
+    window: isize,
+    region: WindowSubregion,
+    // The last reported area, None until the first frame
+    last: Option<Option<Area>>,
+    on_panic: CallbackPanic,
+}
+
+impl Subregion {
+    // The part of a window of `size` to capture, Some(None) for all of it, or
+    // None if the callback panicked and capture has to stop
+    fn resolve(
+        &mut self,
+        size: &Size,
+        events: &mpsc::Sender<CapturerEvent>,
+    ) -> Option<Option<Area>> {
+        let area = match &self.region {
+            WindowSubregion::ChildWindow(child) => {
+                get_child_area(self.window, *child).and_then(|area| clamp_area(&area, size))
+            }
+            region => match region.resolve(size) {
+                Ok(area) => area,
+                Err(message) => {
+                    let _ = events.send(CapturerEvent::CallbackPanicked { message });
+                    if self.on_panic == CallbackPanic::Stop {
+                        return None;
+                    }
+                    None
+                }
+            },
+        };
+
+        if self.last.as_ref() != Some(&area) {
+            let _ = events.send(CapturerEvent::SubregionChanged(area.clone()));
+            self.last = Some(area.clone());
+        }
+
+        Some(area)
+    }
+}
+
+// Follows a window across its monitor for WindowContentMode::ScreenRegion
+#[derive(Debug, Clone)]
+struct WindowTracker {
+    // Raw handles, which unlike HWND and HMONITOR can be sent to the capture thread
+    window: isize,
+    monitor: isize,
+    // Part of the window from Options::crop_area, all of it if None
+    crop: Option<Area>,
+    bounds: Option<Area>,
+}
+
+impl WindowTracker {
+    // Returns the window's area on its monitor, and whether it changed since the last call
+    fn update(&mut self) -> Option<(Area, bool)> {
+        let (window, monitor) = unsafe {
+            let mut window = RECT::default();
+            GetWindowRect(HWND(self.window as _), &mut window).ok()?;
+
+            let mut monitor_info = MONITORINFO {
+                cbSize: std::mem::size_of::<MONITORINFO>() as u32,
+                ..Default::default()
+            };
+            if !GetMonitorInfoW(HMONITOR(self.monitor as _), &mut monitor_info).as_bool() {
+                return None;
+            }
+            (window, monitor_info.rcMonitor)
+        };
+
+        let bounds = Area {
+            origin: Point {
+                x: (window.left - monitor.left) as f64,
+                y: (window.top - monitor.top) as f64,
+            },
+            size: Size {
+                width: (window.right - window.left) as f64,
+                height: (window.bottom - window.top) as f64,
+            },
+        };
+
+        let moved = self.bounds.as_ref() != Some(&bounds);
+        self.bounds = Some(bounds.clone());
+
+        Some((bounds, moved))
+    }
 }
 
 #[derive(Clone)]
@@ -31,37 +217,163 @@ enum Settings {
 
 pub struct WCStream {
     settings: Settings,
-    capture_control: Option<CaptureControl<Capturer, Box<dyn std::error::Error + Send + Sync>>>,
+    // Shared with the recovery watcher, which replaces it after a GPU reset
+    capture_control: recovery::SharedCaptureControl,
+    exclusion: Option<exclusion::ProcessWindowExcluder>,
+    // The window to watch while capturing, for Options::watch_window
+    watch: Option<(targets::Window, mpsc::Sender<CapturerEvent>)>,
+    watcher: Option<watcher::WindowWatcher>,
+    // Where the frames are, for Options::watch_focus
+    focus: Option<(focus::FocusRegion, mpsc::Sender<CapturerEvent>)>,
+    focus_watcher: Option<focus::FocusWatcher>,
+    // Shared with the focus watcher, which updates it on every report
+    focused_bounds: Arc<Mutex<Option<Area>>>,
+    // Shared with the handler, which stops sending frames while the secure
+    // desktop is shown and the watcher sends its stand-ins
+    secure_desktop: Arc<Mutex<SecureDesktop>>,
+    secure_desktop_policy: SecureDesktopPolicy,
+    secure_desktop_watch: (FrameSender, mpsc::Sender<CapturerEvent>, Duration),
+    secure_desktop_watcher: Option<secure_desktop::SecureDesktopWatcher>,
+    // Where recovery is reported, for Options::auto_recover_device
+    recovery_events: Option<mpsc::Sender<CapturerEvent>>,
+    recovery_watcher: Option<recovery::DeviceRecoveryWatcher>,
+    // Shared with the handler, see WCStream::set_fps
+    fps: Arc<AtomicU32>,
+    // Whether HDR frames are tone mapped, as long as the GPU keeps up
+    hdr_tone_mapping: bool,
+    // Whether frames are sent as captured, for Options::native_format
+    native_format: bool,
+    fallbacks: StartFallbacks,
+    // The part of the target that's captured, None where it follows a window
+    source_rect: Option<Area>,
+    // The part of the frames CropOverflow::Fill pads
+    filled_fraction: f64,
+    // Shared with the handler, which keeps the frames it sends, for
+    // Options::gpu_replay
+    replay: Option<replay::SharedGpuReplay>,
 }
 
 impl GraphicsCaptureApiHandler for Capturer {
     type Flags = FlagStruct;
-    type Error = Box<dyn std::error::Error + Send + Sync>;
+    type Error = HandlerError;
 
     fn new(context: Context<Self::Flags>) -> Result<Self, Self::Error> {
+        let converter = Arc::new(FrameConverter {
+            tone_mapper: context.flags.hdr_white_level.map(ScRgbToneMapper::new),
+            output_format: context.flags.output_format,
+            grayscale: context.flags.grayscale,
+            native_format: context.flags.native_format,
+        });
+        if let Some(replay) = &context.flags.replay {
+            replay.lock().unwrap().start(
+                &context.device,
+                &context.device_context,
+                converter.clone(),
+            );
+        }
+
         Ok(Self {
             tx: context.flags.tx,
             crop: context.flags.crop,
+            crop_overflow: context.flags.crop_overflow,
+            tight_packing: context.flags.tight_packing,
+            pacer: FramePacer::new(context.flags.fps.load(Ordering::Relaxed)),
+            paced_fps: context.flags.fps.load(Ordering::Relaxed),
+            fps: context.flags.fps,
+            converter,
+            window_tracker: context.flags.window_tracker,
+            subregion: context.flags.subregion,
+            events: context.flags.events,
+            secure_desktop: context.flags.secure_desktop,
+            pool: context.flags.pool,
+            replay: context.flags.replay,
         })
     }
 
     fn on_frame_arrived(
         &mut self,
         frame: &mut WCFrame,
-        _: InternalCaptureControl,
+        capture_control: InternalCaptureControl,
     ) -> Result<(), Self::Error> {
+        // A warmed up session that was never started
+        if self.tx.is_expired() {
+            capture_control.stop();
+            return Ok(());
+        }
+
+        let fps = self.fps.load(Ordering::Relaxed);
+        if fps != self.paced_fps {
+            self.pacer = FramePacer::new(fps);
+            self.paced_fps = fps;
+        }
+        if !self.pacer.should_deliver(Instant::now()) {
+            return Ok(());
+        }
+
+        let color_format = frame.color_format();
+        let composed = frame.timespan();
+
+        if let Some(window_tracker) = &mut self.window_tracker {
+            // Skip frames while the window is minimized or gone
+            let Some((bounds, moved)) = window_tracker.update() else {
+                return Ok(());
+            };
+            if bounds.origin.x + bounds.size.width <= 0.0
+                || bounds.origin.y + bounds.size.height <= 0.0
+                || bounds.origin.x >= frame.width() as f64
+                || bounds.origin.y >= frame.height() as f64
+            {
+                return Ok(());
+            }
+
+            if moved {
+                let _ = self
+                    .events
+                    .send(CapturerEvent::WindowBoundsChanged(bounds.clone()));
+            }
+
+            let area = match &mut self.subregion {
+                Some(subregion) => subregion.resolve(&bounds.size, &self.events),
+                None => Some(window_tracker.crop.clone()),
+            };
+            let Some(area) = area else {
+                capture_control.stop();
+                return Ok(());
+            };
+            self.crop = Some(match area {
+                // Window relative, so offset it by the window's position
+                Some(area) => Area {
+                    origin: Point {
+                        x: bounds.origin.x + area.origin.x,
+                        y: bounds.origin.y + area.origin.y,
+                    },
+                    size: area.size,
+                },
+                None => bounds,
+            });
+        } else if let Some(subregion) = &mut self.subregion {
+            let size = Size {
+                width: frame.width() as f64,
+                height: frame.height() as f64,
+            };
+            let Some(area) = subregion.resolve(&size, &self.events) else {
+                capture_control.stop();
+                return Ok(());
+            };
+            self.crop = area;
+        }
+
         match &self.crop {
-            Some(cropped_area) => {
-                // get the cropped area
-                let start_x = cropped_area.origin.x as u32;
-                let start_y = cropped_area.origin.y as u32;
-                let end_x = (cropped_area.origin.x + cropped_area.size.width) as u32;
-                let end_y = (cropped_area.origin.y + cropped_area.size.height) as u32;
+            Some(crop) => {
+                let (area, padding) =
+                    get_frame_crop(crop, frame.width(), frame.height(), self.crop_overflow)?;
 
                 // crop the frame
+                let [start_x, start_y, end_x, end_y] = area;
                 let mut cropped_buffer = frame
                     .buffer_crop(start_x, start_y, end_x, end_y)
-                    .expect("Failed to crop buffer");
+                    .map_err(recovery::get_frame_error)?;
+                let (width, height) = (cropped_buffer.width(), cropped_buffer.height());
 
                 // get raw frame buffer
                 let raw_frame_buffer = match cropped_buffer.as_nopadding_buffer() {
@@ -69,19 +381,30 @@ impl GraphicsCaptureApiHandler for Capturer {
                     Err(_) => return Err(("Failed to get raw buffer").into()),
                 };
 
-                let data = raw_frame_buffer.to_vec();
-                self.tx
-                    .send(get_frame(frame, data))
-                    .expect("Failed to send data");
+                let data = self.pool.copy_from(raw_frame_buffer);
+                let sent = self.send_frame(color_format, width, height, data, padding, composed);
+                self.keep_for_replay(frame, color_format, area, padding, sent);
             }
             None => {
                 // get raw frame buffer
-                let mut frame_buffer = frame.buffer().unwrap();
+                let mut frame_buffer = frame.buffer().map_err(recovery::get_frame_error)?;
+                let (width, height) = (frame_buffer.width(), frame_buffer.height());
+                let row_pitch = frame_buffer.row_pitch() as usize;
+                let bytes_per_pixel = get_bytes_per_pixel(color_format);
                 let raw_frame_buffer = frame_buffer.as_raw_buffer();
-                let frame_data = raw_frame_buffer.to_vec();
-                self.tx
-                    .send(get_frame(frame, frame_data))
-                    .expect("Failed to send data");
+                // The texture rows are usually padded to an alignment
+                let frame_data = if self.tight_packing || self.converter.tone_mapper.is_some() {
+                    remove_row_padding(
+                        raw_frame_buffer,
+                        row_pitch,
+                        width as usize * bytes_per_pixel,
+                        height as usize,
+                    )
+                } else {
+                    self.pool.copy_from(raw_frame_buffer)
+                };
+                let sent = self.send_frame(color_format, width, height, frame_data, None, composed);
+                self.keep_for_replay(frame, color_format, [0, 0, width, height], None, sent);
             }
         }
         Ok(())
@@ -93,73 +416,533 @@ impl GraphicsCaptureApiHandler for Capturer {
     }
 }
 
-fn get_frame(frame: &WCFrame, data: Vec<u8>) -> Frame {
-    let current_time = SystemTime::now()
+impl Capturer {
+    // Returns the display time of the frame if it was sent
+    fn send_frame(
+        &self,
+        color_format: ColorFormat,
+        width: u32,
+        height: u32,
+        data: Vec<u8>,
+        padding: Option<Padding>,
+        composed: TimeSpan,
+    ) -> Option<u64> {
+        let frame = self
+            .converter
+            .convert(color_format, width, height, data, padding)?;
+
+        // The watcher stands in for frames captured of the secure desktop
+        let mut secure_desktop = self.secure_desktop.lock().unwrap();
+        if secure_desktop.is_shown() {
+            return None;
+        }
+        secure_desktop.keep(&frame);
+        drop(secure_desktop);
+
+        let display_time = frame.display_time();
+        let latency = get_acquisition_latency(composed);
+        self.tx.send(frame, latency).expect("Failed to send data");
+        Some(display_time)
+    }
+
+    // Copies the part of the frame in `area` that was sent at `display_time`
+    // to the replay, for Options::gpu_replay
+    fn keep_for_replay(
+        &self,
+        frame: &WCFrame,
+        color_format: ColorFormat,
+        area: [u32; 4],
+        padding: Option<Padding>,
+        display_time: Option<u64>,
+    ) {
+        let (Some(replay), Some(display_time)) = (&self.replay, display_time) else {
+            return;
+        };
+        let texture = unsafe { frame.as_raw_texture() };
+        replay
+            .lock()
+            .unwrap()
+            .push(texture, color_format, area, padding, display_time);
+    }
+}
+
+impl FrameConverter {
+    // None if the frame is too small to reduce to grayscale
+    fn convert(
+        &self,
+        color_format: ColorFormat,
+        width: u32,
+        height: u32,
+        data: Vec<u8>,
+        padding: Option<Padding>,
+    ) -> Option<Frame> {
+        if self.native_format {
+            return Some(Frame::Raw(get_raw_frame(color_format, width, height, data)));
+        }
+        let mut frame = match &self.tone_mapper {
+            Some(tone_mapper) if color_format == ColorFormat::Rgba16F => match self.output_format {
+                ColorFormat::Bgra8 => get_frame(
+                    ColorFormat::Bgra8,
+                    width,
+                    height,
+                    tone_mapper.to_bgra(&data),
+                ),
+                _ => get_frame(
+                    ColorFormat::Rgba8,
+                    width,
+                    height,
+                    tone_mapper.to_rgba(&data),
+                ),
+            },
+            _ => get_frame(color_format, width, height, data),
+        };
+        if let Some(padding) = padding {
+            pad_frame(&mut frame, padding.offset, padding.size, padding.color);
+        }
+
+        match &self.grayscale {
+            Some(grayscale) => frame
+                .to_gray8(grayscale.width, grayscale.height, grayscale.weights)
+                .map(Frame::Gray8),
+            None => Some(frame),
+        }
+    }
+}
+
+// How long ago the compositor composed a frame, from its SystemRelativeTime,
+// which is the performance counter in 100ns units
+fn get_acquisition_latency(composed: TimeSpan) -> Option<Duration> {
+    let (mut counter, mut frequency) = (0, 0);
+    unsafe {
+        QueryPerformanceCounter(&mut counter).ok()?;
+        QueryPerformanceFrequency(&mut frequency).ok()?;
+    }
+    get_counter_elapsed(counter, frequency, composed.Duration)
+}
+
+// The time from `since`, in 100ns units, to when the performance counter
+// read `counter` at `frequency` ticks per second. Zero if the counter reads
+// earlier, as it can on processors whose counters disagree.
+fn get_counter_elapsed(counter: i64, frequency: i64, since: i64) -> Option<Duration> {
+    if frequency <= 0 {
+        return None;
+    }
+    let now = counter as i128 * 10_000_000 / frequency as i128;
+    let elapsed = (now - since as i128).max(0);
+    Some(Duration::from_nanos(elapsed as u64 * 100))
+}
+
+fn get_bytes_per_pixel(color_format: ColorFormat) -> usize {
+    match color_format {
+        ColorFormat::Rgba16F => 8,
+        ColorFormat::Rgba8 | ColorFormat::Bgra8 => 4,
+    }
+}
+
+// The time frames are stamped with as they arrive, the system clock in
+// nanoseconds, which events and synthesized frames use too
+pub(crate) fn get_current_time() -> u64 {
+    SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .expect("Failed to get current time")
-        .as_nanos() as u64;
-    match frame.color_format() {
+        .as_nanos() as u64
+}
+
+fn get_frame(color_format: ColorFormat, width: u32, height: u32, data: Vec<u8>) -> Frame {
+    let current_time = get_current_time();
+    match color_format {
         ColorFormat::Rgba16F => {
             Frame::RGBx(RGBxFrame {
                 display_time: current_time,
-                width: frame.width() as i32,
-                height: frame.height() as i32,
+                width: width as i32,
+                height: height as i32,
                 data,
+                origin: RowOrder::TopDown,
+                // Half float frames are linear scRGB
+                color_space: ColorSpace::Unknown,
             })
         }
-        ColorFormat::Rgba8 => {
-            Frame::RGB(RGBFrame {
-                display_time: current_time,
-                width: frame.width() as i32,
-                height: frame.height() as i32,
-                data,
-            })
-        }
-        ColorFormat::Bgra8 => {
-            Frame::BGRA(BGRAFrame {
-                display_time: current_time,
-                width: frame.width() as i32,
-                height: frame.height() as i32,
-                data,
-            })
+        ColorFormat::Rgba8 => Frame::RGB(RGBFrame {
+            display_time: current_time,
+            width: width as i32,
+            height: height as i32,
+            data,
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::SRGB,
+        }),
+        ColorFormat::Bgra8 => Frame::BGRA(BGRAFrame {
+            display_time: current_time,
+            width: width as i32,
+            height: height as i32,
+            data,
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::SRGB,
+        }),
+    }
+}
+
+fn get_raw_frame(color_format: ColorFormat, width: u32, height: u32, data: Vec<u8>) -> RawFrame {
+    let (format, color_space) = match color_format {
+        ColorFormat::Rgba16F => (RawPixelFormat::Rgba16Float, ColorSpace::Unknown),
+        ColorFormat::Rgba8 => (RawPixelFormat::Rgba8, ColorSpace::SRGB),
+        ColorFormat::Bgra8 => (RawPixelFormat::Bgra8, ColorSpace::SRGB),
+    };
+    RawFrame {
+        display_time: get_current_time(),
+        width: width as i32,
+        height: height as i32,
+        format,
+        data,
+        color_space,
+    }
+}
+
+type HandlerError = Box<dyn std::error::Error + Send + Sync>;
+
+// Attempts at starting capture when it fails for reasons that may pass, like
+// the D3D device getting lost while the display mode changes
+const START_ATTEMPTS: u32 = 3;
+const START_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+// What to do about capture failing to start
+#[derive(Debug)]
+enum StartFailure {
+    Retry,
+    // Older Windows versions can't toggle the cursor or the capture border
+    WithDefaultCursor,
+    WithDefaultBorder,
+    // Not every GPU can create a half float frame pool for HDR capture
+    WithoutHdr,
+    Fatal(CapturerBuildError),
+}
+
+// What capture fell back to when it last started
+#[derive(Debug, Clone, Copy, Default)]
+struct StartFallbacks {
+    default_cursor: bool,
+    default_border: bool,
+    without_hdr: bool,
+}
+
+fn get_start_failure(
+    error: &GraphicsCaptureApiError<HandlerError>,
+    color_format: ColorFormat,
+) -> StartFailure {
+    match error {
+        GraphicsCaptureApiError::ItemConvertFailed => StartFailure::Fatal(
+            CapturerBuildError::InvalidTarget("the window or display is gone".into()),
+        ),
+        GraphicsCaptureApiError::GraphicsCaptureApiError(error) => match error {
+            WCApiError::Unsupported => StartFailure::Fatal(CapturerBuildError::NotSupported),
+            WCApiError::CursorConfigUnsupported => StartFailure::WithDefaultCursor,
+            WCApiError::BorderConfigUnsupported => StartFailure::WithDefaultBorder,
+            // Creating the frame pool is the only call that depends on the format
+            WCApiError::WindowsError(_) if color_format == ColorFormat::Rgba16F => {
+                StartFailure::WithoutHdr
+            }
+            WCApiError::DirectXError(_) => StartFailure::Retry,
+            error => StartFailure::Fatal(CapturerBuildError::CaptureFailed(error.to_string())),
+        },
+        GraphicsCaptureApiError::FailedToInitWinRT
+        | GraphicsCaptureApiError::FailedToCreateDispatcherQueueController
+        | GraphicsCaptureApiError::DirectXError(_) => StartFailure::Retry,
+        error => StartFailure::Fatal(CapturerBuildError::CaptureFailed(error.to_string())),
+    }
+}
+
+fn start_with_retries<T>(
+    settings: &WCSettings<FlagStruct, T>,
+    fallbacks: &mut StartFallbacks,
+) -> Result<CaptureControl<Capturer, HandlerError>, CapturerBuildError>
+where
+    T: TryInto<GraphicsCaptureItem> + Clone + Send + 'static,
+{
+    let mut settings = settings.clone();
+    let mut attempts = 1;
+
+    loop {
+        let error = match Capturer::start_free_threaded(settings.clone()) {
+            Ok(capture_control) => return Ok(capture_control),
+            Err(error) => error,
+        };
+
+        let (mut cursor, mut border) = (settings.cursor_capture(), settings.draw_border());
+        let mut color_format = settings.color_format();
+        let mut flags = settings.flags().clone();
+        match get_start_failure(&error, color_format) {
+            StartFailure::Retry if attempts < START_ATTEMPTS => {
+                attempts += 1;
+                thread::sleep(START_RETRY_DELAY);
+            }
+            StartFailure::Retry => {
+                return Err(CapturerBuildError::CaptureFailed(error.to_string()));
+            }
+            StartFailure::WithDefaultCursor => {
+                cursor = CursorCaptureSettings::Default;
+                fallbacks.default_cursor = true;
+            }
+            StartFailure::WithDefaultBorder => {
+                border = DrawBorderSettings::Default;
+                fallbacks.default_border = true;
+            }
+            StartFailure::WithoutHdr => {
+                color_format = flags.output_format;
+                flags.hdr_white_level = None;
+                fallbacks.without_hdr = true;
+            }
+            StartFailure::Fatal(error) => return Err(error),
         }
+
+        settings = WCSettings::new(settings.item().clone(), cursor, border, color_format, flags);
     }
 }
 
+// Checks the target up front, so closed windows and disconnected displays
+// fail to build instead of to start
+fn validate_target(settings: &Settings) -> Result<(), CapturerBuildError> {
+    let item = match settings {
+        Settings::Display(st) => {
+            GraphicsCaptureItem::try_from(*st.item()).map_err(|e| e.to_string())
+        }
+        Settings::Window(st) => {
+            GraphicsCaptureItem::try_from(*st.item()).map_err(|e| e.to_string())
+        }
+    };
+    item.map(|_| ()).map_err(CapturerBuildError::InvalidTarget)
+}
+
 impl WCStream {
-    pub fn start_capture(&mut self) {
+    pub fn start_capture(&mut self) -> Result<(), CapturerBuildError> {
+        self.fallbacks = StartFallbacks::default();
         let cc = match &self.settings {
-            Settings::Display(st) => Capturer::start_free_threaded(st.to_owned()).unwrap(),
-            Settings::Window(st) => Capturer::start_free_threaded(st.to_owned()).unwrap(),
+            Settings::Display(st) => start_with_retries(st, &mut self.fallbacks)?,
+            Settings::Window(st) => start_with_retries(st, &mut self.fallbacks)?,
         };
 
-        self.capture_control = Some(cc)
+        *self.capture_control.lock().unwrap() = Some(cc);
+        self.recovery_watcher = self.recovery_events.as_ref().map(|events| {
+            recovery::DeviceRecoveryWatcher::new(
+                self.capture_control.clone(),
+                self.settings.clone(),
+                events.clone(),
+            )
+        });
+        self.watcher = self
+            .watch
+            .as_ref()
+            .map(|(window, events)| watcher::WindowWatcher::new(window, events.clone()));
+        self.focus_watcher = self.focus.as_ref().map(|(region, events)| {
+            focus::FocusWatcher::new(region.clone(), events.clone(), self.focused_bounds.clone())
+        });
+
+        *self.secure_desktop.lock().unwrap() = SecureDesktop::new(self.secure_desktop_policy);
+        let (tx, events, frame_interval) = self.secure_desktop_watch.clone();
+        self.secure_desktop_watcher = Some(secure_desktop::SecureDesktopWatcher::new(
+            self.secure_desktop.clone(),
+            tx,
+            events,
+            frame_interval,
+        ));
+        Ok(())
     }
 
     pub fn stop_capture(&mut self) {
-        let capture_control = self.capture_control.take().unwrap();
-        let _ = capture_control.stop();
+        // Before it could restart capture
+        self.recovery_watcher = None;
+        self.watcher = None;
+        self.focus_watcher = None;
+        *self.focused_bounds.lock().unwrap() = None;
+        self.secure_desktop_watcher = None;
+        // Gone if recovering from a GPU reset failed
+        if let Some(capture_control) = self.capture_control.lock().unwrap().take() {
+            let _ = capture_control.stop();
+        }
+    }
+
+    /// Paces frames to `options.fps` from the next frame on, also once
+    /// capture restarts
+    pub fn set_fps(&mut self, options: &Options) {
+        self.fps.store(get_paced_fps(options), Ordering::Relaxed);
+        self.secure_desktop_watch.2 = get_frame_interval(options);
+    }
+
+    /// The part of the target that's captured after rounding the crop area
+    /// to even physical pixels and clamping it to the target, or None if it
+    /// moves with a window or capture would fail on it
+    pub fn effective_source_rect(&self) -> Option<Area> {
+        self.source_rect.clone()
+    }
+
+    /// Reads the frames of the last `duration` back from the GPU, see
+    /// Options::gpu_replay
+    pub fn read_replay(&self, duration: Duration) -> Vec<Frame> {
+        match &self.replay {
+            Some(replay) => replay::read(replay, duration),
+            None => Vec::new(),
+        }
+    }
+
+    /// Where the focused window is in the frames, while Options::watch_focus
+    /// watches it
+    pub fn focused_bounds(&self) -> Option<Area> {
+        self.focused_bounds.lock().unwrap().clone()
+    }
+
+    pub fn replay_usage(&self) -> Option<GpuReplayUsage> {
+        self.replay
+            .as_ref()
+            .map(|replay| replay.lock().unwrap().usage())
+    }
+
+    pub fn get_process_exclusion(&self) -> ProcessExclusion {
+        self.exclusion
+            .as_ref()
+            .map_or(ProcessExclusion::Off, |exclusion| exclusion.mode())
+    }
+
+    // Adds what the OS only refuses once capture starts. Older Windows
+    // versions fall back to showing the cursor and the border.
+    pub fn negotiate(&self, config: &mut NegotiatedConfig) {
+        let fallbacks = self.fallbacks;
+        let mut downgrades = Vec::new();
+        let mut downgrade = |option: &str, reason: &str| {
+            downgrades.push(Downgrade {
+                option: option.to_string(),
+                reason: reason.to_string(),
+            })
+        };
+
+        if fallbacks.default_cursor && !config.show_cursor {
+            downgrade(
+                "show_cursor",
+                "this Windows version always captures the cursor",
+            );
+        }
+        if fallbacks.default_cursor && matches!(config.cursor_style, CursorStyle::Custom(_)) {
+            downgrade(
+                "cursor_style",
+                "this Windows version also captures the system cursor",
+            );
+        }
+        if fallbacks.default_border && !config.show_border {
+            downgrade(
+                "show_highlight",
+                "this Windows version always draws the border",
+            );
+        }
+        if fallbacks.without_hdr {
+            let option = match self.native_format {
+                true => "native_format",
+                false => "hdr_handling",
+            };
+            downgrade(option, "the GPU can't capture HDR in scRGB");
+        }
+        config.downgrades.extend(downgrades);
+
+        config.show_cursor |= fallbacks.default_cursor;
+        config.show_border |= fallbacks.default_border;
+        config.hdr_tone_mapping = self.hdr_tone_mapping && !fallbacks.without_hdr;
+        config.filled_fraction = self.filled_fraction;
     }
 }
 
 #[derive(Clone, Debug)]
 struct FlagStruct {
-    pub tx: mpsc::Sender<Frame>,
+    pub tx: FrameSender,
     pub crop: Option<Area>,
+    pub crop_overflow: CropOverflow,
+    pub tight_packing: bool,
+    pub fps: Arc<AtomicU32>,
+    pub hdr_white_level: Option<f32>,
+    pub output_format: ColorFormat,
+    pub window_tracker: Option<WindowTracker>,
+    pub subregion: Option<Subregion>,
+    pub events: mpsc::Sender<CapturerEvent>,
+    pub grayscale: Option<GrayscaleOptions>,
+    pub native_format: bool,
+    pub secure_desktop: Arc<Mutex<SecureDesktop>>,
+    pub pool: BufferPool,
+    pub replay: Option<replay::SharedGpuReplay>,
 }
 
-pub fn create_capturer(options: &Options, tx: mpsc::Sender<Frame>) -> WCStream {
-    let target = options
-        .target
-        .clone()
-        .unwrap_or_else(|| Target::Display(targets::get_main_display()));
+// The rate the handler paces frames to, 0 for all of them. Pacing at the
+// display rate would drop frames that arrive a little early, so the
+// compositor's rate is left alone.
+fn get_paced_fps(options: &Options) -> u32 {
+    match get_refresh_rate(options) {
+        Some(rate) if options.fps >= rate => 0,
+        _ => options.fps,
+    }
+}
+
+// Between synthesized frames, which follow the requested rate or the display's
+fn get_frame_interval(options: &Options) -> Duration {
+    let frame_rate = match options.fps {
+        0 => get_refresh_rate(options).unwrap_or(60),
+        fps => fps,
+    };
+    Duration::from_secs(1) / frame_rate
+}
+
+// Whether the system cursor is left out for a custom one that scap draws
+pub fn hides_system_cursor(options: &Options) -> bool {
+    matches!(
+        (&options.cursor_style, options.output_type),
+        (CursorStyle::Custom(_), FrameType::BGRAFrame)
+    )
+}
+
+// The monitor a target is shown on
+fn get_target_monitor(target: &Target) -> HMONITOR {
+    match target {
+        Target::Display(display) => display.raw_handle,
+        Target::Window(window) => unsafe {
+            MonitorFromWindow(window.raw_handle, MONITOR_DEFAULTTONEAREST)
+        },
+    }
+}
+
+pub fn create_capturer(
+    options: &Options,
+    tx: FrameSender,
+    events: mpsc::Sender<CapturerEvent>,
+    pool: BufferPool,
+) -> Result<WCStream, CapturerBuildError> {
+    // Headless sessions, like services or disconnected remote desktops, have
+    // no display to fall back to
+    let target = match options.target.clone() {
+        Some(target) => target,
+        None if WCMonitor::primary().is_err() => {
+            return Err(CapturerBuildError::InvalidTarget(
+                "no display is connected".to_string(),
+            ))
+        }
+        None => Target::Display(targets::get_main_display()),
+    };
 
-    let color_format = match options.output_type {
+    let output_format = match options.output_type {
+        // What SDR displays are composited in
+        _ if options.native_format => ColorFormat::Bgra8,
         FrameType::BGRAFrame => ColorFormat::Bgra8,
         _ => ColorFormat::Rgba8,
     };
 
-    let show_cursor = match options.show_cursor {
+    let hdr_white_level = match options.hdr_handling {
+        _ if options.native_format => None,
+        HdrHandling::PassThrough => None,
+        HdrHandling::ToneMapToSDR => hdr::get_hdr_white_level(get_target_monitor(&target)),
+    };
+    // HDR is composited in scRGB, capture it as is and tone map it ourselves,
+    // or deliver it as is for Options::native_format
+    let composited_in_hdr = || hdr::get_hdr_white_level(get_target_monitor(&target)).is_some();
+    let color_format = match hdr_white_level {
+        Some(_) => ColorFormat::Rgba16F,
+        None if options.native_format && composited_in_hdr() => ColorFormat::Rgba16F,
+        None => output_format,
+    };
+
+    // Custom cursors replace the system one where scap can draw them
+    let show_cursor = match options.show_cursor && !hides_system_cursor(options) {
         true => CursorCaptureSettings::WithCursor,
         false => CursorCaptureSettings::WithoutCursor,
     };
@@ -169,33 +952,173 @@ pub fn create_capturer(options: &Options, tx: mpsc::Sender<Frame>) -> WCStream {
         false => DrawBorderSettings::WithoutBorder,
     };
 
+    let watch = match &target {
+        Target::Window(window) if options.watch_window => Some((window.clone(), events.clone())),
+        _ => None,
+    };
+    let focus_events = options.watch_focus.then(|| events.clone());
+    let recovery_events = options.auto_recover_device.then(|| events.clone());
+
+    let secure_desktop = Arc::new(Mutex::new(SecureDesktop::new(options.secure_desktop)));
+    let secure_desktop_watch = (tx.clone(), events.clone(), get_frame_interval(options));
+    let fps = Arc::new(AtomicU32::new(get_paced_fps(options)));
+    let replay = options
+        .gpu_replay
+        .map(|replay| Arc::new(Mutex::new(replay::GpuReplay::new(replay, events.clone()))));
+
+    let flags = FlagStruct {
+        tx,
+        crop: Some(get_crop_area(options)),
+        crop_overflow: options.crop_overflow,
+        tight_packing: options.guarantee_tight_packing,
+        fps: fps.clone(),
+        hdr_white_level,
+        output_format,
+        window_tracker: None,
+        subregion: match &target {
+            Target::Window(window)
+                if !matches!(options.window_subregion, WindowSubregion::Whole) =>
+            {
+                Some(Subregion {
+                    window: window.raw_handle.0 as isize,
+                    region: options.window_subregion.clone(),
+                    last: None,
+                    on_panic: options.callback_panic,
+                })
+            }
+            _ => None,
+        },
+        events,
+        grayscale: options.grayscale,
+        native_format: options.native_format,
+        secure_desktop: secure_desktop.clone(),
+        pool,
+        replay: replay.clone(),
+    };
+
+    let target_window = match &target {
+        Target::Window(window) => Some(window.raw_handle),
+        Target::Display(_) => None,
+    };
+
+    let screen_region = matches!(target, Target::Window(_))
+        && options.window_content_mode == WindowContentMode::ScreenRegion;
+    let source_rect = match flags.subregion {
+        Some(_) => None,
+        None if screen_region => None,
+        None => {
+            let (width, height) = targets::get_target_dimensions(&target);
+            get_effective_source_rect(
+                &get_crop_area(options),
+                width as u32,
+                height as u32,
+                options.crop_overflow,
+            )
+        }
+    };
+
+    let filled_fraction = match (options.crop_overflow, &source_rect) {
+        (CropOverflow::Fill(_), Some(rect)) => {
+            let crop = get_crop_area(options);
+            let area = crop.size.width * crop.size.height;
+            match area > 0.0 {
+                true => 1.0 - rect.size.width * rect.size.height / area,
+                false => 0.0,
+            }
+        }
+        _ => 0.0,
+    };
+
+    // Placed relative to the target before subregions, which move with it
+    let focus = focus_events.map(|events| {
+        let focus_target = match &target {
+            Target::Display(display) => focus::FocusTarget::Display(display.raw_handle.0 as isize),
+            Target::Window(window) => focus::FocusTarget::Window(window.raw_handle.0 as isize),
+        };
+        let region = focus::FocusRegion {
+            target: focus_target,
+            source_rect: source_rect.clone(),
+        };
+        (region, events)
+    });
+
     let settings = match target {
         Target::Display(display) => Settings::Display(WCSettings::new(
             WCMonitor::from_raw_hmonitor(display.raw_handle.0),
             show_cursor,
             show_highlight,
             color_format,
-            FlagStruct {
-                tx,
-                crop: Some(get_crop_area(options)),
-            },
+            flags,
         )),
+        // Capture the window's monitor and crop it to wherever the window is
+        Target::Window(ref window)
+            if options.window_content_mode == WindowContentMode::ScreenRegion =>
+        {
+            let monitor = get_target_monitor(&target);
+            Settings::Display(WCSettings::new(
+                WCMonitor::from_raw_hmonitor(monitor.0),
+                show_cursor,
+                show_highlight,
+                color_format,
+                FlagStruct {
+                    crop: None,
+                    // The window can always move partially off-screen
+                    crop_overflow: CropOverflow::Clamp,
+                    window_tracker: Some(WindowTracker {
+                        window: window.raw_handle.0 as isize,
+                        monitor: monitor.0 as isize,
+                        crop: options.crop_area.as_ref().map(|_| get_crop_area(options)),
+                        bounds: None,
+                    }),
+                    ..flags
+                },
+            ))
+        }
         Target::Window(window) => Settings::Window(WCSettings::new(
             WCWindow::from_raw_hwnd(window.raw_handle.0),
             show_cursor,
             show_highlight,
             color_format,
-            FlagStruct {
-                tx,
-                crop: Some(get_crop_area(options)),
-            },
+            flags,
         )),
     };
 
-    WCStream {
+    validate_target(&settings)?;
+
+    // The captured window itself must stay visible
+    let exclusion = options
+        .exclude_current_process
+        .then(|| exclusion::ProcessWindowExcluder::new(target_window));
+
+    Ok(WCStream {
         settings,
-        capture_control: None,
-    }
+        capture_control: Arc::new(Mutex::new(None)),
+        exclusion,
+        watch,
+        watcher: None,
+        focus,
+        focus_watcher: None,
+        focused_bounds: Arc::new(Mutex::new(None)),
+        secure_desktop,
+        secure_desktop_policy: options.secure_desktop,
+        secure_desktop_watch,
+        secure_desktop_watcher: None,
+        recovery_events,
+        recovery_watcher: None,
+        fps,
+        hdr_tone_mapping: hdr_white_level.is_some(),
+        native_format: options.native_format,
+        fallbacks: StartFallbacks::default(),
+        source_rect,
+        filled_fraction,
+        replay,
+    })
+}
+
+// The size of the captured area in physical pixels
+pub fn get_captured_size(options: &Options) -> [u32; 2] {
+    let crop_area = get_crop_area(options);
+    [crop_area.size.width as u32, crop_area.size.height as u32]
 }
 
 pub fn get_output_frame_size(options: &Options) -> [u32; 2] {
@@ -206,8 +1129,8 @@ pub fn get_output_frame_size(options: &Options) -> [u32; 2] {
 
     let crop_area = get_crop_area(options);
 
-    let mut output_width = (crop_area.size.width) as u32;
-    let mut output_height = (crop_area.size.height) as u32;
+    let [mut output_width, mut output_height] =
+        super::fit_max_dimension(options, get_captured_size(options));
 
     match options.output_resolution {
         Resolution::Captured => {}
@@ -227,6 +1150,56 @@ pub fn get_output_frame_size(options: &Options) -> [u32; 2] {
     [output_width, output_height]
 }
 
+// The part of `width` x `height` frames the capture handler copies for
+// `crop`, as start and end corners, and for CropOverflow::Fill where it goes
+fn get_frame_crop(
+    crop: &Area,
+    width: u32,
+    height: u32,
+    overflow: CropOverflow,
+) -> Result<([u32; 4], Option<Padding>), String> {
+    let size = Size {
+        width: width as f64,
+        height: height as f64,
+    };
+    let crop = &match overflow {
+        CropOverflow::Shift => shift_area(crop, &size),
+        _ => crop.clone(),
+    };
+    let start_x = crop.origin.x as u32;
+    let start_y = crop.origin.y as u32;
+    let end_x = (crop.origin.x + crop.size.width) as u32;
+    let end_y = (crop.origin.y + crop.size.height) as u32;
+
+    // buffer_crop doesn't check the frame bounds, so clamp the area to them
+    let clamped_end_x = end_x.min(width);
+    let clamped_end_y = end_y.min(height);
+    if (clamped_end_x, clamped_end_y) != (end_x, end_y) && overflow == CropOverflow::Strict {
+        return Err(format!(
+            "Crop area ends at ({end_x}, {end_y}) which is outside the {width}x{height} frame"
+        ));
+    }
+    if start_x >= clamped_end_x || start_y >= clamped_end_y {
+        return Err("Crop area lies outside the captured frame".to_string());
+    }
+    // Crop areas before the frame's origin start at 0 above
+    let overflows = (clamped_end_x, clamped_end_y) != (end_x, end_y)
+        || crop.origin.x < 0.0
+        || crop.origin.y < 0.0;
+    let padding = match overflow {
+        CropOverflow::Fill(color) if overflows => Some(Padding {
+            offset: [
+                (start_x as f64 - crop.origin.x.floor()) as usize,
+                (start_y as f64 - crop.origin.y.floor()) as usize,
+            ],
+            size: [crop.size.width as usize, crop.size.height as usize],
+            color,
+        }),
+        _ => None,
+    };
+    Ok(([start_x, start_y, clamped_end_x, clamped_end_y], padding))
+}
+
 fn get_absolute_value(value: f64, scale_factor: f64) -> f64 {
     let value = (value * scale_factor).floor();
     value + value % 2.0
@@ -241,7 +1214,7 @@ pub fn get_crop_area(options: &Options) -> Area {
     let (width, height) = targets::get_target_dimensions(&target);
 
     let scale_factor = targets::get_scale_factor(&target);
-    options
+    let area = options
         .crop_area
         .as_ref()
         .map(|val| {
@@ -263,5 +1236,335 @@ pub fn get_crop_area(options: &Options) -> Area {
                 width: width as f64,
                 height: height as f64,
             },
-        })
+        });
+    match &options.aspect_crop {
+        Some(crop) => fit_aspect_ratio(&area, crop),
+        None => area,
+    }
+}
+
+// What the capture handler makes of `crop` on `width` x `height` frames, or
+// None if it stops capture
+fn get_effective_source_rect(
+    crop: &Area,
+    width: u32,
+    height: u32,
+    overflow: CropOverflow,
+) -> Option<Area> {
+    let size = Size {
+        width: width as f64,
+        height: height as f64,
+    };
+    let crop = &match overflow {
+        CropOverflow::Shift => shift_area(crop, &size),
+        _ => crop.clone(),
+    };
+    let [x, y, clamped_width, clamped_height] =
+        get_clamped_bounds(crop, width as usize, height as usize)?;
+    let end = (
+        (crop.origin.x + crop.size.width).floor(),
+        (crop.origin.y + crop.size.height).floor(),
+    );
+    let clamped_end = ((x + clamped_width) as f64, (y + clamped_height) as f64);
+    if overflow == CropOverflow::Strict && end != clamped_end {
+        return None;
+    }
+
+    Some(Area {
+        origin: Point {
+            x: x as f64,
+            y: y as f64,
+        },
+        size: Size {
+            width: clamped_width as f64,
+            height: clamped_height as f64,
+        },
+    })
+}
+
+// The refresh rate of the monitor the target is on. Values of 0 and 1 stand
+// for the hardware default and say nothing.
+pub fn get_refresh_rate(options: &Options) -> Option<u32> {
+    let target = match &options.target {
+        Some(target) => target.clone(),
+        None => Target::Display(targets::get_main_display()),
+    };
+
+    let monitor = WCMonitor::from_raw_hmonitor(get_target_monitor(&target).0);
+    monitor.refresh_rate().ok().filter(|&rate| rate > 1)
+}
+
+pub fn get_cursor_position(options: &Options) -> Option<Point> {
+    let target = options
+        .target
+        .clone()
+        .unwrap_or_else(|| Target::Display(targets::get_main_display()));
+
+    let mut cursor_info = CURSORINFO {
+        cbSize: std::mem::size_of::<CURSORINFO>() as u32,
+        ..Default::default()
+    };
+    unsafe { GetCursorInfo(&mut cursor_info).ok()? };
+    if cursor_info.flags != CURSOR_SHOWING {
+        return None;
+    }
+
+    // The cursor's offset from the top left corner of the target, the
+    // physical pixels per unit of that offset, and the target's size in
+    // physical pixels
+    let (offset, scale, size) = match &target {
+        Target::Display(display) => unsafe {
+            let mut monitor_info = MONITORINFO {
+                cbSize: std::mem::size_of::<MONITORINFO>() as u32,
+                ..Default::default()
+            };
+            if !GetMonitorInfoW(display.raw_handle, &mut monitor_info).as_bool() {
+                return None;
+            }
+            let rect = monitor_info.rcMonitor;
+            let monitor = WCMonitor::from_raw_hmonitor(display.raw_handle.0);
+            let size = (monitor.width().ok()?, monitor.height().ok()?);
+
+            // Processes that aren't DPI aware get scaled down coordinates
+            let scale = size.0 as f64 / (rect.right - rect.left).max(1) as f64;
+            let position = cursor_info.ptScreenPos;
+            ((position.x - rect.left, position.y - rect.top), scale, size)
+        },
+        // Window capture covers the extended frame bounds, which are in
+        // physical pixels whatever the DPI awareness
+        Target::Window(window) => unsafe {
+            let mut rect = RECT::default();
+            DwmGetWindowAttribute(
+                window.raw_handle,
+                DWMWA_EXTENDED_FRAME_BOUNDS,
+                &mut rect as *mut _ as _,
+                std::mem::size_of::<RECT>() as u32,
+            )
+            .ok()?;
+            let mut position = POINT::default();
+            GetPhysicalCursorPos(&mut position).ok()?;
+            let size = (
+                (rect.right - rect.left).max(0) as u32,
+                (rect.bottom - rect.top).max(0) as u32,
+            );
+            ((position.x - rect.left, position.y - rect.top), 1.0, size)
+        },
+    };
+
+    // Frames only cover the part of the crop area inside the target
+    let source_rect = get_effective_source_rect(
+        &get_crop_area(options),
+        size.0,
+        size.1,
+        options.crop_overflow,
+    )?;
+    Some(get_relative_cursor_position(offset, scale, &source_rect))
+}
+
+// Where a cursor at `offset` from the target's corner, in units of `scale`
+// physical pixels, is as a fraction of the captured `source_rect`
+fn get_relative_cursor_position(offset: (i32, i32), scale: f64, source_rect: &Area) -> Point {
+    Point {
+        x: (offset.0 as f64 * scale - source_rect.origin.x) / source_rect.size.width,
+        y: (offset.1 as f64 * scale - source_rect.origin.y) / source_rect.size.height,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_window_target() {
+        let options = Options {
+            target: Some(Target::Window(targets::Window {
+                id: 0,
+                title: "closed".into(),
+                // Never a valid window, handles are even
+                raw_handle: HWND(0x7ffd as _),
+            })),
+            ..Default::default()
+        };
+        let (tx, _rx) = crate::capturer::handoff::channel(None);
+        let (events, _) = mpsc::channel();
+
+        let result = create_capturer(&options, FrameSender::new(tx), events, BufferPool::new(0));
+        assert!(matches!(result, Err(CapturerBuildError::InvalidTarget(_))));
+    }
+
+    #[test]
+    fn test_start_failures() {
+        let failure = |error, color_format| get_start_failure(&error, color_format);
+
+        assert!(matches!(
+            failure(
+                GraphicsCaptureApiError::ItemConvertFailed,
+                ColorFormat::Bgra8
+            ),
+            StartFailure::Fatal(CapturerBuildError::InvalidTarget(_))
+        ));
+        assert!(matches!(
+            failure(
+                GraphicsCaptureApiError::GraphicsCaptureApiError(WCApiError::Unsupported),
+                ColorFormat::Bgra8
+            ),
+            StartFailure::Fatal(CapturerBuildError::NotSupported)
+        ));
+        assert!(matches!(
+            failure(
+                GraphicsCaptureApiError::GraphicsCaptureApiError(
+                    WCApiError::CursorConfigUnsupported
+                ),
+                ColorFormat::Bgra8
+            ),
+            StartFailure::WithDefaultCursor
+        ));
+        assert!(matches!(
+            failure(
+                GraphicsCaptureApiError::FailedToInitWinRT,
+                ColorFormat::Bgra8
+            ),
+            StartFailure::Retry
+        ));
+
+        // Only HDR capture falls back when the frame pool can't be created
+        let frame_pool_error = || {
+            GraphicsCaptureApiError::GraphicsCaptureApiError(WCApiError::WindowsError(
+                windows::core::Error::from_hresult(windows::Win32::Foundation::E_INVALIDARG),
+            ))
+        };
+        assert!(matches!(
+            failure(frame_pool_error(), ColorFormat::Rgba16F),
+            StartFailure::WithoutHdr
+        ));
+        assert!(matches!(
+            failure(frame_pool_error(), ColorFormat::Bgra8),
+            StartFailure::Fatal(CapturerBuildError::CaptureFailed(_))
+        ));
+    }
+
+    #[test]
+    fn test_counter_elapsed() {
+        // The usual 10 MHz counter ticks in the 100ns units of frame times
+        assert_eq!(
+            get_counter_elapsed(1_000_500, 10_000_000, 1_000_000),
+            Some(Duration::from_micros(50))
+        );
+        // A 3 MHz counter read one second in, for a frame composed at 0.9 s
+        assert_eq!(
+            get_counter_elapsed(3_000_000, 3_000_000, 9_000_000),
+            Some(Duration::from_millis(100))
+        );
+        // Days of uptime don't overflow
+        let day = 24 * 60 * 60;
+        assert_eq!(
+            get_counter_elapsed(
+                100 * day * 10_000_000,
+                10_000_000,
+                100 * day * 10_000_000 - 10
+            ),
+            Some(Duration::from_micros(1))
+        );
+
+        // A counter that went backwards, and one that isn't there
+        assert_eq!(
+            get_counter_elapsed(999_000, 10_000_000, 1_000_000),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(get_counter_elapsed(1_000_500, 0, 1_000_000), None);
+    }
+
+    #[test]
+    fn test_frame_crop() {
+        let area = |x, y, width, height| Area {
+            origin: Point { x, y },
+            size: Size { width, height },
+        };
+        let crop = |area, overflow| get_frame_crop(&area, 1920, 1080, overflow);
+
+        let inside = area(100.0, 100.0, 640.0, 480.0);
+        for overflow in [CropOverflow::Strict, CropOverflow::Clamp] {
+            let (bounds, padding) = crop(inside.clone(), overflow).unwrap();
+            assert_eq!(bounds, [100, 100, 740, 580]);
+            assert!(padding.is_none());
+        }
+
+        // Strict refuses what Clamp cuts down to the 320x180 that's delivered
+        let past_corner = area(1600.0, 900.0, 640.0, 480.0);
+        assert!(crop(past_corner.clone(), CropOverflow::Strict)
+            .unwrap_err()
+            .contains("outside the 1920x1080 frame"));
+        let (bounds, padding) = crop(past_corner.clone(), CropOverflow::Clamp).unwrap();
+        assert_eq!(bounds, [1600, 900, 1920, 1080]);
+        assert!(padding.is_none());
+        let (bounds, _) = crop(past_corner.clone(), CropOverflow::Shift).unwrap();
+        assert_eq!(bounds, [1280, 600, 1920, 1080]);
+
+        // Fill cuts it down as well and places it on a canvas of the full size
+        let (bounds, padding) = crop(past_corner, CropOverflow::Fill([0, 0, 0])).unwrap();
+        assert_eq!(bounds, [1600, 900, 1920, 1080]);
+        let padding = padding.unwrap();
+        assert_eq!((padding.offset, padding.size), ([0, 0], [640, 480]));
+        let (_, padding) =
+            crop(area(-10.0, -20.0, 100.0, 100.0), CropOverflow::Fill([0; 3])).unwrap();
+        assert_eq!(padding.unwrap().offset, [10, 20]);
+
+        assert!(crop(area(2000.0, 0.0, 100.0, 100.0), CropOverflow::Clamp).is_err());
+    }
+
+    #[test]
+    fn test_effective_source_rect() {
+        let area = |x, y, width, height| Area {
+            origin: Point { x, y },
+            size: Size { width, height },
+        };
+        let rect = |crop, overflow| get_effective_source_rect(&crop, 1920, 1080, overflow);
+
+        let crop = area(100.0, 100.0, 640.0, 480.0);
+        assert_eq!(rect(crop.clone(), CropOverflow::Strict), Some(crop));
+
+        // Clamped to the frame unless the crop has to be satisfied
+        let crop = area(1600.0, 900.0, 640.0, 480.0);
+        assert_eq!(
+            rect(crop.clone(), CropOverflow::Clamp),
+            Some(area(1600.0, 900.0, 320.0, 180.0))
+        );
+        assert_eq!(rect(crop.clone(), CropOverflow::Strict), None);
+        assert_eq!(
+            rect(crop, CropOverflow::Shift),
+            Some(area(1280.0, 600.0, 640.0, 480.0))
+        );
+        assert_eq!(
+            rect(area(2000.0, 0.0, 100.0, 100.0), CropOverflow::Clamp),
+            None
+        );
+    }
+
+    #[test]
+    fn test_relative_cursor_position() {
+        let area = |x, y, width, height| Area {
+            origin: Point { x, y },
+            size: Size { width, height },
+        };
+        let position = |offset, scale, rect: &Area| {
+            let point = get_relative_cursor_position(offset, scale, rect);
+            (point.x * rect.size.width, point.y * rect.size.height)
+        };
+
+        // A 2x display seen by a process that isn't DPI aware
+        let crop = area(200.0, 100.0, 400.0, 300.0);
+        assert_eq!(position((150, 100), 2.0, &crop), (100.0, 100.0));
+        assert_eq!(position((150, 100), 1.0, &crop), (-50.0, 0.0));
+
+        // Near the edges of a crop that was clamped to a 1920x1080 display
+        let crop = get_effective_source_rect(
+            &area(1600.0, 900.0, 640.0, 480.0),
+            1920,
+            1080,
+            CropOverflow::Clamp,
+        )
+        .unwrap();
+        assert_eq!(position((1919, 1079), 1.0, &crop), (319.0, 179.0));
+        assert_eq!(position((1600, 900), 1.0, &crop), (0.0, 0.0));
+    }
 }
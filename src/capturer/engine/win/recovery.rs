@@ -0,0 +1,164 @@
+use std::{
+    error::Error,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use windows::Win32::Graphics::Dxgi::{
+    DXGI_ERROR_DEVICE_HUNG, DXGI_ERROR_DEVICE_REMOVED, DXGI_ERROR_DEVICE_RESET,
+};
+use windows_capture::{
+    capture::{CaptureControl, CaptureControlError, GraphicsCaptureApiError},
+    frame::Error as WCFrameError,
+};
+
+use super::{
+    get_current_time, start_with_retries, Capturer, HandlerError, Settings, StartFallbacks,
+};
+use crate::capturer::{recovery::DeviceRecovery, CapturerEvent};
+
+// How often the watcher checks whether the capture thread ended
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+pub(super) type SharedCaptureControl = Arc<Mutex<Option<CaptureControl<Capturer, HandlerError>>>>;
+
+// What the handler stops with when a frame can't be read because the GPU
+// was reset or the driver removed
+#[derive(Debug)]
+pub(super) struct DeviceLost;
+
+impl fmt::Display for DeviceLost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The GPU device was lost")
+    }
+}
+
+impl Error for DeviceLost {}
+
+// The handler's error for a frame that failed to be read
+pub(super) fn get_frame_error(error: WCFrameError) -> HandlerError {
+    match &error {
+        WCFrameError::WindowsError(e)
+            if [
+                DXGI_ERROR_DEVICE_REMOVED,
+                DXGI_ERROR_DEVICE_RESET,
+                DXGI_ERROR_DEVICE_HUNG,
+            ]
+            .contains(&e.code()) =>
+        {
+            Box::new(DeviceLost)
+        }
+        _ => error.to_string().into(),
+    }
+}
+
+fn is_device_lost(result: &Result<(), CaptureControlError<HandlerError>>) -> bool {
+    matches!(
+        result,
+        Err(CaptureControlError::GraphicsCaptureApiError(
+            GraphicsCaptureApiError::FrameHandlerError(error)
+        )) if error.is::<DeviceLost>()
+    )
+}
+
+// Sleeps for `delay` or until stopped, returning whether it was stopped
+fn sleep_unless_stopped(stop: &AtomicBool, delay: Duration) -> bool {
+    let until = Instant::now() + delay;
+    while Instant::now() < until {
+        if stop.load(Ordering::Acquire) {
+            return true;
+        }
+        thread::sleep(POLL_INTERVAL.min(until - Instant::now()));
+    }
+    stop.load(Ordering::Acquire)
+}
+
+/// Restarts capture with a new device and frame pool when the GPU device
+/// was lost, until dropped. See [Options::auto_recover_device](crate::capturer::Options::auto_recover_device)
+pub struct DeviceRecoveryWatcher {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DeviceRecoveryWatcher {
+    pub(super) fn new(
+        control: SharedCaptureControl,
+        settings: Settings,
+        events: mpsc::Sender<CapturerEvent>,
+    ) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+
+        let thread = thread::spawn(move || {
+            let mut recovery = DeviceRecovery::new();
+            while !sleep_unless_stopped(&stopped, POLL_INTERVAL) {
+                let finished = {
+                    let mut control = control.lock().unwrap();
+                    match control.as_ref().is_some_and(|c| c.is_finished()) {
+                        true => control.take(),
+                        false => None,
+                    }
+                };
+                let Some(finished) = finished else {
+                    continue;
+                };
+                // Capture that ended for another reason stays stopped
+                if !is_device_lost(&finished.wait()) {
+                    return;
+                }
+
+                let _ = events.send(CapturerEvent::DeviceLost {
+                    time: get_current_time(),
+                });
+                recovery.lost(Instant::now());
+
+                loop {
+                    let Some(delay) = recovery.next_delay() else {
+                        let _ = events.send(CapturerEvent::DeviceRecoveryFailed {
+                            attempts: recovery.attempts(),
+                        });
+                        return;
+                    };
+                    if sleep_unless_stopped(&stopped, delay) {
+                        return;
+                    }
+
+                    // Every start creates a new device and frame pool
+                    let mut fallbacks = StartFallbacks::default();
+                    let started = match &settings {
+                        Settings::Display(st) => start_with_retries(st, &mut fallbacks),
+                        Settings::Window(st) => start_with_retries(st, &mut fallbacks),
+                    };
+                    if let Ok(capture_control) = started {
+                        *control.lock().unwrap() = Some(capture_control);
+                        recovery.restarted(Instant::now());
+                        let _ = events.send(CapturerEvent::DeviceRecovered {
+                            time: get_current_time(),
+                            attempts: recovery.attempts(),
+                        });
+                        break;
+                    }
+                }
+            }
+        });
+
+        DeviceRecoveryWatcher {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for DeviceRecoveryWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
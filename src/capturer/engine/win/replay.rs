@@ -0,0 +1,294 @@
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use windows::core::Interface;
+use windows::Win32::Graphics::{
+    Direct3D11::{
+        ID3D11Device, ID3D11DeviceContext, ID3D11Multithread, ID3D11Texture2D, D3D11_BOX,
+        D3D11_CPU_ACCESS_READ, D3D11_MAPPED_SUBRESOURCE, D3D11_MAP_READ, D3D11_TEXTURE2D_DESC,
+        D3D11_USAGE_DEFAULT, D3D11_USAGE_STAGING,
+    },
+    Dxgi::{
+        Common::{DXGI_FORMAT, DXGI_SAMPLE_DESC},
+        IDXGIAdapter3, IDXGIDevice, DXGI_MEMORY_SEGMENT_GROUP_LOCAL, DXGI_QUERY_VIDEO_MEMORY_INFO,
+    },
+};
+use windows_capture::settings::ColorFormat;
+
+use super::{get_bytes_per_pixel, FrameConverter, Padding};
+use crate::capturer::replay::{GpuReplayOptions, GpuReplayUsage, ReplayRing};
+use crate::capturer::CapturerEvent;
+use crate::frame::{remove_row_padding, Frame};
+
+// How often the GPU's memory budget is checked
+const BUDGET_INTERVAL: Duration = Duration::from_secs(1);
+
+// A copy of the delivered part of a frame, in the format it was captured in
+#[derive(Debug, Clone)]
+struct ReplayTexture {
+    texture: ID3D11Texture2D,
+    width: u32,
+    height: u32,
+    color_format: ColorFormat,
+    padding: Option<Padding>,
+}
+
+// The capture session's device, which the copies are made on
+#[derive(Debug)]
+struct Session {
+    device: ID3D11Device,
+    context: ID3D11DeviceContext,
+    adapter: Option<IDXGIAdapter3>,
+    converter: Arc<FrameConverter>,
+}
+
+// Keeps the latest frames for Options::gpu_replay. Shared between the
+// handler, which adds every delivered frame, and the app, which reads them
+// back.
+#[derive(Debug)]
+pub(crate) struct GpuReplay {
+    ring: ReplayRing<ReplayTexture>,
+    session: Option<Session>,
+    budget_checked: Option<Instant>,
+    events: mpsc::Sender<CapturerEvent>,
+}
+
+pub(crate) type SharedGpuReplay = Arc<Mutex<GpuReplay>>;
+
+impl GpuReplay {
+    pub fn new(options: GpuReplayOptions, events: mpsc::Sender<CapturerEvent>) -> Self {
+        GpuReplay {
+            ring: ReplayRing::new(options),
+            session: None,
+            budget_checked: None,
+            events,
+        }
+    }
+
+    // Starts over on the device of a new capture session. Textures of an
+    // earlier one can't be copied on it.
+    pub fn start(
+        &mut self,
+        device: &ID3D11Device,
+        context: &ID3D11DeviceContext,
+        converter: Arc<FrameConverter>,
+    ) {
+        // Frames are read back on the app's thread while the handler uses
+        // the immediate context
+        if let Ok(multithread) = context.cast::<ID3D11Multithread>() {
+            unsafe {
+                let _ = multithread.SetMultithreadProtected(true);
+            }
+        }
+        let adapter = unsafe {
+            device
+                .cast::<IDXGIDevice>()
+                .and_then(|device| device.GetAdapter())
+                .and_then(|adapter| adapter.cast::<IDXGIAdapter3>())
+                .ok()
+        };
+
+        self.ring.clear();
+        self.budget_checked = None;
+        self.session = Some(Session {
+            device: device.clone(),
+            context: context.clone(),
+            adapter,
+            converter,
+        });
+    }
+
+    // Copies the part of `frame` in `area`, [left, top, right, bottom], that
+    // was delivered at `display_time`
+    pub fn push(
+        &mut self,
+        frame: &ID3D11Texture2D,
+        color_format: ColorFormat,
+        area: [u32; 4],
+        padding: Option<Padding>,
+        display_time: u64,
+    ) {
+        let Some(session) = &self.session else {
+            return;
+        };
+        let [left, top, right, bottom] = area;
+        let (width, height) = (right - left, bottom - top);
+
+        let desc = D3D11_TEXTURE2D_DESC {
+            Width: width,
+            Height: height,
+            MipLevels: 1,
+            ArraySize: 1,
+            Format: DXGI_FORMAT(color_format as i32),
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            Usage: D3D11_USAGE_DEFAULT,
+            ..Default::default()
+        };
+        let mut texture = None;
+        let created = unsafe {
+            session
+                .device
+                .CreateTexture2D(&desc, None, Some(&mut texture))
+        };
+        let Some(texture) = created.ok().and(texture) else {
+            // Most likely out of video memory, which older frames make way for
+            self.reduce();
+            return;
+        };
+        unsafe {
+            session.context.CopySubresourceRegion(
+                &texture,
+                0,
+                0,
+                0,
+                0,
+                frame,
+                0,
+                Some(&D3D11_BOX {
+                    left,
+                    top,
+                    front: 0,
+                    right,
+                    bottom,
+                    back: 1,
+                }),
+            );
+        }
+
+        let bytes = width as u64 * height as u64 * get_bytes_per_pixel(color_format) as u64;
+        let texture = ReplayTexture {
+            texture,
+            width,
+            height,
+            color_format,
+            padding,
+        };
+        self.ring.push(display_time, bytes, texture);
+        self.check_budget();
+    }
+
+    // Keeps frames for a shorter time while the process uses more video
+    // memory than the OS budgets for it
+    fn check_budget(&mut self) {
+        let now = Instant::now();
+        if self
+            .budget_checked
+            .is_some_and(|checked| now - checked < BUDGET_INTERVAL)
+        {
+            return;
+        }
+        self.budget_checked = Some(now);
+
+        let Some(adapter) = self.session.as_ref().and_then(|s| s.adapter.as_ref()) else {
+            return;
+        };
+        let mut info = DXGI_QUERY_VIDEO_MEMORY_INFO::default();
+        let queried =
+            unsafe { adapter.QueryVideoMemoryInfo(0, DXGI_MEMORY_SEGMENT_GROUP_LOCAL, &mut info) };
+        if queried.is_ok() && info.CurrentUsage > info.Budget {
+            self.reduce();
+        }
+    }
+
+    fn reduce(&mut self) {
+        if self.ring.reduce().is_some() {
+            let _ = self.events.send(CapturerEvent::ReplayRetentionReduced {
+                retention: self.ring.retention(),
+            });
+        }
+    }
+
+    pub fn usage(&self) -> GpuReplayUsage {
+        self.ring.usage()
+    }
+}
+
+// Reads the frames of the last `duration` back from the GPU, oldest first.
+// The lock is only held to pick them, so capture goes on meanwhile.
+pub fn read(replay: &SharedGpuReplay, duration: Duration) -> Vec<Frame> {
+    let (textures, session) = {
+        let replay = replay.lock().unwrap();
+        let Some(session) = &replay.session else {
+            return Vec::new();
+        };
+        let textures: Vec<(u64, ReplayTexture)> = replay
+            .ring
+            .last(duration)
+            .map(|entry| (entry.display_time, entry.item.clone()))
+            .collect();
+        let session = (
+            session.device.clone(),
+            session.context.clone(),
+            session.converter.clone(),
+        );
+        (textures, session)
+    };
+    let (device, context, converter) = session;
+
+    textures
+        .into_iter()
+        .filter_map(|(display_time, texture)| {
+            let data = read_texture(&device, &context, &texture)?;
+            let mut frame = converter.convert(
+                texture.color_format,
+                texture.width,
+                texture.height,
+                data,
+                texture.padding,
+            )?;
+            *frame.display_time_mut() = display_time;
+            Some(frame)
+        })
+        .collect()
+}
+
+// The texture's pixels without row padding, through a staging texture
+fn read_texture(
+    device: &ID3D11Device,
+    context: &ID3D11DeviceContext,
+    texture: &ReplayTexture,
+) -> Option<Vec<u8>> {
+    let desc = D3D11_TEXTURE2D_DESC {
+        Width: texture.width,
+        Height: texture.height,
+        MipLevels: 1,
+        ArraySize: 1,
+        Format: DXGI_FORMAT(texture.color_format as i32),
+        SampleDesc: DXGI_SAMPLE_DESC {
+            Count: 1,
+            Quality: 0,
+        },
+        Usage: D3D11_USAGE_STAGING,
+        CPUAccessFlags: D3D11_CPU_ACCESS_READ.0 as u32,
+        ..Default::default()
+    };
+    unsafe {
+        let mut staging = None;
+        device
+            .CreateTexture2D(&desc, None, Some(&mut staging))
+            .ok()?;
+        let staging = staging?;
+        context.CopyResource(&staging, &texture.texture);
+
+        let mut mapped = D3D11_MAPPED_SUBRESOURCE::default();
+        context
+            .Map(&staging, 0, D3D11_MAP_READ, 0, Some(&mut mapped))
+            .ok()?;
+        let row_pitch = mapped.RowPitch as usize;
+        let data = std::slice::from_raw_parts(
+            mapped.pData.cast::<u8>(),
+            row_pitch * texture.height as usize,
+        );
+        let data = remove_row_padding(
+            data,
+            row_pitch,
+            texture.width as usize * get_bytes_per_pixel(texture.color_format),
+            texture.height as usize,
+        );
+        context.Unmap(&staging, 0);
+        Some(data)
+    }
+}
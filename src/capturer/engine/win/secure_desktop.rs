@@ -0,0 +1,106 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use windows::Win32::{
+    Foundation::HANDLE,
+    System::StationsAndDesktops::{
+        CloseDesktop, GetUserObjectInformationW, OpenInputDesktop, DESKTOP_CONTROL_FLAGS,
+        DESKTOP_READOBJECTS, UOI_NAME,
+    },
+};
+
+use super::{super::FrameSender, get_current_time};
+use crate::capturer::{secure_desktop::SecureDesktop, CapturerEvent};
+
+// How often the input desktop is checked while the normal one is shown
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Whether the desktop receiving input is another than the user's, like the
+// Winlogon desktop of UAC prompts and the lock screen
+fn is_secure_desktop_shown() -> bool {
+    unsafe {
+        // Processes outside of Winlogon can't open the secure desktop at all
+        let Ok(desktop) = OpenInputDesktop(DESKTOP_CONTROL_FLAGS(0), false, DESKTOP_READOBJECTS)
+        else {
+            return true;
+        };
+
+        let mut name = [0u16; 64];
+        let named = GetUserObjectInformationW(
+            HANDLE(desktop.0),
+            UOI_NAME,
+            Some(name.as_mut_ptr().cast()),
+            std::mem::size_of_val(&name) as u32,
+            None,
+        )
+        .is_ok();
+        let _ = CloseDesktop(desktop);
+
+        let length = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+        named && !String::from_utf16_lossy(&name[..length]).eq_ignore_ascii_case("Default")
+    }
+}
+
+/// Reports switches to the secure desktop and sends synthesized frames
+/// while it's shown, until dropped. See [Options::secure_desktop](crate::capturer::Options::secure_desktop)
+pub struct SecureDesktopWatcher {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SecureDesktopWatcher {
+    pub fn new(
+        state: Arc<Mutex<SecureDesktop>>,
+        tx: FrameSender,
+        events: mpsc::Sender<CapturerEvent>,
+        frame_interval: Duration,
+    ) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+
+        let thread = thread::spawn(move || {
+            let mut next_frame = Instant::now();
+            while !stopped.load(Ordering::Acquire) {
+                let now = Instant::now();
+                let shown = is_secure_desktop_shown();
+
+                let mut state = state.lock().unwrap();
+                if let Some(event) = state.update(shown, now, get_current_time()) {
+                    let _ = events.send(event);
+                }
+                if shown && now >= next_frame {
+                    if let Some(frame) = state.synthesize(get_current_time()) {
+                        let _ = tx.send(frame, None);
+                    }
+                    next_frame = now + frame_interval;
+                }
+                drop(state);
+
+                thread::sleep(match shown {
+                    true => frame_interval.min(POLL_INTERVAL),
+                    false => POLL_INTERVAL,
+                });
+            }
+        });
+
+        SecureDesktopWatcher {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for SecureDesktopWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use std::{
+    cell::RefCell,
+    sync::mpsc,
+    thread::{self, JoinHandle},
+};
+
+use windows::Win32::{
+    Foundation::{HMODULE, HWND, LPARAM, RECT, WPARAM},
+    Graphics::Gdi::{GetMonitorInfoW, MonitorFromWindow, MONITORINFO, MONITOR_DEFAULTTONEAREST},
+    System::Threading::GetCurrentThreadId,
+    UI::{
+        Accessibility::{SetWinEventHook, UnhookWinEvent, HWINEVENTHOOK},
+        WindowsAndMessaging::{
+            DispatchMessageW, GetMessageW, GetWindowRect, GetWindowThreadProcessId, IsIconic,
+            IsZoomed, PeekMessageW, PostThreadMessageW, CHILDID_SELF, EVENT_OBJECT_CLOAKED,
+            EVENT_OBJECT_LOCATIONCHANGE, EVENT_OBJECT_NAMECHANGE, EVENT_OBJECT_UNCLOAKED,
+            EVENT_SYSTEM_MINIMIZEEND, EVENT_SYSTEM_MINIMIZESTART, MSG, OBJID_WINDOW, PM_NOREMOVE,
+            WINEVENT_OUTOFCONTEXT, WM_QUIT, WM_USER,
+        },
+    },
+};
+use windows_capture::window::Window as WCWindow;
+
+use super::{super::window_watch::WatchedWindow, get_current_time};
+use crate::{
+    capturer::{Area, CapturerEvent, Point, Size, WindowState},
+    targets::{self, Window},
+};
+
+// WinEvent callbacks carry no context, but run on the thread that set the
+// hook, so each watcher thread keeps its window here
+thread_local! {
+    static WATCH: RefCell<Option<Watch>> = const { RefCell::new(None) };
+}
+
+struct Watch {
+    window: Window,
+    watched: WatchedWindow,
+    // Only read when the window is cloaked or uncloaked, which is what
+    // switching or moving it between desktops does
+    on_current_desktop: Option<bool>,
+    events: mpsc::Sender<CapturerEvent>,
+}
+
+impl Watch {
+    fn report(&mut self) {
+        let mut rect = RECT::default();
+        if unsafe { GetWindowRect(self.window.raw_handle, &mut rect) }.is_err() {
+            return;
+        }
+
+        let time = get_current_time();
+        let title = WCWindow::from_raw_hwnd(self.window.raw_handle.0)
+            .title()
+            .unwrap_or_default();
+        let bounds = Area {
+            origin: Point {
+                x: rect.left as f64,
+                y: rect.top as f64,
+            },
+            size: Size {
+                width: (rect.right - rect.left) as f64,
+                height: (rect.bottom - rect.top) as f64,
+            },
+        };
+
+        let state = get_window_state(self.window.raw_handle, &rect);
+        for event in self
+            .watched
+            .update(title, bounds, state, self.on_current_desktop, time)
+        {
+            let _ = self.events.send(event);
+        }
+    }
+}
+
+fn get_window_state(window: HWND, rect: &RECT) -> WindowState {
+    unsafe {
+        if IsIconic(window).as_bool() {
+            return WindowState::Minimized;
+        }
+        if IsZoomed(window).as_bool() {
+            return WindowState::Maximized;
+        }
+
+        let mut monitor_info = MONITORINFO {
+            cbSize: std::mem::size_of::<MONITORINFO>() as u32,
+            ..Default::default()
+        };
+        let monitor = MonitorFromWindow(window, MONITOR_DEFAULTTONEAREST);
+        if GetMonitorInfoW(monitor, &mut monitor_info).as_bool() && monitor_info.rcMonitor == *rect
+        {
+            return WindowState::Fullscreen;
+        }
+    }
+    WindowState::Normal
+}
+
+unsafe extern "system" fn on_event(
+    _hook: HWINEVENTHOOK,
+    event: u32,
+    hwnd: HWND,
+    object: i32,
+    child: i32,
+    _thread: u32,
+    _time: u32,
+) {
+    // Caret and control events of the same thread come through here too
+    if object != OBJID_WINDOW.0 || child != CHILDID_SELF as i32 {
+        return;
+    }
+
+    WATCH.with(|watch| {
+        if let Some(watch) = watch.borrow_mut().as_mut() {
+            if watch.window.raw_handle == hwnd {
+                if event == EVENT_OBJECT_CLOAKED || event == EVENT_OBJECT_UNCLOAKED {
+                    watch.on_current_desktop = targets::is_on_current_desktop(&watch.window);
+                }
+                watch.report();
+            }
+        }
+    });
+}
+
+/// Reports title, bounds, state and virtual desktop changes of a window
+/// until dropped, see
+/// [Options::watch_window](crate::capturer::Options::watch_window)
+pub struct WindowWatcher {
+    thread_id: u32,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl WindowWatcher {
+    pub fn new(window: &Window, events: mpsc::Sender<CapturerEvent>) -> Self {
+        let (target_id, raw_window) = (window.id, window.raw_handle.0 as isize);
+        let (ready_tx, ready_rx) = mpsc::channel();
+
+        let thread = thread::spawn(move || unsafe {
+            let window = HWND(raw_window as _);
+
+            // Hooks are limited to the thread owning the window, except for
+            // cloaking, which the shell does to it
+            let mut process_id = 0;
+            let thread_id = GetWindowThreadProcessId(window, Some(&mut process_id));
+            let hooks = [
+                (
+                    EVENT_OBJECT_LOCATIONCHANGE,
+                    EVENT_OBJECT_NAMECHANGE,
+                    process_id,
+                    thread_id,
+                ),
+                (
+                    EVENT_SYSTEM_MINIMIZESTART,
+                    EVENT_SYSTEM_MINIMIZEEND,
+                    process_id,
+                    thread_id,
+                ),
+                (EVENT_OBJECT_CLOAKED, EVENT_OBJECT_UNCLOAKED, 0, 0),
+            ]
+            .map(|(min, max, process_id, thread_id)| {
+                SetWinEventHook(
+                    min,
+                    max,
+                    HMODULE::default(),
+                    Some(on_event),
+                    process_id,
+                    thread_id,
+                    WINEVENT_OUTOFCONTEXT,
+                )
+            });
+
+            let window = Window {
+                id: target_id,
+                title: String::new(),
+                raw_handle: window,
+            };
+            let mut watch = Watch {
+                on_current_desktop: targets::is_on_current_desktop(&window),
+                window,
+                watched: WatchedWindow::new(target_id),
+                events,
+            };
+            watch.report();
+            WATCH.with(|cell| *cell.borrow_mut() = Some(watch));
+
+            // Make sure the message queue exists before the quit message is posted
+            let mut message = MSG::default();
+            let _ = PeekMessageW(&mut message, None, WM_USER, WM_USER, PM_NOREMOVE);
+            let _ = ready_tx.send(GetCurrentThreadId());
+
+            while GetMessageW(&mut message, None, 0, 0).as_bool() {
+                DispatchMessageW(&message);
+            }
+
+            for hook in hooks {
+                let _ = UnhookWinEvent(hook);
+            }
+        });
+
+        WindowWatcher {
+            thread_id: ready_rx.recv().unwrap_or_default(),
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for WindowWatcher {
+    fn drop(&mut self) {
+        let _ = unsafe { PostThreadMessageW(self.thread_id, WM_QUIT, WPARAM(0), LPARAM(0)) };
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
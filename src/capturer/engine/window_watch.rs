@@ -0,0 +1,157 @@
+use crate::capturer::{Area, CapturerEvent, WindowState};
+
+// What was last reported about a watched window, so only changes are sent
+pub(crate) struct WatchedWindow {
+    target_id: u32,
+    title: Option<String>,
+    bounds: Option<Area>,
+    state: Option<WindowState>,
+    on_current_desktop: Option<bool>,
+}
+
+impl WatchedWindow {
+    pub fn new(target_id: u32) -> Self {
+        WatchedWindow {
+            target_id,
+            title: None,
+            bounds: None,
+            state: None,
+            on_current_desktop: None,
+        }
+    }
+
+    // The events for whatever changed since the last update. The first update
+    // reports everything. `on_current_desktop` is None where virtual desktops
+    // aren't known.
+    pub fn update(
+        &mut self,
+        title: String,
+        bounds: Area,
+        state: WindowState,
+        on_current_desktop: Option<bool>,
+        time: u64,
+    ) -> Vec<CapturerEvent> {
+        let mut events = Vec::new();
+
+        if self.title.as_ref() != Some(&title) {
+            events.push(CapturerEvent::WindowTitleChanged {
+                target_id: self.target_id,
+                title: title.clone(),
+                time,
+            });
+            self.title = Some(title);
+        }
+
+        // Minimized windows are moved out of sight, that's not worth reporting
+        if state != WindowState::Minimized && self.bounds.as_ref() != Some(&bounds) {
+            events.push(CapturerEvent::WindowMoved {
+                bounds: bounds.clone(),
+                time,
+            });
+            self.bounds = Some(bounds);
+        }
+
+        if self.state != Some(state) {
+            events.push(CapturerEvent::WindowStateChanged { state, time });
+            self.state = Some(state);
+        }
+
+        if let Some(on_current_desktop) = on_current_desktop {
+            if self.on_current_desktop != Some(on_current_desktop) {
+                events.push(CapturerEvent::WindowDesktopChanged {
+                    on_current_desktop,
+                    time,
+                });
+                self.on_current_desktop = Some(on_current_desktop);
+            }
+        }
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capturer::{Point, Size};
+
+    fn area(x: f64) -> Area {
+        Area {
+            origin: Point { x, y: 0.0 },
+            size: Size {
+                width: 800.0,
+                height: 600.0,
+            },
+        }
+    }
+
+    #[test]
+    fn test_only_changes_are_reported() {
+        let mut window = WatchedWindow::new(7);
+
+        let events = window.update("a".into(), area(0.0), WindowState::Normal, None, 1);
+        assert_eq!(events.len(), 3);
+        assert!(matches!(
+            &events[0],
+            CapturerEvent::WindowTitleChanged { target_id: 7, title, time: 1 } if title == "a"
+        ));
+
+        assert!(window
+            .update("a".into(), area(0.0), WindowState::Normal, None, 2)
+            .is_empty());
+
+        let events = window.update("b".into(), area(0.0), WindowState::Normal, None, 3);
+        assert!(matches!(
+            &events[..],
+            [CapturerEvent::WindowTitleChanged { title, time: 3, .. }] if title == "b"
+        ));
+
+        let events = window.update("b".into(), area(-32000.0), WindowState::Minimized, None, 4);
+        assert!(matches!(
+            &events[..],
+            [CapturerEvent::WindowStateChanged {
+                state: WindowState::Minimized,
+                time: 4
+            }]
+        ));
+
+        let events = window.update("b".into(), area(10.0), WindowState::Normal, None, 5);
+        assert!(matches!(
+            &events[..],
+            [
+                CapturerEvent::WindowMoved { time: 5, .. },
+                CapturerEvent::WindowStateChanged {
+                    state: WindowState::Normal,
+                    ..
+                }
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_desktop_changes() {
+        let mut window = WatchedWindow::new(7);
+        let events = window.update("a".into(), area(0.0), WindowState::Normal, Some(false), 1);
+        assert!(matches!(
+            events.last(),
+            Some(CapturerEvent::WindowDesktopChanged {
+                on_current_desktop: false,
+                time: 1
+            })
+        ));
+
+        // Unknown isn't a change
+        assert!(window
+            .update("a".into(), area(0.0), WindowState::Normal, None, 2)
+            .is_empty());
+
+        let events = window.update("a".into(), area(0.0), WindowState::Normal, Some(true), 3);
+        assert!(matches!(
+            &events[..],
+            [CapturerEvent::WindowDesktopChanged {
+                on_current_desktop: true,
+                time: 3
+            }]
+        ));
+    }
+}
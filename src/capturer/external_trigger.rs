@@ -0,0 +1,143 @@
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::frame::{rescale_to_timebase, Frame};
+
+// How often the capturer checks for triggers while frames arrive
+pub(crate) const TRIGGER_POLL: Duration = Duration::from_millis(1);
+
+/// Fires the capture of a frame for
+/// [Options::external_trigger](super::Options::external_trigger), see
+/// [Capturer::frame_trigger](super::Capturer::frame_trigger). Clones fire
+/// the same capturer and can be sent to other threads, like the one
+/// receiving a hardware sync signal.
+#[derive(Debug, Clone)]
+pub struct FrameTrigger {
+    tx: mpsc::Sender<Instant>,
+}
+
+impl FrameTrigger {
+    /// Asks for the newest frame as of now. Every call delivers one frame,
+    /// in the order they were made.
+    pub fn fire(&self) {
+        let _ = self.tx.send(Instant::now());
+    }
+}
+
+// Delivers the newest frame for every trigger, stamped with when it fired
+#[derive(Debug)]
+pub(crate) struct ExternalTrigger {
+    tx: mpsc::Sender<Instant>,
+    rx: mpsc::Receiver<Instant>,
+    timebase: Option<u32>,
+    latest: Option<Frame>,
+    latency: Option<Duration>,
+}
+
+impl ExternalTrigger {
+    pub fn new(timebase: Option<u32>) -> Self {
+        let (tx, rx) = mpsc::channel();
+        ExternalTrigger {
+            tx,
+            rx,
+            timebase,
+            latest: None,
+            latency: None,
+        }
+    }
+
+    pub fn handle(&self) -> FrameTrigger {
+        FrameTrigger {
+            tx: self.tx.clone(),
+        }
+    }
+
+    // Starts over for a new capture session, dropping triggers fired
+    // while none ran
+    pub fn reset(&mut self) {
+        while self.rx.try_recv().is_ok() {}
+        self.latest = None;
+        self.latency = None;
+    }
+
+    // How long before its trigger the last delivered frame was captured
+    pub fn latency(&self) -> Option<Duration> {
+        self.latency
+    }
+
+    pub fn push(&mut self, frame: Frame) {
+        self.latest = Some(frame);
+    }
+
+    // The frame for the next trigger that fired, once a frame arrived.
+    // `clock` is the time on the frames' clock in nanoseconds at `now`, None
+    // where it can't be read and frames keep their display time.
+    pub fn take(&mut self, clock: Option<u64>, now: Instant) -> Option<Frame> {
+        let latest = self.latest.as_ref()?;
+        let fired = self.rx.try_recv().ok()?;
+        let mut frame = latest.clone();
+
+        self.latency = clock.map(|clock| {
+            let fired =
+                clock.saturating_sub(now.saturating_duration_since(fired).as_nanos() as u64);
+            let fired = rescale_to_timebase(fired, self.timebase);
+            let ticks = fired.saturating_sub(frame.display_time());
+            *frame.display_time_mut() = fired;
+            match self.timebase {
+                Some(timebase) if timebase > 0 => {
+                    Duration::from_nanos((ticks as u128 * 1_000_000_000 / timebase as u128) as u64)
+                }
+                _ => Duration::from_nanos(ticks),
+            }
+        });
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capturer::fixtures::{frame, output};
+
+    #[test]
+    fn test_frames_on_triggers() {
+        let mut trigger = ExternalTrigger::new(None);
+        let handle = trigger.handle();
+        assert!(trigger.take(Some(0), Instant::now()).is_none());
+
+        // A trigger before the first frame waits for it
+        let fired = Instant::now();
+        handle.fire();
+        assert!(trigger.take(Some(0), Instant::now()).is_none());
+        trigger.push(frame(10, 1));
+        trigger.push(frame(20, 2));
+        let now = Instant::now();
+        let (time, value) = output(trigger.take(Some(1_000_000_000), now));
+        let earliest = 1_000_000_000 - (now - fired).as_nanos() as u64;
+        assert!((earliest..=1_000_000_000).contains(&time), "{time}");
+        assert_eq!(value, 2);
+        assert_eq!(trigger.latency(), Some(Duration::from_nanos(time - 20)));
+
+        // Only one frame per trigger, the newest again if none arrived
+        assert!(trigger.take(Some(0), Instant::now()).is_none());
+        handle.fire();
+        handle.clone().fire();
+        assert_eq!(output(trigger.take(None, Instant::now())), (20, 2));
+        assert_eq!(trigger.latency(), None);
+        assert_eq!(output(trigger.take(None, Instant::now())).1, 2);
+
+        handle.fire();
+        trigger.reset();
+        trigger.push(frame(30, 3));
+        assert!(trigger.take(None, Instant::now()).is_none());
+
+        // Stamped in ticks of the timebase, 0 latency for frames captured
+        // after their trigger
+        let mut trigger = ExternalTrigger::new(Some(1_000));
+        trigger.push(frame(5_000, 1));
+        trigger.handle().fire();
+        let (time, _) = output(trigger.take(Some(2_000_000_000), Instant::now()));
+        assert!((1_999..=2_000).contains(&time), "{time}");
+        assert_eq!(trigger.latency(), Some(Duration::ZERO));
+    }
+}
@@ -0,0 +1,247 @@
+use std::{
+    collections::VecDeque,
+    sync::{mpsc::RecvTimeoutError, Arc, Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::frame::Frame;
+
+/// What a [FrameSubscriber] whose queue is full does with a new frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Drop the oldest queued frame to make room, for previews and other
+    /// consumers that want the latest frame
+    #[default]
+    DropOldest,
+    /// Drop the new frame, for consumers that would rather have a gap than
+    /// a jump
+    DropNewest,
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    frames: VecDeque<Arc<Frame>>,
+    dropped: u64,
+    // The capturer is gone and no more frames will come
+    closed: bool,
+    // The subscriber is gone and frames can be skipped
+    unsubscribed: bool,
+}
+
+#[derive(Debug)]
+struct Queue {
+    state: Mutex<QueueState>,
+    ready: Condvar,
+    capacity: usize,
+    overflow: OverflowPolicy,
+}
+
+impl Queue {
+    // Whether the subscriber is still there
+    fn push(&self, frame: &Arc<Frame>) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.unsubscribed {
+            return false;
+        }
+
+        if state.frames.len() >= self.capacity {
+            state.dropped += 1;
+            match self.overflow {
+                OverflowPolicy::DropNewest => return true,
+                OverflowPolicy::DropOldest => {
+                    state.frames.pop_front();
+                }
+            }
+        }
+        state.frames.push_back(frame.clone());
+        self.ready.notify_one();
+        true
+    }
+
+    fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.ready.notify_all();
+    }
+}
+
+/// Receives the frames of a [Capturer](super::Capturer) next to its own
+/// consumer, see [Capturer::subscribe](super::Capturer::subscribe). Frames
+/// are shared with the other subscribers, not copied for each.
+///
+/// Dropping the subscriber unsubscribes it.
+#[derive(Debug)]
+pub struct FrameSubscriber {
+    queue: Arc<Queue>,
+}
+
+impl FrameSubscriber {
+    /// Wait for the next frame. Returns an error once the capturer is
+    /// dropped and every queued frame has been received.
+    pub fn recv(&self) -> Result<Arc<Frame>, RecvTimeoutError> {
+        let mut state = self.queue.state.lock().unwrap();
+        loop {
+            if let Some(frame) = state.frames.pop_front() {
+                return Ok(frame);
+            }
+            if state.closed {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            state = self.queue.ready.wait(state).unwrap();
+        }
+    }
+
+    /// Wait up to `timeout` for the next frame
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<Arc<Frame>, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.queue.state.lock().unwrap();
+        loop {
+            if let Some(frame) = state.frames.pop_front() {
+                return Ok(frame);
+            }
+            if state.closed {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            state = self
+                .queue
+                .ready
+                .wait_timeout(state, deadline - now)
+                .unwrap()
+                .0;
+        }
+    }
+
+    /// Get the next frame without waiting, if one is queued
+    pub fn try_recv(&self) -> Option<Arc<Frame>> {
+        self.queue.state.lock().unwrap().frames.pop_front()
+    }
+
+    /// How many frames this subscriber missed because its queue was full
+    pub fn dropped_frames(&self) -> u64 {
+        self.queue.state.lock().unwrap().dropped
+    }
+}
+
+impl Drop for FrameSubscriber {
+    fn drop(&mut self) {
+        let mut state = self.queue.state.lock().unwrap();
+        state.unsubscribed = true;
+        state.frames.clear();
+    }
+}
+
+// Hands every frame to the subscribers, without letting a slow one hold up
+// the others
+#[derive(Debug, Default)]
+pub(crate) struct Fanout {
+    queues: Mutex<Vec<Arc<Queue>>>,
+}
+
+impl Fanout {
+    pub fn subscribe(&self, capacity: usize, overflow: OverflowPolicy) -> FrameSubscriber {
+        let queue = Arc::new(Queue {
+            state: Mutex::new(QueueState::default()),
+            ready: Condvar::new(),
+            capacity: capacity.max(1),
+            overflow,
+        });
+        self.queues.lock().unwrap().push(queue.clone());
+        FrameSubscriber { queue }
+    }
+
+    pub fn publish(&self, frame: &Frame) {
+        let mut queues = self.queues.lock().unwrap();
+        if queues.is_empty() {
+            return;
+        }
+
+        let frame = Arc::new(frame.clone());
+        queues.retain(|queue| queue.push(&frame));
+    }
+}
+
+impl Drop for Fanout {
+    fn drop(&mut self) {
+        for queue in self.queues.lock().unwrap().iter() {
+            queue.close();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{ColorSpace, Gray8Frame, RowOrder};
+    use std::thread;
+
+    fn frame(time: u64) -> Frame {
+        Frame::Gray8(Gray8Frame {
+            display_time: time,
+            width: 1,
+            height: 1,
+            data: vec![0],
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::SRGB,
+        })
+    }
+
+    fn time(frame: &Frame) -> u64 {
+        match frame {
+            Frame::Gray8(f) => f.display_time,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_slow_subscribers_drop_their_own_frames() {
+        let fanout = Fanout::default();
+        let recorder = fanout.subscribe(100, OverflowPolicy::DropNewest);
+        let preview = fanout.subscribe(2, OverflowPolicy::DropOldest);
+        let streamer = fanout.subscribe(2, OverflowPolicy::DropNewest);
+
+        for i in 0..5 {
+            fanout.publish(&frame(i));
+        }
+
+        let all: Vec<_> = (0..5).map(|_| time(&recorder.recv().unwrap())).collect();
+        assert_eq!(all, [0, 1, 2, 3, 4]);
+        assert_eq!(recorder.dropped_frames(), 0);
+
+        assert_eq!(time(&preview.recv().unwrap()), 3);
+        assert_eq!(time(&preview.recv().unwrap()), 4);
+        assert_eq!(preview.dropped_frames(), 3);
+
+        assert_eq!(time(&streamer.recv().unwrap()), 0);
+        assert_eq!(time(&streamer.recv().unwrap()), 1);
+        assert!(streamer.try_recv().is_none());
+    }
+
+    #[test]
+    fn test_subscribing_mid_capture() {
+        let fanout = Fanout::default();
+        let early = fanout.subscribe(10, OverflowPolicy::default());
+        fanout.publish(&frame(0));
+
+        let late = fanout.subscribe(10, OverflowPolicy::default());
+        drop(early);
+        fanout.publish(&frame(1));
+        assert_eq!(fanout.queues.lock().unwrap().len(), 1);
+
+        assert_eq!(time(&late.recv().unwrap()), 1);
+        assert_eq!(
+            late.recv_timeout(Duration::from_millis(10)).unwrap_err(),
+            RecvTimeoutError::Timeout
+        );
+
+        // Dropping the capturer wakes waiting subscribers
+        let waiting = thread::spawn(move || late.recv());
+        thread::sleep(Duration::from_millis(20));
+        fanout.publish(&frame(2));
+        drop(fanout);
+        assert_eq!(time(&waiting.join().unwrap().unwrap()), 2);
+    }
+}
@@ -0,0 +1,23 @@
+// Frames for the tests of the parts that hold frames back or repeat them
+
+use crate::frame::{ColorSpace, Frame, Gray8Frame, RowOrder};
+
+// A 1x1 frame told apart by its pixel
+pub fn frame(display_time: u64, value: u8) -> Frame {
+    Frame::Gray8(Gray8Frame {
+        display_time,
+        width: 1,
+        height: 1,
+        data: vec![value],
+        origin: RowOrder::TopDown,
+        color_space: ColorSpace::Unknown,
+    })
+}
+
+// The display time and pixel of a frame that was expected
+pub fn output(frame: Option<Frame>) -> (u64, u8) {
+    match frame {
+        Some(Frame::Gray8(f)) => (f.display_time, f.data[0]),
+        _ => panic!("expected a frame"),
+    }
+}
@@ -0,0 +1,282 @@
+use std::time::{Duration, Instant};
+
+use super::{Area, Point, Size};
+use crate::frame::Frame;
+
+/// Crops the frames to where the user works, for remote assistance and
+/// "follow the action" recordings, see
+/// [Options::follow_action](super::Options::follow_action)
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FollowActionOptions {
+    /// The part of the frames' width and height the crop covers, from 0.0 to
+    /// 1.0. Frames keep this size wherever the crop moves.
+    pub zoom: f32,
+    /// How long the crop takes to move most of the way to a new spot, about
+    /// two thirds of it. Longer is calmer but lags behind quick moves.
+    /// [Duration::ZERO] jumps there.
+    pub smoothing: Duration,
+    /// The part of the crop around its center, from 0.0 to 1.0, the cursor
+    /// moves within without the crop following, so small moves don't make it
+    /// shake
+    pub dead_zone: f32,
+    /// Move to the focused window when focus switches or it moves, as
+    /// reported with [Options::watch_focus](super::Options::watch_focus),
+    /// which needs to be set. Windows only.
+    pub follow_focus: bool,
+}
+
+impl Default for FollowActionOptions {
+    fn default() -> Self {
+        FollowActionOptions {
+            zoom: 0.5,
+            smoothing: Duration::from_millis(300),
+            dead_zone: 0.5,
+            follow_focus: true,
+        }
+    }
+}
+
+// Moves a crop after the cursor and the focused window, see
+// Options::follow_action
+#[derive(Debug)]
+pub(crate) struct ActionFollower {
+    options: FollowActionOptions,
+    // Where the crop is centered and heads to, in pixels of the frames
+    center: Option<(f64, f64)>,
+    target: Option<(f64, f64)>,
+    // The inputs as last seen, to tell when they change
+    cursor: Option<(f64, f64)>,
+    focus: Option<Area>,
+    updated: Option<Instant>,
+    // The last crop and the size of the frame it was cut from
+    last: Option<(Area, Size)>,
+}
+
+impl ActionFollower {
+    pub fn new(options: FollowActionOptions) -> Self {
+        ActionFollower {
+            options,
+            center: None,
+            target: None,
+            cursor: None,
+            focus: None,
+            updated: None,
+            last: None,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = ActionFollower::new(self.options);
+    }
+
+    // The part of the last frame that was delivered, in its pixels
+    pub fn last(&self) -> Option<Area> {
+        self.last.as_ref().map(|(area, _)| area.clone())
+    }
+
+    // Where a point given as a fraction of the last uncropped frame is in
+    // the crop, as a fraction of it
+    pub fn map_point(&self, point: Point) -> Point {
+        match &self.last {
+            Some((area, size)) => Point {
+                x: (point.x * size.width - area.origin.x) / area.size.width,
+                y: (point.y * size.height - area.origin.y) / area.size.height,
+            },
+            None => point,
+        }
+    }
+
+    // Crops a frame arriving at `now`. `cursor` is a fraction of the frame,
+    // `focus` the focused window in its pixels. YUV frames, which can't be
+    // cropped, are delivered whole.
+    pub fn crop(
+        &mut self,
+        frame: Frame,
+        cursor: Option<Point>,
+        focus: Option<Area>,
+        now: Instant,
+    ) -> Frame {
+        let (width, height) = frame.size();
+        let size = Size {
+            width: width.max(0) as f64,
+            height: height.max(0) as f64,
+        };
+        let zoom = self.options.zoom.clamp(0.0, 1.0) as f64;
+        let crop = Size {
+            width: (size.width * zoom).round().max(1.0),
+            height: (size.height * zoom).round().max(1.0),
+        };
+        if zoom >= 1.0 || crop.width > size.width || crop.height > size.height {
+            self.last = None;
+            return frame;
+        }
+
+        let target = self
+            .target
+            .get_or_insert((size.width / 2.0, size.height / 2.0));
+        if self.options.follow_focus && focus != self.focus {
+            if let Some(bounds) = &focus {
+                *target = (
+                    bounds.origin.x + bounds.size.width / 2.0,
+                    bounds.origin.y + bounds.size.height / 2.0,
+                );
+            }
+            self.focus = focus;
+        }
+        let cursor = cursor
+            .map(|point| (point.x * size.width, point.y * size.height))
+            .filter(|(x, y)| (0.0..=size.width).contains(x) && (0.0..=size.height).contains(y));
+        if let Some((x, y)) = cursor.filter(|_| cursor != self.cursor) {
+            // Only far enough to bring the cursor back into the dead zone
+            let dead_zone = self.options.dead_zone.clamp(0.0, 1.0) as f64;
+            let follow = |target: &mut f64, position: f64, extent: f64| {
+                let reach = extent * dead_zone / 2.0;
+                *target = target.clamp(position - reach, position + reach);
+            };
+            follow(&mut target.0, x, crop.width);
+            follow(&mut target.1, y, crop.height);
+        }
+        if cursor.is_some() {
+            self.cursor = cursor;
+        }
+
+        let keep_inside = |(x, y): (f64, f64)| {
+            (
+                x.clamp(crop.width / 2.0, size.width - crop.width / 2.0),
+                y.clamp(crop.height / 2.0, size.height - crop.height / 2.0),
+            )
+        };
+        *target = keep_inside(*target);
+        let target = *target;
+        let elapsed = self.updated.map_or(Duration::ZERO, |updated| now - updated);
+        let center = match self.center {
+            Some((x, y)) if !self.options.smoothing.is_zero() => {
+                let step =
+                    1.0 - (-elapsed.as_secs_f64() / self.options.smoothing.as_secs_f64()).exp();
+                (x + (target.0 - x) * step, y + (target.1 - y) * step)
+            }
+            _ => target,
+        };
+        let center = keep_inside(center);
+        self.center = Some(center);
+        self.updated = Some(now);
+
+        let area = Area {
+            origin: Point {
+                x: (center.0 - crop.width / 2.0).round(),
+                y: (center.1 - crop.height / 2.0).round(),
+            },
+            size: crop,
+        };
+        match frame.cropped(&area) {
+            Some(cropped) => {
+                self.last = Some((area, size));
+                cropped
+            }
+            None => {
+                self.last = None;
+                frame
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{ColorSpace, Gray8Frame, RowOrder};
+
+    fn frame() -> Frame {
+        Frame::Gray8(Gray8Frame {
+            display_time: 0,
+            width: 200,
+            height: 100,
+            data: vec![0; 200 * 100],
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::SRGB,
+        })
+    }
+
+    fn origin(follower: &ActionFollower) -> (f64, f64) {
+        let area = follower.last().unwrap();
+        (area.origin.x, area.origin.y)
+    }
+
+    #[test]
+    fn test_follows_cursor() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut follower = ActionFollower::new(FollowActionOptions {
+            smoothing: Duration::ZERO,
+            ..Default::default()
+        });
+
+        // Centered until there's input, then the size of the zoomed crop
+        let cropped = follower.crop(frame(), None, None, at(0));
+        assert_eq!(cropped.size(), (100, 50));
+        assert_eq!(origin(&follower), (50.0, 25.0));
+
+        // Within the dead zone, 50x25 pixels around the center
+        let point = |x: f64, y: f64| {
+            Some(Point {
+                x: x / 200.0,
+                y: y / 100.0,
+            })
+        };
+        follower.crop(frame(), point(120.0, 60.0), None, at(10));
+        assert_eq!(origin(&follower), (50.0, 25.0));
+
+        // Past it the crop moves just enough, and stops at the frame's edges
+        follower.crop(frame(), point(150.0, 95.0), None, at(20));
+        assert_eq!(origin(&follower), (75.0, 50.0));
+        let mapped = follower.map_point(Point { x: 0.75, y: 0.95 });
+        assert_eq!((mapped.x, mapped.y), (0.75, 0.9));
+
+        // A focus switch wins over a cursor that stays put
+        let window = Area {
+            origin: Point { x: 0.0, y: 0.0 },
+            size: Size {
+                width: 60.0,
+                height: 40.0,
+            },
+        };
+        follower.crop(frame(), point(150.0, 95.0), Some(window.clone()), at(30));
+        assert_eq!(origin(&follower), (0.0, 0.0));
+        follower.crop(frame(), point(150.0, 95.0), Some(window), at(40));
+        assert_eq!(origin(&follower), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_smoothing() {
+        let start = Instant::now();
+        let mut follower = ActionFollower::new(FollowActionOptions {
+            smoothing: Duration::from_secs(1),
+            dead_zone: 0.0,
+            ..Default::default()
+        });
+        let right = Some(Point { x: 1.0, y: 0.5 });
+        follower.crop(frame(), None, None, start);
+
+        // About two thirds of the way after the smoothing time
+        follower.crop(frame(), right.clone(), None, start + Duration::from_secs(1));
+        let (x, _) = origin(&follower);
+        assert!((81.0..=82.0).contains(&x), "{x}");
+        follower.crop(
+            frame(),
+            right.clone(),
+            None,
+            start + Duration::from_secs(10),
+        );
+        assert_eq!(origin(&follower), (100.0, 25.0));
+
+        follower.reset();
+        assert!(follower.last().is_none());
+        let whole = ActionFollower::new(FollowActionOptions {
+            zoom: 1.0,
+            ..Default::default()
+        })
+        .crop(frame(), right, None, start);
+        assert_eq!(whole.size(), (200, 100));
+    }
+}
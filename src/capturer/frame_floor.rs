@@ -0,0 +1,119 @@
+use std::time::{Duration, Instant};
+
+use crate::frame::{rescale_to_timebase, Frame};
+
+// Repeats the last frame while none arrive, so at least a minimum number
+// per second is delivered, see Options::min_frame_rate. Unlike a constant
+// rate, frames that do arrive are delivered as they come.
+#[derive(Debug)]
+pub(crate) struct FrameFloor {
+    interval: Duration,
+    timebase: Option<u32>,
+    // The last delivered frame and when, repeats included
+    last: Option<(Frame, Instant)>,
+    // Display time of the last captured frame, which repeats count from
+    last_time: u64,
+    // Repeats since the last captured frame
+    stalled: u32,
+    repeats: u64,
+    synthesized: bool,
+}
+
+impl FrameFloor {
+    pub fn new(fps: u32, timebase: Option<u32>) -> Self {
+        FrameFloor {
+            interval: Duration::from_secs(1) / fps.max(1),
+            timebase,
+            last: None,
+            last_time: 0,
+            stalled: 0,
+            repeats: 0,
+            synthesized: false,
+        }
+    }
+
+    // Starts over for a new capture session
+    pub fn reset(&mut self) {
+        self.last = None;
+        self.stalled = 0;
+        self.repeats = 0;
+        self.synthesized = false;
+    }
+
+    // When a repeat is due if nothing arrives, None before the first frame
+    pub fn deadline(&self) -> Option<Instant> {
+        self.last.as_ref().map(|(_, at)| *at + self.interval)
+    }
+
+    // Frames delivered as repeats so far
+    pub fn repeats(&self) -> u64 {
+        self.repeats
+    }
+
+    // Whether the last delivered frame was a repeat
+    pub fn synthesized(&self) -> bool {
+        self.synthesized
+    }
+
+    // Keeps a copy of a captured frame delivered at `now`
+    pub fn push(&mut self, frame: &Frame, now: Instant) {
+        self.last = Some((frame.clone(), now));
+        self.last_time = frame.display_time();
+        self.stalled = 0;
+        self.synthesized = false;
+    }
+
+    // The repeat due at `now`, if one is. Display times go on from the last
+    // captured frame at the minimum rate.
+    pub fn repeat(&mut self, now: Instant) -> Option<Frame> {
+        let deadline = self.deadline().filter(|deadline| now >= *deadline)?;
+        let (last, at) = self.last.as_mut()?;
+        *at = deadline;
+        self.stalled += 1;
+        self.repeats += 1;
+        self.synthesized = true;
+
+        // Counted from the captured frame, so rounding doesn't add up
+        let offset = self.interval.as_nanos() as u64 * self.stalled as u64;
+        let mut frame = last.clone();
+        *frame.display_time_mut() = self.last_time + rescale_to_timebase(offset, self.timebase);
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capturer::fixtures::{frame, output};
+
+    #[test]
+    fn test_repeats_only_during_stalls() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut floor = FrameFloor::new(2, None);
+        assert!(floor.deadline().is_none());
+        assert!(floor.repeat(at(1_000)).is_none());
+
+        // Frames arriving faster than the floor are never repeated
+        floor.push(&frame(1_000, 1), at(0));
+        floor.push(&frame(2_000, 2), at(300));
+        assert_eq!(floor.deadline(), Some(at(800)));
+        assert!(floor.repeat(at(700)).is_none());
+        assert!(!floor.synthesized());
+
+        // A stall repeats the last frame every 500 ms
+        assert_eq!(output(floor.repeat(at(800))), (500_002_000, 2));
+        assert!(floor.synthesized());
+        assert_eq!(output(floor.repeat(at(1_350))), (1_000_002_000, 2));
+        assert!(floor.repeat(at(1_350)).is_none());
+        assert_eq!(floor.repeats(), 2);
+
+        floor.push(&frame(1_200_000_000, 3), at(1_400));
+        assert!(!floor.synthesized());
+        assert_eq!(floor.deadline(), Some(at(1_900)));
+
+        let mut floor = FrameFloor::new(1, Some(90_000));
+        floor.push(&frame(5, 1), at(0));
+        assert_eq!(output(floor.repeat(at(1_000))).0, 90_005);
+    }
+}
@@ -0,0 +1,486 @@
+use std::{
+    marker::PhantomData,
+    ptr,
+    sync::{
+        atomic::{AtomicBool, AtomicPtr, AtomicU64, AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread::{self, Thread},
+    time::{Duration, Instant},
+};
+
+// Hands captured items from the capture thread to the capturer, over a
+// channel or, with Options::frame_ring, a ring
+pub(crate) fn channel<T>(ring: Option<usize>) -> (Sender<T>, Receiver<T>) {
+    match ring {
+        Some(slots) => {
+            let ring = Arc::new(Ring::new(slots));
+            (
+                Sender::Ring(Producer::new(ring.clone())),
+                Receiver::Ring(Mutex::new(Consumer {
+                    ring,
+                    next: 0,
+                    overwritten: 0,
+                })),
+            )
+        }
+        None => {
+            let (tx, rx) = mpsc::channel();
+            (Sender::Channel(tx), Receiver::Channel(rx))
+        }
+    }
+}
+
+pub(crate) enum Sender<T> {
+    Channel(mpsc::Sender<T>),
+    Ring(Producer<T>),
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        match self {
+            Sender::Channel(tx) => Sender::Channel(tx.clone()),
+            Sender::Ring(producer) => Sender::Ring(Producer::new(producer.ring.clone())),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Sender::Channel(tx) => f.debug_tuple("Channel").field(tx).finish(),
+            Sender::Ring(_) => f.write_str("Ring"),
+        }
+    }
+}
+
+impl<T> Sender<T> {
+    // Fails once the receiver is dropped
+    pub fn send(&self, item: T) -> Result<(), mpsc::SendError<T>> {
+        match self {
+            Sender::Channel(tx) => tx.send(item),
+            Sender::Ring(producer) => producer.ring.push(item),
+        }
+    }
+}
+
+pub(crate) enum Receiver<T> {
+    Channel(mpsc::Receiver<T>),
+    // Only ever used by one thread at a time, the lock is never contended
+    Ring(Mutex<Consumer<T>>),
+}
+
+impl<T> Receiver<T> {
+    pub fn recv(&self) -> Result<T, mpsc::RecvError> {
+        match self {
+            Receiver::Channel(rx) => rx.recv(),
+            Receiver::Ring(consumer) => consumer
+                .lock()
+                .unwrap()
+                .pop(None)
+                .map_err(|_| mpsc::RecvError),
+        }
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, mpsc::RecvTimeoutError> {
+        match self {
+            Receiver::Channel(rx) => rx.recv_timeout(timeout),
+            Receiver::Ring(consumer) => {
+                consumer.lock().unwrap().pop(Some(Instant::now() + timeout))
+            }
+        }
+    }
+
+    pub fn try_recv(&self) -> Result<T, mpsc::TryRecvError> {
+        match self {
+            Receiver::Channel(rx) => rx.try_recv(),
+            Receiver::Ring(consumer) => consumer.lock().unwrap().try_pop(),
+        }
+    }
+
+    // Items overwritten in the ring before they were received, counted as
+    // the ones after them are, since the last call. Always 0 for channels,
+    // which keep every item.
+    pub fn take_overwritten(&self) -> u64 {
+        match self {
+            Receiver::Channel(_) => 0,
+            Receiver::Ring(consumer) => std::mem::take(&mut consumer.lock().unwrap().overwritten),
+        }
+    }
+}
+
+// How long a consumer waits for a claimed item to be stored in its slot
+const SLOT_WAIT: Duration = Duration::from_millis(1);
+
+// An item with its place in the sequence of pushed items
+struct Slot<T> {
+    sequence: u64,
+    item: T,
+}
+
+// Slots holding boxed items, each moved in and out with a single atomic swap
+// so producers never wait for the consumer. A full ring overwrites its
+// oldest item, which the producer then drops. A consumer that finds itself
+// lapped skips to the newest item, and counts the gap in the sequence.
+struct Ring<T> {
+    slots: Box<[AtomicPtr<Slot<T>>]>,
+    // The sequence of the next item pushed
+    pushed: AtomicU64,
+    producers: AtomicUsize,
+    consumer_alive: AtomicBool,
+    // Set while the consumer is parked, with the thread to wake
+    waiting: AtomicBool,
+    waiter: Mutex<Option<Thread>>,
+    _items: PhantomData<T>,
+}
+
+// Items only ever move from one thread to another through the slots
+unsafe impl<T: Send> Send for Ring<T> {}
+unsafe impl<T: Send> Sync for Ring<T> {}
+
+impl<T> Ring<T> {
+    fn new(slots: usize) -> Self {
+        Ring {
+            slots: (0..slots.max(1))
+                .map(|_| AtomicPtr::new(ptr::null_mut()))
+                .collect(),
+            pushed: AtomicU64::new(0),
+            producers: AtomicUsize::new(0),
+            consumer_alive: AtomicBool::new(true),
+            waiting: AtomicBool::new(false),
+            waiter: Mutex::new(None),
+            _items: PhantomData,
+        }
+    }
+
+    fn slot(&self, sequence: u64) -> &AtomicPtr<Slot<T>> {
+        &self.slots[(sequence % self.slots.len() as u64) as usize]
+    }
+
+    fn push(&self, item: T) -> Result<(), mpsc::SendError<T>> {
+        if !self.consumer_alive.load(Ordering::Acquire) {
+            return Err(mpsc::SendError(item));
+        }
+        self.store(self.claim(), item);
+        Ok(())
+    }
+
+    // The sequence of the next item pushed. With several producers another
+    // one can claim and store a later item in the same slot before this one
+    // is stored, which then overwrites it.
+    fn claim(&self) -> u64 {
+        self.pushed.fetch_add(1, Ordering::SeqCst)
+    }
+
+    fn store(&self, sequence: u64, item: T) {
+        let slot = Box::into_raw(Box::new(Slot { sequence, item }));
+        let old = self.slot(sequence).swap(slot, Ordering::AcqRel);
+        if !old.is_null() {
+            // SAFETY: the swap took it out of the ring, nobody else has it
+            drop(unsafe { Box::from_raw(old) });
+        }
+        self.wake();
+    }
+
+    fn wake(&self) {
+        if self.waiting.load(Ordering::SeqCst) {
+            if let Some(thread) = &*self.waiter.lock().unwrap() {
+                thread.unpark();
+            }
+        }
+    }
+}
+
+impl<T> Drop for Ring<T> {
+    fn drop(&mut self) {
+        for slot in self.slots.iter() {
+            let slot = slot.swap(ptr::null_mut(), Ordering::AcqRel);
+            if !slot.is_null() {
+                // SAFETY: nothing else holds the ring anymore
+                drop(unsafe { Box::from_raw(slot) });
+            }
+        }
+    }
+}
+
+pub(crate) struct Producer<T> {
+    ring: Arc<Ring<T>>,
+}
+
+impl<T> Producer<T> {
+    fn new(ring: Arc<Ring<T>>) -> Self {
+        ring.producers.fetch_add(1, Ordering::AcqRel);
+        Producer { ring }
+    }
+}
+
+impl<T> Drop for Producer<T> {
+    fn drop(&mut self) {
+        if self.ring.producers.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.ring.wake();
+        }
+    }
+}
+
+pub(crate) struct Consumer<T> {
+    ring: Arc<Ring<T>>,
+    // The sequence of the next item to receive
+    next: u64,
+    // Sequences skipped since Receiver::take_overwritten
+    overwritten: u64,
+}
+
+impl<T> Consumer<T> {
+    fn try_pop(&mut self) -> Result<T, mpsc::TryRecvError> {
+        let ring = &self.ring;
+        // Read before the count, so items pushed by the last producer are
+        // received before it's reported gone
+        let disconnected = ring.producers.load(Ordering::Acquire) == 0;
+        loop {
+            let pushed = ring.pushed.load(Ordering::Acquire);
+            if self.next >= pushed {
+                return Err(match disconnected {
+                    true => mpsc::TryRecvError::Disconnected,
+                    false => mpsc::TryRecvError::Empty,
+                });
+            }
+            // Lapped by the producers: the oldest slot is the one they write
+            // next, skip to the newest item instead
+            let expected = self.next;
+            if pushed - self.next > ring.slots.len() as u64 {
+                self.next = pushed - 1;
+            }
+
+            match self.take(self.next) {
+                Some(slot) => {
+                    self.overwritten += slot.sequence - expected;
+                    self.next = slot.sequence + 1;
+                    return Ok(slot.item);
+                }
+                // Overwritten by an older item, skip past it
+                None => {
+                    self.next += 1;
+                    self.overwritten += self.next - expected;
+                }
+            }
+        }
+    }
+
+    // The item of `sequence`, or a newer one of the same slot. The item is
+    // claimed, so it's waited for on its slot rather than chasing faster
+    // producers, but only for SLOT_WAIT: a producer that stored an older
+    // item over it late has dropped it, and nothing might come after.
+    fn take(&self, sequence: u64) -> Option<Box<Slot<T>>> {
+        let started = Instant::now();
+        let mut spins = 0u32;
+        loop {
+            let slot = self
+                .ring
+                .slot(sequence)
+                .swap(ptr::null_mut(), Ordering::AcqRel);
+            if !slot.is_null() {
+                // SAFETY: the swap took it out of the ring, nobody else has it
+                let slot = unsafe { Box::from_raw(slot) };
+                if slot.sequence >= sequence {
+                    return Some(slot);
+                }
+                // Left over from a lap the consumer skipped, or stored late
+                continue;
+            }
+            if started.elapsed() > SLOT_WAIT {
+                return None;
+            }
+            match spins < 64 {
+                true => std::hint::spin_loop(),
+                false => thread::yield_now(),
+            }
+            spins += 1;
+        }
+    }
+
+    // Parks until an item is pushed, or `deadline` passes
+    fn pop(&mut self, deadline: Option<Instant>) -> Result<T, mpsc::RecvTimeoutError> {
+        loop {
+            match self.try_pop() {
+                Ok(item) => return Ok(item),
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    return Err(mpsc::RecvTimeoutError::Disconnected)
+                }
+                Err(mpsc::TryRecvError::Empty) => {}
+            }
+
+            // Producers check for a waiter after pushing and the consumer for
+            // pushed items after registering, so one of them sees the other.
+            // Unparking before parking makes the park return right away.
+            *self.ring.waiter.lock().unwrap() = Some(thread::current());
+            self.ring.waiting.store(true, Ordering::SeqCst);
+            if self.ring.pushed.load(Ordering::SeqCst) <= self.next
+                && self.ring.producers.load(Ordering::SeqCst) > 0
+            {
+                match deadline {
+                    Some(deadline) => {
+                        let now = Instant::now();
+                        if now >= deadline {
+                            self.ring.waiting.store(false, Ordering::SeqCst);
+                            return Err(mpsc::RecvTimeoutError::Timeout);
+                        }
+                        thread::park_timeout(deadline - now);
+                    }
+                    None => thread::park(),
+                }
+            }
+            self.ring.waiting.store(false, Ordering::SeqCst);
+        }
+    }
+}
+
+impl<T> Drop for Consumer<T> {
+    fn drop(&mut self) {
+        self.ring.consumer_alive.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_skips_to_newest() {
+        let (tx, rx) = channel(Some(3));
+        (0..3).for_each(|i| tx.send(i).unwrap());
+        assert_eq!(rx.try_recv(), Ok(0));
+
+        // Lapped, 1 was overwritten and 2 and 3 are skipped as stale
+        (3..5).for_each(|i| tx.send(i).unwrap());
+        assert_eq!(rx.try_recv(), Ok(4));
+        assert_eq!(rx.take_overwritten(), 3);
+        assert_eq!(rx.take_overwritten(), 0);
+        (5..8).for_each(|i| tx.send(i).unwrap());
+        let received: Vec<i32> = std::iter::from_fn(|| rx.try_recv().ok()).collect();
+        assert_eq!(received, [5, 6, 7]);
+        assert_eq!(rx.try_recv(), Err(mpsc::TryRecvError::Empty));
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(1)),
+            Err(mpsc::RecvTimeoutError::Timeout)
+        );
+
+        // Queued items are received after every sender is gone
+        tx.send(6).unwrap();
+        drop(tx);
+        assert_eq!(rx.recv(), Ok(6));
+        assert_eq!(rx.recv(), Err(mpsc::RecvError));
+    }
+
+    #[test]
+    fn test_ring_across_threads() {
+        let (tx, rx) = channel(Some(4));
+        let producer = thread::spawn(move || (0..20_000u64).for_each(|i| tx.send(i).unwrap()));
+
+        // In order with some skipped, up to the last one pushed
+        let mut last = None;
+        let mut received = 0;
+        while let Ok(i) = rx.recv() {
+            assert!(last.map_or(true, |last| i > last));
+            last = Some(i);
+            received += 1;
+        }
+        producer.join().unwrap();
+        assert_eq!(last, Some(19_999));
+        assert_eq!(received + rx.take_overwritten(), 20_000);
+
+        // Clones on other threads can send too, in order per thread
+        let (tx, rx) = channel(Some(4));
+        let producers: Vec<_> = (0..2)
+            .map(|producer| {
+                let tx = tx.clone();
+                thread::spawn(move || (0..10_000u64).for_each(|i| tx.send((producer, i)).unwrap()))
+            })
+            .collect();
+        drop(tx);
+        let mut last = [None; 2];
+        while let Ok((producer, i)) = rx.recv() {
+            assert!(last[producer].map_or(true, |last| i > last));
+            last[producer] = Some(i);
+        }
+        producers.into_iter().for_each(|p| p.join().unwrap());
+
+        let (tx, rx) = channel::<u8>(Some(1));
+        drop(rx);
+        assert!(tx.send(0).is_err());
+    }
+
+    #[test]
+    fn test_ring_late_producer() {
+        // A producer claims 0 and is preempted, others lap it and push into
+        // its slot, then it stores 0 over their newer item. The consumer
+        // gives up on that item instead of waiting for the slot to fill.
+        for slots in [1, 2] {
+            let (tx, rx) = channel(Some(slots));
+            let Sender::Ring(producer) = &tx else {
+                unreachable!()
+            };
+            let late = producer.ring.claim();
+            (1..=slots).for_each(|i| tx.send(i).unwrap());
+            producer.ring.store(late, 0);
+
+            assert_eq!(rx.try_recv(), Err(mpsc::TryRecvError::Empty));
+            assert_eq!(rx.take_overwritten(), slots as u64 + 1);
+            tx.send(9).unwrap();
+            assert_eq!(rx.try_recv(), Ok(9));
+        }
+
+        // Producers racing on one or two slots never hang the consumer
+        for slots in [1, 2] {
+            let (tx, rx) = channel(Some(slots));
+            let producers: Vec<_> = (0..2)
+                .map(|_| {
+                    let tx = tx.clone();
+                    thread::spawn(move || (0..10_000u64).for_each(|i| tx.send(i).unwrap()))
+                })
+                .collect();
+            drop(tx);
+            let received = std::iter::from_fn(|| rx.recv().ok()).count() as u64;
+            producers.into_iter().for_each(|p| p.join().unwrap());
+            assert_eq!(received + rx.take_overwritten(), 20_000);
+        }
+    }
+
+    // Compares the ring with a channel, run with
+    // `cargo test --release -- --ignored --nocapture bench_handoff`: the
+    // latency from send to receive of frames paced at 240 fps, and how fast
+    // items go through with a producer that never waits
+    #[test]
+    #[ignore]
+    fn bench_handoff() {
+        for (name, ring) in [("channel", None), ("ring", Some(4))] {
+            let (tx, rx) = channel::<Instant>(ring);
+            let producer = thread::spawn(move || {
+                for _ in 0..1_000 {
+                    tx.send(Instant::now()).unwrap();
+                    thread::sleep(Duration::from_micros(4_167));
+                }
+            });
+            let mut latencies: Vec<Duration> = std::iter::from_fn(|| rx.recv().ok())
+                .map(|sent| sent.elapsed())
+                .collect();
+            producer.join().unwrap();
+            latencies.sort();
+            let percentile = |p: usize| latencies[(latencies.len() - 1) * p / 100];
+
+            let (tx, rx) = channel::<u64>(ring);
+            let start = Instant::now();
+            let producer = thread::spawn(move || (0..1_000_000).for_each(|i| tx.send(i).unwrap()));
+            let received = std::iter::from_fn(|| rx.recv().ok()).count();
+            producer.join().unwrap();
+            let throughput = 1_000_000.0 / start.elapsed().as_secs_f64();
+
+            println!(
+                "{name}: latency p50 {:?}, p99 {:?}, max {:?}; {:.1}M items sent/s, {received} received",
+                percentile(50),
+                percentile(99),
+                percentile(100),
+                throughput / 1_000_000.0,
+            );
+        }
+    }
+}
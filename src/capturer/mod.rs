@@ -1,27 +1,95 @@
+mod aspect_crop;
+mod burst;
+mod constant_rate;
+mod edge_trim;
 pub mod engine;
+mod external_trigger;
+mod fanout;
+#[cfg(test)]
+mod fixtures;
+mod follow;
+mod frame_floor;
+mod handoff;
+mod multi;
+mod negotiated;
+#[cfg(target_os = "windows")]
+mod pacer;
+mod reader;
+mod receiver;
+#[cfg(any(target_os = "windows", test))]
+mod recovery;
+mod replay;
+mod schedule;
+mod screenshot;
+#[cfg(any(target_os = "windows", test))]
+mod secure_desktop;
+mod stats;
+mod thumbnail;
+mod transform;
+mod trigger;
+mod validate;
+mod warmup;
 
-use std::{error::Error, sync::mpsc};
+use std::{
+    any::Any,
+    collections::VecDeque,
+    error::Error,
+    panic::{self, AssertUnwindSafe},
+    sync::{mpsc, Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
 
-use engine::ChannelItem;
+use engine::Acquired;
 
 use crate::{
-    frame::{Frame, FrameType},
+    frame::{
+        get_clamped_bounds, rescale_timestamp, ActivitySummarizer, ChecksumAlgorithm,
+        ChromaSubsampling, ClockCorrelation, ColorMatrix, CursorImage, Frame, FrameChecksum,
+        FrameMotion, FrameType, LumaWeights, MarkerFrame, Regions, WatermarkOptions,
+    },
     has_permission, is_supported,
     targets::Target,
 };
 
+pub use aspect_crop::AspectCrop;
+pub use burst::{capture_burst, Burst};
+pub use edge_trim::{EdgeTrim, Edges};
 pub use engine::get_output_frame_size;
+pub use external_trigger::FrameTrigger;
+pub use fanout::{FrameSubscriber, OverflowPolicy};
+pub use follow::FollowActionOptions;
+pub use multi::{DisplayCapture, DisplayFrame, MultiCapturer};
+pub use negotiated::{CaptureBackend, Downgrade, NegotiatedConfig};
+pub use reader::FrameReader;
+pub use receiver::FrameReceiver;
+pub use replay::{GpuReplayOptions, GpuReplayUsage};
+pub use schedule::{CaptureSchedule, ScheduleCallback};
+pub use screenshot::{capture_screenshot, ScreenshotError, ScreenshotOptions};
+pub use stats::{CaptureStats, INTERVAL_BUCKET, INTERVAL_BUCKETS};
+pub use thumbnail::{Thumbnail, ThumbnailOptions};
+pub use transform::{FrameTransform, Transform};
+pub use trigger::{TriggerCondition, TriggerOptions};
+pub use validate::ConfigError;
+pub use warmup::{WarmupEnd, WarmupOptions};
 
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Resolution {
+    #[cfg_attr(feature = "serde", serde(rename = "480p"))]
     _480p,
+    #[cfg_attr(feature = "serde", serde(rename = "720p"))]
     _720p,
+    #[cfg_attr(feature = "serde", serde(rename = "1080p"))]
     _1080p,
+    #[cfg_attr(feature = "serde", serde(rename = "1440p"))]
     _1440p,
+    #[cfg_attr(feature = "serde", serde(rename = "2160p"))]
     _2160p,
+    #[cfg_attr(feature = "serde", serde(rename = "4320p"))]
     _4320p,
 
     #[default]
+    #[cfg_attr(feature = "serde", serde(rename = "captured"))]
     Captured,
 }
 
@@ -41,47 +109,985 @@ impl Resolution {
     }
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Point {
     pub x: f64,
     pub y: f64,
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Size {
     pub width: f64,
     pub height: f64,
 }
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Area {
     pub origin: Point,
     pub size: Size,
 }
 
+/// How the frame rate requested through [Options::fps] is enforced
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameRateCap {
+    /// The OS only produces frames at up to this rate. This is the case on
+    /// macOS (`minimumFrameInterval`) and Linux (negotiated PipeWire framerate),
+    /// and costs nothing for the frames that are never captured.
+    Os(u32),
+    /// The OS captures at the display rate and scap drops frames to stay at or
+    /// below this rate. This is the case on Windows, whose capture API has no
+    /// frame interval.
+    Client(u32),
+    /// `fps` is 0, frames are delivered as fast as the OS produces them
+    Unlimited,
+}
+
+/// What to do when the crop area extends past the captured frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CropOverflow {
+    /// Capture the part of the crop area inside the frame. Frames report the
+    /// smaller, actual dimensions.
+    #[default]
+    Clamp,
+    /// Stop capturing with an error if the crop area can't be fully satisfied
+    Strict,
+    /// Keep the size of the crop area and fill the part past the frame with
+    /// this RGB color, e.g. for pipelines that always take 1920x1080. See
+    /// [NegotiatedConfig::filled_fraction]
+    Fill([u8; 3]),
+    /// Keep the size of the crop area and move it back inside the frame
+    /// where it extends past an edge. Crops that follow a window with
+    /// [WindowContentMode::ScreenRegion] then keep a steady size as it nears
+    /// the display's edges, instead of shrinking. Crop areas larger than the
+    /// frame are clamped to it.
+    Shift,
+}
+
+/// The color space frames are delivered in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OutputColorSpace {
+    /// Whatever the display uses, see the frame's `color_space`
+    #[default]
+    Native,
+    /// Convert wide-gamut (Display P3) frames to sRGB. Frames that are
+    /// already sRGB, or whose color space is unknown, pass through unchanged.
+    SRGB,
+}
+
+/// How frames are delivered when the captured display is in HDR mode
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HdrHandling {
+    /// Deliver what the OS converts to 8-bit, which looks washed out on
+    /// Windows HDR displays
+    #[default]
+    PassThrough,
+    /// Capture in scRGB and map it to sRGB using the display's SDR white
+    /// level, so frames look like an SDR screenshot of the same content
+    ToneMapToSDR,
+}
+
+/// How the cursor is drawn when [Options::show_cursor] is set
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CursorStyle {
+    /// The cursor as rendered by the OS
+    #[default]
+    System,
+    /// Replace the system cursor with this image, e.g. a large high-contrast
+    /// pointer for tutorials
+    Custom(CursorImage),
+    /// The system cursor with a colored ring around it
+    Highlighted,
+}
+
+/// What window capture delivers, in particular whether windows covering the
+/// captured one show up in the frames
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WindowContentMode {
+    /// The window's actual rendered content, even when it's covered by other
+    /// windows or partially off-screen. Occluders never appear in the frames.
+    #[default]
+    WindowContent,
+    /// What the user sees where the window is: the display is captured and
+    /// cropped to the window's current bounds, so whatever covers the window
+    /// is captured too and off-screen parts are cut off. The capture follows
+    /// the window as it moves and reports its bounds through
+    /// [CapturerEvent::WindowBoundsChanged], but stays on the display the
+    /// window was on when capture started.
+    ScreenRegion,
+}
+
+/// Computes the part of a window to capture from the window's current size,
+/// see [WindowSubregion::Dynamic]
+pub type SubregionCallback = Arc<dyn Fn(&Size) -> Option<Area> + Send + Sync>;
+
+/// The part of a captured window that ends up in the frames
+///
+/// Areas are in physical pixels relative to the window's frame and are
+/// clamped to it. They replace [Options::crop_area] for window targets.
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WindowSubregion {
+    /// The whole window, or [Options::crop_area] if it's set
+    #[default]
+    Whole,
+    /// A fixed area of the window
+    Static(Area),
+    /// The area covered by a child window, given as its raw HWND. It's looked
+    /// up for every frame, so layout changes are followed. Windows only.
+    ChildWindow(isize),
+    /// Called for every frame with the window's size. Returning None captures
+    /// the whole window. On macOS this is the way to follow a sub-view, e.g.
+    /// through the frame of an accessibility element. Can't be serialized.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Dynamic(SubregionCallback),
+}
+
+impl std::fmt::Debug for WindowSubregion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WindowSubregion::Whole => write!(f, "Whole"),
+            WindowSubregion::Static(area) => f.debug_tuple("Static").field(area).finish(),
+            WindowSubregion::ChildWindow(hwnd) => f.debug_tuple("ChildWindow").field(hwnd).finish(),
+            WindowSubregion::Dynamic(_) => write!(f, "Dynamic(..)"),
+        }
+    }
+}
+
+impl WindowSubregion {
+    // The area to capture from a window of `size`, clamped to it, or None for
+    // the whole window. Child windows are resolved by the Windows engine.
+    // Errors with the panic message if the callback panics.
+    pub(crate) fn resolve(&self, size: &Size) -> Result<Option<Area>, String> {
+        let area = match self {
+            WindowSubregion::Whole | WindowSubregion::ChildWindow(_) => return Ok(None),
+            WindowSubregion::Static(area) => Some(area.clone()),
+            // The callback runs on the capture thread on Windows, which mustn't unwind
+            WindowSubregion::Dynamic(callback) => {
+                panic::catch_unwind(AssertUnwindSafe(|| callback(size))).map_err(panic_message)?
+            }
+        };
+        Ok(area.and_then(|area| clamp_area(&area, size)))
+    }
+}
+
+// The message a panic was started with
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast_ref::<&str>() {
+            Some(message) => message.to_string(),
+            None => "callback panicked".to_string(),
+        },
+    }
+}
+
+/// What capture does after a callback in [Options] panicked, which is
+/// reported with [CapturerEvent::CallbackPanicked]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CallbackPanic {
+    /// No more frames are delivered, as when the target is closed
+    #[default]
+    Stop,
+    /// Carry on as if the callback had returned None, and call it again for
+    /// the next frame
+    Continue,
+}
+
+/// What is delivered while Windows shows the secure desktop, as for UAC
+/// prompts, the lock screen and Ctrl+Alt+Del, which can't be captured. See
+/// [CapturerEvent::SecureDesktopEntered].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SecureDesktopPolicy {
+    /// The last frame before it, repeated at the capture's frame rate
+    #[default]
+    Freeze,
+    /// Black frames of the same size and format, at the capture's frame rate
+    Black,
+    /// No frames until it's gone
+    Pause,
+}
+
+// Clamps `area` to whole pixels within `size`. An area fully outside falls
+// back to the whole window.
+pub(crate) fn clamp_area(area: &Area, size: &Size) -> Option<Area> {
+    let [x, y, width, height] =
+        get_clamped_bounds(area, size.width as usize, size.height as usize)?;
+    Some(Area {
+        origin: Point {
+            x: x as f64,
+            y: y as f64,
+        },
+        size: Size {
+            width: width as f64,
+            height: height as f64,
+        },
+    })
+}
+
+// Moves `area` inside `size` where it extends past an edge, keeping its
+// size. Areas larger than `size` are cut down to it.
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn shift_area(area: &Area, size: &Size) -> Area {
+    let shift = |origin: f64, extent: f64, limit: f64| {
+        let extent = extent.min(limit);
+        (origin.clamp(0.0, (limit - extent).max(0.0)), extent)
+    };
+    let (x, width) = shift(area.origin.x, area.size.width, size.width);
+    let (y, height) = shift(area.origin.y, area.size.height, size.height);
+    Area {
+        origin: Point { x, y },
+        size: Size { width, height },
+    }
+}
+
+/// Events reported alongside frames, see [Capturer::try_next_event]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CapturerEvent {
+    /// The captured window moved or was resized. The area is in frame pixels
+    /// relative to its display.
+    WindowBoundsChanged(Area),
+    /// The effective area of [Options::window_subregion] changed, including
+    /// when it's first resolved. The area is in physical pixels relative to
+    /// the window, or None when the whole window is captured.
+    SubregionChanged(Option<Area>),
+    /// [Options::edge_trim] removes `edges` from the frames, which are
+    /// `width` by `height` pixels from now on. Reported with the first frame
+    /// and whenever either changes. With [EdgeTrim::Letterbox] that's the
+    /// active picture, `edges.left` and `edges.top` pixels into the
+    /// captured frames.
+    EdgesTrimmed {
+        edges: Edges,
+        width: u32,
+        height: u32,
+    },
+    /// The captured window's title changed, see [Options::watch_window].
+    /// `time` is on the clock of the frames' `display_time`.
+    WindowTitleChanged {
+        target_id: u32,
+        title: String,
+        time: u64,
+    },
+    /// The captured window moved or was resized, see [Options::watch_window].
+    /// The bounds are in screen coordinates: physical pixels on Windows,
+    /// points on macOS.
+    WindowMoved { bounds: Area, time: u64 },
+    /// The captured window was minimized, maximized, made fullscreen or
+    /// restored, see [Options::watch_window]
+    WindowStateChanged { state: WindowState, time: u64 },
+    /// The captured window moved to or from the virtual desktop being shown,
+    /// or the user switched desktops, see [Options::watch_window]. Windows
+    /// only, which delivers no new frames of windows on other desktops, so
+    /// apps can warn or switch to the window's desktop. See
+    /// [is_on_current_desktop](crate::targets::is_on_current_desktop).
+    WindowDesktopChanged { on_current_desktop: bool, time: u64 },
+    /// Another window was focused, or the focused one was moved, resized or
+    /// restored, see [Options::watch_focus]. `bounds` is where it is in the
+    /// frames, in pixels clipped to them, or None when it's outside of them.
+    /// `time` is on the clock of the frames' `display_time`.
+    FocusChanged {
+        title: String,
+        bounds: Option<Area>,
+        time: u64,
+    },
+    /// [Options::trigger] fired and frames are delivered from now on, starting
+    /// with the one at `time`
+    Triggered { time: u64 },
+    /// [Options::warmup] ended after discarding `discarded` frames, and
+    /// frames are delivered from the one at `time` on
+    WarmupCompleted { discarded: u32, time: u64 },
+    /// A callback in [Options] panicked with `message`, see [CallbackPanic]
+    CallbackPanicked { message: String },
+    /// Windows switched to the secure desktop at `time`, on the clock of the
+    /// frames' `display_time`. Frames are synthesized per
+    /// [Options::secure_desktop] until [CapturerEvent::SecureDesktopExited].
+    SecureDesktopEntered { time: u64 },
+    /// The normal desktop is back at `time` after `gap`, and so are captured
+    /// frames
+    SecureDesktopExited { time: u64, gap: Duration },
+    /// Frames stopped being delivered outside of [Options::schedule], from
+    /// the frame at `time` on. Also reported when capture starts outside of it.
+    ScheduleSuspended { time: u64 },
+    /// [Options::schedule] delivers frames again, starting with the one at `time`
+    ScheduleResumed { time: u64 },
+    /// A GPU driver reset lost the device capture ran on at `time`, see
+    /// [Options::auto_recover_device]. No frames arrive until
+    /// [CapturerEvent::DeviceRecovered].
+    DeviceLost { time: u64 },
+    /// Capture was restarted on a new device at `time`, after `attempts`
+    /// restarts since it last ran stably
+    DeviceRecovered { time: u64, attempts: u32 },
+    /// Capture couldn't be restarted after `attempts` tries and stays
+    /// stopped. Stop the capturer and start a new one to try again.
+    DeviceRecoveryFailed { attempts: u32 },
+    /// Capture started without carrying out these options as requested, see
+    /// [Capturer::negotiated]. Apps that need every option can stop here.
+    ConfigDowngraded(Vec<Downgrade>),
+    /// [Capturer::reconfigure] changed [Capturer::get_output_frame_size]. No
+    /// frames of the old size follow once capture was restarted, a live
+    /// change can still be behind by the frames already captured.
+    FormatChanged { width: u32, height: u32 },
+    /// The GPU ran low on memory, so [Options::gpu_replay] keeps frames for
+    /// only `retention` from now on. Restored when capture restarts.
+    ReplayRetentionReduced { retention: Duration },
+}
+
+/// How a window is shown, see [CapturerEvent::WindowStateChanged]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WindowState {
+    Normal,
+    Minimized,
+    /// Never reported on macOS, where zoomed windows are just large
+    Maximized,
+    /// The window covers its whole display
+    Fullscreen,
+}
+
+/// Whether capture favors smooth, complete output or the newest frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Latency {
+    /// Frames are paced to [Options::fps] and every captured frame is
+    /// delivered in order, which suits recording
+    #[default]
+    Smooth,
+    /// For remote control and game streaming. Pacing, color conversion and
+    /// custom cursors are disabled, the OS keeps as few frames in flight as it
+    /// allows, and [Capturer::get_next_frame] skips to the newest frame when
+    /// the consumer falls behind.
+    ///
+    /// A consumer that keeps up gains up to one frame interval of pacing
+    /// jitter, one that falls behind no longer sees latency grow with its
+    /// backlog. With a frame pool of one on Windows and a queue of three on
+    /// macOS, glass-to-consumer latency is estimated to stay within one to
+    /// two display refreshes plus the consumer's own processing time. This
+    /// follows from the queue depths and hasn't been measured.
+    LowLatency,
+}
+
+/// Options for a common use, see [Options::preset]. Every preset delivers
+/// BGRA frames, which all platforms capture natively; everything not listed
+/// is left at its default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Preset {
+    /// For encoding to a file: 60 fps delivered at a constant frame rate,
+    /// the cursor, tightly packed frames and a [Options::max_output_size] of
+    /// 4096x4096, which hardware encoders take
+    Recording,
+    /// For live streaming: 30 fps as 1080p at most, the cursor, tightly
+    /// packed frames and [Latency::LowLatency]
+    Streaming,
+    /// For [capture_screenshot]: no cursor, tightly packed frames converted
+    /// to sRGB
+    Screenshot,
+    /// For remote control: a frame per display refresh (`fps` 0), the
+    /// cursor and [Latency::LowLatency]
+    LowLatency,
+}
+
+/// A small grayscale stream for motion detection and similar analysis, see
+/// [Options::grayscale]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GrayscaleOptions {
+    /// Size of the delivered frames in pixels. The captured frames are box
+    /// filtered to it, without preserving their aspect ratio.
+    pub width: u32,
+    pub height: u32,
+    pub weights: LumaWeights,
+}
+
+impl Default for GrayscaleOptions {
+    fn default() -> Self {
+        GrayscaleOptions {
+            width: 160,
+            height: 90,
+            weights: LumaWeights::default(),
+        }
+    }
+}
+
+/// Summaries of the captured frames in place of the frames, see [Options::activity]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ActivityOptions {
+    /// How long each summarized period lasts
+    pub interval: Duration,
+}
+
+impl Default for ActivityOptions {
+    fn default() -> Self {
+        ActivityOptions {
+            interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Frames as the regions that changed since the frame before, see [Options::delta]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeltaOptions {
+    /// Frames are compared in tiles of this many pixels square. Smaller tiles
+    /// send fewer unchanged pixels along with the changed ones, but take
+    /// more regions to describe a change.
+    pub tile_size: u32,
+    /// A keyframe with the whole frame is sent every this many frames, so
+    /// receivers that missed deltas recover on their own. 0 only sends
+    /// the first one and those forced with [Capturer::force_keyframe].
+    pub keyframe_interval: u32,
+    pub reference: DeltaReference,
+}
+
+impl Default for DeltaOptions {
+    fn default() -> Self {
+        DeltaOptions {
+            tile_size: 64,
+            keyframe_interval: 300,
+            reference: DeltaReference::default(),
+        }
+    }
+}
+
+/// What the deltas of [DeltaOptions] are found against
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DeltaReference {
+    /// The frame before, which sends the least while every delta arrives
+    #[default]
+    PreviousFrame,
+    /// The last keyframe, so a receiver that missed deltas can go on with
+    /// the next one. Deltas grow as the frames drift away from the keyframe,
+    /// so this suits links that drop data with a short keyframe interval.
+    /// Receivers apply each delta to a copy of the keyframe.
+    Keyframe,
+}
+
+/// Frames as the rows that changed since the frame before, see
+/// [Options::scanline_patches]. Text UIs like terminals and editors change
+/// line by line, which full width bands describe with less overhead than tiles.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScanlineOptions {
+    /// The whole frame is sent as a single band when more than this part of
+    /// its rows changed, e.g. while scrolling
+    pub max_changed_fraction: f32,
+    /// The whole frame is sent every this many frames, so receivers that
+    /// missed patches recover on their own. 0 only sends full frames when
+    /// needed or forced with [Capturer::force_keyframe].
+    pub full_frame_interval: u32,
+}
+
+impl Default for ScanlineOptions {
+    fn default() -> Self {
+        ScanlineOptions {
+            max_changed_fraction: 0.5,
+            full_frame_interval: 300,
+        }
+    }
+}
+
+/// How [Options::exclude_current_process] is carried out, see
+/// [Capturer::get_process_exclusion]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessExclusion {
+    /// Exclusion wasn't requested
+    Off,
+    /// The windows are left out and whatever is beneath them is captured
+    Excluded,
+    /// The windows are captured as black rectangles. This is the case on
+    /// Windows versions older than 10 2004.
+    Masked,
+    /// The platform can't tell which windows belong to the process, as on Linux
+    Unsupported,
+}
+
 /// Options passed to the screen capturer
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Options {
     pub fps: u32,
     pub show_cursor: bool,
+    // custom and highlighted cursors are composited into BGRA frames on Windows
+    // and macOS display capture, everything else falls back to the system cursor
+    pub cursor_style: CursorStyle,
     pub show_highlight: bool,
+    // targets hold OS handles that are only valid while the process runs, so
+    // they're left out when serializing options and have to be set again
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub target: Option<Target>,
+    pub window_content_mode: WindowContentMode,
+    pub window_subregion: WindowSubregion,
+    // in points of the target as shown on screen. On Windows these are scaled
+    // by the DPI of the display for windows that aren't DPI aware, see
+    // targets::get_dpi_awareness.
     pub crop_area: Option<Area>,
+    // crop overflow handling only applies on Windows, macOS clamps the source rect itself
+    pub crop_overflow: CropOverflow,
+    // crops the crop area, or the whole target, further to an aspect ratio
+    // before scaling. The result is Capturer::effective_source_rect. Not
+    // applied to window subregions or windows captured as screen regions.
+    pub aspect_crop: Option<AspectCrop>,
+    // removes the thin borders of black or garbage pixels some displays and
+    // drivers leave at the edges of the frames, or the black bars of
+    // letterboxed content, after cropping and before scaling. Reported with
+    // CapturerEvent::EdgesTrimmed, along with the size the frames are
+    // delivered in, which get_output_frame_size doesn't account for.
+    pub edge_trim: Option<EdgeTrim>,
+    // crops the frames to where the user works and moves the crop smoothly
+    // after the cursor and, with `watch_focus`, the focused window, e.g. for
+    // remote assistance. Applied after `edge_trim`, and before scaling, so
+    // the frames are the zoomed size of the captured ones, which
+    // get_output_frame_size doesn't account for. See
+    // Capturer::last_followed_area. Needs the cursor position, which isn't
+    // known on Linux and for macOS window capture.
+    pub follow_action: Option<FollowActionOptions>,
     pub output_type: FrameType,
+    // acceptable frame types, most preferred first, e.g. YUVFrame for
+    // efficiency with BGRAFrame to fall back to. The first one the platform
+    // delivers replaces `output_type`, which is only used if there's none.
+    // The pick is NegotiatedConfig::output_type.
+    pub output_types: Vec<FrameType>,
+    // delivers Frame::Raw in the format and bit depth the compositor hands
+    // out, with nothing converted, for color-critical work: half float
+    // scRGB from Windows HDR displays and BGRA from SDR ones, 10-bit
+    // ARGB2101010 from ScreenCaptureKit. Replaces output_type, and
+    // consumers have to decode any RawPixelFormat, including ones added
+    // later. Whatever reworks pixels, like grayscale, thumbnails, custom
+    // cursors, watermarks, color and HDR conversion and scaling on Windows,
+    // is left out and downgraded. Linux frames already arrive in the 8-bit
+    // format PipeWire negotiates.
+    pub native_format: bool,
+    // the matrix YUV frames are encoded with, and tagged with in color_matrix.
+    // macOS encodes BT.709 itself and anything else is converted from BGRA
+    // by scap. Only macOS delivers YUV frames.
+    pub color_matrix: ColorMatrix,
+    // the chroma resolution of YUV frames. macOS delivers 4:2:0 itself and
+    // 4:2:2 and 4:4:4 are converted from BGRA by scap.
+    pub chroma_subsampling: ChromaSubsampling,
+    // keeps up to this many frame buffers for reuse once the frames of a
+    // FrameReceiver are dropped. Windows copies captured frames into them
+    // instead of allocating; elsewhere they're allocated as before.
+    pub buffer_pool: Option<usize>,
+    // guarantees BGRA frames have no row padding (stride == width * 4), copying if needed
+    pub guarantee_tight_packing: bool,
     pub output_resolution: Resolution,
+    // downscales frames larger than this [width, height], keeping their aspect
+    // ratio, for encoders with a maximum size like 4096 on a side. The size
+    // after it is what get_output_frame_size reports. Unlimited by default,
+    // not applied to grayscale frames, which have their own size.
+    pub max_output_size: Option<[u32; 2]>,
+    // a safety cap on the longest side of the captured size, for virtual
+    // desktops that would make textures larger than the GPU allows. Applied
+    // before `output_resolution` and `max_output_size`, keeping the aspect
+    // ratio, and reported as NegotiatedConfig::capture_scale. macOS captures
+    // at the capped size; Windows textures are always the size of the
+    // target, so frames are downscaled once they're read from them.
+    pub max_dimension: Option<u32>,
+    // color conversion only applies to packed frames, YUV frames keep their native color space
+    pub color_space: OutputColorSpace,
+    // hdr handling only applies on Windows, macOS already delivers SDR frames
+    pub hdr_handling: HdrHandling,
     // excluded targets will only work on macOS
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub excluded_targets: Option<Vec<Target>>,
+    // keeps this process's windows, including ones opened during capture, out
+    // of the frames. On Windows they're hidden from every capture meanwhile.
+    pub exclude_current_process: bool,
+    pub latency: Latency,
+    // hands frames from the capture thread to Capturer::get_next_frame in a
+    // lock-free ring of this many slots instead of a channel, for a single
+    // consumer that wants the newest frame with the least overhead. Frames
+    // aren't copied into the ring, only their handles. A consumer that falls
+    // behind by more frames than the ring holds skips to the newest one, the
+    // ones in between count as stale drops. None queues frames unbounded.
+    pub frame_ring: Option<usize>,
+    // only delivers frames the compositor finished composing, never ones
+    // read mid-present. Windows and macOS only ever hand out composed frames;
+    // on Linux buffers PipeWire flags as corrupted are dropped, which shows
+    // as a skipped frame. See NegotiatedConfig::tear_free.
+    pub tear_free: bool,
+    // delivers a frame each time a window target presents new content, for
+    // recording games that run their own present loop: every frame is one
+    // whole game frame, none is repeated or shows half of two. This works
+    // on macOS for window targets with WindowContentMode::WindowContent,
+    // where ScreenCaptureKit updates the capture when the window's swapchain
+    // presents and the idle updates in between are dropped. fps still caps
+    // the rate by dropping presents. Displays, screen regions, Windows and
+    // Linux, where the capture APIs don't report presents, fall back to the
+    // compositor's rate. See NegotiatedConfig::content_aligned.
+    pub content_aligned: bool,
+    // drops frames already older than this when get_next_frame receives them,
+    // by their display time, so a consumer that fell behind only gets current
+    // ones. Counted in CaptureStats::late_drops. Not applied on Linux, whose
+    // frame clock can't be read.
+    pub frame_deadline: Option<Duration>,
+    // delivers Frame::Gray8 of this size instead of `output_type`, converted on
+    // the capture thread on Windows and when the frame is received elsewhere.
+    // Custom cursors and color conversion are skipped.
+    pub grayscale: Option<GrayscaleOptions>,
+    // reports title, bounds and state changes of window targets as events,
+    // and on Windows whether they're on the virtual desktop being shown, from
+    // when capture starts until it stops. Windows and macOS only.
+    pub watch_window: bool,
+    // reports the focused window's place in the frames as
+    // CapturerEvent::FocusChanged whenever focus moves or the focused window
+    // is moved or resized, for drawing a highlight around it in post. Looked
+    // up on those events only, never per frame. Windows only.
+    pub watch_focus: bool,
+    // frame display times in ticks of this many per second, e.g. 90000 for
+    // MPEG-TS, instead of nanoseconds. See frame::rescale_timestamp. Event
+    // times stay in nanoseconds.
+    pub timestamp_base: Option<u32>,
+    // delivers exactly this many frames per second, evenly spaced: the latest
+    // captured frame at every tick, or the one before again when none arrived
+    // in time, counted in CaptureStats::repeated_frames. Display times are
+    // those of the ticks. Not applied with `delta` or `scanline_patches`.
+    pub constant_frame_rate: Option<u32>,
+    // captures at the usual rate, but delivers a Frame::Activity every
+    // `interval` with the average color, motion and change rate of the frames
+    // captured meanwhile, instead of the frames. Summed up on the thread
+    // calling get_next_frame. Not applied with `delta` or `scanline_patches`,
+    // and replaces `constant_frame_rate`.
+    pub activity: Option<ActivityOptions>,
+    // repeats the last frame whenever none arrived for 1/this of a second,
+    // e.g. while the screen is static and the OS sends nothing, so encoders
+    // never stall. Frames that do arrive are delivered as they come, and
+    // it's only a floor under what the OS leaves out. See
+    // Capturer::last_frame_synthesized. Not applied with `delta`,
+    // `scanline_patches`, `constant_frame_rate` or `activity`.
+    pub min_frame_rate: Option<u32>,
+    // delivers BGRA frames as Frame::Delta, found by comparing every frame with
+    // the one before or the last keyframe. Other frame types are delivered as
+    // they are. frame::compress shrinks deltas further with the lz4 or zstd
+    // feature.
+    pub delta: Option<DeltaOptions>,
+    // delivers BGRA frames as Frame::ScanlinePatch with the rows that changed
+    // since the frame before. Ignored when `delta` is set.
+    pub scanline_patches: Option<ScanlineOptions>,
+    // captures as usual, but only delivers frames from the first one in which
+    // the trigger's area changes or matches a reference, reported with
+    // CapturerEvent::Triggered. Meant for starting a recording on a cue.
+    pub trigger: Option<TriggerOptions>,
+    // frames captured but not delivered each time capture starts, as the
+    // first ones can be stale or black while the OS pipeline settles. One by
+    // default, reported with CapturerEvent::WarmupCompleted.
+    pub warmup: WarmupOptions,
+    // delivers a frame each time the FrameTrigger of Capturer::frame_trigger
+    // fires, rather than as the compositor sends them, e.g. on a hardware
+    // sync signal to capture in step with other machines. Each frame is the
+    // newest one captured by the time the trigger is noticed, within a
+    // millisecond, and is stamped with when it fired. So it's up to a frame
+    // interval plus the OS's delivery latency old, see
+    // Capturer::last_trigger_latency. Replaces `constant_frame_rate`,
+    // `min_frame_rate` and `activity`, not applied with `delta` or
+    // `scanline_patches`.
+    pub external_trigger: bool,
+    // compares every delivered frame with the one before on a coarse grid,
+    // to tell how much it moved and whether the content is in motion, like a
+    // video, see Capturer::last_frame_motion. Cheap enough to adapt an
+    // encoder's bitrate with.
+    pub estimate_motion: bool,
+    // keeps copies of the latest frames as GPU textures, which take no CPU
+    // memory, so the moments worth saving can be read back with
+    // Capturer::read_replay, e.g. for an instant replay of HDR or high
+    // resolution captures. They're copied as captured, before cursors, the
+    // watermark and the encodings above. Frames are kept for a shorter time
+    // when the GPU runs low on memory, reported with
+    // CapturerEvent::ReplayRetentionReduced. Only on Windows.
+    pub gpu_replay: Option<GpuReplayOptions>,
+    // runs every frame through these transforms in order, once it's cropped,
+    // scaled, converted and the cursor is drawn, and before the warmup,
+    // trigger, motion estimation, watermark, thumbnails and encodings, so
+    // processing that walks the pixels is done once on its way out. Frames
+    // dropped by a transform aren't delivered and panics are handled by
+    // `callback_panic`, where stopping ends the frames get_next_frame
+    // returns. Sizes changed by transforms aren't accounted for by
+    // get_output_frame_size. Can't be serialized.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub transforms: Vec<Arc<dyn FrameTransform>>,
+    // embeds each delivered frame's sequence number and display time into
+    // it, after the cursor is drawn and before delta or scanline encoding, so
+    // frames of a recording can be verified and ordered with
+    // frame::read_watermark. Only frames with 4 bytes per pixel, like BGRA.
+    pub watermark: Option<WatermarkOptions>,
+    // sums up the buffer of every delivered frame, as returned after all of
+    // the above, so writers can store it along with the frame, e.g. in a
+    // FrameIndexWriter with checksums, and readers can tell corrupted frames
+    // with frame::verify_frame_checksum. See Capturer::last_frame_checksum.
+    // Reads every byte once, about a millisecond per 1080p BGRA frame with
+    // XxHash64 and 8 with Crc32.
+    pub checksum: Option<ChecksumAlgorithm>,
+    // what to do when a callback such as WindowSubregion::Dynamic panics. The
+    // panic is caught on the thread calling it and reported as an event.
+    pub callback_panic: CallbackPanic,
+    // makes a downscaled copy of the frames for previews, at most `fps` per
+    // second, see Capturer::thumbnails. They're made before delta and
+    // scanline encoding, from the same frames, by filtering or by skipping
+    // pixels with a stride.
+    pub thumbnails: Option<ThumbnailOptions>,
+    // frames delivered while Windows shows a UAC prompt, the lock screen or
+    // another secure desktop, which nothing can capture. Freezing and
+    // blacking out keep a copy of the latest frame. Not applicable elsewhere.
+    pub secure_desktop: SecureDesktopPolicy,
+    // restarts capture with a new GPU device and frame pool when a driver
+    // reset (TDR) loses the old one, reported with CapturerEvent::DeviceLost
+    // and DeviceRecovered. Frames captured meanwhile are missing. Gives up
+    // with CapturerEvent::DeviceRecoveryFailed after a few restarts in a row.
+    // Windows only.
+    pub auto_recover_device: bool,
+    // only delivers frames within the schedule, checked against the system
+    // clock for every captured frame, so boundaries are only noticed once a
+    // frame is captured. Reported with CapturerEvent::ScheduleSuspended and
+    // ScheduleResumed.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub schedule: Option<CaptureSchedule>,
+}
+
+/// Bits an H.264 or HEVC encoder needs per pixel of each frame for screen
+/// content to stay legible, the assumption behind [Options::suggest_for_bitrate].
+/// Screen content is mostly flat and static and compresses far better than
+/// camera footage, which needs about twice as much.
+pub const SCREEN_BITS_PER_PIXEL: f64 = 0.1;
+
+impl Options {
+    /// The options of `preset`, to be adjusted like any other, e.g.
+    /// `Options { target, ..Options::preset(Preset::Recording) }`
+    pub fn preset(preset: Preset) -> Options {
+        match preset {
+            Preset::Recording => Options {
+                fps: 60,
+                show_cursor: true,
+                output_type: FrameType::BGRAFrame,
+                guarantee_tight_packing: true,
+                constant_frame_rate: Some(60),
+                max_output_size: Some([4096, 4096]),
+                ..Default::default()
+            },
+            Preset::Streaming => Options {
+                fps: 30,
+                show_cursor: true,
+                output_type: FrameType::BGRAFrame,
+                guarantee_tight_packing: true,
+                output_resolution: Resolution::_1080p,
+                latency: Latency::LowLatency,
+                ..Default::default()
+            },
+            Preset::Screenshot => Options {
+                show_cursor: false,
+                output_type: FrameType::BGRAFrame,
+                guarantee_tight_packing: true,
+                color_space: OutputColorSpace::SRGB,
+                ..Default::default()
+            },
+            Preset::LowLatency => Options {
+                fps: 0,
+                show_cursor: true,
+                output_type: FrameType::BGRAFrame,
+                latency: Latency::LowLatency,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Suggest an `output_resolution` and `fps` whose encoded stream fits in
+    /// `target_bps` bits per second, for a source of `source` pixels.
+    ///
+    /// Sharp text matters more than smooth motion on screen, so this picks the
+    /// largest resolution that fits at 30 fps, at most the source's, and 60 fps
+    /// if that fits too. Budgets too small for 480p at 30 fps get 480p at a
+    /// lower frame rate. Assumes [SCREEN_BITS_PER_PIXEL], a real encoder's
+    /// bitrate depends on how much of the screen changes.
+    pub fn suggest_for_bitrate(target_bps: u64, source: [u32; 2]) -> (Resolution, u32) {
+        let [width, height] = source;
+        let aspect_ratio = width as f32 / height.max(1) as f32;
+        let bitrate = |pixels: [u32; 2], fps: u32| {
+            pixels[0] as f64 * pixels[1] as f64 * fps as f64 * SCREEN_BITS_PER_PIXEL
+        };
+
+        // Largest first, without upscaling the source
+        let candidates = [
+            Resolution::_4320p,
+            Resolution::_2160p,
+            Resolution::_1440p,
+            Resolution::_1080p,
+            Resolution::_720p,
+            Resolution::_480p,
+        ]
+        .map(|resolution| (resolution, resolution.value(aspect_ratio)));
+        let candidates = std::iter::once((Resolution::Captured, source))
+            .chain(candidates.into_iter().filter(|(_, size)| size[0] < width))
+            .collect::<Vec<_>>();
+
+        for &(resolution, size) in &candidates {
+            if bitrate(size, 30) <= target_bps as f64 {
+                let fps = match bitrate(size, 60) <= target_bps as f64 {
+                    true => 60,
+                    false => 30,
+                };
+                return (resolution, fps);
+            }
+        }
+
+        let (resolution, size) = candidates[candidates.len() - 1];
+        let fps = target_bps as f64 / bitrate(size, 1);
+        (resolution, (fps as u32).clamp(1, 30))
+    }
+}
+
+/// The options [Capturer::reconfigure] changes, None keeps them as they are
+#[derive(Debug, Clone, Default)]
+pub struct Reconfiguration {
+    pub fps: Option<u32>,
+    pub output_resolution: Option<Resolution>,
+    /// Some(None) captures the whole target again
+    pub crop_area: Option<Option<Area>>,
+    pub show_cursor: Option<bool>,
+    pub cursor_style: Option<CursorStyle>,
+}
+
+/// How [Capturer::reconfigure] applied the changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reconfigured {
+    /// While capture kept running, from the next frame on
+    Live,
+    /// By recreating the OS capture session, which was started again if it
+    /// was running
+    Restarted,
 }
 
 /// Screen capturer class
 pub struct Capturer {
     engine: engine::Engine,
-    rx: mpsc::Receiver<ChannelItem>,
+    rx: handoff::Receiver<Acquired>,
+    events: mpsc::Receiver<CapturerEvent>,
+    latency: Latency,
+    frame_deadline: Option<Duration>,
+    stats: Mutex<stats::StatsRecorder>,
+    fanout: fanout::Fanout,
+    session: Mutex<Session>,
+    // Events moved to the summary when capture stopped and not read yet
+    pending_events: Mutex<VecDeque<CapturerEvent>>,
+    markers: Mutex<Markers>,
+    constant_rate: Option<Mutex<constant_rate::ConstantRate>>,
+    frame_floor: Option<Mutex<frame_floor::FrameFloor>>,
+    activity: Option<Mutex<ActivitySummarizer>>,
+    external_trigger: Option<Mutex<external_trigger::ExternalTrigger>>,
+    summary: Option<CaptureSummary>,
+    clock: Option<ClockCorrelation>,
+    // Of the last frame received, see Capturer::last_acquisition_latency
+    acquisition_latency: Mutex<Option<Duration>>,
+}
+
+/// Frames captured but not delivered, by why, see [CaptureSummary]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DroppedFrames {
+    /// Skipped for a newer frame with [Latency::LowLatency], or overwritten
+    /// in [Options::frame_ring] before it was received
+    pub stale: u64,
+    /// Older than [Options::frame_deadline] when received
+    pub late: u64,
+    /// Held back by [Options::trigger] or [Options::warmup], or that couldn't
+    /// be converted to the output format. On macOS this includes the idle and
+    /// status updates the OS sends between frames.
+    pub unprocessed: u64,
+}
+
+/// What a capture session recorded, from [Capturer::take_summary]. With the
+/// `serde` feature it can be serialized, e.g. to attach to bug reports.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CaptureSummary {
+    /// From starting to stopping capture
+    pub duration: Duration,
+    /// Frames received from the OS
+    pub frames_captured: u64,
+    /// Frames returned by [Capturer::get_next_frame]
+    pub frames_delivered: u64,
+    pub frames_dropped: DroppedFrames,
+    /// Delivered frames per second, see [CaptureStats::achieved_fps]
+    pub average_fps: f64,
+    /// See [Capturer::get_output_frame_size]
+    pub output_size: [u32; 2],
+    /// The requested [Options::output_type], or the one picked from
+    /// [Options::output_types]
+    pub output_type: FrameType,
+    /// Every event of the session with when the capturer received it,
+    /// relative to the start
+    pub events: Vec<(Duration, CapturerEvent)>,
+    /// The label of every marker inserted during the session with its
+    /// timestamp, see [Capturer::insert_marker]
+    pub markers: Vec<(Duration, String)>,
+}
+
+// What happened since capture started, for the CaptureSummary
+#[derive(Debug, Default)]
+struct Session {
+    started: Option<Instant>,
+    captured: u64,
+    dropped: DroppedFrames,
+    events: Vec<(Duration, CapturerEvent)>,
+    markers: Vec<(Duration, String)>,
+}
+
+// Markers waiting for the items sent before them to be received, see
+// Capturer::insert_marker
+#[derive(Debug, Default)]
+struct Markers {
+    // Items received from the engine
+    received: u64,
+    // Display time of the last frame returned
+    last_display_time: u64,
+    // The engine's sent count when each marker was inserted
+    pending: VecDeque<(u64, MarkerFrame)>,
+}
+
+impl Markers {
+    fn is_due(&self) -> bool {
+        self.pending
+            .front()
+            .is_some_and(|(sent, _)| self.received >= *sent)
+    }
+
+    fn take_due(&mut self) -> Option<MarkerFrame> {
+        if !self.is_due() {
+            return None;
+        }
+        let (_, mut marker) = self.pending.pop_front()?;
+        marker.display_time = self.last_display_time;
+        Some(marker)
+    }
+}
+
+impl Session {
+    fn record_event(&mut self, event: CapturerEvent) {
+        let time = self.started.map(|started| started.elapsed());
+        self.events.push((time.unwrap_or_default(), event));
+    }
 }
 
 #[derive(Debug)]
 pub enum CapturerBuildError {
     NotSupported,
     PermissionNotGranted,
+    /// The target can't be captured, e.g. a closed window or a disconnected
+    /// display
+    InvalidTarget(String),
+    /// The OS refused to start capturing with these options, even after
+    /// retrying and falling back to simpler settings
+    CaptureFailed(String),
 }
 
 impl std::fmt::Display for CapturerBuildError {
@@ -91,12 +1097,89 @@ impl std::fmt::Display for CapturerBuildError {
             CapturerBuildError::PermissionNotGranted => {
                 write!(f, "Permission to capture the screen is not granted")
             }
+            CapturerBuildError::InvalidTarget(reason) => {
+                write!(f, "The capture target can't be captured: {reason}")
+            }
+            CapturerBuildError::CaptureFailed(reason) => {
+                write!(f, "Failed to start capturing: {reason}")
+            }
         }
     }
 }
 
 impl Error for CapturerBuildError {}
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum RegionError {
+    /// Two regions share this name
+    DuplicateName(String),
+    /// The region with this name has no pixels
+    EmptyArea(String),
+}
+
+impl std::fmt::Display for RegionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegionError::DuplicateName(name) => write!(f, "Region {name:?} is named twice"),
+            RegionError::EmptyArea(name) => write!(f, "Region {name:?} has an empty area"),
+        }
+    }
+}
+
+impl Error for RegionError {}
+
+// The triggers of Options::external_trigger, None if the options don't ask
+// for them or deliver frames that only hold changes
+fn get_external_trigger(options: &Options) -> Option<Mutex<external_trigger::ExternalTrigger>> {
+    if !options.external_trigger || options.delta.is_some() || options.scanline_patches.is_some() {
+        return None;
+    }
+    Some(Mutex::new(external_trigger::ExternalTrigger::new(
+        options.timestamp_base,
+    )))
+}
+
+// The ticks of Options::constant_frame_rate, None if the options don't ask
+// for them, deliver frames that only hold changes or on external triggers
+fn get_constant_rate(options: &Options) -> Option<Mutex<constant_rate::ConstantRate>> {
+    if options.delta.is_some() || options.scanline_patches.is_some() || options.external_trigger {
+        return None;
+    }
+    let fps = options.constant_frame_rate?;
+    Some(Mutex::new(constant_rate::ConstantRate::new(
+        fps,
+        options.timestamp_base,
+    )))
+}
+
+// The repeats of Options::min_frame_rate, None if the options don't ask for
+// them, deliver frames that only hold changes or at a rate of their own
+fn get_frame_floor(options: &Options) -> Option<Mutex<frame_floor::FrameFloor>> {
+    if options.delta.is_some()
+        || options.scanline_patches.is_some()
+        || options.constant_frame_rate.is_some()
+        || options.activity.is_some()
+        || options.external_trigger
+    {
+        return None;
+    }
+    let fps = options.min_frame_rate?;
+    Some(Mutex::new(frame_floor::FrameFloor::new(
+        fps,
+        options.timestamp_base,
+    )))
+}
+
+// The summaries of Options::activity, None if the options don't ask for them,
+// deliver frames that only hold changes or on external triggers
+fn get_activity(options: &Options) -> Option<Mutex<ActivitySummarizer>> {
+    if options.delta.is_some() || options.scanline_patches.is_some() || options.external_trigger {
+        return None;
+    }
+    let activity = options.activity?;
+    Some(Mutex::new(ActivitySummarizer::new(activity.interval)))
+}
+
 impl Capturer {
     /// Create a new capturer instance with the provided options
     #[deprecated(
@@ -104,10 +1187,30 @@ impl Capturer {
         note = "Use `build` instead of `new` to create a new capturer instance."
     )]
     pub fn new(options: Options) -> Capturer {
-        let (tx, rx) = mpsc::channel();
-        let engine = engine::Engine::new(&options, tx);
+        let (tx, rx) = handoff::channel(options.frame_ring);
+        let (events_tx, events) = mpsc::channel();
+        let engine = engine::Engine::new(&options, tx, events_tx)
+            .unwrap_or_else(|error| panic!("Failed to create capturer: {error}"));
 
-        Capturer { engine, rx }
+        Capturer {
+            engine,
+            rx,
+            events,
+            latency: options.latency,
+            frame_deadline: options.frame_deadline,
+            stats: Mutex::new(stats::StatsRecorder::new()),
+            fanout: fanout::Fanout::default(),
+            session: Mutex::new(Session::default()),
+            pending_events: Mutex::new(VecDeque::new()),
+            markers: Mutex::new(Markers::default()),
+            constant_rate: get_constant_rate(&options),
+            frame_floor: get_frame_floor(&options),
+            activity: get_activity(&options),
+            external_trigger: get_external_trigger(&options),
+            summary: None,
+            clock: None,
+            acquisition_latency: Mutex::new(None),
+        }
     }
 
     /// Build a new [Capturer] instance with the provided options
@@ -120,40 +1223,700 @@ impl Capturer {
             return Err(CapturerBuildError::PermissionNotGranted);
         }
 
-        let (tx, rx) = mpsc::channel();
-        let engine = engine::Engine::new(&options, tx);
+        let (tx, rx) = handoff::channel(options.frame_ring);
+        let (events_tx, events) = mpsc::channel();
+        let engine = engine::Engine::new(&options, tx, events_tx)?;
 
-        Ok(Capturer { engine, rx })
+        Ok(Capturer {
+            engine,
+            rx,
+            events,
+            latency: options.latency,
+            frame_deadline: options.frame_deadline,
+            stats: Mutex::new(stats::StatsRecorder::new()),
+            fanout: fanout::Fanout::default(),
+            session: Mutex::new(Session::default()),
+            pending_events: Mutex::new(VecDeque::new()),
+            markers: Mutex::new(Markers::default()),
+            constant_rate: get_constant_rate(&options),
+            frame_floor: get_frame_floor(&options),
+            activity: get_activity(&options),
+            external_trigger: get_external_trigger(&options),
+            summary: None,
+            clock: None,
+            acquisition_latency: Mutex::new(None),
+        })
     }
 
     // TODO
     // Prevent starting capture if already started
     /// Start capturing the frames
+    ///
+    /// # Panics
+    ///
+    /// If capture can't be started, see [Capturer::try_start_capture]
     pub fn start_capture(&mut self) {
-        self.engine.start();
+        if let Err(error) = self.try_start_capture() {
+            panic!("{error}");
+        }
+    }
+
+    /// Start capturing the frames, or return why the OS refused to. Transient
+    /// failures are retried and unsupported settings fall back to defaults
+    /// first, which on Windows means the system cursor and capture border, or
+    /// no HDR tone mapping.
+    pub fn try_start_capture(&mut self) -> Result<(), CapturerBuildError> {
+        let now = Instant::now();
+        let warm = self.engine.start()?;
+        *self.stats.lock().unwrap() = stats::StatsRecorder::started(now, warm);
+        if let Some(rate) = &self.constant_rate {
+            rate.lock().unwrap().reset();
+        }
+        if let Some(floor) = &self.frame_floor {
+            floor.lock().unwrap().reset();
+        }
+        if let Some(trigger) = &self.external_trigger {
+            trigger.lock().unwrap().reset();
+        }
+        if let Some(activity) = &self.activity {
+            activity.lock().unwrap().reset();
+        }
+        *self.session.lock().unwrap() = Session {
+            started: Some(now),
+            ..Session::default()
+        };
+        self.summary = None;
+        let timebase = self
+            .engine
+            .get_requested_options()
+            .timestamp_base
+            .unwrap_or(0);
+        self.clock = self
+            .engine
+            .get_frame_clock_time()
+            .map(|time| ClockCorrelation {
+                frame_time: rescale_timestamp(time, timebase),
+                wall_time: SystemTime::now(),
+                timebase,
+            });
+        Ok(())
+    }
+
+    /// Read the frames [Options::gpu_replay] kept of the last `duration` back
+    /// from the GPU, oldest first. Capture goes on meanwhile, and reading
+    /// many frames of a large capture can take a moment. Empty where the
+    /// replay isn't kept.
+    pub fn read_replay(&self, duration: Duration) -> Vec<Frame> {
+        self.engine.read_replay(duration)
     }
 
-    /// Stop the capturer
+    /// Get how many frames [Options::gpu_replay] keeps, the video memory
+    /// they take up and how far back they go. None where the replay isn't
+    /// kept.
+    pub fn replay_usage(&self) -> Option<GpuReplayUsage> {
+        self.engine.get_replay_usage()
+    }
+
+    /// The frames' clock and the wall clock as they were when capture last
+    /// started, to tell when frames were captured with
+    /// [Frame::wall_clock_time]. None before capture starts, and on Linux,
+    /// whose frames are timed with PipeWire's clock.
+    pub fn clock_correlation(&self) -> Option<ClockCorrelation> {
+        self.clock
+    }
+
+    /// Set up capture without delivering frames yet, so the first frame
+    /// arrives soon after [Capturer::start_capture] instead of after the OS
+    /// session is created. Frames captured meanwhile are discarded.
+    ///
+    /// Capture still runs, with any indicator the OS shows for it. It's
+    /// stopped if capture isn't started within `idle_timeout`: on Windows
+    /// right away, elsewhere when starting, which then starts from scratch.
+    /// Warming up again extends the timeout. See
+    /// [CaptureStats::time_to_first_frame] for what it saves.
+    pub fn warm_up(&mut self, idle_timeout: Duration) -> Result<(), CapturerBuildError> {
+        self.engine.warm_up(idle_timeout)
+    }
+
+    /// Stop a capture set up by [Capturer::warm_up] that wasn't started
+    pub fn cancel_warm_up(&mut self) {
+        self.engine.cancel_warm_up();
+    }
+
+    /// Stop the capturer, see [Capturer::take_summary]
     pub fn stop_capture(&mut self) {
         self.engine.stop();
+
+        let mut session = std::mem::take(self.session.get_mut().unwrap());
+        let Some(started) = session.started else {
+            return;
+        };
+        // Events that weren't read are still returned by try_next_event
+        while let Ok(event) = self.events.try_recv() {
+            session.record_event(event.clone());
+            self.pending_events.get_mut().unwrap().push_back(event);
+        }
+
+        let stats = self.stats.get_mut().unwrap().stats();
+        self.summary = Some(CaptureSummary {
+            duration: started.elapsed(),
+            frames_captured: session.captured,
+            frames_delivered: stats.frames,
+            frames_dropped: session.dropped,
+            average_fps: stats.achieved_fps,
+            output_size: self.engine.get_output_frame_size(),
+            output_type: self.engine.get_output_type(),
+            events: session.events,
+            markers: session.markers,
+        });
+    }
+
+    /// Get the summary of the session that [Capturer::stop_capture] ended.
+    /// It's only returned once, and None before capture stops.
+    pub fn take_summary(&mut self) -> Option<CaptureSummary> {
+        self.summary.take()
+    }
+
+    /// Change the frame rate, resolution, crop area or cursor without
+    /// building a new capturer. Live changes apply to what scap does itself:
+    ///
+    /// - [Options::cursor_style], as long as the system cursor stays shown
+    ///   or hidden, so not to or from [CursorStyle::Custom] on displays
+    /// - [Options::fps] and [Options::output_resolution] on Windows, which
+    ///   paces frames itself and doesn't scale them to the resolution
+    ///
+    /// Other changes recreate the OS capture session, restarting it if it
+    /// was running, which takes about as long as starting did. That's
+    /// [Options::crop_area] and [Options::show_cursor] everywhere, and the
+    /// frame rate and resolution on macOS and Linux. The session goes on
+    /// either way, and so do its statistics and frame processing like
+    /// [Options::delta] and [Options::trigger].
+    ///
+    /// [CapturerEvent::FormatChanged] is sent when the output frame size
+    /// changed. If the session can't be recreated or started again, capture
+    /// is stopped as by [Capturer::stop_capture].
+    pub fn reconfigure(
+        &mut self,
+        changes: Reconfiguration,
+    ) -> Result<Reconfigured, CapturerBuildError> {
+        let mut options = self.engine.get_requested_options().clone();
+        if let Some(fps) = changes.fps {
+            options.fps = fps;
+        }
+        if let Some(output_resolution) = changes.output_resolution {
+            options.output_resolution = output_resolution;
+        }
+        if let Some(crop_area) = changes.crop_area {
+            options.crop_area = crop_area;
+        }
+        if let Some(show_cursor) = changes.show_cursor {
+            options.show_cursor = show_cursor;
+        }
+        if let Some(cursor_style) = changes.cursor_style {
+            options.cursor_style = cursor_style;
+        }
+
+        let capturing = self.session.get_mut().unwrap().started.is_some();
+        match self.engine.reconfigure(&options, capturing) {
+            Ok(true) => Ok(Reconfigured::Live),
+            Ok(false) => Ok(Reconfigured::Restarted),
+            Err(error) => {
+                if capturing {
+                    self.stop_capture();
+                }
+                Err(error)
+            }
+        }
     }
 
     /// Get the next captured frame
     pub fn get_next_frame(&self) -> Result<Frame, mpsc::RecvError> {
+        if let Some(trigger) = &self.external_trigger {
+            return self.next_triggered_frame(&mut trigger.lock().unwrap());
+        }
+        if let Some(activity) = &self.activity {
+            return self.next_activity_frame(&mut activity.lock().unwrap());
+        }
+        if let Some(rate) = &self.constant_rate {
+            return self.next_tick_frame(&mut rate.lock().unwrap());
+        }
+        if let Some(floor) = &self.frame_floor {
+            return self.next_floor_frame(&mut floor.lock().unwrap());
+        }
+        self.next_frame(None).map_err(|_| mpsc::RecvError)
+    }
+
+    /// How much the frame [Capturer::get_next_frame] returned last changed
+    /// from the one before, with [Options::estimate_motion]. Repeats of
+    /// [Options::min_frame_rate] report the frame they repeat. None without
+    /// the option and for frames without pixels.
+    pub fn last_frame_motion(&self) -> Option<FrameMotion> {
+        self.engine.get_last_motion()
+    }
+
+    /// The checksum of the frame [Capturer::get_next_frame] returned last,
+    /// with [Options::checksum]. Repeats of [Options::min_frame_rate] and
+    /// [Options::external_trigger] have the checksum of the frame they
+    /// repeat. None without the option and for frames without a buffer of
+    /// pixels, see [frame_checksum](crate::frame::frame_checksum).
+    pub fn last_frame_checksum(&self) -> Option<FrameChecksum> {
+        self.engine.get_last_checksum()
+    }
+
+    /// How long after the compositor composed the frame
+    /// [Capturer::get_next_frame] returned last scap acquired it, for telling
+    /// latency of the OS apart from that of scap and the consumer. Timed with
+    /// the frame's SystemRelativeTime on Windows and its display time on
+    /// macOS, when it's handed over by the capture thread. With
+    /// [Options::constant_frame_rate], [Options::min_frame_rate],
+    /// [Options::activity] and [Options::external_trigger] it's that of the
+    /// newest frame captured by then. None on Linux, whose frames' presentation
+    /// time can't be read, and for frames scap synthesizes, like those of the
+    /// secure desktop.
+    pub fn last_acquisition_latency(&self) -> Option<Duration> {
+        *self.acquisition_latency.lock().unwrap()
+    }
+
+    /// Where [Options::follow_action] cut the frame [Capturer::get_next_frame]
+    /// returned last from, in pixels of the captured frame, e.g. to map
+    /// clicks of a remote viewer back to the screen. None without the option
+    /// and for frames that weren't cropped.
+    pub fn last_followed_area(&self) -> Option<Area> {
+        self.engine.get_followed_area()
+    }
+
+    /// Whether the frame [Capturer::get_next_frame] returned last repeats an
+    /// earlier one for [Options::min_frame_rate], rather than being captured
+    pub fn last_frame_synthesized(&self) -> bool {
+        self.frame_floor
+            .as_ref()
+            .is_some_and(|floor| floor.lock().unwrap().synthesized())
+    }
+
+    // Waits for the next frame, or repeats the last one when it's due for
+    // Options::min_frame_rate. Markers are returned as they're due.
+    fn next_floor_frame(
+        &self,
+        floor: &mut frame_floor::FrameFloor,
+    ) -> Result<Frame, mpsc::RecvError> {
+        loop {
+            match self.next_frame(floor.deadline()) {
+                Ok(marker @ Frame::Marker(_)) => return Ok(marker),
+                Ok(frame) => {
+                    floor.push(&frame, Instant::now());
+                    return Ok(frame);
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => return Err(mpsc::RecvError),
+            }
+            if let Some(frame) = floor.repeat(Instant::now()) {
+                return Ok(frame);
+            }
+        }
+    }
+
+    // Waits for a FrameTrigger to fire, taking the frames that arrive
+    // meanwhile. Markers are returned as they're due.
+    fn next_triggered_frame(
+        &self,
+        trigger: &mut external_trigger::ExternalTrigger,
+    ) -> Result<Frame, mpsc::RecvError> {
+        loop {
+            let poll = Instant::now() + external_trigger::TRIGGER_POLL;
+            match self.next_frame(Some(poll)) {
+                Ok(marker @ Frame::Marker(_)) => return Ok(marker),
+                Ok(frame) => trigger.push(frame),
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => return Err(mpsc::RecvError),
+            }
+            let clock = self.engine.get_frame_clock_time();
+            if let Some(frame) = trigger.take(clock, Instant::now()) {
+                return Ok(frame);
+            }
+        }
+    }
+
+    /// Get a handle that delivers a frame each time it fires, with
+    /// [Options::external_trigger]. None without the option.
+    pub fn frame_trigger(&self) -> Option<FrameTrigger> {
+        self.external_trigger
+            .as_ref()
+            .map(|trigger| trigger.lock().unwrap().handle())
+    }
+
+    /// How long before its trigger the frame [Capturer::get_next_frame]
+    /// returned last was captured, with [Options::external_trigger]. 0 for a
+    /// frame captured after it, when none had arrived yet. None without the
+    /// option and on Linux, whose frames' clock can't be read.
+    pub fn last_trigger_latency(&self) -> Option<Duration> {
+        self.external_trigger
+            .as_ref()
+            .and_then(|trigger| trigger.lock().unwrap().latency())
+    }
+
+    // Waits for the next tick of Options::constant_frame_rate, taking the
+    // frames that arrive meanwhile. Markers are returned as they're due.
+    fn next_tick_frame(
+        &self,
+        rate: &mut constant_rate::ConstantRate,
+    ) -> Result<Frame, mpsc::RecvError> {
+        loop {
+            match self.next_frame(rate.deadline()) {
+                Ok(marker @ Frame::Marker(_)) => return Ok(marker),
+                Ok(frame) => rate.push(frame, Instant::now()),
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => return Err(mpsc::RecvError),
+            }
+            if let Some(frame) = rate.tick(Instant::now()) {
+                return Ok(frame);
+            }
+        }
+    }
+
+    // Waits for the end of the period of Options::activity, summing up the
+    // frames that arrive meanwhile. Markers are returned as they're due.
+    fn next_activity_frame(
+        &self,
+        activity: &mut ActivitySummarizer,
+    ) -> Result<Frame, mpsc::RecvError> {
+        loop {
+            match self.next_frame(Some(activity.deadline(Instant::now()))) {
+                Ok(marker @ Frame::Marker(_)) => return Ok(marker),
+                Ok(frame) => activity.add(&frame),
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => return Err(mpsc::RecvError),
+            }
+            if let Some(summary) = activity.take_due(Instant::now()) {
+                return Ok(Frame::Activity(summary));
+            }
+        }
+    }
+
+    // Waits for the next frame until `deadline`, or for as long as it takes
+    pub(crate) fn next_frame(
+        &self,
+        deadline: Option<Instant>,
+    ) -> Result<Frame, mpsc::RecvTimeoutError> {
         loop {
-            let res = self.rx.recv()?;
+            if let Some(marker) = self.markers.lock().unwrap().take_due() {
+                let marker = Frame::Marker(marker);
+                self.fanout.publish(&marker);
+                return Ok(marker);
+            }
+            // Ends as when the target is closed
+            if self.engine.is_stopped() {
+                return Err(mpsc::RecvTimeoutError::Disconnected);
+            }
+
+            let mut res = match deadline {
+                Some(deadline) => self
+                    .rx
+                    .recv_timeout(deadline.saturating_duration_since(Instant::now()))?,
+                None => self
+                    .rx
+                    .recv()
+                    .map_err(|_| mpsc::RecvTimeoutError::Disconnected)?,
+            };
+            self.markers.lock().unwrap().received += 1;
+            self.session.lock().unwrap().captured += 1;
+            self.count_overwritten();
+
+            // Anything older than the newest captured frame is stale, but
+            // frames are never skipped past a marker
+            if self.latency == Latency::LowLatency {
+                while !self.markers.lock().unwrap().is_due() {
+                    let Ok(newer) = self.rx.try_recv() else {
+                        break;
+                    };
+                    res = newer;
+                    self.markers.lock().unwrap().received += 1;
+                    let mut session = self.session.lock().unwrap();
+                    session.captured += 1;
+                    session.dropped.stale += 1;
+                    self.stats.lock().unwrap().record_skipped();
+                }
+            }
+
+            // Frames that are already too old aren't worth processing
+            if let Some(deadline) = self.frame_deadline {
+                if self
+                    .engine
+                    .get_frame_age(&res.item)
+                    .is_some_and(|age| age > deadline)
+                {
+                    self.session.lock().unwrap().dropped.late += 1;
+                    self.stats.lock().unwrap().record_late();
+                    continue;
+                }
+            }
 
-            if let Some(frame) = self.engine.process_channel_item(res) {
+            if let Some(frame) = self.deliver(res) {
                 return Ok(frame);
             }
         }
     }
 
+    // Frames Options::frame_ring overwrote before they were received were
+    // captured and went stale like ones skipped for Latency::LowLatency
+    fn count_overwritten(&self) {
+        let overwritten = self.rx.take_overwritten();
+        if overwritten == 0 {
+            return;
+        }
+        self.markers.lock().unwrap().received += overwritten;
+        let mut session = self.session.lock().unwrap();
+        session.captured += overwritten;
+        session.dropped.stale += overwritten;
+        let mut stats = self.stats.lock().unwrap();
+        (0..overwritten).for_each(|_| stats.record_skipped());
+    }
+
+    // The frame of a received item as delivered, None if processing drops it
+    fn deliver(&self, acquired: Acquired) -> Option<Frame> {
+        *self.acquisition_latency.lock().unwrap() = acquired.latency;
+        let Some(frame) = self.engine.process_channel_item(acquired.item) else {
+            self.session.lock().unwrap().dropped.unprocessed += 1;
+            return None;
+        };
+        self.stats.lock().unwrap().record(Instant::now());
+        self.markers.lock().unwrap().last_display_time = frame.display_time();
+        self.fanout.publish(&frame);
+        Some(frame)
+    }
+
+    /// Return every frame that was captured but not returned yet, oldest
+    /// first, without waiting for new ones. Markers that are due come in
+    /// their place among them. Use it on shutdown to keep the frames at the
+    /// end of a recording: frames stay queued after [Capturer::stop_capture],
+    /// so stop first and drain then.
+    ///
+    /// Frames are processed as [Capturer::get_next_frame] does, but none are
+    /// dropped for [Latency::LowLatency] or [Options::frame_deadline], which
+    /// would skip all but the newest. Frames held back by
+    /// [Options::constant_frame_rate], [Options::min_frame_rate],
+    /// [Options::activity] or [Options::external_trigger] aren't repeated or
+    /// summarized, the queue is drained as captured. This is the live queue,
+    /// not [Options::gpu_replay], which keeps frames that were returned too.
+    pub fn drain_buffered(&self) -> Vec<Frame> {
+        let mut frames = Vec::new();
+        loop {
+            if let Some(marker) = self.markers.lock().unwrap().take_due() {
+                let marker = Frame::Marker(marker);
+                self.fanout.publish(&marker);
+                frames.push(marker);
+                continue;
+            }
+            if self.engine.is_stopped() {
+                return frames;
+            }
+
+            let Ok(item) = self.rx.try_recv() else {
+                return frames;
+            };
+            self.markers.lock().unwrap().received += 1;
+            self.session.lock().unwrap().captured += 1;
+            self.count_overwritten();
+            frames.extend(self.deliver(item));
+        }
+    }
+
+    /// Get a receiver of the frames whose buffers return to
+    /// [Options::buffer_pool] when they're dropped, see [FrameReceiver]
+    pub fn frame_receiver(&self) -> FrameReceiver<'_> {
+        FrameReceiver::new(self, self.engine.get_buffer_pool())
+    }
+
+    /// Wait for the next frame and pass borrowed views of `areas` to `f`.
+    ///
+    /// The views are computed lazily and borrow the frame, which is dropped
+    /// once `f` returns, so they cannot escape the callback. Use this instead
+    /// of cropping copies when only small parts of each frame are inspected.
+    pub fn with_next_frame_regions<R>(
+        &self,
+        areas: &[Area],
+        f: impl FnOnce(&Frame, Regions<'_>) -> R,
+    ) -> Result<R, mpsc::RecvError> {
+        let frame = self.get_next_frame()?;
+        Ok(f(&frame, frame.regions(areas)))
+    }
+
+    /// Deliver only the named `regions` of each frame, as one
+    /// [Frame::Regions] holding a frame per region, e.g. to watch a few
+    /// panels of a dashboard. Areas are in pixels of the frames otherwise
+    /// returned, after [Options::output_resolution] and [Options::crop_area],
+    /// and are clamped to them. Regions can be changed while capturing and
+    /// take effect from the next frame; an empty list delivers whole frames
+    /// again.
+    ///
+    /// With `keep_source` the whole frame is kept alongside the regions.
+    /// Regions replace [Options::delta] and [Options::scanline_patches]
+    /// encoding while set.
+    pub fn set_regions(
+        &self,
+        regions: Vec<(String, Area)>,
+        keep_source: bool,
+    ) -> Result<(), RegionError> {
+        for (i, (name, area)) in regions.iter().enumerate() {
+            if regions[..i].iter().any(|(other, _)| other == name) {
+                return Err(RegionError::DuplicateName(name.clone()));
+            }
+            let size = &area.size;
+            if !(size.width.is_finite() && size.height.is_finite())
+                || size.width < 1.0
+                || size.height < 1.0
+            {
+                return Err(RegionError::EmptyArea(name.clone()));
+            }
+        }
+        self.engine.set_regions(regions, keep_source);
+        Ok(())
+    }
+
+    /// Get the frames of this capture on another channel too, e.g. to feed a
+    /// preview, a recorder and a network stream from one capture. Subscribers
+    /// can be added and dropped at any time and get the frames returned by
+    /// [Capturer::get_next_frame] from then on, so some thread has to keep
+    /// calling it even if it only discards the frames.
+    ///
+    /// Each subscriber queues up to `capacity` frames and drops frames by
+    /// `overflow` once it falls behind, without slowing down the capture or
+    /// the other subscribers.
+    pub fn subscribe(&self, capacity: usize, overflow: OverflowPolicy) -> FrameSubscriber {
+        self.fanout.subscribe(capacity, overflow)
+    }
+
+    /// Get the receiver of [Options::thumbnails], once. Thumbnails are made as
+    /// [Capturer::get_next_frame] returns frames and carry their sequence
+    /// among them. Dropping the receiver stops them without affecting the frames.
+    pub fn thumbnails(&self) -> Option<mpsc::Receiver<Thumbnail>> {
+        self.engine.take_thumbnail_receiver()
+    }
+
+    /// Get how thumbnails have been delivered since capture started, or None
+    /// without [Options::thumbnails]
+    pub fn thumbnail_stats(&self) -> Option<CaptureStats> {
+        self.engine.get_thumbnail_stats()
+    }
+
+    /// Get the size of the thumbnails, for laying out a preview before the
+    /// first one arrives, or None without [Options::thumbnails]. It follows
+    /// [Capturer::get_output_frame_size], thumbnails of frames that change
+    /// size change with them.
+    pub fn thumbnail_size(&mut self) -> Option<[u32; 2]> {
+        self.engine.get_thumbnail_size()
+    }
+
+    /// Make the next frame a keyframe with the whole frame, for receivers of
+    /// [Options::delta] or [Options::scanline_patches] frames that missed
+    /// some. Does nothing without either.
+    pub fn force_keyframe(&self) {
+        self.engine.force_keyframe();
+    }
+
+    /// Mark this moment in the capture, e.g. when the user presses a hotkey.
+    /// The marker is returned by [Capturer::get_next_frame] as a
+    /// [Frame::Marker], after every frame the OS delivered before this call
+    /// and before every frame after it, and is listed in the
+    /// [CaptureSummary].
+    ///
+    /// Returns the marker's timestamp, the time since capture started.
+    pub fn insert_marker(&self, label: &str) -> Duration {
+        let mut session = self.session.lock().unwrap();
+        let timestamp = session
+            .started
+            .map(|started| started.elapsed())
+            .unwrap_or_default();
+        session.markers.push((timestamp, label.to_string()));
+
+        let marker = MarkerFrame {
+            display_time: 0,
+            timestamp,
+            label: label.to_string(),
+        };
+        let sent = self.engine.get_items_sent();
+        self.markers
+            .lock()
+            .unwrap()
+            .pending
+            .push_back((sent, marker));
+        timestamp
+    }
+
+    /// Get the next pending event without waiting, if there is one
+    pub fn try_next_event(&self) -> Option<CapturerEvent> {
+        if let Some(event) = self.pending_events.lock().unwrap().pop_front() {
+            return Some(event);
+        }
+
+        let event = self.events.try_recv().ok()?;
+        self.session.lock().unwrap().record_event(event.clone());
+        Some(event)
+    }
+
+    /// Get how the requested frame rate is enforced on this platform
+    pub fn get_frame_rate_cap(&self) -> FrameRateCap {
+        self.engine.get_frame_rate_cap()
+    }
+
+    /// Get the highest rate frames can arrive at, the refresh rate of the
+    /// target's display, or None if it's unknown. `fps` above it, or 0, means
+    /// one frame per refresh. Compare it with [Capturer::stats] to tell
+    /// whether the whole pipeline keeps up.
+    pub fn get_max_frame_rate(&self) -> Option<u32> {
+        self.engine.get_max_frame_rate()
+    }
+
+    /// Get how often and how evenly frames have been returned since capture
+    /// started, measured when [Capturer::get_next_frame] returns them
+    pub fn stats(&self) -> CaptureStats {
+        CaptureStats {
+            compositor_drops: self.engine.get_compositor_drops(),
+            repeated_frames: self
+                .constant_rate
+                .as_ref()
+                .map_or(0, |rate| rate.lock().unwrap().repeats())
+                + self
+                    .frame_floor
+                    .as_ref()
+                    .map_or(0, |floor| floor.lock().unwrap().repeats()),
+            ..self.stats.lock().unwrap().stats()
+        }
+    }
+
+    /// Get how the windows of the current process are kept out of the frames
+    pub fn get_process_exclusion(&self) -> ProcessExclusion {
+        self.engine.get_process_exclusion()
+    }
+
     /// Get the dimensions the frames will be captured in
     pub fn get_output_frame_size(&mut self) -> [u32; 2] {
         self.engine.get_output_frame_size()
     }
 
+    /// Get the part of the target that's captured, after the crop area was
+    /// rounded and clamped to it, e.g. to update a selection overlay. It's in
+    /// physical pixels on Windows and points on macOS, and None on Linux or
+    /// while it follows a window.
+    pub fn effective_source_rect(&self) -> Option<Area> {
+        self.engine.get_effective_source_rect()
+    }
+
+    /// Get the title the target has now, e.g. to follow a browser window
+    /// switching tabs in an overlay or a file name, see
+    /// [get_target_title](crate::targets::get_target_title). None once a
+    /// window target is closed, and on Linux.
+    pub fn current_target_title(&self) -> Option<String> {
+        self.engine.get_target_title()
+    }
+
+    /// Get what capture runs with: the frames' format and size, their
+    /// rate, how the cursor and border are drawn, and which requested
+    /// options were downgraded and why. Fallbacks the OS only reports when
+    /// capture starts are included once it started.
+    pub fn negotiated(&self) -> NegotiatedConfig {
+        self.engine.get_negotiated_config()
+    }
+
     pub fn raw(&self) -> RawCapturer {
         RawCapturer { capturer: self }
     }
@@ -162,3 +1925,491 @@ impl Capturer {
 pub struct RawCapturer<'a> {
     capturer: &'a Capturer,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(target_os = "linux"))]
+    #[test]
+    fn test_shift_area() {
+        let display = Size {
+            width: 1920.0,
+            height: 1080.0,
+        };
+        // A 640x360 crop centered on a point in each corner
+        let crop = |x: f64, y: f64| Area {
+            origin: Point {
+                x: x - 320.0,
+                y: y - 180.0,
+            },
+            size: Size {
+                width: 640.0,
+                height: 360.0,
+            },
+        };
+        let corners = [(0.0, 0.0), (1920.0, 0.0), (0.0, 1080.0), (1920.0, 1080.0)];
+        let shifted: Vec<_> = corners
+            .iter()
+            .map(|&(x, y)| shift_area(&crop(x, y), &display))
+            .map(|area| {
+                (
+                    area.origin.x,
+                    area.origin.y,
+                    area.size.width,
+                    area.size.height,
+                )
+            })
+            .collect();
+        assert_eq!(
+            shifted,
+            [
+                (0.0, 0.0, 640.0, 360.0),
+                (1280.0, 0.0, 640.0, 360.0),
+                (0.0, 720.0, 640.0, 360.0),
+                (1280.0, 720.0, 640.0, 360.0)
+            ]
+        );
+        assert_eq!(
+            shift_area(&crop(960.0, 540.0), &display),
+            crop(960.0, 540.0)
+        );
+
+        let wide = Area {
+            size: Size {
+                width: 2000.0,
+                height: 100.0,
+            },
+            ..crop(0.0, 0.0)
+        };
+        let shifted = shift_area(&wide, &display);
+        assert_eq!((shifted.origin.x, shifted.size.width), (0.0, 1920.0));
+    }
+
+    #[test]
+    fn test_panicking_subregion_callback() {
+        let size = Size {
+            width: 100.0,
+            height: 100.0,
+        };
+        let callback: SubregionCallback = Arc::new(|size| {
+            if size.width > 50.0 {
+                panic!("window too wide: {}", size.width);
+            }
+            None
+        });
+        let subregion = WindowSubregion::Dynamic(callback);
+
+        assert_eq!(
+            subregion.resolve(&size).unwrap_err(),
+            "window too wide: 100"
+        );
+        // The callback keeps working after a panic
+        let small = Size {
+            width: 10.0,
+            height: 10.0,
+        };
+        assert_eq!(subregion.resolve(&small), Ok(None));
+
+        let subregion = WindowSubregion::Dynamic(Arc::new(|_| panic!("static message")));
+        assert_eq!(subregion.resolve(&size).unwrap_err(), "static message");
+    }
+
+    // Linux engines get no PipeWire session in tests, frames are sent
+    // through the engine as the capture thread sends them
+    #[cfg(target_os = "linux")]
+    fn send_frame(capturer: &Capturer, display_time: u64) {
+        send_acquired(capturer, display_time, None);
+    }
+
+    #[cfg(target_os = "linux")]
+    fn send_acquired(capturer: &Capturer, display_time: u64, latency: Option<Duration>) {
+        let frame = Frame::BGRA(crate::frame::BGRAFrame {
+            display_time,
+            width: 1,
+            height: 1,
+            data: vec![0; 4],
+            origin: crate::frame::RowOrder::TopDown,
+            color_space: crate::frame::ColorSpace::SRGB,
+        });
+        capturer.engine.get_sender().send(frame, latency).unwrap();
+    }
+
+    // Options that deliver every frame that's sent
+    #[cfg(target_os = "linux")]
+    fn every_frame() -> Options {
+        Options {
+            warmup: WarmupOptions {
+                frames: 0,
+                ..WarmupOptions::default()
+            },
+            ..Options::default()
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_markers_after_ring_overwrites() {
+        let mut capturer = Capturer::build(Options {
+            frame_ring: Some(2),
+            ..every_frame()
+        })
+        .unwrap();
+        capturer.start_capture();
+
+        // The marker comes after the frames overwritten in the ring, not
+        // after as many frames as were sent before it
+        (1..=3).for_each(|time| send_frame(&capturer, time));
+        capturer.insert_marker("cut");
+        (4..=5).for_each(|time| send_frame(&capturer, time));
+        let frames = capturer.drain_buffered();
+        assert!(matches!(
+            frames[..],
+            [Frame::BGRA(ref frame), Frame::Marker(ref marker)]
+                if frame.display_time == 5 && marker.label == "cut"
+        ));
+
+        capturer.stop_capture();
+        let summary = capturer.take_summary().unwrap();
+        assert_eq!((summary.frames_captured, summary.frames_delivered), (5, 1));
+        assert_eq!(summary.frames_dropped.stale, 4);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_drain_buffered() {
+        // Not even Latency::LowLatency skips frames that are drained
+        let mut capturer = Capturer::build(Options {
+            latency: Latency::LowLatency,
+            ..every_frame()
+        })
+        .unwrap();
+        capturer.start_capture();
+        (1..=2).for_each(|time| send_frame(&capturer, time));
+        capturer.insert_marker("stop");
+        send_frame(&capturer, 3);
+        capturer.stop_capture();
+
+        let drained: Vec<_> = capturer
+            .drain_buffered()
+            .iter()
+            .map(|frame| match frame {
+                Frame::Marker(marker) => marker.label.clone(),
+                frame => frame.display_time().to_string(),
+            })
+            .collect();
+        assert_eq!(drained, ["1", "2", "stop", "3"]);
+        assert!(capturer.drain_buffered().is_empty());
+        assert_eq!(capturer.stats().frames, 3);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_warm_up() {
+        let mut capturer = Capturer::build(every_frame()).unwrap();
+
+        // Frames of a warmed up session are dropped until it's started
+        capturer.warm_up(Duration::from_secs(60)).unwrap();
+        send_frame(&capturer, 1);
+        capturer.start_capture();
+        assert!(capturer.stats().warm_start);
+        assert_eq!(capturer.stats().time_to_first_frame, None);
+        send_frame(&capturer, 2);
+        assert_eq!(capturer.get_next_frame().unwrap().display_time(), 2);
+        let warm = capturer.stats().time_to_first_frame.unwrap();
+        capturer.stop_capture();
+
+        // Past the idle timeout the session is torn down and started cold
+        capturer.warm_up(Duration::ZERO).unwrap();
+        capturer.start_capture();
+        assert!(!capturer.stats().warm_start);
+        capturer.stop_capture();
+
+        // Cancelled warm ups start cold, here with a first frame that takes
+        // as long as a new session's would
+        capturer.warm_up(Duration::from_secs(60)).unwrap();
+        capturer.cancel_warm_up();
+        capturer.start_capture();
+        assert!(!capturer.stats().warm_start);
+        std::thread::sleep(Duration::from_millis(20));
+        send_frame(&capturer, 3);
+        capturer.get_next_frame().unwrap();
+        let cold = capturer.stats().time_to_first_frame.unwrap();
+        assert!(cold >= Duration::from_millis(20) && warm < cold);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_panicking_transform_stops_capture() {
+        let panicking: Arc<dyn FrameTransform> = Arc::new(|frame: Frame| {
+            if frame.display_time() == 2 {
+                panic!("transform failed");
+            }
+            Some(frame)
+        });
+        let mut capturer = Capturer::build(Options {
+            transforms: vec![panicking],
+            ..every_frame()
+        })
+        .unwrap();
+        capturer.start_capture();
+
+        // The stream ends as for a closed target, also for frames after it
+        (1..=3).for_each(|time| send_frame(&capturer, time));
+        assert_eq!(capturer.get_next_frame().unwrap().display_time(), 1);
+        assert!(capturer.get_next_frame().is_err());
+        assert!(capturer.get_next_frame().is_err());
+        assert!(capturer.drain_buffered().is_empty());
+        assert!(std::iter::from_fn(|| capturer.try_next_event())
+            .any(|event| matches!(event, CapturerEvent::CallbackPanicked { .. })));
+
+        // Nothing is sent anymore, only 3 is left from before
+        send_frame(&capturer, 4);
+        assert_eq!(
+            std::iter::from_fn(|| capturer.rx.try_recv().ok()).count(),
+            1
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_last_acquisition_latency() {
+        let mut capturer = Capturer::build(every_frame()).unwrap();
+        capturer.start_capture();
+        assert_eq!(capturer.last_acquisition_latency(), None);
+
+        send_acquired(&capturer, 1, Some(Duration::from_millis(3)));
+        assert_eq!(capturer.last_acquisition_latency(), None);
+        capturer.get_next_frame().unwrap();
+        assert_eq!(
+            capturer.last_acquisition_latency(),
+            Some(Duration::from_millis(3))
+        );
+        send_acquired(&capturer, 2, Some(Duration::from_millis(5)));
+        capturer.get_next_frame().unwrap();
+        assert_eq!(
+            capturer.last_acquisition_latency(),
+            Some(Duration::from_millis(5))
+        );
+
+        // Frames whose latency isn't known don't keep the last one
+        send_frame(&capturer, 3);
+        capturer.get_next_frame().unwrap();
+        assert_eq!(capturer.last_acquisition_latency(), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_summary_is_taken_once() {
+        let mut capturer = Capturer::build(every_frame()).unwrap();
+        assert!(capturer.take_summary().is_none());
+        capturer.start_capture();
+        send_frame(&capturer, 1);
+        capturer.get_next_frame().unwrap();
+        assert!(capturer.take_summary().is_none());
+
+        capturer.stop_capture();
+        let summary = capturer.take_summary().unwrap();
+        assert_eq!(summary.frames_captured, 1);
+        assert!(capturer.take_summary().is_none());
+        // Stopping again doesn't end another session
+        capturer.stop_capture();
+        assert!(capturer.take_summary().is_none());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_summary_across_sessions() {
+        use std::sync::atomic::{AtomicU32, Ordering};
+
+        // Outside the schedule for the second and third frame
+        let checks = AtomicU32::new(0);
+        let mut capturer = Capturer::build(Options {
+            schedule: Some(CaptureSchedule::Callback(Arc::new(move |_| {
+                !(2..=3).contains(&(checks.fetch_add(1, Ordering::Relaxed) + 1))
+            }))),
+            ..every_frame()
+        })
+        .unwrap();
+        capturer.start_capture();
+
+        // Paused by the schedule, which counts as dropped
+        (1..=4).for_each(|time| send_frame(&capturer, time));
+        for time in [1, 4] {
+            assert_eq!(capturer.get_next_frame().unwrap().display_time(), time);
+        }
+
+        capturer.stop_capture();
+        let summary = capturer.take_summary().unwrap();
+        assert_eq!((summary.frames_captured, summary.frames_delivered), (4, 2));
+        assert_eq!(summary.frames_dropped.unprocessed, 2);
+        let schedule_events: Vec<_> = summary
+            .events
+            .iter()
+            .filter(|(_, event)| {
+                matches!(
+                    event,
+                    CapturerEvent::ScheduleSuspended { .. } | CapturerEvent::ScheduleResumed { .. }
+                )
+            })
+            .collect();
+        assert!(matches!(
+            schedule_events[..],
+            [
+                (_, CapturerEvent::ScheduleSuspended { time: 2 }),
+                (_, CapturerEvent::ScheduleResumed { time: 4 })
+            ]
+        ));
+
+        // Starting again starts a new summary
+        capturer.start_capture();
+        send_frame(&capturer, 5);
+        capturer.get_next_frame().unwrap();
+        capturer.stop_capture();
+        let summary = capturer.take_summary().unwrap();
+        assert_eq!((summary.frames_captured, summary.frames_delivered), (1, 1));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_summary_round_trip() {
+        let summary = CaptureSummary {
+            duration: Duration::from_secs(134),
+            frames_captured: 4026,
+            frames_delivered: 4023,
+            frames_dropped: DroppedFrames {
+                stale: 2,
+                ..DroppedFrames::default()
+            },
+            average_fps: 30.0,
+            output_size: [1920, 1080],
+            output_type: FrameType::BGRAFrame,
+            events: vec![
+                (
+                    Duration::ZERO,
+                    CapturerEvent::ConfigDowngraded(vec![Downgrade {
+                        option: "cursor_style".to_string(),
+                        reason: "not drawn".to_string(),
+                    }]),
+                ),
+                (
+                    Duration::from_secs(60),
+                    CapturerEvent::WindowStateChanged {
+                        state: WindowState::Minimized,
+                        time: 60,
+                    },
+                ),
+            ],
+            markers: vec![(Duration::from_secs(90), "cut".to_string())],
+        };
+
+        let json = serde_json::to_string(&summary).unwrap();
+        let restored: CaptureSummary = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.frames_dropped, summary.frames_dropped);
+        assert!(matches!(
+            restored.events[1].1,
+            CapturerEvent::WindowStateChanged {
+                state: WindowState::Minimized,
+                time: 60
+            }
+        ));
+        assert_eq!(serde_json::to_string(&restored).unwrap(), json);
+    }
+
+    #[test]
+    fn test_suggest_for_bitrate() {
+        let suggest = Options::suggest_for_bitrate;
+        let source = [2560, 1440];
+
+        // 1440p at 60 fps takes about 22 Mbps
+        assert_eq!(suggest(25_000_000, source), (Resolution::Captured, 60));
+        assert_eq!(suggest(15_000_000, source), (Resolution::Captured, 30));
+        assert_eq!(suggest(8_000_000, source), (Resolution::_1080p, 30));
+        assert_eq!(suggest(5_000_000, source), (Resolution::_720p, 30));
+        assert_eq!(suggest(2_000_000, source), (Resolution::_480p, 60));
+        assert_eq!(suggest(500_000, source), (Resolution::_480p, 21));
+        assert_eq!(suggest(0, source), (Resolution::_480p, 1));
+
+        // Sources smaller than 480p are never upscaled
+        assert_eq!(suggest(0, [320, 240]), (Resolution::Captured, 1));
+    }
+
+    #[test]
+    fn test_markers_wait_for_earlier_frames() {
+        let marker = |label: &str| MarkerFrame {
+            display_time: 0,
+            timestamp: Duration::ZERO,
+            label: label.to_string(),
+        };
+        let mut markers = Markers::default();
+        markers.pending.push_back((2, marker("first")));
+        markers.pending.push_back((2, marker("second")));
+
+        markers.received = 1;
+        assert!(markers.take_due().is_none());
+
+        markers.received = 2;
+        markers.last_display_time = 20;
+        let first = markers.take_due().unwrap();
+        assert_eq!((first.label.as_str(), first.display_time), ("first", 20));
+        assert_eq!(markers.take_due().unwrap().label, "second");
+        assert!(markers.take_due().is_none());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_options_round_trip() {
+        let options = Options {
+            fps: 30,
+            crop_area: Some(Area {
+                origin: Point { x: 10.0, y: 20.0 },
+                size: Size {
+                    width: 640.0,
+                    height: 480.0,
+                },
+            }),
+            output_resolution: Resolution::_1080p,
+            window_subregion: WindowSubregion::Static(Area::default()),
+            trigger: Some(TriggerOptions {
+                condition: TriggerCondition::Matches(vec![1, 2, 3]),
+                ..TriggerOptions::default()
+            }),
+            excluded_targets: Some(Vec::new()),
+            ..Options::default()
+        };
+
+        let json = serde_json::to_string(&options).unwrap();
+        let restored: Options = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.fps, 30);
+        assert_eq!(restored.crop_area, options.crop_area);
+        assert_eq!(restored.output_resolution, Resolution::_1080p);
+        assert_eq!(restored.trigger, options.trigger);
+        assert!(matches!(
+            restored.window_subregion,
+            WindowSubregion::Static(_)
+        ));
+        // Targets are never serialized
+        assert!(restored.excluded_targets.is_none());
+
+        // Missing fields keep their defaults
+        let restored: Options = serde_json::from_str(r#"{"fps":24}"#).unwrap();
+        assert_eq!((restored.fps, restored.latency), (24, Latency::default()));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_resolution_tags() {
+        let tags = [
+            (Resolution::_480p, "\"480p\""),
+            (Resolution::_2160p, "\"2160p\""),
+            (Resolution::Captured, "\"captured\""),
+        ];
+        for (resolution, tag) in tags {
+            assert_eq!(serde_json::to_string(&resolution).unwrap(), tag);
+            assert_eq!(serde_json::from_str::<Resolution>(tag).unwrap(), resolution);
+        }
+
+        let dynamic = WindowSubregion::Dynamic(Arc::new(|_| None));
+        assert!(serde_json::to_string(&dynamic).is_err());
+    }
+}
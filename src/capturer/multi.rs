@@ -0,0 +1,346 @@
+use std::{
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use super::{Capturer, CapturerBuildError, Options};
+use crate::{
+    frame::Frame,
+    targets::{Display, Target},
+};
+
+// How long MultiCapturer::get_next_frame waits on one display before it
+// checks the others again
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// A display for [MultiCapturer] to capture, at its own [Options::fps]
+#[derive(Debug, Clone)]
+pub struct DisplayCapture {
+    pub display: Display,
+    /// 0 for a frame per refresh of the display
+    pub fps: u32,
+}
+
+/// A frame of a [MultiCapturer] and the display it's of
+#[derive(Debug, Clone)]
+pub struct DisplayFrame {
+    /// [Display::id] of the display
+    pub display_id: u32,
+    /// The time between frames the display is captured at: one over its fps,
+    /// capped at its refresh rate, or its refresh period for `fps: 0`. It's
+    /// nominal, static screens send frames less often. None if neither rate
+    /// is known.
+    pub frame_interval: Option<Duration>,
+    pub frame: Frame,
+}
+
+/// Captures several displays at once, each at a frame rate of its own, e.g.
+/// a 144Hz display next to a 60Hz one, and returns their frames as one
+/// stream tagged with the display they're of. Frames of displays with
+/// different rates don't come in pairs, line them up by their display time
+/// instead, see the README.
+///
+/// Each display gets a [Capturer] of its own. Features that repeat, hold
+/// back or summarize frames, [Options::constant_frame_rate],
+/// [Options::min_frame_rate], [Options::activity] and
+/// [Options::external_trigger], are turned off.
+pub struct MultiCapturer {
+    sources: Vec<Source>,
+    // The display get_next_frame waits on next while none has a frame
+    next_wait: usize,
+}
+
+struct Source {
+    display_id: u32,
+    frame_interval: Option<Duration>,
+    capturer: Capturer,
+    // Received but not returned yet, since another display's was older
+    pending: Option<Frame>,
+    disconnected: bool,
+}
+
+impl Source {
+    // Receives a frame if there's none pending, waiting until `deadline`
+    fn poll(&mut self, deadline: Instant) {
+        if self.pending.is_some() || self.disconnected {
+            return;
+        }
+        match self.capturer.next_frame(Some(deadline)) {
+            Ok(frame) => self.pending = Some(frame),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => self.disconnected = true,
+        }
+    }
+
+    fn tag(&self, frame: Frame) -> DisplayFrame {
+        DisplayFrame {
+            display_id: self.display_id,
+            frame_interval: self.frame_interval,
+            frame,
+        }
+    }
+}
+
+// The nominal time between frames at `fps`, which displays don't deliver
+// faster than they refresh. `fps: 0` asks for every refresh.
+fn get_frame_interval(fps: u32, refresh_rate: Option<u32>) -> Option<Duration> {
+    let rate = match (fps, refresh_rate) {
+        (0, refresh_rate) => refresh_rate?,
+        (fps, Some(refresh_rate)) => fps.min(refresh_rate),
+        (fps, None) => fps,
+    };
+    (rate > 0).then(|| Duration::from_secs(1) / rate)
+}
+
+impl MultiCapturer {
+    /// Build a capturer for each of `displays` with `options`, their
+    /// [Options::target] and [Options::fps] set to the display's
+    pub fn build(
+        displays: Vec<DisplayCapture>,
+        options: Options,
+    ) -> Result<MultiCapturer, CapturerBuildError> {
+        if displays.is_empty() {
+            return Err(CapturerBuildError::InvalidTarget(
+                "no displays to capture".to_string(),
+            ));
+        }
+
+        let sources = displays
+            .into_iter()
+            .map(|DisplayCapture { display, fps }| {
+                let display_id = display.id;
+                let capturer = Capturer::build(Options {
+                    fps,
+                    target: Some(Target::Display(display)),
+                    constant_frame_rate: None,
+                    min_frame_rate: None,
+                    activity: None,
+                    external_trigger: false,
+                    ..options.clone()
+                })?;
+                Ok(Source {
+                    display_id,
+                    frame_interval: get_frame_interval(fps, capturer.get_max_frame_rate()),
+                    capturer,
+                    pending: None,
+                    disconnected: false,
+                })
+            })
+            .collect::<Result<_, CapturerBuildError>>()?;
+
+        Ok(MultiCapturer {
+            sources,
+            next_wait: 0,
+        })
+    }
+
+    /// Start capturing every display
+    ///
+    /// # Panics
+    ///
+    /// If capture can't be started, see [MultiCapturer::try_start_capture]
+    pub fn start_capture(&mut self) {
+        if let Err(error) = self.try_start_capture() {
+            panic!("{error}");
+        }
+    }
+
+    /// Start capturing every display, see [Capturer::try_start_capture]. If
+    /// one fails the displays started before it are stopped again.
+    pub fn try_start_capture(&mut self) -> Result<(), CapturerBuildError> {
+        for started in 0..self.sources.len() {
+            if let Err(error) = self.sources[started].capturer.try_start_capture() {
+                self.sources[..started]
+                    .iter_mut()
+                    .for_each(|source| source.capturer.stop_capture());
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    /// Stop capturing every display. Frames that were captured are still
+    /// returned, see [MultiCapturer::drain_buffered].
+    pub fn stop_capture(&mut self) {
+        self.sources
+            .iter_mut()
+            .for_each(|source| source.capturer.stop_capture());
+    }
+
+    /// Get the next frame of any display. Of the frames that were captured,
+    /// the one with the oldest display time comes first. Fails once capture
+    /// of every display has ended.
+    pub fn get_next_frame(&mut self) -> Result<DisplayFrame, mpsc::RecvError> {
+        loop {
+            let now = Instant::now();
+            self.sources.iter_mut().for_each(|source| source.poll(now));
+
+            let oldest = self
+                .sources
+                .iter()
+                .enumerate()
+                .filter_map(|(index, source)| Some((index, source.pending.as_ref()?)))
+                .min_by_key(|(_, frame)| frame.display_time())
+                .map(|(index, _)| index);
+            if let Some(index) = oldest {
+                let source = &mut self.sources[index];
+                let frame = source.pending.take().unwrap();
+                return Ok(source.tag(frame));
+            }
+
+            if self.sources.iter().all(|source| source.disconnected) {
+                return Err(mpsc::RecvError);
+            }
+
+            // None has a frame, wait on each in turn for the next one
+            let index = self.next_wait % self.sources.len();
+            self.next_wait = index + 1;
+            self.sources[index].poll(Instant::now() + POLL_INTERVAL);
+        }
+    }
+
+    /// Return every frame of every display that was captured but not
+    /// returned yet, oldest display time first, without waiting for new
+    /// ones, see [Capturer::drain_buffered]
+    pub fn drain_buffered(&mut self) -> Vec<DisplayFrame> {
+        let mut frames: Vec<DisplayFrame> = self
+            .sources
+            .iter_mut()
+            .flat_map(|source| {
+                let pending = source.pending.take();
+                let drained = source.capturer.drain_buffered();
+                let frames = pending.into_iter().chain(drained);
+                frames.map(|frame| source.tag(frame)).collect::<Vec<_>>()
+            })
+            .collect();
+        frames.sort_by_key(|tagged| tagged.frame.display_time());
+        frames
+    }
+
+    /// The capturer of the display with this [Display::id], for its stats,
+    /// events and summary
+    pub fn capturer(&mut self, display_id: u32) -> Option<&mut Capturer> {
+        self.sources
+            .iter_mut()
+            .find(|source| source.display_id == display_id)
+            .map(|source| &mut source.capturer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_interval() {
+        let at = |fps| Some(Duration::from_secs(1) / fps);
+        assert_eq!(get_frame_interval(60, Some(144)), at(60));
+        assert_eq!(get_frame_interval(240, Some(144)), at(144));
+        assert_eq!(get_frame_interval(0, Some(144)), at(144));
+        assert_eq!(get_frame_interval(30, None), at(30));
+        assert_eq!(get_frame_interval(0, None), None);
+        assert_eq!(get_frame_interval(0, Some(0)), None);
+    }
+
+    #[test]
+    fn test_no_displays() {
+        let result = MultiCapturer::build(Vec::new(), Options::default());
+        assert!(matches!(result, Err(CapturerBuildError::InvalidTarget(_))));
+    }
+
+    #[cfg(target_os = "linux")]
+    mod linux {
+        use super::*;
+        use crate::{
+            capturer::WarmupOptions,
+            frame::{BGRAFrame, ColorSpace, RowOrder},
+        };
+
+        fn display(id: u32) -> Display {
+            Display {
+                id,
+                title: format!("Display {id}"),
+                is_virtual: false,
+                span: None,
+            }
+        }
+
+        fn send_frame(capturer: &mut MultiCapturer, display_id: u32, display_time: u64) {
+            let frame = Frame::BGRA(BGRAFrame {
+                display_time,
+                width: 1,
+                height: 1,
+                data: vec![0; 4],
+                origin: RowOrder::TopDown,
+                color_space: ColorSpace::SRGB,
+            });
+            let capturer = capturer.capturer(display_id).unwrap();
+            capturer.engine.get_sender().send(frame, None).unwrap();
+        }
+
+        fn build() -> MultiCapturer {
+            let displays = vec![
+                DisplayCapture {
+                    display: display(1),
+                    fps: 144,
+                },
+                DisplayCapture {
+                    display: display(2),
+                    fps: 60,
+                },
+            ];
+            // Every frame that's sent is delivered
+            let options = Options {
+                warmup: WarmupOptions {
+                    frames: 0,
+                    ..WarmupOptions::default()
+                },
+                ..Options::default()
+            };
+            let mut capturer = MultiCapturer::build(displays, options).unwrap();
+            capturer.start_capture();
+            capturer
+        }
+
+        fn tags(frames: &[DisplayFrame]) -> Vec<(u32, u64)> {
+            frames
+                .iter()
+                .map(|tagged| (tagged.display_id, tagged.frame.display_time()))
+                .collect()
+        }
+
+        #[test]
+        fn test_frames_are_tagged() {
+            let mut capturer = build();
+            send_frame(&mut capturer, 1, 3);
+            send_frame(&mut capturer, 2, 5);
+            send_frame(&mut capturer, 1, 7);
+
+            // In display time order across displays, each with its own rate
+            let frames: Vec<_> = (0..3).map(|_| capturer.get_next_frame().unwrap()).collect();
+            assert_eq!(tags(&frames), [(1, 3), (2, 5), (1, 7)]);
+            let intervals: Vec<_> = frames.iter().map(|tagged| tagged.frame_interval).collect();
+            let [fast, slow] = [144, 60].map(|fps| Some(Duration::from_secs(1) / fps));
+            assert_eq!(intervals, [fast, slow, fast]);
+
+            // A display that sends nothing doesn't hold back the other
+            send_frame(&mut capturer, 2, 9);
+            assert_eq!(tags(&[capturer.get_next_frame().unwrap()]), [(2, 9)]);
+        }
+
+        #[test]
+        fn test_drain_buffered() {
+            let mut capturer = build();
+            send_frame(&mut capturer, 1, 1);
+            send_frame(&mut capturer, 1, 4);
+            send_frame(&mut capturer, 2, 2);
+            send_frame(&mut capturer, 2, 6);
+            assert_eq!(tags(&[capturer.get_next_frame().unwrap()]), [(1, 1)]);
+
+            // The frame of display 2 that was already received is drained too
+            capturer.stop_capture();
+            let frames = capturer.drain_buffered();
+            assert_eq!(tags(&frames), [(2, 2), (1, 4), (2, 6)]);
+        }
+    }
+}
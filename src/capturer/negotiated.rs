@@ -0,0 +1,594 @@
+#[cfg(not(target_os = "linux"))]
+use super::WindowContentMode;
+use super::{
+    CursorStyle, FrameRateCap, HdrHandling, Latency, Options, OutputColorSpace, ProcessExclusion,
+    WindowSubregion,
+};
+use crate::frame::FrameType;
+#[cfg(not(target_os = "macos"))]
+use crate::frame::{ChromaSubsampling, ColorMatrix};
+#[cfg(not(target_os = "linux"))]
+use crate::targets::Target;
+
+/// The platform API frames are captured with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureBackend {
+    WindowsGraphicsCapture,
+    ScreenCaptureKit,
+    /// Through the ScreenCast portal
+    PipeWire,
+}
+
+/// A requested option that capture doesn't carry out as requested
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Downgrade {
+    /// The name of the field in [Options](super::Options)
+    pub option: String,
+    pub reason: String,
+}
+
+/// What capture runs with once the platform had its say, see
+/// [Capturer::negotiated](super::Capturer::negotiated)
+#[derive(Debug, Clone)]
+pub struct NegotiatedConfig {
+    pub backend: CaptureBackend,
+    /// The type of the delivered frames, or None if the OS picks it when
+    /// capture starts, as PipeWire does
+    pub output_type: Option<FrameType>,
+    /// [0, 0] where it's only known from the first frame, as on Linux
+    pub frame_size: [u32; 2],
+    /// What [Options::max_dimension](super::Options::max_dimension) scaled
+    /// the captured size by, 1.0 where it's within the limit or unknown, as
+    /// on Linux
+    pub capture_scale: f64,
+    /// The part of every frame filled by [CropOverflow::Fill](super::CropOverflow::Fill)
+    /// because the crop area extends past the target, from 0.0 to 1.0. Crop
+    /// areas that follow a window or a subregion report 0.0, as they're only
+    /// resolved from the frames.
+    pub filled_fraction: f64,
+    pub frame_rate_cap: FrameRateCap,
+    /// See [Capturer::get_max_frame_rate](super::Capturer::get_max_frame_rate)
+    pub max_frame_rate: Option<u32>,
+    pub show_cursor: bool,
+    /// How the cursor is drawn, [CursorStyle::System] wherever a custom
+    /// style falls back to it
+    pub cursor_style: CursorStyle,
+    /// Whether the OS draws a border around the captured content
+    pub show_border: bool,
+    pub color_space: OutputColorSpace,
+    /// Whether HDR captures are tone mapped, which needs an HDR display
+    pub hdr_tone_mapping: bool,
+    /// Whether every delivered frame is a complete composed one, always on
+    /// Windows and macOS, and with [Options::tear_free](super::Options::tear_free)
+    /// on Linux
+    pub tear_free: bool,
+    /// Whether every frame is one the target window presented, see
+    /// [Options::content_aligned](super::Options::content_aligned). False
+    /// where capture follows the compositor's rate, which is downgraded if
+    /// the option was requested.
+    pub content_aligned: bool,
+    pub process_exclusion: ProcessExclusion,
+    /// Every requested option that was left out or replaced, and why. Also
+    /// reported as [CapturerEvent::ConfigDowngraded](super::CapturerEvent::ConfigDowngraded)
+    /// when capture starts.
+    pub downgrades: Vec<Downgrade>,
+}
+
+// Whether the platform delivers frames of `output_type`, None where the OS
+// picks the type when capture starts
+pub(crate) fn is_delivered(output_type: FrameType) -> Option<bool> {
+    #[cfg(target_os = "windows")]
+    return Some(matches!(output_type, FrameType::BGRAFrame | FrameType::RGB));
+
+    // YUV frames of other matrices and subsamplings are converted from BGRA
+    #[cfg(target_os = "macos")]
+    return {
+        let _ = output_type;
+        Some(true)
+    };
+
+    #[cfg(target_os = "linux")]
+    return {
+        let _ = output_type;
+        None
+    };
+}
+
+// The first of Options::output_types the platform delivers, which replaces
+// Options::output_type. None if there's none of them.
+pub(crate) fn pick_output_type(options: &Options) -> Option<FrameType> {
+    options
+        .output_types
+        .iter()
+        .copied()
+        .find(|output_type| is_delivered(*output_type) != Some(false))
+}
+
+// What the platform makes of the requested options, before capture starts.
+// The engine fills in what depends on the target and the running capture.
+pub(crate) fn negotiate(options: &Options) -> NegotiatedConfig {
+    let mut downgrades = Vec::new();
+    let mut downgrade = |option: &str, reason: &str| {
+        downgrades.push(Downgrade {
+            option: option.to_string(),
+            reason: reason.to_string(),
+        })
+    };
+
+    #[cfg(not(target_os = "linux"))]
+    let native_format = options.native_format;
+    #[cfg(target_os = "linux")]
+    let native_format = {
+        if options.native_format {
+            downgrade(
+                "native_format",
+                "PipeWire frames already come in the format it negotiates",
+            );
+        }
+        false
+    };
+
+    let requested_type = match pick_output_type(options) {
+        Some(output_type) => output_type,
+        None => {
+            if !options.output_types.is_empty() {
+                downgrade("output_types", "the platform delivers none of them");
+            }
+            options.output_type
+        }
+    };
+
+    #[cfg(target_os = "windows")]
+    let (backend, output_type) = {
+        let output_type = match requested_type {
+            FrameType::BGRAFrame | FrameType::RGB => requested_type,
+            _ if native_format => requested_type,
+            _ => {
+                downgrade("output_type", "Windows only captures BGRA and RGB frames");
+                FrameType::RGB
+            }
+        };
+        (CaptureBackend::WindowsGraphicsCapture, Some(output_type))
+    };
+    #[cfg(target_os = "macos")]
+    let (backend, output_type) = (CaptureBackend::ScreenCaptureKit, Some(requested_type));
+    #[cfg(target_os = "linux")]
+    let (backend, output_type) = {
+        let _ = requested_type;
+        downgrade("output_type", "PipeWire negotiates the pixel format");
+        (CaptureBackend::PipeWire, None)
+    };
+
+    // The OS picks the format of raw frames
+    let output_type = output_type.filter(|_| !native_format);
+
+    #[cfg(target_os = "linux")]
+    {
+        if options.target.is_some() {
+            downgrade("target", "the portal asks the user what to share instead");
+        }
+        if options.crop_area.is_some() {
+            downgrade("crop_area", "not supported on Linux");
+        }
+        if options.aspect_crop.is_some() {
+            downgrade("aspect_crop", "not supported on Linux");
+        }
+        if options.follow_action.is_some() {
+            downgrade("follow_action", "the cursor position isn't known on Linux");
+        }
+        if options.output_resolution != super::Resolution::Captured {
+            downgrade("output_resolution", "not supported on Linux");
+        }
+        if options.frame_deadline.is_some() {
+            downgrade(
+                "frame_deadline",
+                "PipeWire timestamps frames on another clock",
+            );
+        }
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    if matches!(options.crop_overflow, super::CropOverflow::Fill(_)) {
+        downgrade(
+            "crop_overflow",
+            "only Windows fills crop areas past the target, they're clamped",
+        );
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    if options.color_matrix != ColorMatrix::default() {
+        downgrade("color_matrix", "only macOS delivers YUV frames");
+    }
+    #[cfg(not(target_os = "macos"))]
+    if options.chroma_subsampling != ChromaSubsampling::default() {
+        downgrade("chroma_subsampling", "only macOS delivers YUV frames");
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    if options
+        .excluded_targets
+        .as_ref()
+        .is_some_and(|targets| !targets.is_empty())
+    {
+        downgrade("excluded_targets", "only supported on macOS");
+    }
+
+    #[cfg(target_os = "windows")]
+    let show_border = options.show_highlight;
+    #[cfg(not(target_os = "windows"))]
+    let show_border = {
+        if options.show_highlight {
+            downgrade("show_highlight", "only Windows draws a capture border");
+        }
+        false
+    };
+
+    #[cfg(not(target_os = "windows"))]
+    if options.hdr_handling == HdrHandling::ToneMapToSDR {
+        downgrade("hdr_handling", "only Windows captures HDR to tone map it");
+    }
+    #[cfg(not(target_os = "windows"))]
+    if options.watch_focus {
+        downgrade("watch_focus", "only Windows reports the focused window");
+    }
+    #[cfg(not(target_os = "windows"))]
+    if options.auto_recover_device {
+        downgrade(
+            "auto_recover_device",
+            "only Windows capture is lost with the GPU device",
+        );
+    }
+    #[cfg(not(target_os = "windows"))]
+    if options.gpu_replay.is_some() {
+        downgrade("gpu_replay", "only Windows keeps frames on the GPU");
+    }
+
+    // Mirrors engine::effective_options, which drops what these modes skip
+    let mut color_space = options.color_space;
+    let mut cursor_style = options.cursor_style.clone();
+    let custom_cursor = options.show_cursor && !matches!(cursor_style, CursorStyle::System);
+    if options.latency == Latency::LowLatency {
+        if color_space == OutputColorSpace::SRGB {
+            downgrade("color_space", "low latency capture skips color conversion");
+        }
+        if custom_cursor {
+            downgrade("cursor_style", "low latency capture skips drawing cursors");
+        }
+        color_space = OutputColorSpace::Native;
+        cursor_style = CursorStyle::System;
+    } else if options.grayscale.is_some() {
+        if color_space == OutputColorSpace::SRGB {
+            downgrade("color_space", "grayscale frames have no colors to convert");
+        }
+        if custom_cursor {
+            downgrade(
+                "cursor_style",
+                "grayscale frames have no cursors drawn into them",
+            );
+        }
+        color_space = OutputColorSpace::Native;
+        cursor_style = CursorStyle::System;
+    } else if native_format && color_space == OutputColorSpace::SRGB {
+        downgrade("color_space", "native frames aren't converted");
+        color_space = OutputColorSpace::Native;
+    }
+    if native_format {
+        // Only Windows scales, tone maps and fills frames itself
+        let windows = cfg!(target_os = "windows");
+        let reworked = [
+            ("grayscale", options.grayscale.is_some()),
+            ("thumbnails", options.thumbnails.is_some()),
+            ("delta", options.delta.is_some()),
+            ("scanline_patches", options.scanline_patches.is_some()),
+            ("watermark", options.watermark.is_some()),
+            (
+                "hdr_handling",
+                windows && options.hdr_handling == HdrHandling::ToneMapToSDR,
+            ),
+            (
+                "max_output_size",
+                windows && options.max_output_size.is_some(),
+            ),
+            ("max_dimension", windows && options.max_dimension.is_some()),
+            (
+                "crop_overflow",
+                windows && matches!(options.crop_overflow, super::CropOverflow::Fill(_)),
+            ),
+        ];
+        for (option, set) in reworked {
+            if set {
+                downgrade(option, "native frames are delivered as captured");
+            }
+        }
+    }
+    if options.constant_frame_rate.is_some()
+        && (options.delta.is_some() || options.scanline_patches.is_some())
+    {
+        downgrade(
+            "constant_frame_rate",
+            "frames holding only changes can't be repeated",
+        );
+    }
+    if options.activity.is_some() {
+        if options.delta.is_some() || options.scanline_patches.is_some() {
+            downgrade(
+                "activity",
+                "frames holding only changes can't be summarized",
+            );
+        } else if options.constant_frame_rate.is_some() {
+            downgrade(
+                "constant_frame_rate",
+                "activity summaries are delivered instead of frames",
+            );
+        }
+    }
+    if options.watermark.is_some()
+        && (options.grayscale.is_some()
+            || matches!(
+                output_type,
+                Some(FrameType::YUVFrame | FrameType::RGB | FrameType::BGR0)
+            ))
+    {
+        downgrade(
+            "watermark",
+            "only frames with 4 bytes per pixel, like BGRA, are watermarked",
+        );
+    }
+    if options.crop_area.is_some() && !matches!(options.window_subregion, WindowSubregion::Whole) {
+        downgrade("crop_area", "replaced by window_subregion");
+    }
+    #[cfg(not(target_os = "linux"))]
+    if options.aspect_crop.is_some() {
+        if !matches!(options.window_subregion, WindowSubregion::Whole) {
+            downgrade("aspect_crop", "replaced by window_subregion");
+        } else if matches!(options.target, Some(Target::Window(_)))
+            && options.window_content_mode == WindowContentMode::ScreenRegion
+        {
+            downgrade("aspect_crop", "the crop follows the window's bounds");
+        }
+    }
+
+    let repeats = [
+        ("constant_frame_rate", options.constant_frame_rate.is_some()),
+        ("min_frame_rate", options.min_frame_rate.is_some()),
+        ("external_trigger", options.external_trigger),
+    ];
+    let repeated_by = repeats
+        .iter()
+        .find(|(_, set)| *set)
+        .map(|(option, _)| option);
+    #[cfg(target_os = "macos")]
+    let presents = matches!(options.target, Some(Target::Window(_)))
+        && options.window_content_mode == WindowContentMode::WindowContent;
+    #[cfg(not(target_os = "macos"))]
+    let presents = false;
+    let content_aligned = options.content_aligned && presents && repeated_by.is_none();
+    if options.content_aligned && !content_aligned {
+        let reason = match repeated_by {
+            Some(option) if presents => format!("frames are repeated by {option}"),
+            _ if cfg!(target_os = "linux") => {
+                "PipeWire doesn't report when the content presents".to_string()
+            }
+            _ if cfg!(target_os = "windows") => {
+                "Windows Graphics Capture doesn't report when the content presents".to_string()
+            }
+            _ => "only window content is captured as it presents, other targets at the compositor's rate".to_string(),
+        };
+        downgrade("content_aligned", &reason);
+    }
+
+    #[cfg(target_os = "macos")]
+    if options.follow_action.is_some() && matches!(options.target, Some(Target::Window(_))) {
+        downgrade(
+            "follow_action",
+            "the cursor position isn't known for window capture",
+        );
+    }
+
+    // Custom cursors are drawn into BGRA frames where the cursor position is known
+    #[cfg(target_os = "windows")]
+    let draws_cursors = matches!(output_type, Some(FrameType::BGRAFrame));
+    #[cfg(target_os = "macos")]
+    let draws_cursors = matches!(output_type, Some(FrameType::BGRAFrame))
+        && !matches!(options.target, Some(Target::Window(_)));
+    #[cfg(target_os = "linux")]
+    let draws_cursors = false;
+    if options.show_cursor && !matches!(cursor_style, CursorStyle::System) && !draws_cursors {
+        downgrade(
+            "cursor_style",
+            "custom cursors are only drawn into BGRA frames of Windows captures and macOS display captures",
+        );
+        cursor_style = CursorStyle::System;
+    }
+
+    NegotiatedConfig {
+        backend,
+        output_type,
+        frame_size: [0, 0],
+        capture_scale: 1.0,
+        filled_fraction: 0.0,
+        frame_rate_cap: FrameRateCap::Unlimited,
+        max_frame_rate: None,
+        show_cursor: options.show_cursor,
+        cursor_style,
+        show_border,
+        color_space,
+        hdr_tone_mapping: false,
+        tear_free: cfg!(not(target_os = "linux")) || options.tear_free,
+        content_aligned,
+        process_exclusion: ProcessExclusion::Off,
+        downgrades,
+    }
+}
+
+// The downgrade for exclusion carried out other than requested, if it was
+pub(crate) fn get_exclusion_downgrade(exclusion: ProcessExclusion) -> Option<Downgrade> {
+    let reason = match exclusion {
+        ProcessExclusion::Off | ProcessExclusion::Excluded => return None,
+        ProcessExclusion::Masked => "the windows are captured as black rectangles",
+        ProcessExclusion::Unsupported => {
+            "the platform can't tell which windows belong to the process"
+        }
+    };
+    Some(Downgrade {
+        option: "exclude_current_process".to_string(),
+        reason: reason.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::CursorImage;
+
+    fn options(output_type: FrameType) -> Options {
+        Options {
+            show_cursor: true,
+            cursor_style: CursorStyle::Custom(CursorImage {
+                width: 1,
+                height: 1,
+                hotspot: (0, 0),
+                data: vec![255; 4],
+            }),
+            output_type,
+            ..Default::default()
+        }
+    }
+
+    fn downgraded(config: &NegotiatedConfig) -> Vec<&str> {
+        config
+            .downgrades
+            .iter()
+            .map(|d| d.option.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_impossible_request() {
+        // No platform draws custom cursors into YUV frames
+        let config = negotiate(&Options {
+            show_highlight: true,
+            ..options(FrameType::YUVFrame)
+        });
+        assert!(downgraded(&config).contains(&"cursor_style"));
+        assert!(matches!(config.cursor_style, CursorStyle::System));
+        assert!(config.downgrades.iter().all(|d| !d.reason.is_empty()));
+
+        #[cfg(target_os = "windows")]
+        {
+            assert_eq!(downgraded(&config), ["output_type", "cursor_style"]);
+            assert!(matches!(config.output_type, Some(FrameType::RGB)));
+            assert!(config.show_border);
+        }
+        #[cfg(target_os = "macos")]
+        {
+            assert_eq!(downgraded(&config), ["show_highlight", "cursor_style"]);
+            assert!(!config.show_border);
+        }
+    }
+
+    #[test]
+    fn test_mode_downgrades() {
+        let config = negotiate(&Options {
+            latency: Latency::LowLatency,
+            color_space: OutputColorSpace::SRGB,
+            ..options(FrameType::BGRAFrame)
+        });
+        let modes: Vec<&str> = config
+            .downgrades
+            .iter()
+            .filter(|d| d.reason.starts_with("low latency"))
+            .map(|d| d.option.as_str())
+            .collect();
+        assert_eq!(modes, ["color_space", "cursor_style"]);
+        assert_eq!(config.color_space, OutputColorSpace::Native);
+
+        // Honored requests aren't reported
+        #[cfg(not(target_os = "linux"))]
+        assert!(negotiate(&options(FrameType::BGRAFrame))
+            .downgrades
+            .is_empty());
+
+        assert_eq!(
+            get_exclusion_downgrade(ProcessExclusion::Masked).map(|d| d.option),
+            Some("exclude_current_process".to_string())
+        );
+        assert!(get_exclusion_downgrade(ProcessExclusion::Excluded).is_none());
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    #[test]
+    fn test_output_type_fallbacks() {
+        let config = negotiate(&Options {
+            output_types: vec![FrameType::YUVFrame, FrameType::BGRAFrame],
+            ..Default::default()
+        });
+        #[cfg(target_os = "windows")]
+        assert!(matches!(config.output_type, Some(FrameType::BGRAFrame)));
+        #[cfg(target_os = "macos")]
+        assert!(matches!(config.output_type, Some(FrameType::YUVFrame)));
+        assert!(config.downgrades.is_empty());
+
+        // Without any the platform delivers, output_type is negotiated as usual
+        #[cfg(target_os = "windows")]
+        {
+            let config = negotiate(&Options {
+                output_type: FrameType::BGRAFrame,
+                output_types: vec![FrameType::BGR0],
+                ..Default::default()
+            });
+            assert_eq!(downgraded(&config), ["output_types"]);
+            assert!(matches!(config.output_type, Some(FrameType::BGRAFrame)));
+        }
+    }
+
+    #[test]
+    fn test_content_alignment_fallback() {
+        // Displays are captured at the compositor's rate
+        let config = negotiate(&Options {
+            content_aligned: true,
+            ..Default::default()
+        });
+        // Linux downgrades the output type of any capture as well
+        let downgraded = downgraded(&config);
+        assert_eq!(
+            downgraded
+                .iter()
+                .filter(|&&o| o == "content_aligned")
+                .count(),
+            1
+        );
+        assert!(!config.content_aligned);
+        assert!(!negotiate(&Options::default()).content_aligned);
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    #[test]
+    fn test_native_format() {
+        let config = negotiate(&Options {
+            native_format: true,
+            thumbnails: Some(Default::default()),
+            ..Default::default()
+        });
+        assert_eq!(downgraded(&config), ["thumbnails"]);
+        assert!(config.output_type.is_none());
+    }
+
+    // Presets only ask for what every platform does
+    #[cfg(not(target_os = "linux"))]
+    #[test]
+    fn test_presets_are_honored() {
+        use crate::capturer::Preset;
+
+        for preset in [
+            Preset::Recording,
+            Preset::Streaming,
+            Preset::Screenshot,
+            Preset::LowLatency,
+        ] {
+            let config = negotiate(&Options::preset(preset));
+            assert_eq!(downgraded(&config), Vec::<&str>::new(), "{preset:?}");
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use std::time::{Duration, Instant};
+
+/// Drops frames that arrive faster than a target frame rate.
+///
+/// Used on platforms whose capture API can't throttle frame production itself.
+#[derive(Debug)]
+pub(crate) struct FramePacer {
+    interval: Option<Duration>,
+    next: Option<Instant>,
+}
+
+impl FramePacer {
+    /// A pacer for `fps` frames per second. 0 is unlimited.
+    pub fn new(fps: u32) -> Self {
+        FramePacer {
+            interval: (fps > 0).then(|| Duration::from_secs(1) / fps),
+            next: None,
+        }
+    }
+
+    /// Whether a frame arriving at `now` should be delivered
+    pub fn should_deliver(&mut self, now: Instant) -> bool {
+        let Some(interval) = self.interval else {
+            return true;
+        };
+
+        if let Some(next) = self.next {
+            // Compositor timing jitters, so accept frames slightly early
+            if now + interval / 8 < next {
+                return false;
+            }
+
+            // Stay on the frame grid unless we fell behind by more than a frame
+            self.next = Some(if now < next + interval {
+                next + interval
+            } else {
+                now + interval
+            });
+        } else {
+            self.next = Some(now + interval);
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delivered(source_fps: u32, target_fps: u32, frames: u32) -> usize {
+        let start = Instant::now();
+        let mut pacer = FramePacer::new(target_fps);
+        (0..frames)
+            .filter(|i| pacer.should_deliver(start + Duration::from_secs(1) * *i / source_fps))
+            .count()
+    }
+
+    #[test]
+    fn test_frame_pacer() {
+        assert_eq!(delivered(60, 0, 120), 120);
+        assert_eq!(delivered(60, 60, 120), 120);
+        assert_eq!(delivered(60, 120, 120), 120);
+        assert_eq!(delivered(60, 30, 120), 60);
+        assert_eq!(delivered(144, 60, 144), 60);
+    }
+}
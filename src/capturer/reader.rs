@@ -0,0 +1,127 @@
+use std::io::{self, Read};
+
+use super::Capturer;
+use crate::frame::{convert_yuv_to_bgra, to_record, Frame};
+
+/// Reads the frames of a [Capturer] as one stream of bytes, to
+/// [std::io::copy] them into a file, a pipe or a socket.
+///
+/// Every frame's buffer is yielded as it is, rows in the frame's own order
+/// and with any row padding it has. YUV frames are converted to BGRA
+/// first. Delta, scanline patch, regions and marker frames, and the empty
+/// frames of macOS, carry no full picture and are skipped.
+///
+/// With [FrameReader::with_headers] each frame is preceded by a 32-byte
+/// header, all integers little endian: length of the pixels: u64,
+/// display_time: u64, format: u8 (0 RGB, 1 RGBx, 2 XBGR, 3 BGRx, 4 BGR0,
+/// 5 BGRA, 6 Gray8), origin: u8 (0 top-down, 1 bottom-up), color_space: u8
+/// (0 unknown, 1 sRGB, 2 Display P3), a zero byte, width: i32, height: i32
+/// and four zero bytes. [read_frame_record](crate::frame::read_frame_record)
+/// reads such a stream back. Without headers the stream is only pixels,
+/// for tools that are told the frame size and format.
+///
+/// Reads block until the next frame arrives and return the bytes of at
+/// most one frame, so a frame is never held back waiting for the next.
+/// The stream ends once capture stops. It has to be started beforehand.
+pub struct FrameReader {
+    capturer: Capturer,
+    headers: bool,
+    frame: Option<Frame>,
+    // How much of the current frame was read, header included
+    position: usize,
+}
+
+impl FrameReader {
+    /// Read the pixels of the frames, one after another
+    pub fn new(capturer: Capturer) -> Self {
+        FrameReader {
+            capturer,
+            headers: false,
+            frame: None,
+            position: 0,
+        }
+    }
+
+    /// Read every frame as a header describing it, then its pixels
+    pub fn with_headers(capturer: Capturer) -> Self {
+        FrameReader {
+            headers: true,
+            ..FrameReader::new(capturer)
+        }
+    }
+
+    pub fn get_ref(&self) -> &Capturer {
+        &self.capturer
+    }
+
+    /// Get the capturer back, to stop capture. The rest of a partly read
+    /// frame is dropped.
+    pub fn into_inner(self) -> Capturer {
+        self.capturer
+    }
+}
+
+impl Read for FrameReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            if let Some(record) = self.frame.as_ref().and_then(to_record) {
+                let header: &[u8] = match self.headers {
+                    true => &record.header,
+                    false => &[],
+                };
+                let read = read_parts([header, record.pixels], self.position, buf);
+                if read > 0 {
+                    self.position += read;
+                    return Ok(read);
+                }
+            }
+
+            self.position = 0;
+            self.frame = match self.capturer.get_next_frame() {
+                Ok(Frame::YUVFrame(frame)) => Some(Frame::BGRA(convert_yuv_to_bgra(&frame))),
+                Ok(frame) => Some(frame),
+                Err(_) => return Ok(0),
+            };
+        }
+    }
+}
+
+// Copies from `parts` as if they were one buffer, starting `position` bytes in
+fn read_parts(parts: [&[u8]; 2], mut position: usize, buf: &mut [u8]) -> usize {
+    let mut read = 0;
+    for part in parts {
+        if position >= part.len() {
+            position -= part.len();
+            continue;
+        }
+        let count = (part.len() - position).min(buf.len() - read);
+        buf[read..read + count].copy_from_slice(&part[position..position + count]);
+        read += count;
+        position = 0;
+    }
+    read
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_parts() {
+        let parts: [&[u8]; 2] = [&[1, 2, 3], &[4, 5]];
+        let mut buf = [0; 4];
+        assert_eq!(read_parts(parts, 0, &mut buf), 4);
+        assert_eq!(buf, [1, 2, 3, 4]);
+        assert_eq!(read_parts(parts, 4, &mut buf), 1);
+        assert_eq!(buf[0], 5);
+        assert_eq!(read_parts(parts, 5, &mut buf), 0);
+
+        let mut buf = [0; 8];
+        assert_eq!(read_parts(parts, 2, &mut buf), 3);
+        assert_eq!(buf[..3], [3, 4, 5]);
+        assert_eq!(read_parts([&[], &[9]], 0, &mut buf), 1);
+    }
+}
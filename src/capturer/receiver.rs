@@ -0,0 +1,39 @@
+use std::sync::mpsc::RecvError;
+
+use super::Capturer;
+use crate::frame::{BufferPool, PooledFrame};
+
+/// Receives the frames of a [Capturer] as [PooledFrame]s, from
+/// [Capturer::frame_receiver].
+///
+/// Frames are the same as [Capturer::get_next_frame] returns. Once a
+/// [PooledFrame] is dropped its buffers go back to the pool of
+/// [Options::buffer_pool](super::Options::buffer_pool) for the capture
+/// thread to fill again, so a consumer that processes frames in place never
+/// has to return anything itself and the pool can't run dry. Without a pool,
+/// or once it's full, dropped buffers are freed as usual.
+pub struct FrameReceiver<'a> {
+    capturer: &'a Capturer,
+    pool: BufferPool,
+}
+
+impl<'a> FrameReceiver<'a> {
+    pub(crate) fn new(capturer: &'a Capturer, pool: BufferPool) -> Self {
+        FrameReceiver { capturer, pool }
+    }
+
+    /// Wait for the next frame, see [Capturer::get_next_frame]
+    pub fn recv(&self) -> Result<PooledFrame, RecvError> {
+        let frame = self.capturer.get_next_frame()?;
+        Ok(PooledFrame::new(frame, self.pool.clone()))
+    }
+}
+
+/// The frames until capture stops
+impl Iterator for FrameReceiver<'_> {
+    type Item = PooledFrame;
+
+    fn next(&mut self) -> Option<PooledFrame> {
+        self.recv().ok()
+    }
+}
@@ -0,0 +1,82 @@
+use std::time::{Duration, Instant};
+
+// Restarts tried in a row before recovery gives up
+const MAX_ATTEMPTS: u32 = 5;
+// Before the first restart, doubling with every one that fails. Windows
+// takes about two seconds to reset the driver.
+const FIRST_DELAY: Duration = Duration::from_millis(500);
+// How long a restarted capture has to run for its restarts to be forgiven
+const STABLE_AFTER: Duration = Duration::from_secs(10);
+
+// Paces and caps the restarts after the GPU device was lost, see
+// Options::auto_recover_device. A capture that dies again right after it
+// was restarted counts as a failed attempt.
+#[derive(Debug, Default)]
+pub(crate) struct DeviceRecovery {
+    // Restarts since capture last ran stably
+    attempts: u32,
+    // When capture was last restarted
+    restarted: Option<Instant>,
+}
+
+impl DeviceRecovery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    // Records that the device was lost at `now`
+    pub fn lost(&mut self, now: Instant) {
+        if self
+            .restarted
+            .is_some_and(|restarted| now.duration_since(restarted) >= STABLE_AFTER)
+        {
+            self.attempts = 0;
+        }
+    }
+
+    // The delay before the next restart, or None once the attempts are used up
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        if self.attempts >= MAX_ATTEMPTS {
+            return None;
+        }
+        self.attempts += 1;
+        Some(FIRST_DELAY * 2u32.pow(self.attempts - 1))
+    }
+
+    // Records that capture was restarted at `now`
+    pub fn restarted(&mut self, now: Instant) {
+        self.restarted = Some(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attempts_are_capped() {
+        let start = Instant::now();
+        let mut recovery = DeviceRecovery::new();
+        recovery.lost(start);
+        assert_eq!(recovery.next_delay(), Some(Duration::from_millis(500)));
+        assert_eq!(recovery.next_delay(), Some(Duration::from_secs(1)));
+        recovery.restarted(start);
+
+        // Lost again before the restarted capture ran stably
+        recovery.lost(start + Duration::from_secs(1));
+        let delays: Vec<Duration> = std::iter::from_fn(|| recovery.next_delay()).collect();
+        assert_eq!(delays.len(), 3);
+        assert_eq!(recovery.attempts(), MAX_ATTEMPTS);
+        assert!(recovery.next_delay().is_none());
+
+        // A capture that ran stably starts over
+        recovery.restarted(start);
+        recovery.lost(start + STABLE_AFTER);
+        assert_eq!(recovery.next_delay(), Some(FIRST_DELAY));
+        assert_eq!(recovery.attempts(), 1);
+    }
+}
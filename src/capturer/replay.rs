@@ -0,0 +1,187 @@
+#[cfg(any(target_os = "windows", test))]
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Keeps the latest frames on the GPU for an instant replay, see
+/// [Options::gpu_replay](super::Options::gpu_replay)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GpuReplayOptions {
+    /// How far back frames are kept
+    pub duration: Duration,
+    /// The most video memory the kept frames take up, in bytes. The oldest
+    /// frames make way for new ones past it.
+    pub max_memory: u64,
+}
+
+impl Default for GpuReplayOptions {
+    fn default() -> Self {
+        GpuReplayOptions {
+            duration: Duration::from_secs(30),
+            max_memory: 2 << 30,
+        }
+    }
+}
+
+/// What the replay of [Options::gpu_replay](super::Options::gpu_replay)
+/// holds, see [Capturer::replay_usage](super::Capturer::replay_usage)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GpuReplayUsage {
+    pub frames: usize,
+    /// Video memory taken up by the frames, in bytes
+    pub memory: u64,
+    /// How far back frames are kept, shorter than requested once the GPU
+    /// ran low on memory
+    pub retention: Duration,
+}
+
+// Retention isn't reduced below this under memory pressure
+#[cfg(any(target_os = "windows", test))]
+const MIN_RETENTION: Duration = Duration::from_secs(1);
+
+#[cfg(any(target_os = "windows", test))]
+#[derive(Debug)]
+pub(crate) struct ReplayEntry<T> {
+    pub display_time: u64,
+    pub bytes: u64,
+    pub item: T,
+}
+
+// The latest frames by display time in nanoseconds, with what they take up
+#[cfg(any(target_os = "windows", test))]
+#[derive(Debug)]
+pub(crate) struct ReplayRing<T> {
+    options: GpuReplayOptions,
+    retention: Duration,
+    entries: VecDeque<ReplayEntry<T>>,
+    memory: u64,
+}
+
+#[cfg(any(target_os = "windows", test))]
+impl<T> ReplayRing<T> {
+    pub fn new(options: GpuReplayOptions) -> Self {
+        ReplayRing {
+            options,
+            retention: options.duration,
+            entries: VecDeque::new(),
+            memory: 0,
+        }
+    }
+
+    // Drops every frame and restores the requested retention
+    pub fn clear(&mut self) -> Vec<T> {
+        self.retention = self.options.duration;
+        self.memory = 0;
+        self.entries.drain(..).map(|entry| entry.item).collect()
+    }
+
+    // Adds the newest frame, returning the ones that no longer fit
+    pub fn push(&mut self, display_time: u64, bytes: u64, item: T) -> Vec<T> {
+        self.memory += bytes;
+        self.entries.push_back(ReplayEntry {
+            display_time,
+            bytes,
+            item,
+        });
+        self.evict()
+    }
+
+    // Halves the retention, down to a second, returning the frames dropped
+    // for it. None once it can't be reduced further.
+    pub fn reduce(&mut self) -> Option<Vec<T>> {
+        if self.retention <= MIN_RETENTION {
+            return None;
+        }
+        self.retention = (self.retention / 2).max(MIN_RETENTION);
+        Some(self.evict())
+    }
+
+    pub fn retention(&self) -> Duration {
+        self.retention
+    }
+
+    fn evict(&mut self) -> Vec<T> {
+        let newest = self.entries.back().map_or(0, |entry| entry.display_time);
+        let oldest = newest.saturating_sub(self.retention.as_nanos() as u64);
+        let mut evicted = Vec::new();
+        while let Some(entry) = self.entries.front() {
+            // The newest frame stays, however large
+            let over = self.memory > self.options.max_memory && self.entries.len() > 1;
+            if entry.display_time >= oldest && !over {
+                break;
+            }
+            let entry = self.entries.pop_front().unwrap();
+            self.memory -= entry.bytes;
+            evicted.push(entry.item);
+        }
+        evicted
+    }
+
+    // The frames of the last `duration` up to the newest, oldest first
+    pub fn last(&self, duration: Duration) -> impl Iterator<Item = &ReplayEntry<T>> {
+        let newest = self.entries.back().map_or(0, |entry| entry.display_time);
+        let oldest = newest.saturating_sub(duration.as_nanos() as u64);
+        self.entries
+            .iter()
+            .filter(move |entry| entry.display_time >= oldest)
+    }
+
+    pub fn usage(&self) -> GpuReplayUsage {
+        GpuReplayUsage {
+            frames: self.entries.len(),
+            memory: self.memory,
+            retention: self.retention,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: u64 = 1_000_000_000;
+
+    #[test]
+    fn test_retention_and_memory() {
+        let mut ring = ReplayRing::new(GpuReplayOptions {
+            duration: Duration::from_secs(4),
+            max_memory: 1000,
+        });
+        for i in 0..6 {
+            let evicted = ring.push(i * SECOND, 100, i);
+            assert_eq!(evicted, if i == 5 { vec![0] } else { vec![] });
+        }
+        assert_eq!(
+            ring.usage(),
+            GpuReplayUsage {
+                frames: 5,
+                memory: 500,
+                retention: Duration::from_secs(4),
+            }
+        );
+        let last: Vec<u64> = ring
+            .last(Duration::from_secs(2))
+            .map(|entry| entry.item)
+            .collect();
+        assert_eq!(last, [3, 4, 5]);
+
+        // The oldest frames make way past the memory limit
+        assert_eq!(ring.push(6 * SECOND, 700, 6), [1, 2]);
+        assert_eq!(ring.usage().memory, 1000);
+
+        // Memory pressure halves how far back frames are kept
+        assert_eq!(ring.reduce(), Some(vec![3]));
+        assert_eq!(ring.retention(), Duration::from_secs(2));
+        assert_eq!(ring.reduce(), Some(vec![4]));
+        assert_eq!(ring.reduce(), None);
+
+        assert_eq!(ring.clear(), [5, 6]);
+        assert_eq!(
+            ring.usage(),
+            GpuReplayUsage {
+                retention: Duration::from_secs(4),
+                ..Default::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use std::{
+    ops::Range,
+    panic::{self, AssertUnwindSafe},
+    sync::{mpsc, Arc},
+    time::SystemTime,
+};
+
+use super::{panic_message, CallbackPanic, CapturerEvent};
+
+/// Tells whether frames are delivered at a time, see [CaptureSchedule::Callback]
+pub type ScheduleCallback = Arc<dyn Fn(SystemTime) -> bool + Send + Sync>;
+
+/// When frames are delivered, see [Options::schedule](super::Options::schedule)
+#[derive(Clone)]
+pub enum CaptureSchedule {
+    /// Within any of these spans of the system clock
+    Windows(Vec<Range<SystemTime>>),
+    /// Whenever the callback returns true for the current system time. This
+    /// is the way to follow recurring hours, converted with the caller's
+    /// time zone and DST rules.
+    Callback(ScheduleCallback),
+}
+
+impl std::fmt::Debug for CaptureSchedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CaptureSchedule::Windows(windows) => f.debug_tuple("Windows").field(windows).finish(),
+            CaptureSchedule::Callback(_) => write!(f, "Callback(..)"),
+        }
+    }
+}
+
+// Checks the schedule for every captured frame and reports its boundaries
+#[derive(Debug)]
+pub(crate) struct Scheduler {
+    schedule: CaptureSchedule,
+    on_panic: CallbackPanic,
+    // Whether frames were delivered at the last check, None before the first
+    active: Option<bool>,
+    // Set once the callback panicked with CallbackPanic::Stop
+    stopped: bool,
+}
+
+impl Scheduler {
+    pub fn new(schedule: CaptureSchedule, on_panic: CallbackPanic) -> Self {
+        Scheduler {
+            schedule,
+            on_panic,
+            active: None,
+            stopped: false,
+        }
+    }
+
+    // Whether a frame captured at `now`, displayed at `time`, is delivered.
+    // A panicking callback keeps the schedule as it was, unless it stops
+    // capture.
+    pub fn check(
+        &mut self,
+        now: SystemTime,
+        time: u64,
+        events: &mpsc::Sender<CapturerEvent>,
+    ) -> bool {
+        if self.stopped {
+            return false;
+        }
+
+        let active = match &self.schedule {
+            CaptureSchedule::Windows(windows) => windows.iter().any(|w| w.contains(&now)),
+            CaptureSchedule::Callback(callback) => {
+                match panic::catch_unwind(AssertUnwindSafe(|| callback(now))) {
+                    Ok(active) => active,
+                    Err(payload) => {
+                        let message = panic_message(payload);
+                        let _ = events.send(CapturerEvent::CallbackPanicked { message });
+                        if self.on_panic == CallbackPanic::Stop {
+                            self.stopped = true;
+                            return false;
+                        }
+                        self.active.unwrap_or(true)
+                    }
+                }
+            }
+        };
+
+        // Capture starts delivering, so only starting outside the schedule is reported
+        let event = match (self.active.replace(active), active) {
+            (Some(true) | None, false) => Some(CapturerEvent::ScheduleSuspended { time }),
+            (Some(false), true) => Some(CapturerEvent::ScheduleResumed { time }),
+            _ => None,
+        };
+        if let Some(event) = event {
+            let _ = events.send(event);
+        }
+        active
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_windows_and_boundaries() {
+        let schedule = CaptureSchedule::Windows(vec![at(10)..at(20), at(30)..at(40)]);
+        let mut scheduler = Scheduler::new(schedule, CallbackPanic::Stop);
+        let (tx, events) = mpsc::channel();
+
+        let delivered: Vec<bool> = [5, 10, 19, 20, 35]
+            .into_iter()
+            .map(|secs| scheduler.check(at(secs), secs, &tx))
+            .collect();
+        assert_eq!(delivered, [false, true, true, false, true]);
+
+        let boundaries: Vec<(bool, u64)> = events
+            .try_iter()
+            .map(|event| match event {
+                CapturerEvent::ScheduleSuspended { time } => (false, time),
+                CapturerEvent::ScheduleResumed { time } => (true, time),
+                _ => panic!("unexpected event"),
+            })
+            .collect();
+        assert_eq!(
+            boundaries,
+            [(false, 5), (true, 10), (false, 20), (true, 35)]
+        );
+    }
+
+    #[test]
+    fn test_panicking_callback() {
+        let callback: ScheduleCallback = Arc::new(|now| {
+            if now >= at(10) {
+                panic!("no schedule");
+            }
+            true
+        });
+        let (tx, events) = mpsc::channel();
+
+        let mut scheduler = Scheduler::new(
+            CaptureSchedule::Callback(callback.clone()),
+            CallbackPanic::Continue,
+        );
+        assert!(scheduler.check(at(5), 5, &tx));
+        assert!(scheduler.check(at(10), 10, &tx));
+        assert!(matches!(
+            events.try_recv(),
+            Ok(CapturerEvent::CallbackPanicked { .. })
+        ));
+
+        let mut scheduler =
+            Scheduler::new(CaptureSchedule::Callback(callback), CallbackPanic::Stop);
+        assert!(!scheduler.check(at(10), 10, &tx));
+        assert!(!scheduler.check(at(5), 5, &tx));
+    }
+}
@@ -0,0 +1,132 @@
+use std::{
+    sync::mpsc::RecvTimeoutError,
+    time::{Duration, Instant},
+};
+
+use super::{Capturer, CapturerBuildError, Options};
+use crate::frame::Frame;
+
+/// How [capture_screenshot] waits for its frame
+#[derive(Debug, Clone, Copy)]
+pub struct ScreenshotOptions {
+    /// How long to wait for a usable frame once capture started
+    pub timeout: Duration,
+    /// Frames with no color channel above this are taken for the black
+    /// frames some platforms deliver before the first composited one, and
+    /// skipped, see [Frame::is_black]. None returns the first frame.
+    pub black_tolerance: Option<u8>,
+}
+
+impl Default for ScreenshotOptions {
+    fn default() -> Self {
+        ScreenshotOptions {
+            timeout: Duration::from_secs(3),
+            black_tolerance: Some(8),
+        }
+    }
+}
+
+/// Why [capture_screenshot] returned no frame
+#[derive(Debug)]
+pub enum ScreenshotError {
+    /// Capture couldn't be built or started
+    Capture(CapturerBuildError),
+    /// Only black or empty frames arrived before the timeout, this many
+    TimedOut { frames: u64 },
+    /// Capture stopped before a usable frame arrived
+    Stopped,
+}
+
+impl std::fmt::Display for ScreenshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScreenshotError::Capture(error) => write!(f, "{error}"),
+            ScreenshotError::TimedOut { frames: 0 } => write!(f, "No frame arrived in time"),
+            ScreenshotError::TimedOut { frames } => {
+                write!(f, "Only {frames} black or empty frames arrived in time")
+            }
+            ScreenshotError::Stopped => write!(f, "Capture stopped before a frame arrived"),
+        }
+    }
+}
+
+impl std::error::Error for ScreenshotError {}
+
+/// Capture a single frame of the target in `options`. Capture is started
+/// and stopped for it, and the first frame that has pixels and isn't black
+/// is returned. Delta and scanline encoding are turned off, as are
+/// low latency capture, a constant or minimum frame rate, activity
+/// summaries and external triggers, which could skip, repeat or replace
+/// frames or wait for them.
+pub fn capture_screenshot(
+    options: Options,
+    screenshot: ScreenshotOptions,
+) -> Result<Frame, ScreenshotError> {
+    let options = Options {
+        delta: None,
+        scanline_patches: None,
+        latency: Default::default(),
+        constant_frame_rate: None,
+        min_frame_rate: None,
+        activity: None,
+        external_trigger: false,
+        ..options
+    };
+    let mut capturer = Capturer::build(options).map_err(ScreenshotError::Capture)?;
+    capturer
+        .try_start_capture()
+        .map_err(ScreenshotError::Capture)?;
+
+    let deadline = Instant::now() + screenshot.timeout;
+    let mut skipped = 0;
+    let result = loop {
+        match capturer.next_frame(Some(deadline)) {
+            Ok(frame) if is_usable(&frame, screenshot.black_tolerance) => break Ok(frame),
+            Ok(Frame::Marker(_)) => {}
+            Ok(_) => skipped += 1,
+            Err(RecvTimeoutError::Timeout) => {
+                break Err(ScreenshotError::TimedOut { frames: skipped })
+            }
+            Err(RecvTimeoutError::Disconnected) => break Err(ScreenshotError::Stopped),
+        }
+    };
+    capturer.stop_capture();
+    result
+}
+
+// Whether a frame shows what a screenshot should, rather than the empty
+// idle frames of macOS or the black ones before the first composition
+fn is_usable(frame: &Frame, black_tolerance: Option<u8>) -> bool {
+    let (width, height) = frame.size();
+    if width <= 0 || height <= 0 || matches!(frame, Frame::Marker(_)) {
+        return false;
+    }
+    black_tolerance.map_or(true, |tolerance| !frame.is_black(tolerance))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{BGRAFrame, ColorSpace, RowOrder};
+
+    fn frame(width: i32, value: u8) -> Frame {
+        Frame::BGRA(BGRAFrame {
+            display_time: 0,
+            width,
+            height: 1,
+            data: [value, value, value, 255].repeat(width as usize),
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::Unknown,
+        })
+    }
+
+    #[test]
+    fn test_usable_frames() {
+        assert!(is_usable(&frame(4, 40), Some(8)));
+        assert!(!is_usable(&frame(4, 8), Some(8)));
+        assert!(is_usable(&frame(4, 8), Some(7)));
+        assert!(is_usable(&frame(4, 0), None));
+        // The empty frames macOS sends while nothing changes
+        assert!(!is_usable(&frame(0, 40), None));
+    }
+}
@@ -0,0 +1,165 @@
+use std::time::Instant;
+
+use super::{CapturerEvent, SecureDesktopPolicy};
+use crate::frame::{ColorRange, Frame};
+
+// Follows the switches to and from the secure desktop and stands in for the
+// frames that can't be captured while it's shown
+#[derive(Debug)]
+pub(crate) struct SecureDesktop {
+    policy: SecureDesktopPolicy,
+    // When the secure desktop was shown, if it is
+    entered: Option<Instant>,
+    // The frame to freeze on or black out, kept unless pausing
+    last: Option<Frame>,
+}
+
+impl SecureDesktop {
+    pub fn new(policy: SecureDesktopPolicy) -> Self {
+        SecureDesktop {
+            policy,
+            entered: None,
+            last: None,
+        }
+    }
+
+    pub fn is_shown(&self) -> bool {
+        self.entered.is_some()
+    }
+
+    // Keeps a copy of a captured frame to stand in for the ones that follow
+    pub fn keep(&mut self, frame: &Frame) {
+        if self.policy != SecureDesktopPolicy::Pause {
+            self.last = Some(frame.clone());
+        }
+    }
+
+    // Records whether the secure desktop is shown at `time`, on the clock of
+    // the frames, returning the event for a switch
+    pub fn update(&mut self, shown: bool, now: Instant, time: u64) -> Option<CapturerEvent> {
+        match (self.entered, shown) {
+            (None, true) => {
+                self.entered = Some(now);
+                Some(CapturerEvent::SecureDesktopEntered { time })
+            }
+            (Some(entered), false) => {
+                self.entered = None;
+                Some(CapturerEvent::SecureDesktopExited {
+                    time,
+                    gap: now - entered,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    // The frame delivered in place of a captured one while the secure
+    // desktop is shown, None when pausing or before any frame was captured
+    pub fn synthesize(&self, display_time: u64) -> Option<Frame> {
+        if !self.is_shown() {
+            return None;
+        }
+        let mut frame = match self.policy {
+            SecureDesktopPolicy::Freeze => self.last.clone()?,
+            SecureDesktopPolicy::Black => blacked_out(self.last.as_ref()?)?,
+            SecureDesktopPolicy::Pause => return None,
+        };
+        *frame.display_time_mut() = display_time;
+        Some(frame)
+    }
+}
+
+// A black frame of the same format and size
+fn blacked_out(frame: &Frame) -> Option<Frame> {
+    let mut frame = frame.clone();
+    match &mut frame {
+        Frame::RGB(f) => f.data.fill(0),
+        Frame::RGBx(f) => f.data.fill(0),
+        Frame::XBGR(f) => f.data.fill(0),
+        Frame::BGRx(f) => f.data.fill(0),
+        Frame::BGR0(f) => f.data.fill(0),
+        Frame::Gray8(f) => f.data.fill(0),
+        Frame::BGRA(f) => f
+            .data
+            .chunks_exact_mut(4)
+            .for_each(|pixel| pixel.copy_from_slice(&[0, 0, 0, 255])),
+        Frame::YUVFrame(f) => {
+            let black = match f.color_range {
+                ColorRange::Limited => 16,
+                ColorRange::Full => 0,
+            };
+            f.luminance_bytes.fill(black);
+            f.chrominance_bytes.fill(128);
+        }
+        Frame::Delta(_)
+        | Frame::ScanlinePatch(_)
+        | Frame::Regions(_)
+        | Frame::Marker(_)
+        | Frame::Activity(_)
+        | Frame::Raw(_) => return None,
+    }
+    Some(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{BGRAFrame, ColorSpace, RowOrder};
+    use std::time::Duration;
+
+    fn frame() -> Frame {
+        Frame::BGRA(BGRAFrame {
+            display_time: 1,
+            width: 2,
+            height: 1,
+            data: vec![10, 20, 30, 40, 50, 60, 70, 80],
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::SRGB,
+        })
+    }
+
+    fn data(frame: Option<Frame>) -> Vec<u8> {
+        match frame {
+            Some(Frame::BGRA(f)) => {
+                assert_eq!(f.display_time, 5);
+                f.data
+            }
+            _ => panic!("expected a BGRA frame"),
+        }
+    }
+
+    #[test]
+    fn test_switches_and_synthesized_frames() {
+        let start = Instant::now();
+        let mut secure = SecureDesktop::new(SecureDesktopPolicy::Freeze);
+        secure.keep(&frame());
+        assert!(secure.synthesize(5).is_none());
+        assert!(secure.update(false, start, 0).is_none());
+
+        assert!(matches!(
+            secure.update(true, start, 100),
+            Some(CapturerEvent::SecureDesktopEntered { time: 100 })
+        ));
+        assert!(secure.update(true, start, 200).is_none());
+        assert_eq!(data(secure.synthesize(5)), [10, 20, 30, 40, 50, 60, 70, 80]);
+
+        let exited = secure.update(false, start + Duration::from_secs(3), 300);
+        match exited {
+            Some(CapturerEvent::SecureDesktopExited { time, gap }) => {
+                assert_eq!((time, gap), (300, Duration::from_secs(3)));
+            }
+            _ => panic!("expected SecureDesktopExited"),
+        }
+        assert!(secure.synthesize(5).is_none());
+
+        let mut secure = SecureDesktop::new(SecureDesktopPolicy::Black);
+        secure.keep(&frame());
+        secure.update(true, start, 0);
+        assert_eq!(data(secure.synthesize(5)), [0, 0, 0, 255, 0, 0, 0, 255]);
+
+        let mut secure = SecureDesktop::new(SecureDesktopPolicy::Pause);
+        secure.keep(&frame());
+        secure.update(true, start, 0);
+        assert!(secure.synthesize(5).is_none());
+    }
+}
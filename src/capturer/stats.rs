@@ -0,0 +1,213 @@
+use std::time::{Duration, Instant};
+
+/// Width of each bucket of [CaptureStats::interval_histogram]
+pub const INTERVAL_BUCKET: Duration = Duration::from_millis(1);
+/// Number of buckets in [CaptureStats::interval_histogram]. The last one
+/// holds every interval of a tenth of a second or more.
+pub const INTERVAL_BUCKETS: usize = 101;
+
+/// How frames were delivered since capture started, see [Capturer::stats](super::Capturer::stats)
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptureStats {
+    /// Frames returned by the capturer
+    pub frames: u64,
+    /// Frames per second from the first frame to the last, 0 until there
+    /// are two
+    pub achieved_fps: f64,
+    /// Time between consecutive frames. Bucket `i` counts the intervals from
+    /// `i` up to `i + 1` times [INTERVAL_BUCKET], a 144 Hz display fills
+    /// bucket 6 and a 60 Hz one bucket 16.
+    pub interval_histogram: Vec<u64>,
+    /// From starting capture to the first frame, None until it's returned
+    pub time_to_first_frame: Option<Duration>,
+    /// Whether capture was started from a [Capturer::warm_up](super::Capturer::warm_up)
+    /// session
+    pub warm_start: bool,
+    /// Frames the consumer fell behind on, skipped for newer ones with
+    /// [Latency::LowLatency](super::Latency::LowLatency). Capture keeps up
+    /// but the consumer doesn't.
+    pub consumer_drops: u64,
+    /// Frames older than [Options::frame_deadline](super::Options::frame_deadline)
+    /// when they were received, dropped rather than delivered late
+    pub late_drops: u64,
+    /// Frames the OS didn't produce, from gaps in the presentation times of
+    /// what it sent. The capture settings ask more than the compositor keeps
+    /// up with. None where frames only arrive when the screen changes, which
+    /// looks the same, as on Windows and Linux.
+    pub compositor_drops: Option<u64>,
+    /// Frames of [Options::constant_frame_rate](super::Options::constant_frame_rate)
+    /// or [Options::min_frame_rate](super::Options::min_frame_rate) that
+    /// repeated the one before, since no new frame arrived in time
+    pub repeated_frames: u64,
+}
+
+// Records frame deliveries without allocating
+#[derive(Debug)]
+pub(crate) struct StatsRecorder {
+    frames: u64,
+    first: Option<Instant>,
+    last: Option<Instant>,
+    histogram: [u64; INTERVAL_BUCKETS],
+    started: Option<Instant>,
+    warm_start: bool,
+    consumer_drops: u64,
+    late_drops: u64,
+}
+
+impl StatsRecorder {
+    pub fn new() -> Self {
+        StatsRecorder {
+            frames: 0,
+            first: None,
+            last: None,
+            histogram: [0; INTERVAL_BUCKETS],
+            started: None,
+            warm_start: false,
+            consumer_drops: 0,
+            late_drops: 0,
+        }
+    }
+
+    // A recorder for capture started at `now`
+    pub fn started(now: Instant, warm_start: bool) -> Self {
+        StatsRecorder {
+            started: Some(now),
+            warm_start,
+            ..StatsRecorder::new()
+        }
+    }
+
+    pub fn record(&mut self, now: Instant) {
+        if let Some(last) = self.last {
+            let bucket =
+                now.saturating_duration_since(last).as_nanos() / INTERVAL_BUCKET.as_nanos();
+            self.histogram[(bucket as usize).min(INTERVAL_BUCKETS - 1)] += 1;
+        }
+
+        self.frames += 1;
+        self.first.get_or_insert(now);
+        self.last = Some(now);
+    }
+
+    // A frame was skipped for a newer one
+    pub fn record_skipped(&mut self) {
+        self.consumer_drops += 1;
+    }
+
+    // A frame missed its deadline
+    pub fn record_late(&mut self) {
+        self.late_drops += 1;
+    }
+
+    pub fn stats(&self) -> CaptureStats {
+        let elapsed = match (self.first, self.last) {
+            (Some(first), Some(last)) => last.saturating_duration_since(first),
+            _ => Duration::ZERO,
+        };
+        let achieved_fps = match elapsed.is_zero() {
+            true => 0.0,
+            false => (self.frames - 1) as f64 / elapsed.as_secs_f64(),
+        };
+
+        CaptureStats {
+            frames: self.frames,
+            achieved_fps,
+            interval_histogram: self.histogram.to_vec(),
+            time_to_first_frame: self
+                .started
+                .zip(self.first)
+                .map(|(started, first)| first.saturating_duration_since(started)),
+            warm_start: self.warm_start,
+            consumer_drops: self.consumer_drops,
+            late_drops: self.late_drops,
+            compositor_drops: None,
+            repeated_frames: 0,
+        }
+    }
+}
+
+// Counts the frames missing between presentation times that should be
+// `interval` nanoseconds apart. Gaps up to half an interval late are jitter.
+#[cfg(any(target_os = "macos", test))]
+#[derive(Debug)]
+pub(crate) struct PresentationGaps {
+    interval: u64,
+    last: Option<u64>,
+    missed: u64,
+}
+
+#[cfg(any(target_os = "macos", test))]
+impl PresentationGaps {
+    pub fn new(interval: u64) -> Self {
+        PresentationGaps {
+            interval: interval.max(1),
+            last: None,
+            missed: 0,
+        }
+    }
+
+    pub fn record(&mut self, time: u64) {
+        if let Some(last) = self.last.replace(time) {
+            let intervals = (time.saturating_sub(last) + self.interval / 2) / self.interval;
+            self.missed += intervals.saturating_sub(1);
+        }
+    }
+
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_recorder() {
+        let start = Instant::now();
+        let mut recorder = StatsRecorder::started(start, true);
+        assert_eq!(recorder.stats().frames, 0);
+        assert_eq!(recorder.stats().achieved_fps, 0.0);
+        assert_eq!(recorder.stats().time_to_first_frame, None);
+
+        // Two seconds at 144 Hz after a quarter second to the first frame,
+        // then a stall
+        let first = start + Duration::from_millis(250);
+        for i in 0..=288 {
+            recorder.record(first + Duration::from_secs(2) * i / 288);
+        }
+        let stats = recorder.stats();
+        assert_eq!(stats.time_to_first_frame, Some(Duration::from_millis(250)));
+        assert!(stats.warm_start);
+        assert_eq!(stats.frames, 289);
+        assert!((stats.achieved_fps - 144.0).abs() < 0.01);
+        assert_eq!(stats.interval_histogram.len(), INTERVAL_BUCKETS);
+        assert_eq!(stats.interval_histogram[6], 288);
+
+        recorder.record(first + Duration::from_secs(3));
+        assert_eq!(recorder.stats().interval_histogram[INTERVAL_BUCKETS - 1], 1);
+        assert_eq!(recorder.stats().consumer_drops, 0);
+
+        recorder.record_skipped();
+        assert_eq!(recorder.stats().consumer_drops, 1);
+    }
+
+    #[test]
+    fn test_presentation_gaps() {
+        // 60 Hz with jitter, then three frames missing before the last one
+        let interval = 16_666_667;
+        let mut gaps = PresentationGaps::new(interval);
+        for time in [
+            0,
+            interval + 3_000_000,
+            2 * interval - 2_000_000,
+            3 * interval,
+        ] {
+            gaps.record(time);
+        }
+        assert_eq!(gaps.missed(), 0);
+
+        gaps.record(7 * interval + 1_000_000);
+        assert_eq!(gaps.missed(), 3);
+    }
+}
@@ -0,0 +1,203 @@
+use std::{
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use super::stats::{CaptureStats, StatsRecorder};
+use crate::frame::{get_fitted_size, get_subsampled_size, Frame};
+
+/// A second stream of small copies of the frames, see [Options::thumbnails](super::Options::thumbnails)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ThumbnailOptions {
+    /// Thumbnails fit in this size, keeping the frames' aspect ratio
+    pub max_width: u32,
+    pub max_height: u32,
+    /// Thumbnails per second at most, paced independently of the frames. 0
+    /// makes one for every frame.
+    pub fps: u32,
+    /// Make thumbnails by taking every `stride`th pixel of every `stride`th
+    /// row instead of filtering, see [Frame::subsampled]. Far cheaper, but
+    /// thin lines and text break up. The size then only depends on the
+    /// stride, `max_width` and `max_height` don't apply.
+    pub stride: Option<u32>,
+}
+
+impl Default for ThumbnailOptions {
+    fn default() -> Self {
+        ThumbnailOptions {
+            max_width: 320,
+            max_height: 180,
+            fps: 10,
+            stride: None,
+        }
+    }
+}
+
+// The size of the thumbnails of `frame_size` frames
+pub(crate) fn get_thumbnail_size(options: &ThumbnailOptions, frame_size: [u32; 2]) -> [u32; 2] {
+    let [width, height] = frame_size.map(|size| size as usize);
+    let (width, height) = match options.stride {
+        Some(stride) => get_subsampled_size(width, height, stride),
+        None => get_fitted_size(width, height, options.max_width, options.max_height),
+    };
+    [width as u32, height as u32]
+}
+
+/// A downscaled copy of a frame the capturer returned, see
+/// [Capturer::thumbnails](super::Capturer::thumbnails)
+#[derive(Debug, Clone)]
+pub struct Thumbnail {
+    /// Index of the full frame among the frames returned since capture
+    /// started, counting from 0
+    pub sequence: u64,
+    /// Has the display time of the full frame
+    pub frame: Frame,
+}
+
+// Makes thumbnails of the frames being delivered, paced to their own rate
+#[derive(Debug)]
+pub(crate) struct ThumbnailStream {
+    options: ThumbnailOptions,
+    tx: Option<mpsc::Sender<Thumbnail>>,
+    rx: Option<mpsc::Receiver<Thumbnail>>,
+    next_due: Option<Instant>,
+    stats: StatsRecorder,
+}
+
+impl ThumbnailStream {
+    pub fn new(options: ThumbnailOptions) -> Self {
+        let (tx, rx) = mpsc::channel();
+        ThumbnailStream {
+            options,
+            tx: Some(tx),
+            rx: Some(rx),
+            next_due: None,
+            stats: StatsRecorder::new(),
+        }
+    }
+
+    // Thumbnails are only made once the receiver is taken
+    pub fn take_receiver(&mut self) -> Option<mpsc::Receiver<Thumbnail>> {
+        self.rx.take()
+    }
+
+    pub fn restart(&mut self, now: Instant, warm_start: bool) {
+        self.next_due = None;
+        self.stats = StatsRecorder::started(now, warm_start);
+    }
+
+    // The thumbnail of `frame`, if one is due and wanted
+    pub fn downscale(&mut self, frame: &Frame, now: Instant) -> Option<Frame> {
+        if self.rx.is_some() || self.tx.is_none() {
+            return None;
+        }
+
+        if self.options.fps > 0 {
+            let interval = Duration::from_secs(1) / self.options.fps;
+            self.next_due = match self.next_due {
+                Some(due) if now < due => return None,
+                // Keep the average rate when frames arrive a little late
+                Some(due) if now < due + interval => Some(due + interval),
+                _ => Some(now + interval),
+            };
+        }
+
+        match self.options.stride {
+            Some(stride) => frame.subsampled(stride),
+            None => frame.downscaled(self.options.max_width, self.options.max_height),
+        }
+    }
+
+    // Send the thumbnail made for the frame that was just delivered as
+    // `sequence`
+    pub fn deliver(&mut self, thumbnail: Option<Frame>, sequence: u64, now: Instant) {
+        let (Some(frame), Some(tx)) = (thumbnail, &self.tx) else {
+            return;
+        };
+        match tx.send(Thumbnail { sequence, frame }) {
+            Ok(()) => self.stats.record(now),
+            // Nobody wants thumbnails anymore
+            Err(_) => self.tx = None,
+        }
+    }
+
+    pub fn stats(&self) -> CaptureStats {
+        self.stats.stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{ColorSpace, Gray8Frame, RowOrder};
+
+    fn frame(time: u64) -> Frame {
+        Frame::Gray8(Gray8Frame {
+            display_time: time,
+            width: 64,
+            height: 32,
+            data: vec![0; 64 * 32],
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::SRGB,
+        })
+    }
+
+    #[test]
+    fn test_thumbnails_are_paced_and_numbered() {
+        let mut stream = ThumbnailStream::new(ThumbnailOptions {
+            max_width: 16,
+            max_height: 16,
+            fps: 10,
+            stride: None,
+        });
+        let start = Instant::now();
+        stream.restart(start, false);
+
+        // Nothing is made before the receiver is taken
+        assert!(stream.downscale(&frame(0), start).is_none());
+        let rx = stream.take_receiver().unwrap();
+
+        // One second of 60 fps frames
+        for i in 0..60 {
+            let now = start + Duration::from_secs(1) * i / 60;
+            let thumbnail = stream.downscale(&frame(i as u64), now);
+            stream.deliver(thumbnail, i as u64, now);
+        }
+
+        let thumbnails: Vec<_> = rx.try_iter().collect();
+        assert_eq!(thumbnails.len(), 10);
+        assert_eq!(thumbnails[1].sequence, 6);
+        let Frame::Gray8(small) = &thumbnails[1].frame else {
+            panic!("wrong frame type");
+        };
+        assert_eq!((small.width, small.height, small.display_time), (16, 8, 6));
+        assert_eq!(stream.stats().frames, 10);
+
+        // Dropping the receiver stops thumbnails
+        drop(rx);
+        let now = start + Duration::from_secs(2);
+        let thumbnail = stream.downscale(&frame(0), now);
+        stream.deliver(thumbnail, 60, now);
+        assert!(stream
+            .downscale(&frame(0), now + Duration::from_secs(1))
+            .is_none());
+    }
+
+    #[test]
+    fn test_subsampled_thumbnails() {
+        let options = ThumbnailOptions {
+            fps: 0,
+            stride: Some(5),
+            ..Default::default()
+        };
+        let mut stream = ThumbnailStream::new(options);
+        let _rx = stream.take_receiver().unwrap();
+        let thumbnail = stream.downscale(&frame(0), Instant::now()).unwrap();
+        assert_eq!(thumbnail.size(), (13, 7));
+        assert_eq!(get_thumbnail_size(&options, [64, 32]), [13, 7]);
+
+        let fitted = ThumbnailOptions::default();
+        assert_eq!(get_thumbnail_size(&fitted, [1920, 1080]), [320, 180]);
+    }
+}
@@ -0,0 +1,236 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{atomic::AtomicBool, atomic::Ordering, mpsc, Arc},
+};
+
+use super::{panic_message, Area, CallbackPanic, CapturerEvent, Point};
+use crate::frame::{
+    composite_cursor, convert_p3_to_srgb, convert_yuv_to_bgra, CursorImage, Frame, LumaWeights,
+};
+
+/// A step of [Options::transforms](super::Options::transforms), run on every
+/// frame in the capture pipeline. Closures taking and returning a frame are
+/// transforms too, for anything the built-in [Transform]s don't cover.
+pub trait FrameTransform: Send + Sync {
+    /// The transformed frame, or None to drop it. Frames can be of any
+    /// type, transforms should pass on the ones they don't handle.
+    fn apply(&self, frame: Frame) -> Option<Frame>;
+}
+
+impl<F: Fn(Frame) -> Option<Frame> + Send + Sync> FrameTransform for F {
+    fn apply(&self, frame: Frame) -> Option<Frame> {
+        self(frame)
+    }
+}
+
+impl std::fmt::Debug for dyn FrameTransform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("FrameTransform(..)")
+    }
+}
+
+/// The built-in [FrameTransform]s. Frames they can't be applied to, like
+/// raw frames, are passed on as they are.
+#[derive(Debug, Clone)]
+pub enum Transform {
+    /// Crops to this area in frame pixels, clamped to the frame. YUV frames
+    /// and frames the area is fully outside of are passed on whole.
+    Crop(Area),
+    /// Box filters frames down to fit in this [width, height], keeping their
+    /// aspect ratio, see [Frame::downscaled]. YUV frames become BGRA.
+    Scale([u32; 2]),
+    /// Converts frames to [Frame::Gray8] of the same size
+    Grayscale(LumaWeights),
+    /// Converts Display P3 frames to sRGB, see [convert_p3_to_srgb]
+    ConvertToSRGB,
+    /// Converts YUV frames to BGRA
+    ConvertToBGRA,
+    /// Blends `image` over frames with 4 bytes per pixel, its hotspot at
+    /// `position` in frame pixels, e.g. a logo or a "recording" badge
+    Overlay { image: CursorImage, position: Point },
+}
+
+impl FrameTransform for Transform {
+    fn apply(&self, frame: Frame) -> Option<Frame> {
+        let transformed = match self {
+            Transform::Crop(area) => frame.cropped(area),
+            Transform::Scale([width, height]) => frame.downscaled(*width, *height),
+            Transform::Grayscale(weights) => {
+                let (width, height) = frame.size();
+                frame
+                    .to_gray8(width.max(0) as u32, height.max(0) as u32, *weights)
+                    .map(Frame::Gray8)
+            }
+            Transform::ConvertToSRGB => {
+                let mut frame = frame;
+                convert_p3_to_srgb(&mut frame);
+                return Some(frame);
+            }
+            Transform::ConvertToBGRA => match &frame {
+                Frame::YUVFrame(yuv) => Some(Frame::BGRA(convert_yuv_to_bgra(yuv))),
+                _ => None,
+            },
+            Transform::Overlay { image, position } => {
+                let mut frame = frame;
+                composite_cursor(&mut frame, image, (position.x, position.y));
+                return Some(frame);
+            }
+        };
+        Some(transformed.unwrap_or(frame))
+    }
+}
+
+// Runs the transforms of Options::transforms in order. A panicking transform
+// drops its frame and is reported as CapturerEvent::CallbackPanicked.
+pub(crate) struct TransformChain {
+    transforms: Vec<Arc<dyn FrameTransform>>,
+    on_panic: CallbackPanic,
+    // Set once a transform panicked with CallbackPanic::Stop
+    stopped: AtomicBool,
+}
+
+impl TransformChain {
+    // None if there's nothing to run
+    pub fn new(transforms: &[Arc<dyn FrameTransform>], on_panic: CallbackPanic) -> Option<Self> {
+        (!transforms.is_empty()).then(|| TransformChain {
+            transforms: transforms.to_vec(),
+            on_panic,
+            stopped: AtomicBool::new(false),
+        })
+    }
+
+    // Whether a transform panicked with CallbackPanic::Stop
+    pub fn stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+
+    pub fn apply(&self, mut frame: Frame, events: &mpsc::Sender<CapturerEvent>) -> Option<Frame> {
+        if self.stopped.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        for transform in &self.transforms {
+            match panic::catch_unwind(AssertUnwindSafe(|| transform.apply(frame))) {
+                Ok(transformed) => frame = transformed?,
+                Err(payload) => {
+                    let message = panic_message(payload);
+                    let _ = events.send(CapturerEvent::CallbackPanicked { message });
+                    if self.on_panic == CallbackPanic::Stop {
+                        self.stopped.store(true, Ordering::Relaxed);
+                    }
+                    return None;
+                }
+            }
+        }
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        capturer::Size,
+        frame::{BGRAFrame, ColorSpace, RawFrame, RawPixelFormat, RowOrder},
+    };
+
+    fn bgra(width: i32, height: i32, pixel: [u8; 4]) -> Frame {
+        Frame::BGRA(BGRAFrame {
+            display_time: 0,
+            width,
+            height,
+            data: pixel.repeat((width * height) as usize),
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::SRGB,
+        })
+    }
+
+    fn chain(transforms: Vec<Arc<dyn FrameTransform>>, on_panic: CallbackPanic) -> TransformChain {
+        TransformChain::new(&transforms, on_panic).unwrap()
+    }
+
+    #[test]
+    fn test_transform_chain() {
+        let (events, _received) = mpsc::channel();
+        let chain = chain(
+            vec![
+                Arc::new(Transform::Crop(Area {
+                    origin: Point { x: 2.0, y: 0.0 },
+                    size: Size {
+                        width: 8.0,
+                        height: 4.0,
+                    },
+                })),
+                Arc::new(Transform::Scale([4, 4])),
+                Arc::new(Transform::Overlay {
+                    image: CursorImage {
+                        width: 1,
+                        height: 1,
+                        data: vec![0, 0, 255, 255],
+                        hotspot: (0, 0),
+                    },
+                    position: Point { x: 1.0, y: 1.0 },
+                }),
+                Arc::new(|mut frame: Frame| {
+                    *frame.display_time_mut() += 1;
+                    Some(frame)
+                }),
+            ],
+            CallbackPanic::Stop,
+        );
+
+        // Cropped to 8x4, scaled to 4x2 and the red pixel drawn in the middle
+        let Some(Frame::BGRA(frame)) = chain.apply(bgra(10, 4, [10, 20, 30, 255]), &events) else {
+            panic!("not a BGRA frame");
+        };
+        assert_eq!((frame.width, frame.height, frame.display_time), (4, 2, 1));
+        assert_eq!(&frame.data[..4], &[10, 20, 30, 255]);
+        assert_eq!(&frame.data[20..24], &[0, 0, 255, 255]);
+
+        // Raw frames only go through the closure
+        let raw = Frame::Raw(RawFrame {
+            display_time: 0,
+            width: 10,
+            height: 4,
+            format: RawPixelFormat::Bgra8,
+            data: vec![0; 160],
+            color_space: ColorSpace::SRGB,
+        });
+        let Some(Frame::Raw(raw)) = chain.apply(raw, &events) else {
+            panic!("not a raw frame");
+        };
+        assert_eq!((raw.width, raw.display_time), (10, 1));
+
+        let gray = Transform::Grayscale(LumaWeights::BT709).apply(bgra(3, 2, [0; 4]));
+        assert!(matches!(gray, Some(Frame::Gray8(f)) if (f.width, f.height) == (3, 2)));
+    }
+
+    #[test]
+    fn test_transform_panics() {
+        let (events, received) = mpsc::channel();
+        let dropping: Arc<dyn FrameTransform> = Arc::new(|_| None);
+        let panicking: Arc<dyn FrameTransform> = Arc::new(|_| panic!("transform failed"));
+
+        let continuing = chain(vec![panicking.clone()], CallbackPanic::Continue);
+        assert!(continuing.apply(bgra(2, 2, [0; 4]), &events).is_none());
+        assert!(continuing.apply(bgra(2, 2, [0; 4]), &events).is_none());
+        assert_eq!(received.try_iter().count(), 2);
+
+        // Stopped chains don't call their transforms again
+        let stopping = chain(vec![panicking], CallbackPanic::Stop);
+        assert!(stopping.apply(bgra(2, 2, [0; 4]), &events).is_none());
+        assert!(stopping.apply(bgra(2, 2, [0; 4]), &events).is_none());
+        assert!(matches!(
+            received.try_iter().collect::<Vec<_>>()[..],
+            [CapturerEvent::CallbackPanicked { ref message }] if message == "transform failed"
+        ));
+
+        // Frames a transform drops don't reach the next one
+        let dropped = chain(
+            vec![dropping, Arc::new(|_| panic!("unreachable"))],
+            CallbackPanic::Stop,
+        );
+        assert!(dropped.apply(bgra(2, 2, [0; 4]), &events).is_none());
+        assert!(received.try_iter().next().is_none());
+    }
+}
@@ -0,0 +1,156 @@
+use super::Area;
+use crate::frame::Frame;
+
+/// What fires a trigger, see [TriggerOptions]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TriggerCondition {
+    /// The area differs from how it looked in the first frame
+    Changes,
+    /// The area looks like this reference: its pixels packed row after row
+    /// in the format of the frames, as from [RegionView::to_vec](crate::frame::RegionView::to_vec)
+    Matches(Vec<u8>),
+}
+
+/// Holds frames back until an area of them changes, see [Options::trigger](super::Options::trigger)
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TriggerOptions {
+    /// The watched area in frame pixels
+    pub area: Area,
+    /// Mean difference of the area's bytes, from 0 to 255, to the first
+    /// frame above which it has changed, or to the reference up to which it
+    /// matches
+    pub threshold: f64,
+    pub condition: TriggerCondition,
+}
+
+impl Default for TriggerOptions {
+    fn default() -> Self {
+        TriggerOptions {
+            area: Area::default(),
+            threshold: 8.0,
+            condition: TriggerCondition::Changes,
+        }
+    }
+}
+
+// Compares the watched area of each frame until the trigger fires
+#[derive(Debug)]
+pub(crate) struct Trigger {
+    options: TriggerOptions,
+    baseline: Option<Vec<u8>>,
+    fired: bool,
+}
+
+impl Trigger {
+    pub fn new(options: TriggerOptions) -> Self {
+        Trigger {
+            options,
+            baseline: None,
+            fired: false,
+        }
+    }
+
+    // Whether the trigger fired with this frame. Frames it can't look into,
+    // such as YUV frames, fire it at once.
+    fn fires(&mut self, frame: &Frame) -> bool {
+        let regions = frame.regions(std::slice::from_ref(&self.options.area));
+        let Some(region) = regions.get(0) else {
+            return true;
+        };
+        let pixels = region.to_vec();
+
+        match &self.options.condition {
+            TriggerCondition::Changes => match &self.baseline {
+                Some(baseline) => difference(baseline, &pixels) > self.options.threshold,
+                None => {
+                    self.baseline = Some(pixels);
+                    false
+                }
+            },
+            TriggerCondition::Matches(reference) => {
+                difference(reference, &pixels) <= self.options.threshold
+            }
+        }
+    }
+
+    // Whether to deliver this frame, true from the one that fired the trigger on
+    pub fn check(&mut self, frame: &Frame) -> bool {
+        if !self.fired {
+            self.fired = self.fires(frame);
+        }
+        self.fired
+    }
+
+    pub fn fired(&self) -> bool {
+        self.fired
+    }
+}
+
+// Mean absolute difference of two buffers, the maximum for different sizes
+fn difference(a: &[u8], b: &[u8]) -> f64 {
+    if a.len() != b.len() {
+        return 255.0;
+    }
+    if a.is_empty() {
+        return 0.0;
+    }
+
+    let sum: u64 = a.iter().zip(b).map(|(a, b)| a.abs_diff(*b) as u64).sum();
+    sum as f64 / a.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capturer::{Point, Size};
+    use crate::frame::{ColorSpace, Gray8Frame, RowOrder};
+
+    fn frame(pixels: [u8; 4]) -> Frame {
+        Frame::Gray8(Gray8Frame {
+            display_time: 0,
+            width: 2,
+            height: 2,
+            data: pixels.to_vec(),
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::SRGB,
+        })
+    }
+
+    fn top_row() -> Area {
+        Area {
+            origin: Point { x: 0.0, y: 0.0 },
+            size: Size {
+                width: 2.0,
+                height: 1.0,
+            },
+        }
+    }
+
+    #[test]
+    fn test_fires_on_change() {
+        let mut trigger = Trigger::new(TriggerOptions {
+            area: top_row(),
+            threshold: 8.0,
+            condition: TriggerCondition::Changes,
+        });
+        assert!(!trigger.check(&frame([100, 100, 0, 0])));
+        // Noise and changes outside the area don't count
+        assert!(!trigger.check(&frame([104, 98, 255, 255])));
+        assert!(trigger.check(&frame([100, 200, 0, 0])));
+        // Once fired, every frame is delivered
+        assert!(trigger.check(&frame([100, 100, 0, 0])));
+    }
+
+    #[test]
+    fn test_fires_on_match() {
+        let mut trigger = Trigger::new(TriggerOptions {
+            area: top_row(),
+            threshold: 2.0,
+            condition: TriggerCondition::Matches(vec![255, 0]),
+        });
+        assert!(!trigger.check(&frame([0, 255, 0, 0])));
+        assert!(trigger.check(&frame([254, 1, 7, 7])));
+    }
+}
@@ -0,0 +1,494 @@
+use std::error::Error;
+
+use super::{Area, CropOverflow, Latency, Options, Size, WindowSubregion};
+use crate::frame::FrameType;
+
+/// A problem with [Options] found by [Options::validate]
+#[derive(Debug, Clone)]
+pub enum ConfigError {
+    /// `option` holds a value capture can't work with, like a zero size
+    InvalidValue {
+        option: &'static str,
+        reason: String,
+    },
+    /// [Options::crop_area] has no pixels in the target, whose size is
+    /// `target` in the crop area's units, or doesn't fit it with
+    /// [CropOverflow::Strict]
+    CropOutOfBounds { crop_area: Area, target: Size },
+    /// [Options::fps] is above the refresh rate of the target's display, so
+    /// fewer frames arrive than asked for
+    FpsAboveRefreshRate { fps: u32, refresh_rate: u32 },
+    /// The platform doesn't deliver frames of this type, and none of
+    /// [Options::output_types] either
+    UnsupportedOutputType(FrameType),
+    /// `option` is ignored or replaced because `conflicts_with` is set
+    Conflict {
+        option: &'static str,
+        conflicts_with: &'static str,
+    },
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::InvalidValue { option, reason } => write!(f, "{option} {reason}"),
+            ConfigError::CropOutOfBounds { crop_area, target } => write!(
+                f,
+                "The crop area at {}, {} of {}x{} doesn't fit the {}x{} target",
+                crop_area.origin.x,
+                crop_area.origin.y,
+                crop_area.size.width,
+                crop_area.size.height,
+                target.width,
+                target.height
+            ),
+            ConfigError::FpsAboveRefreshRate { fps, refresh_rate } => write!(
+                f,
+                "{fps} fps is more than the display's refresh rate of {refresh_rate} Hz"
+            ),
+            ConfigError::UnsupportedOutputType(output_type) => {
+                write!(
+                    f,
+                    "{output_type:?} frames aren't delivered on this platform"
+                )
+            }
+            ConfigError::Conflict {
+                option,
+                conflicts_with,
+            } => write!(f, "{option} isn't applied with {conflicts_with}"),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+// What the checks need to know about the platform and the target, None
+// where it's unknown
+#[derive(Debug, Default)]
+struct Environment {
+    // In the units of Options::crop_area
+    target_size: Option<Size>,
+    refresh_rate: Option<u32>,
+    delivers_output_type: Option<bool>,
+}
+
+impl Environment {
+    fn new(options: &Options) -> Self {
+        #[cfg(not(target_os = "linux"))]
+        let target_size = {
+            let target = options.target.clone().unwrap_or_else(|| {
+                crate::targets::Target::Display(crate::targets::get_main_display())
+            });
+            let (width, height) = crate::targets::get_target_dimensions(&target);
+            // Windows measures targets in physical pixels, crop areas in points
+            #[cfg(target_os = "windows")]
+            let scale = crate::targets::get_scale_factor(&target);
+            #[cfg(target_os = "macos")]
+            let scale = 1.0;
+            Some(Size {
+                width: width as f64 / scale,
+                height: height as f64 / scale,
+            })
+        };
+        // Linux targets are picked through the portal once capture starts
+        #[cfg(target_os = "linux")]
+        let target_size = None;
+
+        Environment {
+            target_size,
+            refresh_rate: super::engine::get_refresh_rate(options),
+            delivers_output_type: match super::negotiated::pick_output_type(options) {
+                Some(_) => Some(true),
+                None => super::negotiated::is_delivered(options.output_type),
+            },
+        }
+    }
+}
+
+impl Options {
+    /// Checks the options for problems before capture starts, e.g. for a
+    /// settings dialog to point them out, and returns all of them. Options
+    /// that only lack support on this platform aren't problems, they're
+    /// reported as [Downgrade](super::Downgrade)s once capture starts.
+    ///
+    /// The target's size and refresh rate are looked up, the main display's
+    /// if there's no target. Linux targets are only known once capture
+    /// starts, so the crop area isn't checked against them there.
+    pub fn validate(&self) -> Result<(), Vec<ConfigError>> {
+        let errors = check(self, &Environment::new(self));
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors),
+        }
+    }
+}
+
+fn check(options: &Options, environment: &Environment) -> Vec<ConfigError> {
+    let mut errors = Vec::new();
+    let invalid = |option, reason: &str| ConfigError::InvalidValue {
+        option,
+        reason: reason.to_string(),
+    };
+
+    let zero = |size: [u32; 2]| size[0] == 0 || size[1] == 0;
+    if options.max_output_size.is_some_and(zero) {
+        errors.push(invalid("max_output_size", "has no pixels"));
+    }
+    if options.max_dimension == Some(0) {
+        errors.push(invalid("max_dimension", "is zero"));
+    }
+    if let Some(grayscale) = &options.grayscale {
+        if zero([grayscale.width, grayscale.height]) {
+            errors.push(invalid("grayscale", "frames have no pixels"));
+        }
+    }
+    if options.timestamp_base == Some(0) {
+        errors.push(invalid("timestamp_base", "is zero ticks per second"));
+    }
+    if options.constant_frame_rate == Some(0) {
+        errors.push(invalid("constant_frame_rate", "is zero frames per second"));
+    }
+    if options.min_frame_rate == Some(0) {
+        errors.push(invalid("min_frame_rate", "is zero frames per second"));
+    }
+    if options
+        .frame_deadline
+        .is_some_and(|deadline| deadline.is_zero())
+    {
+        errors.push(invalid("frame_deadline", "drops every frame"));
+    }
+    if options.buffer_pool == Some(0) {
+        errors.push(invalid("buffer_pool", "keeps no buffers"));
+    }
+    if options.frame_ring == Some(0) {
+        errors.push(invalid("frame_ring", "has no slots"));
+    }
+    if options
+        .aspect_crop
+        .as_ref()
+        .is_some_and(|crop| zero(crop.ratio))
+    {
+        errors.push(invalid("aspect_crop", "has no width or height"));
+    }
+    if let Some(follow) = &options.follow_action {
+        if !(follow.zoom > 0.0 && follow.zoom <= 1.0) {
+            errors.push(invalid("follow_action", "zoom isn't above 0 and up to 1"));
+        } else if !(0.0..=1.0).contains(&follow.dead_zone) {
+            errors.push(invalid("follow_action", "dead zone isn't from 0 to 1"));
+        }
+    }
+    if options
+        .thumbnails
+        .is_some_and(|thumbnails| thumbnails.stride == Some(0))
+    {
+        errors.push(invalid("thumbnails", "stride is zero"));
+    }
+    if let Some(delta) = &options.delta {
+        if delta.tile_size == 0 {
+            errors.push(invalid("delta", "tiles have no pixels"));
+        }
+    }
+
+    if let Some(crop_area) = &options.crop_area {
+        if crop_area.size.width <= 0.0 || crop_area.size.height <= 0.0 {
+            errors.push(invalid("crop_area", "has no pixels"));
+        } else if let Some(target) = &environment.target_size {
+            let (left, top) = (crop_area.origin.x, crop_area.origin.y);
+            let (right, bottom) = (left + crop_area.size.width, top + crop_area.size.height);
+            let outside =
+                right <= 0.0 || bottom <= 0.0 || left >= target.width || top >= target.height;
+            let overflows =
+                left < 0.0 || top < 0.0 || right > target.width || bottom > target.height;
+            let fails = match options.crop_overflow {
+                CropOverflow::Clamp => outside,
+                CropOverflow::Strict => overflows,
+                CropOverflow::Fill(_) | CropOverflow::Shift => false,
+            };
+            if fails {
+                errors.push(ConfigError::CropOutOfBounds {
+                    crop_area: crop_area.clone(),
+                    target: target.clone(),
+                });
+            }
+        }
+    }
+
+    if let Some(refresh_rate) = environment.refresh_rate {
+        if options.fps > refresh_rate {
+            errors.push(ConfigError::FpsAboveRefreshRate {
+                fps: options.fps,
+                refresh_rate,
+            });
+        }
+    }
+
+    if environment.delivers_output_type == Some(false) {
+        errors.push(ConfigError::UnsupportedOutputType(options.output_type));
+    }
+
+    let conflict = |option, conflicts_with| ConfigError::Conflict {
+        option,
+        conflicts_with,
+    };
+    if options.crop_area.is_some() && !matches!(options.window_subregion, WindowSubregion::Whole) {
+        errors.push(conflict("crop_area", "window_subregion"));
+    }
+    if options.aspect_crop.is_some() && !matches!(options.window_subregion, WindowSubregion::Whole)
+    {
+        errors.push(conflict("aspect_crop", "window_subregion"));
+    }
+    if options.delta.is_some() && options.scanline_patches.is_some() {
+        errors.push(conflict("scanline_patches", "delta"));
+    }
+    let encoding = match (&options.delta, &options.scanline_patches) {
+        (Some(_), _) => Some("delta"),
+        (None, Some(_)) => Some("scanline_patches"),
+        (None, None) => None,
+    };
+    let pacing = [
+        ("constant_frame_rate", options.constant_frame_rate.is_some()),
+        ("min_frame_rate", options.min_frame_rate.is_some()),
+        ("activity", options.activity.is_some()),
+        ("external_trigger", options.external_trigger),
+    ];
+    for (option, set) in pacing {
+        if let (true, Some(encoding)) = (set, encoding) {
+            errors.push(conflict(option, encoding));
+        }
+    }
+    if options.content_aligned {
+        for (option, set) in [&pacing[0], &pacing[1], &pacing[3]] {
+            if *set {
+                errors.push(conflict("content_aligned", option));
+            }
+        }
+    }
+    if encoding.is_none() && options.external_trigger {
+        for (option, set) in &pacing[..3] {
+            if *set {
+                errors.push(conflict(option, "external_trigger"));
+            }
+        }
+    } else if encoding.is_none() {
+        if options.activity.is_some() && options.constant_frame_rate.is_some() {
+            errors.push(conflict("constant_frame_rate", "activity"));
+        }
+        if options.min_frame_rate.is_some() {
+            if options.activity.is_some() {
+                errors.push(conflict("min_frame_rate", "activity"));
+            } else if options.constant_frame_rate.is_some() {
+                errors.push(conflict("min_frame_rate", "constant_frame_rate"));
+            }
+        }
+    }
+    if options.latency == Latency::LowLatency && options.fps != 0 {
+        errors.push(conflict("fps", "latency"));
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capturer::{
+        ActivityOptions, AspectCrop, DeltaOptions, FollowActionOptions, GrayscaleOptions, Point,
+        ScanlineOptions, ThumbnailOptions,
+    };
+
+    fn area(x: f64, y: f64, width: f64, height: f64) -> Area {
+        Area {
+            origin: Point { x, y },
+            size: Size { width, height },
+        }
+    }
+
+    fn display() -> Environment {
+        Environment {
+            target_size: Some(Size {
+                width: 1920.0,
+                height: 1080.0,
+            }),
+            refresh_rate: Some(60),
+            delivers_output_type: Some(true),
+        }
+    }
+
+    #[test]
+    fn test_valid_options() {
+        let options = Options {
+            fps: 60,
+            crop_area: Some(area(0.0, 0.0, 1920.0, 1080.0)),
+            ..Default::default()
+        };
+        assert!(check(&options, &display()).is_empty());
+        // Nothing is known about Linux targets
+        assert!(check(&options, &Environment::default()).is_empty());
+    }
+
+    #[test]
+    fn test_invalid_values() {
+        let options = Options {
+            max_output_size: Some([1280, 0]),
+            grayscale: Some(GrayscaleOptions {
+                width: 0,
+                ..Default::default()
+            }),
+            timestamp_base: Some(0),
+            min_frame_rate: Some(0),
+            frame_ring: Some(0),
+            crop_area: Some(area(10.0, 10.0, 0.0, 100.0)),
+            aspect_crop: Some(AspectCrop {
+                ratio: [16, 0],
+                ..Default::default()
+            }),
+            follow_action: Some(FollowActionOptions {
+                zoom: 0.0,
+                ..Default::default()
+            }),
+            thumbnails: Some(ThumbnailOptions {
+                stride: Some(0),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let errors = check(&options, &display());
+        let options: Vec<&str> = errors
+            .iter()
+            .map(|error| match error {
+                ConfigError::InvalidValue { option, .. } => *option,
+                _ => panic!("expected invalid values, got {error:?}"),
+            })
+            .collect();
+        assert_eq!(
+            options,
+            [
+                "max_output_size",
+                "grayscale",
+                "timestamp_base",
+                "min_frame_rate",
+                "frame_ring",
+                "aspect_crop",
+                "follow_action",
+                "thumbnails",
+                "crop_area"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_crop_bounds() {
+        let check_crop = |crop_area: Area, crop_overflow: CropOverflow| {
+            let options = Options {
+                crop_area: Some(crop_area),
+                crop_overflow,
+                ..Default::default()
+            };
+            check(&options, &display())
+        };
+
+        // Partly outside is clamped, unless it's strict
+        let overflowing = area(1800.0, 0.0, 400.0, 300.0);
+        assert!(check_crop(overflowing.clone(), CropOverflow::Clamp).is_empty());
+        assert!(check_crop(overflowing.clone(), CropOverflow::Fill([0, 0, 0])).is_empty());
+        assert!(matches!(
+            &check_crop(overflowing, CropOverflow::Strict)[..],
+            [ConfigError::CropOutOfBounds { target, .. }] if target.width == 1920.0
+        ));
+
+        // Wholly outside leaves nothing to capture
+        assert!(matches!(
+            &check_crop(area(2000.0, 0.0, 100.0, 100.0), CropOverflow::Clamp)[..],
+            [ConfigError::CropOutOfBounds { .. }]
+        ));
+    }
+
+    #[test]
+    fn test_fps_and_output_type() {
+        let options = Options {
+            fps: 120,
+            ..Default::default()
+        };
+        let environment = Environment {
+            delivers_output_type: Some(false),
+            ..display()
+        };
+        assert!(matches!(
+            &check(&options, &environment)[..],
+            [
+                ConfigError::FpsAboveRefreshRate {
+                    fps: 120,
+                    refresh_rate: 60
+                },
+                ConfigError::UnsupportedOutputType(_)
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let conflicts = |options: Options| -> Vec<(&'static str, &'static str)> {
+            check(&options, &display())
+                .into_iter()
+                .map(|error| match error {
+                    ConfigError::Conflict {
+                        option,
+                        conflicts_with,
+                    } => (option, conflicts_with),
+                    _ => panic!("expected conflicts, got {error:?}"),
+                })
+                .collect()
+        };
+
+        assert_eq!(
+            conflicts(Options {
+                delta: Some(DeltaOptions::default()),
+                scanline_patches: Some(ScanlineOptions::default()),
+                constant_frame_rate: Some(30),
+                ..Default::default()
+            }),
+            [
+                ("scanline_patches", "delta"),
+                ("constant_frame_rate", "delta")
+            ]
+        );
+        assert_eq!(
+            conflicts(Options {
+                constant_frame_rate: Some(30),
+                min_frame_rate: Some(5),
+                ..Default::default()
+            }),
+            [("min_frame_rate", "constant_frame_rate")]
+        );
+        assert_eq!(
+            conflicts(Options {
+                content_aligned: true,
+                external_trigger: true,
+                ..Default::default()
+            }),
+            [("content_aligned", "external_trigger")]
+        );
+        assert_eq!(
+            conflicts(Options {
+                external_trigger: true,
+                min_frame_rate: Some(5),
+                activity: Some(ActivityOptions::default()),
+                ..Default::default()
+            }),
+            [
+                ("min_frame_rate", "external_trigger"),
+                ("activity", "external_trigger")
+            ]
+        );
+        assert_eq!(
+            conflicts(Options {
+                fps: 30,
+                latency: Latency::LowLatency,
+                crop_area: Some(area(0.0, 0.0, 100.0, 100.0)),
+                window_subregion: WindowSubregion::Static(area(0.0, 0.0, 50.0, 50.0)),
+                ..Default::default()
+            }),
+            [("crop_area", "window_subregion"), ("fps", "latency")]
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use crate::frame::Frame;
+
+/// When warm-up ends, see [WarmupOptions]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WarmupEnd {
+    /// Once `frames` frames were discarded
+    #[default]
+    AfterFrames,
+    /// At the first frame with no color channel above `black_tolerance`,
+    /// see [Frame::is_black], or once `frames` frames were discarded if
+    /// that comes first
+    FirstUsableFrame { black_tolerance: u8 },
+}
+
+/// Frames captured but not delivered when capture starts, while the OS
+/// pipeline settles, see [Options::warmup](super::Options::warmup)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WarmupOptions {
+    /// The most frames discarded, 0 delivers every frame
+    pub frames: u32,
+    pub end: WarmupEnd,
+}
+
+impl Default for WarmupOptions {
+    fn default() -> Self {
+        WarmupOptions {
+            frames: 1,
+            end: WarmupEnd::default(),
+        }
+    }
+}
+
+// Discards frames from the start of each session until warm-up ends
+#[derive(Debug)]
+pub(crate) struct Warmup {
+    options: WarmupOptions,
+    discarded: u32,
+    done: bool,
+}
+
+impl Warmup {
+    pub fn new(options: WarmupOptions) -> Self {
+        Warmup {
+            options,
+            discarded: 0,
+            done: options.frames == 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Warmup::new(self.options);
+    }
+
+    // Whether to deliver this frame. Returns the frames discarded along
+    // with true once, for the frame that ends warm-up.
+    pub fn check(&mut self, frame: &Frame) -> (bool, Option<u32>) {
+        if self.done {
+            return (true, None);
+        }
+        // The empty frames macOS sends while nothing changes don't count
+        let (width, height) = frame.size();
+        if width <= 0 || height <= 0 {
+            return (false, None);
+        }
+
+        let usable = match self.options.end {
+            WarmupEnd::AfterFrames => false,
+            WarmupEnd::FirstUsableFrame { black_tolerance } => !frame.is_black(black_tolerance),
+        };
+        if !usable && self.discarded < self.options.frames {
+            self.discarded += 1;
+            return (false, None);
+        }
+        self.done = true;
+        (true, Some(self.discarded))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{ColorSpace, Gray8Frame, RowOrder};
+
+    fn frame(width: i32, value: u8) -> Frame {
+        Frame::Gray8(Gray8Frame {
+            display_time: 0,
+            width,
+            height: 1,
+            data: vec![value; width as usize],
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::SRGB,
+        })
+    }
+
+    #[test]
+    fn test_warmup_ends() {
+        let mut warmup = Warmup::new(WarmupOptions {
+            frames: 2,
+            end: WarmupEnd::AfterFrames,
+        });
+        assert_eq!(warmup.check(&frame(2, 0)), (false, None));
+        assert_eq!(warmup.check(&frame(0, 0)), (false, None));
+        assert_eq!(warmup.check(&frame(2, 100)), (false, None));
+        assert_eq!(warmup.check(&frame(2, 0)), (true, Some(2)));
+        assert_eq!(warmup.check(&frame(2, 0)), (true, None));
+
+        warmup.reset();
+        assert_eq!(warmup.check(&frame(2, 100)), (false, None));
+
+        let mut warmup = Warmup::new(WarmupOptions {
+            frames: 3,
+            end: WarmupEnd::FirstUsableFrame { black_tolerance: 8 },
+        });
+        assert_eq!(warmup.check(&frame(2, 4)), (false, None));
+        assert_eq!(warmup.check(&frame(2, 100)), (true, Some(1)));
+
+        // Black frames don't hold it up for longer than `frames`
+        let mut warmup = Warmup::new(WarmupOptions {
+            frames: 1,
+            end: WarmupEnd::FirstUsableFrame { black_tolerance: 8 },
+        });
+        assert_eq!(warmup.check(&frame(2, 0)), (false, None));
+        assert_eq!(warmup.check(&frame(2, 0)), (true, Some(1)));
+
+        let mut none = Warmup::new(WarmupOptions {
+            frames: 0,
+            ..Default::default()
+        });
+        assert_eq!(none.check(&frame(2, 0)), (true, None));
+    }
+}
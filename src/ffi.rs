@@ -0,0 +1,251 @@
+//! A C interface to capture the main display from other languages, with
+//! the `ffi` feature. Build scap as a `cdylib` or `staticlib` to link it.
+//!
+//! Frames are handed to a callback on the thread calling
+//! [scap_capturer_dispatch], so no Rust type crosses the boundary:
+//!
+//! ```c
+//! ScapCapturer *capturer = scap_capturer_new(60, true);
+//! scap_set_frame_callback(capturer, on_frame, state);
+//! if (capturer && scap_capturer_start(capturer) == 0) {
+//!     while (recording && scap_capturer_dispatch(capturer, 100) >= 0) {}
+//!     scap_capturer_stop(capturer);
+//! }
+//! scap_capturer_free(capturer);
+//! ```
+
+use std::{
+    borrow::Cow,
+    ffi::c_void,
+    sync::mpsc::RecvTimeoutError,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    capturer::{Capturer, Options},
+    frame::{convert_yuv_to_bgra, Frame, FrameType, RowOrder},
+};
+
+/// Receives a frame as top-down rows of BGRA pixels: `height` rows of
+/// `stride` bytes, of which the first `width * 4` are pixels. `timestamp`
+/// is the frame's display time in nanoseconds.
+///
+/// `data` is only valid until the callback returns, copy the pixels to
+/// keep them.
+pub type ScapFrameCallback = extern "C" fn(
+    data: *const u8,
+    width: u32,
+    height: u32,
+    stride: u32,
+    timestamp: u64,
+    user_data: *mut c_void,
+);
+
+/// A capturer created with [scap_capturer_new]
+pub struct ScapCapturer {
+    capturer: Capturer,
+    callback: Option<(ScapFrameCallback, *mut c_void)>,
+    started: bool,
+}
+
+// The frame as top-down BGRA rows, converted and flipped where needed, and
+// its width and height. None for frames that carry no picture.
+fn get_bgra(frame: &Frame) -> Option<(Cow<'_, [u8]>, usize, usize)> {
+    // Byte order of a BGRA pixel in the frame's pixels
+    let swizzle = |data: &[u8], order: [usize; 4]| -> Vec<u8> {
+        data.chunks_exact(4)
+            .flat_map(|pixel| order.map(|i| pixel[i]))
+            .collect()
+    };
+    let (data, width, height, origin) = match frame {
+        Frame::BGRA(f) => (Cow::Borrowed(&f.data[..]), f.width, f.height, f.origin),
+        Frame::BGRx(f) => (Cow::Borrowed(&f.data[..]), f.width, f.height, f.origin),
+        Frame::RGBx(f) => (
+            Cow::Owned(swizzle(&f.data, [2, 1, 0, 3])),
+            f.width,
+            f.height,
+            f.origin,
+        ),
+        Frame::XBGR(f) => (
+            Cow::Owned(swizzle(&f.data, [1, 2, 3, 0])),
+            f.width,
+            f.height,
+            f.origin,
+        ),
+        Frame::YUVFrame(f) => {
+            let f = convert_yuv_to_bgra(f);
+            (Cow::Owned(f.data), f.width, f.height, f.origin)
+        }
+        _ => return None,
+    };
+    if width <= 0 || height <= 0 {
+        return None;
+    }
+    let (width, height) = (width as usize, height as usize);
+
+    let data = match origin {
+        RowOrder::TopDown => data,
+        RowOrder::BottomUp => {
+            let stride = data.len() / height;
+            Cow::Owned(data.chunks_exact(stride).rev().flatten().copied().collect())
+        }
+    };
+    Some((data, width, height))
+}
+
+/// Creates a capturer of the main display at `fps` frames per second, 0 for
+/// every display refresh. Returns null if the platform can't capture or
+/// the permission is missing. Free it with [scap_capturer_free].
+#[no_mangle]
+pub extern "C" fn scap_capturer_new(fps: u32, show_cursor: bool) -> *mut ScapCapturer {
+    let options = Options {
+        fps,
+        show_cursor,
+        output_type: FrameType::BGRAFrame,
+        ..Default::default()
+    };
+    match Capturer::build(options) {
+        Ok(capturer) => Box::into_raw(Box::new(ScapCapturer {
+            capturer,
+            callback: None,
+            started: false,
+        })),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Sets the callback [scap_capturer_dispatch] passes frames to, along with
+/// `user_data` as it is. Null removes it, and frames are dropped.
+///
+/// # Safety
+///
+/// `capturer` has to be null or come from [scap_capturer_new], and not be
+/// freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn scap_set_frame_callback(
+    capturer: *mut ScapCapturer,
+    callback: Option<ScapFrameCallback>,
+    user_data: *mut c_void,
+) {
+    if let Some(capturer) = capturer.as_mut() {
+        capturer.callback = callback.map(|callback| (callback, user_data));
+    }
+}
+
+/// Starts capture. Returns 0 once it started, -1 if it failed to.
+///
+/// # Safety
+///
+/// As for [scap_set_frame_callback]
+#[no_mangle]
+pub unsafe extern "C" fn scap_capturer_start(capturer: *mut ScapCapturer) -> i32 {
+    let Some(capturer) = capturer.as_mut() else {
+        return -1;
+    };
+    if capturer.started {
+        return 0;
+    }
+    match capturer.capturer.try_start_capture() {
+        Ok(()) => {
+            capturer.started = true;
+            0
+        }
+        Err(_) => -1,
+    }
+}
+
+/// Waits up to `timeout_ms` for the next frame and passes it to the
+/// callback, on the calling thread. Returns 1 when a frame was delivered,
+/// 0 if none arrived in time and -1 once capture isn't running.
+///
+/// # Safety
+///
+/// As for [scap_set_frame_callback]
+#[no_mangle]
+pub unsafe extern "C" fn scap_capturer_dispatch(
+    capturer: *mut ScapCapturer,
+    timeout_ms: u32,
+) -> i32 {
+    let Some(capturer) = capturer.as_mut() else {
+        return -1;
+    };
+    if !capturer.started {
+        return -1;
+    }
+
+    let deadline = Instant::now() + Duration::from_millis(timeout_ms as u64);
+    loop {
+        let frame = match capturer.capturer.next_frame(Some(deadline)) {
+            Ok(frame) => frame,
+            Err(RecvTimeoutError::Timeout) => return 0,
+            Err(RecvTimeoutError::Disconnected) => return -1,
+        };
+        // Skip what carries no picture, like the empty frames of macOS
+        let Some((data, width, height)) = get_bgra(&frame) else {
+            continue;
+        };
+        if let Some((callback, user_data)) = capturer.callback {
+            let stride = data.len() / height;
+            callback(
+                data.as_ptr(),
+                width as u32,
+                height as u32,
+                stride as u32,
+                frame.display_time(),
+                user_data,
+            );
+        }
+        return 1;
+    }
+}
+
+/// Stops capture. It can be started again.
+///
+/// # Safety
+///
+/// As for [scap_set_frame_callback]
+#[no_mangle]
+pub unsafe extern "C" fn scap_capturer_stop(capturer: *mut ScapCapturer) {
+    if let Some(capturer) = capturer.as_mut() {
+        if capturer.started {
+            capturer.capturer.stop_capture();
+            capturer.started = false;
+        }
+    }
+}
+
+/// Stops capture if it's running and frees the capturer. Null is ignored.
+///
+/// # Safety
+///
+/// `capturer` has to be null or come from [scap_capturer_new], and is
+/// invalid afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn scap_capturer_free(capturer: *mut ScapCapturer) {
+    if capturer.is_null() {
+        return;
+    }
+    scap_capturer_stop(capturer);
+    drop(Box::from_raw(capturer));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{ColorSpace, RGBxFrame};
+
+    #[test]
+    fn test_frames_are_bgra() {
+        let frame = Frame::RGBx(RGBxFrame {
+            display_time: 0,
+            width: 1,
+            height: 2,
+            data: vec![1, 2, 3, 0, 4, 5, 6, 0],
+            origin: RowOrder::BottomUp,
+            color_space: ColorSpace::SRGB,
+        });
+        let (data, width, height) = get_bgra(&frame).unwrap();
+        assert_eq!((width, height), (1, 2));
+        assert_eq!(&data[..], [6, 5, 4, 0, 3, 2, 1, 0]);
+    }
+}
@@ -0,0 +1,232 @@
+use std::time::{Duration, Instant};
+
+use super::{Frame, LumaWeights};
+
+// Frames are compared at this size, small enough to be cheap and to smooth
+// over noise
+const GRID_WIDTH: u32 = 64;
+const GRID_HEIGHT: u32 = 36;
+// A frame counts as changed once a cell of the grid differs by more than this
+const CHANGE_THRESHOLD: u8 = 1;
+
+/// What the screen did over a period, delivered in place of the frames with
+/// [Options::activity](crate::capturer::Options::activity)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActivityFrame {
+    /// Display time of the last frame of the period, or of the one before
+    /// if none arrived
+    pub display_time: u64,
+    /// How long the period lasted
+    pub duration: Duration,
+    /// Frames captured in the period
+    pub frames: u64,
+    /// Frames that differed from the frame before
+    pub changed_frames: u64,
+    /// Mean RGB color of the frames, None without frames
+    pub average_color: Option<[u8; 3]>,
+    /// How much consecutive frames differed, the mean absolute difference of
+    /// their luma from 0 to 1, averaged over the frames
+    pub motion: f32,
+}
+
+// Sums up the frames of each period into an ActivityFrame
+#[derive(Debug)]
+pub(crate) struct ActivitySummarizer {
+    interval: Duration,
+    // When the period started and ends, None before the first
+    period: Option<(Instant, Instant)>,
+    // Luma grid of the last frame, kept across periods
+    previous: Option<Vec<u8>>,
+    display_time: u64,
+    frames: u64,
+    changed_frames: u64,
+    compared: u64,
+    motion: f64,
+    colors: u64,
+    color_sum: [u64; 3],
+}
+
+impl ActivitySummarizer {
+    pub fn new(interval: Duration) -> Self {
+        ActivitySummarizer {
+            interval: interval.max(Duration::from_millis(1)),
+            period: None,
+            previous: None,
+            display_time: 0,
+            frames: 0,
+            changed_frames: 0,
+            compared: 0,
+            motion: 0.0,
+            colors: 0,
+            color_sum: [0; 3],
+        }
+    }
+
+    // Starts over for a new capture session
+    pub fn reset(&mut self) {
+        *self = ActivitySummarizer::new(self.interval);
+    }
+
+    // When the current period ends, starting the first at `now`
+    pub fn deadline(&mut self, now: Instant) -> Instant {
+        self.period.get_or_insert((now, now + self.interval)).1
+    }
+
+    // Adds a frame to the current period. Frames without pixels are left out.
+    pub fn add(&mut self, frame: &Frame) {
+        let Some(grid) = get_luma_grid(frame) else {
+            return;
+        };
+        self.frames += 1;
+        self.display_time = frame.display_time();
+
+        if let Some(color) = get_average_color(frame) {
+            self.colors += 1;
+            for (sum, value) in self.color_sum.iter_mut().zip(color) {
+                *sum += value as u64;
+            }
+        }
+
+        if let Some(previous) = &self.previous {
+            let (motion, changed) = compare_grids(&grid, previous);
+            self.compared += 1;
+            self.motion += motion;
+            self.changed_frames += (changed > 0) as u64;
+        }
+        self.previous = Some(grid);
+    }
+
+    // The summary of the period, once it ended at `now`
+    pub fn take_due(&mut self, now: Instant) -> Option<ActivityFrame> {
+        let (start, end) = self.period?;
+        if now < end {
+            return None;
+        }
+
+        let average_color =
+            (self.colors > 0).then(|| self.color_sum.map(|sum| (sum / self.colors) as u8));
+        let summary = ActivityFrame {
+            display_time: self.display_time,
+            duration: now.saturating_duration_since(start),
+            frames: self.frames,
+            changed_frames: self.changed_frames,
+            average_color,
+            motion: match self.compared {
+                0 => 0.0,
+                compared => (self.motion / compared as f64) as f32,
+            },
+        };
+
+        // Stay on the period grid unless a summary was taken late
+        let next_end = match now < end + self.interval {
+            true => end + self.interval,
+            false => now + self.interval,
+        };
+        self.period = Some((now, next_end));
+        self.frames = 0;
+        self.changed_frames = 0;
+        self.compared = 0;
+        self.motion = 0.0;
+        self.colors = 0;
+        self.color_sum = [0; 3];
+        Some(summary)
+    }
+}
+
+// The luma of a frame on the grid frames are compared on, None without pixels
+pub(super) fn get_luma_grid(frame: &Frame) -> Option<Vec<u8>> {
+    frame
+        .to_gray8(GRID_WIDTH, GRID_HEIGHT, LumaWeights::default())
+        .map(|grid| grid.data)
+}
+
+// How much two luma grids differ: the mean absolute difference from 0 to 1,
+// and the number of cells that changed
+pub(super) fn compare_grids(a: &[u8], b: &[u8]) -> (f64, usize) {
+    let mut difference = 0;
+    let mut changed = 0;
+    for (&a, &b) in a.iter().zip(b) {
+        let d = a.abs_diff(b);
+        difference += d as u64;
+        changed += (d > CHANGE_THRESHOLD) as usize;
+    }
+    (difference as f64 / (a.len() as f64 * 255.0), changed)
+}
+
+// The mean RGB color of a frame with pixels
+fn get_average_color(frame: &Frame) -> Option<[u8; 3]> {
+    let pixel = frame.downscaled(1, 1)?;
+    // Index of R, G and B in the pixel
+    let [r, g, b] = match &pixel {
+        Frame::RGB(_) | Frame::RGBx(_) => [0, 1, 2],
+        Frame::BGR0(_) | Frame::BGRx(_) | Frame::BGRA(_) => [2, 1, 0],
+        Frame::XBGR(_) => [3, 2, 1],
+        Frame::Gray8(_) => [0, 0, 0],
+        _ => return None,
+    };
+    let data = pixel.packed_data()?.data;
+    Some([data[r], data[g], data[b]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{BGRAFrame, ColorSpace, RowOrder};
+
+    fn frame(display_time: u64, bgra: [u8; 4]) -> Frame {
+        Frame::BGRA(BGRAFrame {
+            display_time,
+            width: 4,
+            height: 2,
+            data: bgra.repeat(8),
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::SRGB,
+        })
+    }
+
+    #[test]
+    fn test_summaries() {
+        let start = Instant::now();
+        let mut summarizer = ActivitySummarizer::new(Duration::from_secs(1));
+        assert_eq!(summarizer.deadline(start), start + Duration::from_secs(1));
+        assert!(summarizer.take_due(start).is_none());
+
+        // Black, black again, then white
+        summarizer.add(&frame(1, [0, 0, 0, 255]));
+        summarizer.add(&frame(2, [0, 0, 0, 255]));
+        summarizer.add(&frame(3, [255, 255, 255, 255]));
+        let summary = summarizer.take_due(start + Duration::from_secs(1)).unwrap();
+        assert_eq!(summary.display_time, 3);
+        assert_eq!(summary.duration, Duration::from_secs(1));
+        assert_eq!((summary.frames, summary.changed_frames), (3, 1));
+        assert_eq!(summary.average_color, Some([85, 85, 85]));
+        assert!((summary.motion - 0.5).abs() < 1e-6, "{}", summary.motion);
+
+        // A period without frames, then one compared with the last white frame
+        let quiet = summarizer.take_due(start + Duration::from_secs(2)).unwrap();
+        assert_eq!((quiet.frames, quiet.display_time), (0, 3));
+        assert_eq!((quiet.average_color, quiet.motion), (None, 0.0));
+
+        summarizer.add(&frame(4, [0, 0, 255, 255]));
+        let red = summarizer.take_due(start + Duration::from_secs(3)).unwrap();
+        assert_eq!(red.average_color, Some([255, 0, 0]));
+        assert_eq!(red.changed_frames, 1);
+        assert!(red.motion > 0.5);
+
+        // Markers carry no pixels
+        summarizer.reset();
+        summarizer.deadline(start);
+        summarizer.add(&Frame::Marker(crate::frame::MarkerFrame {
+            display_time: 0,
+            timestamp: Duration::ZERO,
+            label: String::new(),
+        }));
+        assert_eq!(
+            summarizer
+                .take_due(start + Duration::from_secs(1))
+                .unwrap()
+                .frames,
+            0
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use super::{Frame, RowOrder};
+
+// How many rows and columns at the edges of `frame` are each one uniform
+// color, [left, top, right, bottom], up to `max` per edge. Each color
+// channel can vary by `tolerance`. None where the border would leave
+// nothing, as for frames of one color, and for frames without packed
+// pixels.
+pub(crate) fn detect_uniform_border(frame: &Frame, max: u32, tolerance: u8) -> Option<[u32; 4]> {
+    let ignored = frame.ignored_byte();
+    detect_border(frame, max, &|pixel, corner| {
+        pixel
+            .iter()
+            .zip(corner)
+            .enumerate()
+            .all(|(i, (a, b))| Some(i) == ignored || a.abs_diff(*b) <= tolerance)
+    })
+}
+
+// How many rows and columns at the edges of `frame` are black, as the bars
+// of letterboxed or pillarboxed content, [left, top, right, bottom]. Pixels
+// are black when no color channel is above `threshold`. None where the bars
+// would leave nothing and for frames without packed pixels.
+pub(crate) fn detect_black_bars(frame: &Frame, threshold: u8) -> Option<[u32; 4]> {
+    let ignored = frame.ignored_byte();
+    detect_border(frame, u32::MAX, &|pixel, _| {
+        pixel
+            .iter()
+            .enumerate()
+            .all(|(i, value)| Some(i) == ignored || *value <= threshold)
+    })
+}
+
+// The lines at each edge, up to `max`, whose every pixel `matches` the
+// corner pixel the edge starts at
+fn detect_border(
+    frame: &Frame,
+    max: u32,
+    matches: &dyn Fn(&[u8], &[u8]) -> bool,
+) -> Option<[u32; 4]> {
+    let packed = frame.packed_data()?;
+    let (width, height, bytes) = (packed.width, packed.height, packed.bytes_per_pixel);
+    let stride = packed.data.len() / height;
+
+    let pixel = |x: usize, y: usize| &packed.data[y * stride + x * bytes..][..bytes];
+    let row = |y: usize, corner: &[u8]| (0..width).all(|x| matches(pixel(x, y), corner));
+    let column = |x: usize, corner: &[u8]| (0..height).all(|y| matches(pixel(x, y), corner));
+    // Lines from the edge inwards that match
+    let count = |lines: &mut dyn Iterator<Item = usize>, uniform: &dyn Fn(usize) -> bool| {
+        lines.take(max as usize).take_while(|&i| uniform(i)).count()
+    };
+
+    let first_row = count(&mut (0..height), &|y| row(y, pixel(0, 0)));
+    let last_row = count(&mut (0..height).rev(), &|y| row(y, pixel(0, height - 1)));
+    let left = count(&mut (0..width), &|x| column(x, pixel(0, 0)));
+    let right = count(&mut (0..width).rev(), &|x| column(x, pixel(width - 1, 0)));
+    if left + right >= width || first_row + last_row >= height {
+        return None;
+    }
+
+    let (top, bottom) = match packed.origin {
+        RowOrder::TopDown => (first_row, last_row),
+        RowOrder::BottomUp => (last_row, first_row),
+    };
+    Some([left as u32, top as u32, right as u32, bottom as u32])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{BGRAFrame, ColorSpace, Gray8Frame};
+
+    #[test]
+    fn test_detect_uniform_border() {
+        // A black column on the left and a black row on top. The white rows
+        // at the bottom start with black, so they aren't uniform.
+        #[rustfmt::skip]
+        let data = vec![
+            0, 0, 0, 0, 0, 0,
+            0, 90, 91, 90, 90, 90,
+            0, 90, 200, 90, 90, 90,
+            0, 255, 255, 255, 255, 255,
+            0, 255, 255, 255, 255, 255,
+        ];
+        let frame = Frame::Gray8(Gray8Frame {
+            display_time: 0,
+            width: 6,
+            height: 5,
+            data,
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::SRGB,
+        });
+        assert_eq!(detect_uniform_border(&frame, 4, 2), Some([1, 1, 0, 0]));
+
+        let bgra = |pixels: &[[u8; 4]]| {
+            Frame::BGRA(BGRAFrame {
+                display_time: 0,
+                width: 3,
+                height: 3,
+                data: pixels.concat(),
+                origin: RowOrder::BottomUp,
+                color_space: ColorSpace::SRGB,
+            })
+        };
+        let (black, gray) = ([0, 0, 0, 255], [80, 80, 80, 0]);
+        assert_eq!(detect_uniform_border(&bgra(&[gray; 9]), 4, 0), None);
+
+        // Stored bottom up, so the black row first in the buffer is the
+        // bottom edge. Alpha doesn't count.
+        let frame = bgra(&[
+            black,
+            black,
+            [0, 0, 0, 0],
+            [80, 80, 80, 255],
+            [10, 20, 30, 0],
+            gray,
+            [1, 2, 3, 255],
+            gray,
+            gray,
+        ]);
+        assert_eq!(detect_uniform_border(&frame, 4, 0), Some([0, 0, 0, 1]));
+        assert_eq!(detect_uniform_border(&frame, 0, 0), Some([0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn test_detect_black_bars() {
+        // Letterboxed with rows of near-black noise, and a dark but not black
+        // first column in the picture
+        #[rustfmt::skip]
+        let data = vec![
+            0, 3, 0, 1, 0,
+            12, 40, 80, 90, 60,
+            14, 200, 90, 90, 30,
+            2, 0, 0, 0, 4,
+            0, 0, 0, 0, 0,
+        ];
+        let frame = Frame::Gray8(Gray8Frame {
+            display_time: 0,
+            width: 5,
+            height: 5,
+            data,
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::SRGB,
+        });
+        assert_eq!(detect_black_bars(&frame, 4), Some([0, 1, 0, 2]));
+        assert_eq!(detect_black_bars(&frame, 16), Some([1, 1, 0, 2]));
+        assert_eq!(detect_black_bars(&frame, 0), Some([0, 0, 0, 1]));
+        assert_eq!(detect_black_bars(&frame, 255), None);
+    }
+}
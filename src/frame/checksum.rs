@@ -0,0 +1,291 @@
+use super::Frame;
+
+/// How [frame_checksum] sums up a frame's bytes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ChecksumAlgorithm {
+    /// 64-bit xxHash (XXH64) with seed 0. About a millisecond for a 1080p
+    /// BGRA frame.
+    #[default]
+    XxHash64,
+    /// The CRC-32 of zlib, PNG and Ethernet, for tools that only check those.
+    /// Several times slower, about 8 milliseconds at 1080p, which is half the
+    /// time between frames at 60 fps.
+    Crc32,
+}
+
+/// A checksum of a frame's bytes, see [frame_checksum]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FrameChecksum {
+    pub algorithm: ChecksumAlgorithm,
+    /// The CRC-32 in the lower 32 bits for [ChecksumAlgorithm::Crc32]
+    pub value: u64,
+}
+
+/// Sum up the frame's buffer as it is, row padding included, to tell
+/// whether it was corrupted later on. That's the pixels of packed and raw
+/// frames, which are the bytes a frame record holds, and the luminance
+/// followed by the chrominance bytes of YUV frames. The display time isn't included.
+///
+/// Returns None for frames without a buffer of pixels, like deltas, regions
+/// and markers.
+pub fn frame_checksum(frame: &Frame, algorithm: ChecksumAlgorithm) -> Option<FrameChecksum> {
+    let parts: [&[u8]; 2] = match frame {
+        Frame::YUVFrame(f) => [&f.luminance_bytes, &f.chrominance_bytes],
+        Frame::Raw(f) => [&f.data, &[]],
+        _ => [frame.packed_data()?.data, &[]],
+    };
+
+    let value = match algorithm {
+        ChecksumAlgorithm::XxHash64 => {
+            let mut hasher = XxHash64::new();
+            parts.iter().for_each(|part| hasher.update(part));
+            hasher.finish()
+        }
+        ChecksumAlgorithm::Crc32 => {
+            let crc = parts.iter().fold(!0, |crc, part| update_crc32(crc, part));
+            !crc as u64
+        }
+    };
+    Some(FrameChecksum { algorithm, value })
+}
+
+/// Whether the frame still has the bytes `checksum` was computed from.
+/// False for frames [frame_checksum] doesn't sum up.
+pub fn verify_frame_checksum(frame: &Frame, checksum: &FrameChecksum) -> bool {
+    frame_checksum(frame, checksum.algorithm).as_ref() == Some(checksum)
+}
+
+const CRC32_POLYNOMIAL: u32 = 0xedb88320;
+
+// Slicing-by-8 tables: the first is the CRC of every byte, each next one
+// of the byte followed by one more zero byte than the one before
+const CRC32_TABLES: [[u32; 256]; 8] = get_crc32_tables();
+
+const fn get_crc32_tables() -> [[u32; 256]; 8] {
+    let mut tables = [[0; 256]; 8];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ CRC32_POLYNOMIAL,
+                _ => crc >> 1,
+            };
+            bit += 1;
+        }
+        tables[0][i] = crc;
+        i += 1;
+    }
+
+    let mut i = 0;
+    while i < 256 {
+        let mut table = 1;
+        while table < 8 {
+            let previous = tables[table - 1][i];
+            tables[table][i] = (previous >> 8) ^ tables[0][(previous & 0xff) as usize];
+            table += 1;
+        }
+        i += 1;
+    }
+    tables
+}
+
+// Adds `data` to an uninverted CRC-32, eight bytes at a time
+fn update_crc32(mut crc: u32, data: &[u8]) -> u32 {
+    let table = |t: usize, value: u32| CRC32_TABLES[t][(value & 0xff) as usize];
+    let mut chunks = data.chunks_exact(8);
+    for chunk in &mut chunks {
+        let low = u32::from_le_bytes(chunk[..4].try_into().unwrap()) ^ crc;
+        let high = u32::from_le_bytes(chunk[4..].try_into().unwrap());
+        crc = table(7, low)
+            ^ table(6, low >> 8)
+            ^ table(5, low >> 16)
+            ^ table(4, low >> 24)
+            ^ table(3, high)
+            ^ table(2, high >> 8)
+            ^ table(1, high >> 16)
+            ^ table(0, high >> 24);
+    }
+    for &byte in chunks.remainder() {
+        crc = (crc >> 8) ^ table(0, crc ^ byte as u32);
+    }
+    crc
+}
+
+const PRIME64_1: u64 = 0x9e3779b185ebca87;
+const PRIME64_2: u64 = 0xc2b2ae3d27d4eb4f;
+const PRIME64_3: u64 = 0x165667b19e3779f9;
+const PRIME64_4: u64 = 0x85ebca77c2b2ae63;
+const PRIME64_5: u64 = 0x27d4eb2f165667c5;
+
+// XXH64 with seed 0 over data added in parts
+struct XxHash64 {
+    lanes: [u64; 4],
+    // The start of a 32 byte stripe the last part ended in
+    buffer: [u8; 32],
+    buffered: usize,
+    length: u64,
+}
+
+impl XxHash64 {
+    fn new() -> Self {
+        XxHash64 {
+            lanes: [
+                PRIME64_1.wrapping_add(PRIME64_2),
+                PRIME64_2,
+                0,
+                0u64.wrapping_sub(PRIME64_1),
+            ],
+            buffer: [0; 32],
+            buffered: 0,
+            length: 0,
+        }
+    }
+
+    fn round(lane: u64, input: u64) -> u64 {
+        lane.wrapping_add(input.wrapping_mul(PRIME64_2))
+            .rotate_left(31)
+            .wrapping_mul(PRIME64_1)
+    }
+
+    fn stripe(&mut self, stripe: &[u8]) {
+        for (lane, input) in self.lanes.iter_mut().zip(stripe.chunks_exact(8)) {
+            *lane = Self::round(*lane, u64::from_le_bytes(input.try_into().unwrap()));
+        }
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        self.length += data.len() as u64;
+        if self.buffered > 0 {
+            let taken = data.len().min(32 - self.buffered);
+            self.buffer[self.buffered..][..taken].copy_from_slice(&data[..taken]);
+            self.buffered += taken;
+            data = &data[taken..];
+            if self.buffered < 32 {
+                return;
+            }
+            let buffer = self.buffer;
+            self.stripe(&buffer);
+            self.buffered = 0;
+        }
+
+        let mut stripes = data.chunks_exact(32);
+        for stripe in &mut stripes {
+            self.stripe(stripe);
+        }
+        let rest = stripes.remainder();
+        self.buffer[..rest.len()].copy_from_slice(rest);
+        self.buffered = rest.len();
+    }
+
+    fn finish(&self) -> u64 {
+        let mut hash = match self.length >= 32 {
+            true => {
+                let [a, b, c, d] = self.lanes;
+                let hash = a
+                    .rotate_left(1)
+                    .wrapping_add(b.rotate_left(7))
+                    .wrapping_add(c.rotate_left(12))
+                    .wrapping_add(d.rotate_left(18));
+                self.lanes.iter().fold(hash, |hash, lane| {
+                    (hash ^ Self::round(0, *lane))
+                        .wrapping_mul(PRIME64_1)
+                        .wrapping_add(PRIME64_4)
+                })
+            }
+            false => PRIME64_5,
+        };
+        hash = hash.wrapping_add(self.length);
+
+        let mut rest = &self.buffer[..self.buffered];
+        while rest.len() >= 8 {
+            let input = u64::from_le_bytes(rest[..8].try_into().unwrap());
+            hash = (hash ^ Self::round(0, input))
+                .rotate_left(27)
+                .wrapping_mul(PRIME64_1)
+                .wrapping_add(PRIME64_4);
+            rest = &rest[8..];
+        }
+        if rest.len() >= 4 {
+            let input = u32::from_le_bytes(rest[..4].try_into().unwrap()) as u64;
+            hash = (hash ^ input.wrapping_mul(PRIME64_1))
+                .rotate_left(23)
+                .wrapping_mul(PRIME64_2)
+                .wrapping_add(PRIME64_3);
+            rest = &rest[4..];
+        }
+        for &byte in rest {
+            hash = (hash ^ (byte as u64).wrapping_mul(PRIME64_5))
+                .rotate_left(11)
+                .wrapping_mul(PRIME64_1);
+        }
+
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(PRIME64_2);
+        hash ^= hash >> 29;
+        hash = hash.wrapping_mul(PRIME64_3);
+        hash ^ (hash >> 32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{ColorSpace, Gray8Frame, RowOrder};
+
+    fn xxhash64(parts: &[&[u8]]) -> u64 {
+        let mut hasher = XxHash64::new();
+        parts.iter().for_each(|part| hasher.update(part));
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_known_values() {
+        assert_eq!(!update_crc32(!0, b""), 0);
+        assert_eq!(!update_crc32(!0, b"123456789"), 0xcbf43926);
+        let crc = update_crc32(update_crc32(!0, b"1234"), b"56789");
+        assert_eq!(!crc, 0xcbf43926);
+
+        assert_eq!(xxhash64(&[b""]), 0xef46db3751d8e999);
+        assert_eq!(xxhash64(&[b"Hello, world!\0"]), 0x7b06c531ea43e89f);
+        // The same across stripes however it's split
+        let data: Vec<u8> = (0..100u8).collect();
+        assert_eq!(xxhash64(&[&data]), 0x6ac1e58032166597);
+        assert_eq!(
+            xxhash64(&[&data[..7], &data[7..40], &data[40..]]),
+            0x6ac1e58032166597
+        );
+        assert_eq!(
+            xxhash64(&[&data[..32], &[], &data[32..]]),
+            0x6ac1e58032166597
+        );
+    }
+
+    #[test]
+    fn test_verify_frame_checksum() {
+        let mut frame = Frame::Gray8(Gray8Frame {
+            display_time: 0,
+            width: 3,
+            height: 3,
+            data: b"123456789".to_vec(),
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::SRGB,
+        });
+        let crc = frame_checksum(&frame, ChecksumAlgorithm::Crc32).unwrap();
+        assert_eq!(crc.value, 0xcbf43926);
+        let xxhash = frame_checksum(&frame, ChecksumAlgorithm::XxHash64).unwrap();
+        assert!(verify_frame_checksum(&frame, &xxhash));
+
+        // Retimed frames keep their checksum, changed pixels don't
+        *frame.display_time_mut() = 1000;
+        assert!(verify_frame_checksum(&frame, &crc));
+        if let Frame::Gray8(f) = &mut frame {
+            f.data[4] ^= 1;
+        }
+        assert!(!verify_frame_checksum(&frame, &crc));
+        assert!(!verify_frame_checksum(&frame, &xxhash));
+    }
+}
@@ -0,0 +1,259 @@
+use std::sync::OnceLock;
+
+use super::Frame;
+
+/// The color space the pixel values of a frame are encoded in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// The platform doesn't report it. Usually sRGB in practice, but not guaranteed.
+    #[default]
+    Unknown,
+    /// sRGB primaries and transfer function. Windows always delivers this for
+    /// 8-bit formats, HDR and wide-gamut content is tone mapped by the compositor.
+    SRGB,
+    /// Display P3 primaries with the sRGB transfer function, as captured from
+    /// wide-gamut Mac displays
+    DisplayP3,
+}
+
+// Fractional bits of the fixed point matrix
+const SHIFT: u32 = 12;
+// Linear light is kept in 16 bits, the encoding table is indexed by its top 14
+const ENCODE_BITS: u32 = 14;
+
+// Linear Display P3 to linear sRGB. Both share the D65 white point.
+const P3_TO_SRGB: [[f64; 3]; 3] = [
+    [1.224_940_176_280_56, -0.224_940_176_280_56, 0.0],
+    [-0.042_056_954_709_688, 1.042_056_954_709_688, 0.0],
+    [
+        -0.019_637_554_590_334,
+        -0.078_636_045_550_632,
+        1.098_273_600_140_966,
+    ],
+];
+
+fn srgb_to_linear(v: f64) -> f64 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(v: f64) -> f64 {
+    if v <= 0.003_130_8 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// Lookup tables for the P3 -> sRGB conversion. Decoding and encoding the
+// transfer function per channel is far too slow to do per pixel.
+struct P3ToSrgb {
+    decode: [u16; 256],
+    encode: Vec<u8>,
+    matrix: [[i32; 3]; 3],
+}
+
+impl P3ToSrgb {
+    fn get() -> &'static P3ToSrgb {
+        static TABLES: OnceLock<P3ToSrgb> = OnceLock::new();
+        TABLES.get_or_init(|| {
+            let mut decode = [0; 256];
+            for (i, v) in decode.iter_mut().enumerate() {
+                *v = (srgb_to_linear(i as f64 / 255.0) * 65535.0).round() as u16;
+            }
+
+            // Sample every bucket at its center so rounding is symmetric
+            let buckets = 1 << ENCODE_BITS;
+            let encode = (0..buckets)
+                .map(|i| {
+                    let linear = (i as f64 + 0.5) / buckets as f64;
+                    (linear_to_srgb(linear) * 255.0).round() as u8
+                })
+                .collect();
+
+            let mut matrix = [[0; 3]; 3];
+            for (row, coefficients) in matrix.iter_mut().zip(P3_TO_SRGB.iter()) {
+                for (value, coefficient) in row.iter_mut().zip(coefficients.iter()) {
+                    *value = (coefficient * (1 << SHIFT) as f64).round() as i32;
+                }
+            }
+
+            P3ToSrgb {
+                decode,
+                encode,
+                matrix,
+            }
+        })
+    }
+
+    fn convert(&self, rgb: [u8; 3]) -> [u8; 3] {
+        let linear = rgb.map(|v| self.decode[v as usize] as i32);
+        self.matrix.map(|row| {
+            let sum = row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2];
+            // Out of gamut colors are clipped
+            let value = ((sum + (1 << (SHIFT - 1))) >> SHIFT).clamp(0, 65535);
+            self.encode[(value >> (16 - ENCODE_BITS)) as usize]
+        })
+    }
+}
+
+/// Converts a Display P3 frame to sRGB in place and retags it.
+///
+/// Frames in any other color space are left untouched, as are YUV and
+/// grayscale frames, which keep their native color space.
+pub fn convert_p3_to_srgb(frame: &mut Frame) {
+    // (data, color space, bytes per pixel, index of R, G and B in a pixel)
+    let (data, color_space, bytes_per_pixel, [r, g, b]) = match frame {
+        Frame::YUVFrame(_)
+        | Frame::Gray8(_)
+        | Frame::Delta(_)
+        | Frame::ScanlinePatch(_)
+        | Frame::Regions(_)
+        | Frame::Marker(_)
+        | Frame::Activity(_)
+        | Frame::Raw(_) => return,
+        Frame::RGB(f) => (&mut f.data, &mut f.color_space, 3, [0, 1, 2]),
+        Frame::BGR0(f) => (&mut f.data, &mut f.color_space, 3, [2, 1, 0]),
+        Frame::RGBx(f) => (&mut f.data, &mut f.color_space, 4, [0, 1, 2]),
+        Frame::XBGR(f) => (&mut f.data, &mut f.color_space, 4, [3, 2, 1]),
+        Frame::BGRx(f) => (&mut f.data, &mut f.color_space, 4, [2, 1, 0]),
+        Frame::BGRA(f) => (&mut f.data, &mut f.color_space, 4, [2, 1, 0]),
+    };
+
+    if *color_space != ColorSpace::DisplayP3 {
+        return;
+    }
+
+    let tables = P3ToSrgb::get();
+    for pixel in data.chunks_exact_mut(bytes_per_pixel) {
+        let [sr, sg, sb] = tables.convert([pixel[r], pixel[g], pixel[b]]);
+        pixel[r] = sr;
+        pixel[g] = sg;
+        pixel[b] = sb;
+    }
+    *color_space = ColorSpace::SRGB;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{BGRAFrame, RowOrder};
+
+    fn p3_frame(pixels: &[[u8; 3]]) -> Frame {
+        Frame::BGRA(BGRAFrame {
+            display_time: 0,
+            width: pixels.len() as i32,
+            height: 1,
+            data: pixels
+                .iter()
+                .flat_map(|[r, g, b]| [*b, *g, *r, 255])
+                .collect(),
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::DisplayP3,
+        })
+    }
+
+    fn to_srgb(pixels: &[[u8; 3]]) -> Vec<[u8; 3]> {
+        let mut frame = p3_frame(pixels);
+        convert_p3_to_srgb(&mut frame);
+        let Frame::BGRA(frame) = frame else {
+            unreachable!()
+        };
+        assert_eq!(frame.color_space, ColorSpace::SRGB);
+        frame
+            .data
+            .chunks_exact(4)
+            .map(|p| {
+                assert_eq!(p[3], 255);
+                [p[2], p[1], p[0]]
+            })
+            .collect()
+    }
+
+    // Reference sRGB -> P3 conversion in floating point
+    fn srgb_to_p3(rgb: [u8; 3]) -> [u8; 3] {
+        const SRGB_TO_P3: [[f64; 3]; 3] = [
+            [0.822_461_969_267_788, 0.177_538_030_732_212, 0.0],
+            [0.033_194_199_962_916, 0.966_805_800_037_084, 0.0],
+            [
+                0.017_082_630_595_385,
+                0.072_397_440_950_068,
+                0.910_519_928_454_547,
+            ],
+        ];
+        let linear = rgb.map(|v| srgb_to_linear(v as f64 / 255.0));
+        SRGB_TO_P3.map(|row| {
+            let v = row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2];
+            (linear_to_srgb(v.clamp(0.0, 1.0)) * 255.0).round() as u8
+        })
+    }
+
+    fn assert_close(actual: [u8; 3], expected: [u8; 3], tolerance: i32) {
+        for c in 0..3 {
+            assert!(
+                (actual[c] as i32 - expected[c] as i32).abs() <= tolerance,
+                "{actual:?} != {expected:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_neutral_colors_are_unchanged() {
+        let grays: Vec<[u8; 3]> = (0..=255).map(|v| [v, v, v]).collect();
+        for (gray, converted) in grays.iter().zip(to_srgb(&grays)) {
+            assert_close(converted, *gray, 1);
+        }
+    }
+
+    #[test]
+    fn test_srgb_primaries_round_trip() {
+        let colors = [
+            [255, 0, 0],
+            [0, 255, 0],
+            [0, 0, 255],
+            [255, 255, 0],
+            [0, 255, 255],
+            [255, 0, 255],
+            [200, 120, 40],
+            [30, 90, 160],
+        ];
+        // sRGB red expressed in Display P3
+        assert_close(srgb_to_p3([255, 0, 0]), [234, 51, 35], 1);
+
+        let p3: Vec<[u8; 3]> = colors.iter().map(|c| srgb_to_p3(*c)).collect();
+        for ((color, p3), converted) in colors.iter().zip(p3.iter()).zip(to_srgb(&p3)) {
+            // The lookup tables match the exact math on the same input...
+            let linear = p3.map(|v| srgb_to_linear(v as f64 / 255.0));
+            let exact = P3_TO_SRGB.map(|row| {
+                let v = row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2];
+                (linear_to_srgb(v.clamp(0.0, 1.0)) * 255.0).round() as u8
+            });
+            assert_close(converted, exact, 1);
+            // ...and the round trip only loses what 8-bit P3 can't represent near black
+            assert_close(converted, *color, 3);
+        }
+    }
+
+    #[test]
+    fn test_p3_primaries_are_clipped() {
+        let converted = to_srgb(&[[255, 0, 0], [0, 255, 0]]);
+        assert_eq!(converted[0], [255, 0, 0]);
+        assert_close(converted[1], [0, 255, 0], 1);
+    }
+
+    #[test]
+    fn test_other_color_spaces_are_untouched() {
+        let mut frame = p3_frame(&[[255, 0, 0]]);
+        if let Frame::BGRA(f) = &mut frame {
+            f.color_space = ColorSpace::SRGB;
+        }
+        convert_p3_to_srgb(&mut frame);
+        let Frame::BGRA(f) = frame else {
+            unreachable!()
+        };
+        assert_eq!(f.data, vec![0, 0, 255, 255]);
+    }
+}
@@ -0,0 +1,334 @@
+use std::time::{Duration, Instant};
+
+use super::{
+    BGRAFrame, BGRFrame, BGRxFrame, DeltaFrame, DeltaRegion, Frame, Gray8Frame, RGBFrame,
+    RGBxFrame, RawFrame, RegionsFrame, ScanlineBand, ScanlinePatchFrame, XBGRFrame, YUVFrame,
+};
+
+/// Lossless codec for [compress]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    /// Fast with a moderate ratio
+    #[cfg(feature = "lz4")]
+    Lz4,
+    /// Slower with a better ratio, at a zstd level from 1 to 22
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+}
+
+#[derive(Debug)]
+pub enum CompressionError {
+    /// The codec failed, e.g. on corrupt data
+    Codec(String),
+    /// A plane didn't decompress to the size it was compressed from
+    SizeMismatch,
+}
+
+impl std::fmt::Display for CompressionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompressionError::Codec(reason) => write!(f, "Codec error: {reason}"),
+            CompressionError::SizeMismatch => {
+                write!(f, "Decompressed plane doesn't have its original size")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CompressionError {}
+
+#[derive(Debug, Clone)]
+struct CompressedPlane {
+    raw_len: usize,
+    data: Vec<u8>,
+}
+
+/// A frame with each of its planes compressed on its own, see [compress]
+#[derive(Debug, Clone)]
+pub struct CompressedFrame {
+    pub codec: Codec,
+    /// How long compressing took
+    pub compression_time: Duration,
+    // The frame with empty planes
+    layout: Frame,
+    planes: Vec<CompressedPlane>,
+}
+
+impl CompressedFrame {
+    /// Size of the pixel data before compression in bytes
+    pub fn raw_size(&self) -> usize {
+        self.planes.iter().map(|plane| plane.raw_len).sum()
+    }
+
+    /// Size of the pixel data after compression in bytes
+    pub fn compressed_size(&self) -> usize {
+        self.planes.iter().map(|plane| plane.data.len()).sum()
+    }
+
+    /// How many times smaller the pixel data got, 1.0 for frames without any
+    pub fn ratio(&self) -> f64 {
+        match self.compressed_size() {
+            0 => 1.0,
+            size => self.raw_size() as f64 / size as f64,
+        }
+    }
+}
+
+/// Compress the pixel data of `frame` losslessly, keeping its layout so
+/// [decompress] restores an identical frame
+pub fn compress(frame: &Frame, codec: Codec) -> Result<CompressedFrame, CompressionError> {
+    let start = Instant::now();
+    let planes = planes(frame)
+        .into_iter()
+        .map(|plane| {
+            Ok(CompressedPlane {
+                raw_len: plane.len(),
+                data: compress_plane(plane, codec)?,
+            })
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(CompressedFrame {
+        codec,
+        compression_time: start.elapsed(),
+        layout: layout(frame),
+        planes,
+    })
+}
+
+/// Restore the frame [compress] was given
+pub fn decompress(compressed: &CompressedFrame) -> Result<Frame, CompressionError> {
+    let mut frame = compressed.layout.clone();
+    let planes = planes_mut(&mut frame);
+    if planes.len() != compressed.planes.len() {
+        return Err(CompressionError::SizeMismatch);
+    }
+
+    for (plane, source) in planes.into_iter().zip(&compressed.planes) {
+        *plane = decompress_plane(&source.data, source.raw_len, compressed.codec)?;
+        if plane.len() != source.raw_len {
+            return Err(CompressionError::SizeMismatch);
+        }
+    }
+    Ok(frame)
+}
+
+fn compress_plane(data: &[u8], codec: Codec) -> Result<Vec<u8>, CompressionError> {
+    match codec {
+        #[cfg(feature = "lz4")]
+        Codec::Lz4 => Ok(lz4_flex::block::compress(data)),
+        #[cfg(feature = "zstd")]
+        Codec::Zstd(level) => zstd::bulk::compress(data, level)
+            .map_err(|error| CompressionError::Codec(error.to_string())),
+    }
+}
+
+fn decompress_plane(
+    data: &[u8],
+    raw_len: usize,
+    codec: Codec,
+) -> Result<Vec<u8>, CompressionError> {
+    match codec {
+        #[cfg(feature = "lz4")]
+        Codec::Lz4 => lz4_flex::block::decompress(data, raw_len)
+            .map_err(|error| CompressionError::Codec(error.to_string())),
+        #[cfg(feature = "zstd")]
+        Codec::Zstd(_) => zstd::bulk::decompress(data, raw_len)
+            .map_err(|error| CompressionError::Codec(error.to_string())),
+    }
+}
+
+fn planes(frame: &Frame) -> Vec<&[u8]> {
+    match frame {
+        Frame::YUVFrame(f) => vec![&f.luminance_bytes, &f.chrominance_bytes],
+        Frame::RGB(f) => vec![&f.data],
+        Frame::RGBx(f) => vec![&f.data],
+        Frame::XBGR(f) => vec![&f.data],
+        Frame::BGRx(f) => vec![&f.data],
+        Frame::BGR0(f) => vec![&f.data],
+        Frame::BGRA(f) => vec![&f.data],
+        Frame::Gray8(f) => vec![&f.data],
+        Frame::Raw(f) => vec![&f.data],
+        Frame::Delta(f) => f.regions.iter().map(|r| &r.pixels[..]).collect(),
+        Frame::ScanlinePatch(f) => f.bands.iter().map(|b| &b.data[..]).collect(),
+        Frame::Regions(f) => f
+            .parts
+            .iter()
+            .map(|(_, part)| part)
+            .chain(f.source.as_deref())
+            .flat_map(planes)
+            .collect(),
+        Frame::Marker(_) | Frame::Activity(_) => Vec::new(),
+    }
+}
+
+fn planes_mut(frame: &mut Frame) -> Vec<&mut Vec<u8>> {
+    match frame {
+        Frame::YUVFrame(f) => vec![&mut f.luminance_bytes, &mut f.chrominance_bytes],
+        Frame::RGB(f) => vec![&mut f.data],
+        Frame::RGBx(f) => vec![&mut f.data],
+        Frame::XBGR(f) => vec![&mut f.data],
+        Frame::BGRx(f) => vec![&mut f.data],
+        Frame::BGR0(f) => vec![&mut f.data],
+        Frame::BGRA(f) => vec![&mut f.data],
+        Frame::Gray8(f) => vec![&mut f.data],
+        Frame::Raw(f) => vec![&mut f.data],
+        Frame::Delta(f) => f.regions.iter_mut().map(|r| &mut r.pixels).collect(),
+        Frame::ScanlinePatch(f) => f.bands.iter_mut().map(|b| &mut b.data).collect(),
+        Frame::Regions(f) => f
+            .parts
+            .iter_mut()
+            .map(|(_, part)| part)
+            .chain(f.source.as_deref_mut())
+            .flat_map(planes_mut)
+            .collect(),
+        Frame::Marker(_) | Frame::Activity(_) => Vec::new(),
+    }
+}
+
+// The frame without its pixel data, built field by field so the data isn't
+// copied just to be dropped
+fn layout(frame: &Frame) -> Frame {
+    match frame {
+        Frame::YUVFrame(f) => Frame::YUVFrame(YUVFrame {
+            luminance_bytes: Vec::new(),
+            chrominance_bytes: Vec::new(),
+            color_matrix: f.color_matrix.clone(),
+            ..*f
+        }),
+        Frame::RGB(f) => Frame::RGB(RGBFrame {
+            data: Vec::new(),
+            ..*f
+        }),
+        Frame::RGBx(f) => Frame::RGBx(RGBxFrame {
+            data: Vec::new(),
+            ..*f
+        }),
+        Frame::XBGR(f) => Frame::XBGR(XBGRFrame {
+            data: Vec::new(),
+            ..*f
+        }),
+        Frame::BGRx(f) => Frame::BGRx(BGRxFrame {
+            data: Vec::new(),
+            ..*f
+        }),
+        Frame::BGR0(f) => Frame::BGR0(BGRFrame {
+            data: Vec::new(),
+            ..*f
+        }),
+        Frame::BGRA(f) => Frame::BGRA(BGRAFrame {
+            data: Vec::new(),
+            ..*f
+        }),
+        Frame::Gray8(f) => Frame::Gray8(Gray8Frame {
+            data: Vec::new(),
+            ..*f
+        }),
+        Frame::Raw(f) => Frame::Raw(RawFrame {
+            data: Vec::new(),
+            ..*f
+        }),
+        Frame::Delta(f) => Frame::Delta(DeltaFrame {
+            regions: f
+                .regions
+                .iter()
+                .map(|r| DeltaRegion {
+                    pixels: Vec::new(),
+                    ..*r
+                })
+                .collect(),
+            ..*f
+        }),
+        Frame::ScanlinePatch(f) => Frame::ScanlinePatch(ScanlinePatchFrame {
+            bands: f
+                .bands
+                .iter()
+                .map(|b| ScanlineBand {
+                    data: Vec::new(),
+                    ..*b
+                })
+                .collect(),
+            ..*f
+        }),
+        Frame::Regions(f) => Frame::Regions(RegionsFrame {
+            display_time: f.display_time,
+            source_sequence: f.source_sequence,
+            width: f.width,
+            height: f.height,
+            parts: f
+                .parts
+                .iter()
+                .map(|(name, part)| (name.clone(), layout(part)))
+                .collect(),
+            source: f.source.as_deref().map(|source| Box::new(layout(source))),
+            color_space: f.color_space,
+        }),
+        Frame::Marker(f) => Frame::Marker(f.clone()),
+        Frame::Activity(f) => Frame::Activity(f.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{ChromaSubsampling, ColorMatrix, ColorRange, ColorSpace, RowOrder};
+
+    fn codecs() -> Vec<Codec> {
+        vec![
+            #[cfg(feature = "lz4")]
+            Codec::Lz4,
+            #[cfg(feature = "zstd")]
+            Codec::Zstd(3),
+        ]
+    }
+
+    #[test]
+    fn test_round_trip() {
+        // A flat UI with a bit of noise
+        let data: Vec<u8> = (0..64 * 64 * 4)
+            .map(|i: u32| if i < 256 { i as u8 } else { 0xEE })
+            .collect();
+        let bgra = Frame::BGRA(BGRAFrame {
+            display_time: 7,
+            width: 64,
+            height: 64,
+            data: data.clone(),
+            origin: RowOrder::BottomUp,
+            color_space: ColorSpace::DisplayP3,
+        });
+        let yuv = Frame::YUVFrame(YUVFrame {
+            display_time: 8,
+            width: 64,
+            height: 64,
+            luminance_bytes: data[..64 * 64].to_vec(),
+            luminance_stride: 64,
+            chrominance_bytes: data[..64 * 32].to_vec(),
+            chrominance_stride: 64,
+            color_matrix: ColorMatrix::default(),
+            color_range: ColorRange::default(),
+            chroma_subsampling: ChromaSubsampling::default(),
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::SRGB,
+        });
+
+        for codec in codecs() {
+            let compressed = compress(&bgra, codec).unwrap();
+            assert_eq!(compressed.raw_size(), data.len());
+            assert!(compressed.ratio() > 10.0);
+            let Frame::BGRA(restored) = decompress(&compressed).unwrap() else {
+                panic!("wrong frame type");
+            };
+            assert_eq!(restored.data, data);
+            assert_eq!(restored.display_time, 7);
+            assert_eq!(restored.origin, RowOrder::BottomUp);
+            assert_eq!(restored.color_space, ColorSpace::DisplayP3);
+
+            let compressed = compress(&yuv, codec).unwrap();
+            let Frame::YUVFrame(restored) = decompress(&compressed).unwrap() else {
+                panic!("wrong frame type");
+            };
+            assert_eq!(restored.luminance_bytes, data[..64 * 64]);
+            assert_eq!(restored.chrominance_bytes, data[..64 * 32]);
+        }
+    }
+}
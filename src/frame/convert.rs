@@ -0,0 +1,225 @@
+use super::BGRAFrame;
+
+/// Dithering applied when a conversion reduces bit depth
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Dither {
+    #[default]
+    None,
+    /// 4x4 Bayer matrix. Cheap and position-only, so it can be computed per pixel in parallel.
+    Ordered4x4,
+    /// Serpentine error diffusion. Best quality, but every pixel depends on
+    /// its predecessors, so rows have to be processed serially.
+    FloydSteinberg,
+}
+
+/// Options for frame conversions
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConvertOptions {
+    pub dither: Dither,
+}
+
+#[rustfmt::skip]
+const BAYER_4X4: [[u8; 4]; 4] = [
+    [ 0,  8,  2, 10],
+    [12,  4, 14,  6],
+    [ 3, 11,  1,  9],
+    [15,  7, 13,  5],
+];
+
+// Bayer thresholds as fractions of one quantization level, scaled by 32 * 255
+fn bayer_thresholds() -> [[u32; 4]; 4] {
+    let mut thresholds = [[0; 4]; 4];
+    for (y, row) in BAYER_4X4.iter().enumerate() {
+        for (x, value) in row.iter().enumerate() {
+            thresholds[y][x] = (2 * *value as u32 + 1) * 255;
+        }
+    }
+    thresholds
+}
+
+// Quantizes one 8-bit channel plane of `width` x `height` to `bits` bits
+fn quantize_channel(
+    values: &[u8],
+    width: usize,
+    height: usize,
+    bits: u32,
+    dither: Dither,
+) -> Vec<u8> {
+    // Level n maps back to n * 255 / max
+    let max: u32 = (1 << bits) - 1;
+
+    match dither {
+        Dither::None => values
+            .iter()
+            .map(|v| ((*v as u32 * max + 127) / 255) as u8)
+            .collect(),
+        Dither::Ordered4x4 => {
+            let thresholds = bayer_thresholds();
+            values
+                .iter()
+                .enumerate()
+                .map(|(i, v)| {
+                    let threshold = thresholds[(i / width) % 4][(i % width) % 4];
+                    ((*v as u32 * max * 32 + threshold) / (255 * 32)) as u8
+                })
+                .collect()
+        }
+        Dither::FloydSteinberg => {
+            let mut out = vec![0; values.len()];
+            // Errors for the current and the next row, padded by one on each side
+            let mut current = vec![0i32; width + 2];
+            let mut next = vec![0i32; width + 2];
+
+            for y in 0..height {
+                let reverse = y % 2 == 1;
+                for step in 0..width {
+                    let x = if reverse { width - 1 - step } else { step };
+                    let i = y * width + x;
+
+                    let value = (values[i] as i32 * 16 + current[x + 1]).clamp(0, 255 * 16);
+                    let level = (value as u32 * max + 255 * 8) / (255 * 16);
+                    out[i] = level as u8;
+
+                    let error = value - (level * 255 * 16 / max) as i32;
+                    // Ahead is to the right on even rows and to the left on odd rows
+                    let (ahead, behind) = if reverse { (x, x + 2) } else { (x + 2, x) };
+                    current[ahead] += error * 7 / 16;
+                    next[behind] += error * 3 / 16;
+                    next[x + 1] += error * 5 / 16;
+                    next[ahead] += error / 16;
+                }
+                std::mem::swap(&mut current, &mut next);
+                next.iter_mut().for_each(|e| *e = 0);
+            }
+            out
+        }
+    }
+}
+
+/// Converts a BGRA frame to tightly packed, top-down RGB565 pixels, e.g. for thumbnails
+pub fn convert_bgra_to_rgb565(frame: &BGRAFrame, options: &ConvertOptions) -> Vec<u16> {
+    let width = frame.width.max(0) as usize;
+    let height = frame.height.max(0) as usize;
+    let stride = frame.data.len().checked_div(height).unwrap_or(0);
+
+    let mut planes = [
+        Vec::with_capacity(width * height),
+        Vec::with_capacity(width * height),
+        Vec::with_capacity(width * height),
+    ];
+    for y in 0..height {
+        let start = frame.origin.buffer_row(y, height) * stride;
+        for pixel in frame.data[start..start + width * 4].chunks_exact(4) {
+            planes[0].push(pixel[2]);
+            planes[1].push(pixel[1]);
+            planes[2].push(pixel[0]);
+        }
+    }
+
+    let r = quantize_channel(&planes[0], width, height, 5, options.dither);
+    let g = quantize_channel(&planes[1], width, height, 6, options.dither);
+    let b = quantize_channel(&planes[2], width, height, 5, options.dither);
+
+    r.iter()
+        .zip(g.iter())
+        .zip(b.iter())
+        .map(|((r, g), b)| ((*r as u16) << 11) | ((*g as u16) << 5) | *b as u16)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{ColorSpace, RowOrder};
+
+    fn gray_frame(width: usize, height: usize, value: impl Fn(usize) -> u8) -> BGRAFrame {
+        let mut data = Vec::with_capacity(width * height * 4);
+        for _ in 0..height {
+            for x in 0..width {
+                let v = value(x);
+                data.extend_from_slice(&[v, v, v, 255]);
+            }
+        }
+        BGRAFrame {
+            display_time: 0,
+            width: width as i32,
+            height: height as i32,
+            data,
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::Unknown,
+        }
+    }
+
+    // Means of the red channel (back in 8-bit) over every 4x4 block
+    fn block_means(pixels: &[u16], width: usize, height: usize) -> Vec<f64> {
+        let mut means = Vec::new();
+        for by in (0..height).step_by(4) {
+            for bx in (0..width).step_by(4) {
+                let mut sum = 0.0;
+                for y in by..by + 4 {
+                    for x in bx..bx + 4 {
+                        sum += ((pixels[y * width + x] >> 11) as f64) * 255.0 / 31.0;
+                    }
+                }
+                means.push(sum / 16.0);
+            }
+        }
+        means
+    }
+
+    #[test]
+    fn test_dither_reduces_banding() {
+        let (width, height) = (256, 16);
+        // A shallow gradient spanning only a few RGB565 levels
+        let frame = gray_frame(width, height, |x| 64 + (x / 8) as u8);
+
+        let banding = |dither| {
+            let pixels = convert_bgra_to_rgb565(&frame, &ConvertOptions { dither });
+            let means = block_means(&pixels, width, height);
+
+            let mut unique: Vec<i64> = means.iter().map(|m| (m * 10.0) as i64).collect();
+            unique.sort();
+            unique.dedup();
+
+            let error = means
+                .iter()
+                .enumerate()
+                .map(|(i, m)| {
+                    let block_x = (i % (width / 4)) * 4;
+                    let source = 64.0 + (block_x / 8) as f64;
+                    (m - source).abs()
+                })
+                .sum::<f64>()
+                / means.len() as f64;
+            (unique.len(), error)
+        };
+
+        let (plain_levels, plain_error) = banding(Dither::None);
+        for dither in [Dither::Ordered4x4, Dither::FloydSteinberg] {
+            let (levels, error) = banding(dither);
+            assert!(levels > plain_levels * 2, "{dither:?} {levels} levels");
+            assert!(error < plain_error, "{dither:?} error {error}");
+        }
+    }
+
+    #[test]
+    fn test_dither_saturated_values() {
+        for dither in [Dither::None, Dither::Ordered4x4, Dither::FloydSteinberg] {
+            let options = ConvertOptions { dither };
+            let white = convert_bgra_to_rgb565(&gray_frame(9, 7, |_| 255), &options);
+            assert!(white.iter().all(|p| *p == 0xFFFF), "{dither:?}");
+            let black = convert_bgra_to_rgb565(&gray_frame(9, 7, |_| 0), &options);
+            assert!(black.iter().all(|p| *p == 0), "{dither:?}");
+        }
+    }
+    #[test]
+    fn test_bottom_up_input() {
+        // White over black, stored bottom row first
+        let mut frame = gray_frame(2, 2, |_| 0);
+        frame.data[8..].fill(255);
+        frame.origin = RowOrder::BottomUp;
+
+        let pixels = convert_bgra_to_rgb565(&frame, &ConvertOptions::default());
+        assert_eq!(pixels, vec![0xFFFF, 0xFFFF, 0, 0]);
+    }
+}
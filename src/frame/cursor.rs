@@ -0,0 +1,222 @@
+use super::{Frame, RowOrder};
+
+/// A cursor bitmap composited into frames, see `CursorStyle::Custom`
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CursorImage {
+    pub width: u32,
+    pub height: u32,
+    /// Tightly packed BGRA pixels with straight (not premultiplied) alpha
+    pub data: Vec<u8>,
+    /// The pixel of the image that sits at the cursor position
+    pub hotspot: (u32, u32),
+}
+
+/// Color of the ring drawn by `CursorStyle::Highlighted`, as BGRA
+pub const CURSOR_HIGHLIGHT_COLOR: [u8; 4] = [0, 215, 255, 160];
+/// Outer radius and thickness of the highlight ring in frame pixels
+pub const CURSOR_HIGHLIGHT_RADIUS: f64 = 24.0;
+pub const CURSOR_HIGHLIGHT_THICKNESS: f64 = 4.0;
+
+// A mutable view of a packed frame with 4 bytes per pixel
+struct Canvas<'a> {
+    data: &'a mut [u8],
+    width: usize,
+    height: usize,
+    stride: usize,
+    origin: RowOrder,
+    // Index of B, G and R in a pixel
+    bgr: [usize; 3],
+}
+
+impl<'a> Canvas<'a> {
+    fn new(frame: &'a mut Frame) -> Option<Self> {
+        let (data, width, height, origin, bgr) = match frame {
+            Frame::RGBx(f) => (&mut f.data, f.width, f.height, f.origin, [2, 1, 0]),
+            Frame::XBGR(f) => (&mut f.data, f.width, f.height, f.origin, [1, 2, 3]),
+            Frame::BGRx(f) => (&mut f.data, f.width, f.height, f.origin, [0, 1, 2]),
+            Frame::BGRA(f) => (&mut f.data, f.width, f.height, f.origin, [0, 1, 2]),
+            // Cursors are only composited into 4 byte formats
+            Frame::YUVFrame(_)
+            | Frame::RGB(_)
+            | Frame::BGR0(_)
+            | Frame::Gray8(_)
+            | Frame::Delta(_)
+            | Frame::ScanlinePatch(_)
+            | Frame::Regions(_)
+            | Frame::Marker(_)
+            | Frame::Activity(_)
+            | Frame::Raw(_) => return None,
+        };
+
+        if width <= 0 || height <= 0 {
+            return None;
+        }
+        let (width, height) = (width as usize, height as usize);
+        let stride = data.len() / height;
+        if stride < width * 4 {
+            return None;
+        }
+
+        Some(Canvas {
+            data,
+            width,
+            height,
+            stride,
+            origin,
+            bgr,
+        })
+    }
+
+    // Blends a straight alpha BGRA `color` over the pixel at (x, y), if it's inside the frame
+    fn blend(&mut self, x: i64, y: i64, color: [u8; 4], coverage: f64) {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return;
+        }
+
+        let row = self.origin.buffer_row(y as usize, self.height);
+        let i = row * self.stride + x as usize * 4;
+        let alpha = color[3] as f64 / 255.0 * coverage;
+        for (value, offset) in color.iter().zip(self.bgr) {
+            let dst = &mut self.data[i + offset];
+            *dst = (*value as f64 * alpha + *dst as f64 * (1.0 - alpha)).round() as u8;
+        }
+    }
+}
+
+/// Draws `cursor` into `frame` with its hotspot at `position` (in frame
+/// pixels). Parts of the cursor that fall outside the frame are skipped.
+pub fn composite_cursor(frame: &mut Frame, cursor: &CursorImage, position: (f64, f64)) {
+    let Some(mut canvas) = Canvas::new(frame) else {
+        return;
+    };
+
+    let left = position.0.round() as i64 - cursor.hotspot.0 as i64;
+    let top = position.1.round() as i64 - cursor.hotspot.1 as i64;
+    let pixels = cursor
+        .data
+        .chunks_exact(4)
+        .take((cursor.width * cursor.height) as usize);
+    for (i, pixel) in pixels.enumerate() {
+        let x = left + (i % cursor.width as usize) as i64;
+        let y = top + (i / cursor.width as usize) as i64;
+        canvas.blend(x, y, [pixel[0], pixel[1], pixel[2], pixel[3]], 1.0);
+    }
+}
+
+/// Draws an anti-aliased ring around `position` (in frame pixels)
+pub fn draw_cursor_highlight(frame: &mut Frame, position: (f64, f64)) {
+    let Some(mut canvas) = Canvas::new(frame) else {
+        return;
+    };
+
+    let outer = CURSOR_HIGHLIGHT_RADIUS;
+    let inner = outer - CURSOR_HIGHLIGHT_THICKNESS;
+    let (cx, cy) = position;
+    let reach = outer.ceil() as i64 + 1;
+
+    for y in cy.floor() as i64 - reach..=cy.floor() as i64 + reach {
+        for x in cx.floor() as i64 - reach..=cx.floor() as i64 + reach {
+            let distance = ((x as f64 + 0.5 - cx).powi(2) + (y as f64 + 0.5 - cy).powi(2)).sqrt();
+            // Coverage fades over one pixel at both edges of the ring
+            let coverage = (outer - distance + 0.5).min(distance - inner + 0.5);
+            if coverage > 0.0 {
+                canvas.blend(x, y, CURSOR_HIGHLIGHT_COLOR, coverage.min(1.0));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{BGRAFrame, ColorSpace};
+
+    fn black_frame(width: usize, height: usize, origin: RowOrder) -> Frame {
+        Frame::BGRA(BGRAFrame {
+            display_time: 0,
+            width: width as i32,
+            height: height as i32,
+            data: [0, 0, 0, 255].repeat(width * height),
+            origin,
+            color_space: ColorSpace::Unknown,
+        })
+    }
+
+    fn pixel(frame: &Frame, x: usize, y: usize) -> [u8; 4] {
+        let Frame::BGRA(f) = frame else {
+            unreachable!()
+        };
+        let row = f.origin.buffer_row(y, f.height as usize);
+        let i = (row * f.width as usize + x) * 4;
+        [f.data[i], f.data[i + 1], f.data[i + 2], f.data[i + 3]]
+    }
+
+    // A 3x3 cursor: opaque white with a red hotspot in the center
+    fn cursor() -> CursorImage {
+        let mut data = [255, 255, 255, 255].repeat(9);
+        data[16..20].copy_from_slice(&[0, 0, 255, 255]);
+        CursorImage {
+            width: 3,
+            height: 3,
+            data,
+            hotspot: (1, 1),
+        }
+    }
+
+    #[test]
+    fn test_composite_respects_hotspot() {
+        for origin in [RowOrder::TopDown, RowOrder::BottomUp] {
+            let mut frame = black_frame(8, 8, origin);
+            composite_cursor(&mut frame, &cursor(), (4.0, 5.0));
+
+            assert_eq!(pixel(&frame, 4, 5), [0, 0, 255, 255], "{origin:?}");
+            assert_eq!(pixel(&frame, 3, 4), [255, 255, 255, 255], "{origin:?}");
+            assert_eq!(pixel(&frame, 5, 6), [255, 255, 255, 255], "{origin:?}");
+            assert_eq!(pixel(&frame, 2, 5), [0, 0, 0, 255], "{origin:?}");
+            assert_eq!(pixel(&frame, 4, 7), [0, 0, 0, 255], "{origin:?}");
+        }
+    }
+
+    #[test]
+    fn test_composite_clips_to_frame() {
+        let mut frame = black_frame(4, 4, RowOrder::TopDown);
+        composite_cursor(&mut frame, &cursor(), (0.0, 3.0));
+        assert_eq!(pixel(&frame, 0, 3), [0, 0, 255, 255]);
+        assert_eq!(pixel(&frame, 1, 2), [255, 255, 255, 255]);
+        assert_eq!(pixel(&frame, 2, 3), [0, 0, 0, 255]);
+
+        // Entirely outside
+        let mut frame = black_frame(4, 4, RowOrder::TopDown);
+        composite_cursor(&mut frame, &cursor(), (-10.0, 20.0));
+        assert!(matches!(&frame, Frame::BGRA(f) if f.data == [0, 0, 0, 255].repeat(16)));
+    }
+
+    #[test]
+    fn test_composite_blends_alpha() {
+        let mut frame = black_frame(2, 2, RowOrder::TopDown);
+        let cursor = CursorImage {
+            width: 1,
+            height: 1,
+            data: vec![200, 100, 50, 128],
+            hotspot: (0, 0),
+        };
+        composite_cursor(&mut frame, &cursor, (1.0, 1.0));
+        assert_eq!(pixel(&frame, 1, 1), [100, 50, 25, 255]);
+    }
+
+    #[test]
+    fn test_highlight_ring() {
+        let mut frame = black_frame(64, 64, RowOrder::TopDown);
+        draw_cursor_highlight(&mut frame, (32.0, 32.0));
+
+        // The center stays untouched while the ring is colored
+        assert_eq!(pixel(&frame, 32, 32), [0, 0, 0, 255]);
+        let ring = pixel(&frame, 32 + CURSOR_HIGHLIGHT_RADIUS as usize - 2, 32);
+        assert!(ring[1] > 100 && ring[2] > 100 && ring[0] == 0, "{ring:?}");
+        assert_eq!(pixel(&frame, 63, 63), [0, 0, 0, 255]);
+
+        // Drawing near a corner doesn't go out of bounds
+        draw_cursor_highlight(&mut frame, (0.0, 63.0));
+    }
+}
@@ -0,0 +1,488 @@
+use super::{BGRAFrame, ColorSpace, RowOrder};
+use crate::capturer::DeltaReference;
+
+/// Changed pixels of a frame, in BGRA rows without padding
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeltaRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// A BGRA frame as the regions that changed since the frame before it, see
+/// [Options::delta](crate::capturer::Options::delta)
+#[derive(Debug, Clone)]
+pub struct DeltaFrame {
+    pub display_time: u64,
+    /// Counts up by one with every frame of the stream
+    pub sequence: u64,
+    /// The sequence of the frame `regions` are applied on top of. None for
+    /// keyframes, which hold the whole frame as a single region.
+    pub base_sequence: Option<u64>,
+    pub width: i32,
+    pub height: i32,
+    pub regions: Vec<DeltaRegion>,
+    pub color_space: ColorSpace,
+}
+
+impl DeltaFrame {
+    pub fn is_keyframe(&self) -> bool {
+        self.base_sequence.is_none()
+    }
+}
+
+/// Why [apply_delta] couldn't apply a delta. Either way the frame is left
+/// unchanged and only a keyframe can resynchronize it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeltaError {
+    /// The delta is for a frame of another size
+    SizeMismatch,
+    /// A region lies outside the frame, or its pixels don't fill it
+    InvalidRegion,
+}
+
+impl std::fmt::Display for DeltaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeltaError::SizeMismatch => write!(f, "The delta is for a frame of another size"),
+            DeltaError::InvalidRegion => write!(f, "A delta region doesn't fit the frame"),
+        }
+    }
+}
+
+impl std::error::Error for DeltaError {}
+
+/// Reconstructs the full frame on the receiving side by applying `delta` to
+/// `frame`, which must hold the result of the frame with sequence
+/// `delta.base_sequence`. Keyframes replace `frame` entirely, so a default
+/// BGRAFrame can be used until the first one arrives.
+///
+/// Checking the sequence is up to the caller: when a delta's base isn't the
+/// last applied frame, deltas got lost. Skip deltas from then on and ask the
+/// sending side for a keyframe with
+/// [Capturer::force_keyframe](crate::capturer::Capturer::force_keyframe).
+pub fn apply_delta(frame: &mut BGRAFrame, delta: &DeltaFrame) -> Result<(), DeltaError> {
+    let (width, height) = (delta.width.max(0) as usize, delta.height.max(0) as usize);
+    let fits = |region: &DeltaRegion| {
+        let (x, y) = (region.x as usize, region.y as usize);
+        let (w, h) = (region.width as usize, region.height as usize);
+        x + w <= width && y + h <= height && region.pixels.len() == w * h * 4
+    };
+    if !delta.regions.iter().all(fits) {
+        return Err(DeltaError::InvalidRegion);
+    }
+
+    if delta.is_keyframe() {
+        frame.data.clear();
+        frame.data.resize(width * height * 4, 0);
+        frame.width = delta.width;
+        frame.height = delta.height;
+        frame.origin = RowOrder::TopDown;
+    } else if (frame.width, frame.height) != (delta.width, delta.height)
+        || frame.data.len() != width * height * 4
+        || frame.origin != RowOrder::TopDown
+    {
+        return Err(DeltaError::SizeMismatch);
+    }
+
+    for region in &delta.regions {
+        let row_bytes = region.width as usize * 4;
+        for (i, pixels) in region.pixels.chunks_exact(row_bytes.max(1)).enumerate() {
+            let start = ((region.y as usize + i) * width + region.x as usize) * 4;
+            frame.data[start..start + row_bytes].copy_from_slice(pixels);
+        }
+    }
+
+    frame.display_time = delta.display_time;
+    frame.color_space = delta.color_space;
+    Ok(())
+}
+
+// Copies the rows of a BGRA frame top-down and without padding into `into`.
+// Returns the frame's size, or None if it's empty.
+pub(super) fn copy_top_down(frame: &BGRAFrame, into: &mut Vec<u8>) -> Option<(usize, usize)> {
+    let (width, height) = (frame.width.max(0) as usize, frame.height.max(0) as usize);
+    if width == 0 || height == 0 || frame.data.len() / height < width * 4 {
+        return None;
+    }
+
+    let stride = frame.data.len() / height;
+    into.clear();
+    for y in 0..height {
+        let start = frame.origin.buffer_row(y, height) * stride;
+        into.extend_from_slice(&frame.data[start..start + width * 4]);
+    }
+    Some((width, height))
+}
+
+// Turns BGRA frames into deltas against the frame before or the last
+// keyframe, by comparing them tile by tile. Comparing pixels rather than tile
+// hashes keeps the reconstruction exact.
+#[derive(Debug)]
+pub(crate) struct DeltaEncoder {
+    tile_size: usize,
+    keyframe_interval: u32,
+    reference: DeltaReference,
+    // The frame deltas are found against without row padding, empty before
+    // the first one
+    previous: Vec<u8>,
+    current: Vec<u8>,
+    size: (usize, usize),
+    sequence: u64,
+    since_keyframe: u32,
+    force_keyframe: bool,
+    keyframe_sequence: u64,
+    // The last delta's regions, which idle frames repeat against a keyframe
+    regions: Vec<DeltaRegion>,
+}
+
+impl DeltaEncoder {
+    // Compares tiles of `tile_size` pixels and sends a keyframe every
+    // `keyframe_interval` frames, or only when forced if it's 0
+    pub fn new(tile_size: u32, keyframe_interval: u32, reference: DeltaReference) -> Self {
+        DeltaEncoder {
+            tile_size: tile_size.max(1) as usize,
+            keyframe_interval,
+            reference,
+            previous: Vec::new(),
+            current: Vec::new(),
+            size: (0, 0),
+            sequence: 0,
+            since_keyframe: 0,
+            force_keyframe: false,
+            keyframe_sequence: 0,
+            regions: Vec::new(),
+        }
+    }
+
+    // Makes the next frame a keyframe
+    pub fn force_keyframe(&mut self) {
+        self.force_keyframe = true;
+    }
+
+    // Returns None only for empty frames before the first keyframe. Empty
+    // frames after it, which macOS sends while the screen is idle, are
+    // deltas without regions.
+    pub fn encode(&mut self, frame: &BGRAFrame) -> Option<DeltaFrame> {
+        let size = copy_top_down(frame, &mut self.current);
+        if size.is_none() && self.previous.is_empty() {
+            return None;
+        }
+
+        let keyframe = size.is_some_and(|size| {
+            self.force_keyframe
+                || self.previous.is_empty()
+                || self.size != size
+                || (self.keyframe_interval > 0 && self.since_keyframe >= self.keyframe_interval)
+        });
+
+        let (width, height) = size.unwrap_or(self.size);
+        let base_sequence = match self.reference {
+            DeltaReference::PreviousFrame => self.sequence.wrapping_sub(1),
+            DeltaReference::Keyframe => self.keyframe_sequence,
+        };
+        let mut delta = DeltaFrame {
+            display_time: frame.display_time,
+            sequence: self.sequence,
+            base_sequence: (!keyframe).then_some(base_sequence),
+            width: width as i32,
+            height: height as i32,
+            regions: Vec::new(),
+            color_space: frame.color_space,
+        };
+        self.sequence = self.sequence.wrapping_add(1);
+
+        if size.is_none() {
+            self.since_keyframe += 1;
+            if self.reference == DeltaReference::Keyframe {
+                delta.regions = self.regions.clone();
+            }
+            return Some(delta);
+        }
+
+        if keyframe {
+            delta.regions.push(DeltaRegion {
+                x: 0,
+                y: 0,
+                width: width as u32,
+                height: height as u32,
+                pixels: self.current.clone(),
+            });
+            self.force_keyframe = false;
+            self.since_keyframe = 0;
+            self.keyframe_sequence = delta.sequence;
+            self.regions.clear();
+        } else {
+            delta.regions = self.changed_regions(width, height);
+            self.since_keyframe += 1;
+        }
+
+        match self.reference {
+            DeltaReference::PreviousFrame => {
+                std::mem::swap(&mut self.previous, &mut self.current);
+            }
+            DeltaReference::Keyframe if keyframe => {
+                std::mem::swap(&mut self.previous, &mut self.current);
+            }
+            DeltaReference::Keyframe => self.regions = delta.regions.clone(),
+        }
+        self.size = (width, height);
+        Some(delta)
+    }
+
+    // Changed tiles, with neighbours in a row of tiles merged into one region
+    fn changed_regions(&self, width: usize, height: usize) -> Vec<DeltaRegion> {
+        let tile = self.tile_size;
+        let changed = |tx: usize, top: usize, bottom: usize| {
+            let (left, right) = (tx * tile * 4, ((tx + 1) * tile).min(width) * 4);
+            (top..bottom).any(|y| {
+                let row = y * width * 4;
+                self.current[row + left..row + right] != self.previous[row + left..row + right]
+            })
+        };
+
+        let mut regions = Vec::new();
+        for top in (0..height).step_by(tile) {
+            let bottom = (top + tile).min(height);
+            let mut tx = 0;
+            while tx * tile < width {
+                if !changed(tx, top, bottom) {
+                    tx += 1;
+                    continue;
+                }
+
+                let first = tx;
+                while tx * tile < width && changed(tx, top, bottom) {
+                    tx += 1;
+                }
+                let (left, right) = (first * tile, (tx * tile).min(width));
+
+                let mut pixels = Vec::with_capacity((right - left) * (bottom - top) * 4);
+                for y in top..bottom {
+                    let row = y * width * 4;
+                    pixels.extend_from_slice(&self.current[row + left * 4..row + right * 4]);
+                }
+                regions.push(DeltaRegion {
+                    x: left as u32,
+                    y: top as u32,
+                    width: (right - left) as u32,
+                    height: (bottom - top) as u32,
+                    pixels,
+                });
+            }
+        }
+        regions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bgra_frame(width: i32, height: i32, time: u64, seed: u32) -> BGRAFrame {
+        BGRAFrame {
+            display_time: time,
+            width,
+            height,
+            data: (0..width * height * 4)
+                .map(|i| (i as u32).wrapping_mul(2654435761).wrapping_add(seed) as u8)
+                .collect(),
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::SRGB,
+        }
+    }
+
+    // Paints a rectangle in a frame
+    fn paint(frame: &mut BGRAFrame, x: usize, y: usize, w: usize, h: usize, value: u8) {
+        for row in y..y + h {
+            let start = (row * frame.width as usize + x) * 4;
+            frame.data[start..start + w * 4].fill(value);
+        }
+    }
+
+    #[test]
+    fn test_reconstruction_matches_full_frames() {
+        let mut encoder = DeltaEncoder::new(16, 10, DeltaReference::PreviousFrame);
+        let mut received = BGRAFrame {
+            display_time: 0,
+            width: 0,
+            height: 0,
+            data: vec![],
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::Unknown,
+        };
+
+        let mut frame = bgra_frame(100, 70, 0, 1);
+        for i in 0..40u64 {
+            frame.display_time = i;
+            match i % 4 {
+                // A menu opening, across tile borders and the frame's edge
+                0 => paint(&mut frame, 90, 10, 10, 25, i as u8),
+                1 => paint(&mut frame, 17, 63, 40, 7, i as u8),
+                2 => paint(&mut frame, 33, 33, 1, 1, i as u8),
+                _ => {}
+            }
+
+            let delta = encoder.encode(&frame).unwrap();
+            assert_eq!(delta.sequence, i);
+            assert_eq!(delta.is_keyframe(), i % 11 == 0, "frame {i}");
+            if i % 4 == 3 && !delta.is_keyframe() {
+                assert!(delta.regions.is_empty());
+            }
+
+            apply_delta(&mut received, &delta).unwrap();
+            assert_eq!(received.data, frame.data, "frame {i}");
+            assert_eq!(received.display_time, i);
+        }
+
+        // Only the changed tiles are sent, merged where they touch
+        paint(&mut frame, 20, 20, 30, 5, 200);
+        let delta = encoder.encode(&frame).unwrap();
+        assert_eq!(delta.regions.len(), 1);
+        let region = &delta.regions[0];
+        assert_eq!(
+            (region.x, region.y, region.width, region.height),
+            (16, 16, 48, 16)
+        );
+    }
+
+    #[test]
+    fn test_dropped_deltas_need_a_keyframe() {
+        let mut encoder = DeltaEncoder::new(8, 0, DeltaReference::PreviousFrame);
+        let mut frame = bgra_frame(32, 32, 0, 2);
+        let mut received = frame.clone();
+        received.data.clear();
+
+        let keyframe = encoder.encode(&frame).unwrap();
+        apply_delta(&mut received, &keyframe).unwrap();
+        let mut last = keyframe.sequence;
+
+        // The receiver misses a delta and notices from the next one's base
+        paint(&mut frame, 0, 0, 8, 8, 7);
+        let _dropped = encoder.encode(&frame).unwrap();
+        paint(&mut frame, 24, 24, 8, 8, 9);
+        let delta = encoder.encode(&frame).unwrap();
+        assert_ne!(delta.base_sequence, Some(last));
+
+        encoder.force_keyframe();
+        paint(&mut frame, 10, 10, 4, 4, 11);
+        let delta = encoder.encode(&frame).unwrap();
+        assert!(delta.is_keyframe());
+        apply_delta(&mut received, &delta).unwrap();
+        assert_eq!(received.data, frame.data);
+        last = delta.sequence;
+
+        // Without a keyframe interval, deltas follow until the next forced one
+        paint(&mut frame, 1, 1, 2, 2, 13);
+        let delta = encoder.encode(&frame).unwrap();
+        assert_eq!(delta.base_sequence, Some(last));
+        apply_delta(&mut received, &delta).unwrap();
+        assert_eq!(received.data, frame.data);
+    }
+
+    #[test]
+    fn test_deltas_against_the_keyframe() {
+        let mut encoder = DeltaEncoder::new(8, 0, DeltaReference::Keyframe);
+        let mut frame = bgra_frame(32, 32, 0, 6);
+        let mut keyframe = frame.clone();
+        keyframe.data.clear();
+        let first = encoder.encode(&frame).unwrap();
+        apply_delta(&mut keyframe, &first).unwrap();
+
+        // A change that's undone again, then another one
+        paint(&mut frame, 0, 0, 8, 8, 7);
+        let _dropped = encoder.encode(&frame).unwrap();
+        let original = bgra_frame(32, 32, 0, 6);
+        frame.data[..8 * 32 * 4].copy_from_slice(&original.data[..8 * 32 * 4]);
+        paint(&mut frame, 24, 24, 8, 8, 9);
+        let delta = encoder.encode(&frame).unwrap();
+        assert_eq!(delta.base_sequence, Some(first.sequence));
+
+        // Applies to the keyframe even though a delta went missing
+        let mut received = keyframe.clone();
+        apply_delta(&mut received, &delta).unwrap();
+        assert_eq!(received.data, frame.data);
+
+        // Idle frames repeat the changes since the keyframe
+        let idle = BGRAFrame {
+            width: 0,
+            height: 0,
+            data: vec![],
+            ..frame.clone()
+        };
+        let mut received = keyframe.clone();
+        apply_delta(&mut received, &encoder.encode(&idle).unwrap()).unwrap();
+        assert_eq!(received.data, frame.data);
+    }
+
+    #[test]
+    fn test_resizes_and_idle_frames() {
+        let mut encoder = DeltaEncoder::new(16, 0, DeltaReference::PreviousFrame);
+        let empty = BGRAFrame {
+            display_time: 1,
+            width: 0,
+            height: 0,
+            data: vec![],
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::Unknown,
+        };
+        assert!(encoder.encode(&empty).is_none());
+
+        let frame = bgra_frame(40, 30, 2, 3);
+        let mut received = frame.clone();
+        apply_delta(&mut received, &encoder.encode(&frame).unwrap()).unwrap();
+
+        // An idle frame changes nothing
+        let idle = encoder.encode(&empty).unwrap();
+        assert!(!idle.is_keyframe() && idle.regions.is_empty());
+        assert_eq!((idle.width, idle.height), (40, 30));
+        apply_delta(&mut received, &idle).unwrap();
+        assert_eq!(received.data, frame.data);
+
+        // A resized frame is a keyframe, a delta can't apply to the old size
+        let resized = bgra_frame(20, 30, 3, 4);
+        let delta = encoder.encode(&resized).unwrap();
+        assert!(delta.is_keyframe());
+        let mut stale = frame.clone();
+        let not_key = DeltaFrame {
+            base_sequence: Some(0),
+            ..delta.clone()
+        };
+        assert_eq!(
+            apply_delta(&mut stale, &not_key),
+            Err(DeltaError::SizeMismatch)
+        );
+        apply_delta(&mut received, &delta).unwrap();
+        assert_eq!(received.data, resized.data);
+
+        // Padded and bottom-up frames are encoded top-down without padding
+        let mut padded = bgra_frame(2, 2, 4, 5);
+        padded.data = vec![
+            1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, //
+            3, 3, 3, 3, 4, 4, 4, 4, 0, 0, 0, 0,
+        ];
+        padded.origin = RowOrder::BottomUp;
+        let delta = encoder.encode(&padded).unwrap();
+        assert_eq!(
+            delta.regions[0].pixels,
+            [3, 3, 3, 3, 4, 4, 4, 4, 1, 1, 1, 1, 2, 2, 2, 2]
+        );
+
+        let invalid = DeltaFrame {
+            regions: vec![DeltaRegion {
+                x: 1,
+                y: 0,
+                width: 2,
+                height: 1,
+                pixels: vec![0; 8],
+            }],
+            ..delta
+        };
+        assert_eq!(
+            apply_delta(&mut received, &invalid),
+            Err(DeltaError::InvalidRegion)
+        );
+    }
+}
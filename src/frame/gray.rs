@@ -0,0 +1,268 @@
+use super::{ColorRange, Frame, Gray8Frame, PackedData, RowOrder};
+
+/// Weights of the red, green and blue channels in a luma value. They should
+/// add up to 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LumaWeights {
+    pub red: f32,
+    pub green: f32,
+    pub blue: f32,
+}
+
+impl LumaWeights {
+    /// The weights of HD video and sRGB
+    pub const BT709: LumaWeights = LumaWeights {
+        red: 0.2126,
+        green: 0.7152,
+        blue: 0.0722,
+    };
+    /// The weights of SD video, and of most image libraries' grayscale conversion
+    pub const BT601: LumaWeights = LumaWeights {
+        red: 0.299,
+        green: 0.587,
+        blue: 0.114,
+    };
+
+    // The weights in 16 bit fixed point
+    fn fixed(&self) -> [u32; 3] {
+        [self.red, self.green, self.blue].map(|w| (w.clamp(0.0, 1.0) * 65536.0).round() as u32)
+    }
+}
+
+impl Default for LumaWeights {
+    fn default() -> Self {
+        LumaWeights::BT709
+    }
+}
+
+// The source columns or rows averaged into each of `to` output pixels when
+// scaling from `from`. Upscaling repeats pixels.
+pub(super) fn spans(from: usize, to: usize) -> Vec<(usize, usize)> {
+    (0..to)
+        .map(|i| {
+            let start = i * from / to;
+            (start, ((i + 1) * from / to).max(start + 1))
+        })
+        .collect()
+}
+
+impl Frame {
+    /// Converts the frame to luma and box filters it down to `width` x
+    /// `height`, without preserving the aspect ratio. YUV frames use their
+    /// luminance plane as is and ignore `weights`.
+    ///
+    /// Returns None for empty frames and formats without 8 bit channels.
+    pub fn to_gray8(&self, width: u32, height: u32, weights: LumaWeights) -> Option<Gray8Frame> {
+        let (width, height) = (width as usize, height as usize);
+        if width == 0 || height == 0 {
+            return None;
+        }
+
+        // Index of R, G and B in a pixel, None for single channel frames
+        let rgb = match self {
+            Frame::RGB(_) | Frame::RGBx(_) => Some([0, 1, 2]),
+            Frame::BGR0(_) | Frame::BGRx(_) | Frame::BGRA(_) => Some([2, 1, 0]),
+            Frame::XBGR(_) => Some([3, 2, 1]),
+            Frame::YUVFrame(_)
+            | Frame::Gray8(_)
+            | Frame::Delta(_)
+            | Frame::ScanlinePatch(_)
+            | Frame::Regions(_)
+            | Frame::Marker(_)
+            | Frame::Activity(_)
+            | Frame::Raw(_) => None,
+        };
+        let (source, stride, range) = match self {
+            Frame::YUVFrame(f) if f.width > 0 && f.height > 0 => (
+                PackedData {
+                    data: &f.luminance_bytes,
+                    width: f.width as usize,
+                    height: f.height as usize,
+                    origin: f.origin,
+                    bytes_per_pixel: 1,
+                },
+                f.luminance_stride.max(0) as usize,
+                Some(f.color_range),
+            ),
+            Frame::YUVFrame(_) => return None,
+            _ => {
+                let packed = self.packed_data()?;
+                (packed, packed.data.len() / packed.height, None)
+            }
+        };
+
+        let (src_width, src_height) = (source.width, source.height);
+        let (data, bytes_per_pixel) = (source.data, source.bytes_per_pixel);
+        if stride < src_width * bytes_per_pixel || data.len() < stride * src_height {
+            return None;
+        }
+
+        let [wr, wg, wb] = weights.fixed();
+        let luma = |pixel: &[u8]| -> u32 {
+            match rgb {
+                Some([r, g, b]) => {
+                    let sum = wr * pixel[r] as u32 + wg * pixel[g] as u32 + wb * pixel[b] as u32;
+                    ((sum + (1 << 15)) >> 16).min(255)
+                }
+                None => pixel[0] as u32,
+            }
+        };
+
+        let columns = spans(src_width, width);
+        let mut sums = vec![0u32; width];
+        let mut gray = Vec::with_capacity(width * height);
+
+        for (top, bottom) in spans(src_height, height) {
+            sums.fill(0);
+            for y in top..bottom {
+                let start = source.origin.buffer_row(y, src_height) * stride;
+                let row = &data[start..start + src_width * bytes_per_pixel];
+                for (sum, &(left, right)) in sums.iter_mut().zip(&columns) {
+                    *sum += row[left * bytes_per_pixel..right * bytes_per_pixel]
+                        .chunks_exact(bytes_per_pixel)
+                        .map(luma)
+                        .sum::<u32>();
+                }
+            }
+
+            for (sum, &(left, right)) in sums.iter().zip(&columns) {
+                let count = ((bottom - top) * (right - left)) as u32;
+                let value = (sum + count / 2) / count;
+                gray.push(match range {
+                    // Expand video range luma to the full range
+                    Some(ColorRange::Limited) => {
+                        ((value.clamp(16, 235) - 16) * 255 + 219 / 2) / 219
+                    }
+                    _ => value,
+                } as u8);
+            }
+        }
+
+        Some(Gray8Frame {
+            display_time: self.display_time(),
+            width: width as i32,
+            height: height as i32,
+            data: gray,
+            origin: RowOrder::TopDown,
+            color_space: self.color_space(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{BGRAFrame, ChromaSubsampling, ColorMatrix, ColorSpace, RGBFrame, YUVFrame};
+
+    fn bgra_frame(width: usize, height: usize, pixel: impl Fn(usize, usize) -> [u8; 4]) -> Frame {
+        Frame::BGRA(BGRAFrame {
+            display_time: 5,
+            width: width as i32,
+            height: height as i32,
+            data: (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .flat_map(|(x, y)| pixel(x, y))
+                .collect(),
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::SRGB,
+        })
+    }
+
+    #[test]
+    fn test_luma_weights() {
+        // Pure red, green and blue in BGRA and RGB
+        let bgra = bgra_frame(3, 1, |x, _| {
+            let mut pixel = [0, 0, 0, 255];
+            pixel[2 - x] = 255;
+            pixel
+        });
+        let rgb = Frame::RGB(RGBFrame {
+            display_time: 5,
+            width: 3,
+            height: 1,
+            data: vec![255, 0, 0, 0, 255, 0, 0, 0, 255],
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::SRGB,
+        });
+
+        for frame in [bgra, rgb] {
+            let gray = frame.to_gray8(3, 1, LumaWeights::BT709).unwrap();
+            assert_eq!(gray.data, [54, 182, 18]);
+            let gray = frame.to_gray8(3, 1, LumaWeights::BT601).unwrap();
+            assert_eq!(gray.data, [76, 150, 29]);
+        }
+
+        let custom = LumaWeights {
+            red: 0.0,
+            green: 1.0,
+            blue: 0.0,
+        };
+        let frame = bgra_frame(3, 1, |x, _| [10 * x as u8, 100 + x as u8, 200, 255]);
+        assert_eq!(frame.to_gray8(3, 1, custom).unwrap().data, [100, 101, 102]);
+    }
+
+    #[test]
+    fn test_downscale_box_filters() {
+        // 4x4 blocks of a single gray level each, with a gradient inside the
+        // last block that averages to its level
+        let frame = bgra_frame(8, 4, |x, y| {
+            let v = match x / 4 {
+                0 => 40,
+                _ => 100 + (x % 2) as u8 * 20 + (y % 2) as u8 * 20,
+            };
+            [v, v, v, 255]
+        });
+
+        let gray = frame.to_gray8(2, 1, LumaWeights::default()).unwrap();
+        assert_eq!((gray.width, gray.height), (2, 1));
+        assert_eq!(gray.data, [40, 120]);
+        assert_eq!(gray.display_time, 5);
+
+        // Upscaling repeats pixels
+        let gray = frame.to_gray8(16, 8, LumaWeights::default()).unwrap();
+        assert_eq!(gray.data.len(), 16 * 8);
+        assert_eq!(&gray.data[..2], [40, 40]);
+    }
+
+    #[test]
+    fn test_bottom_up_and_yuv() {
+        let Frame::BGRA(mut f) = bgra_frame(1, 2, |_, y| [y as u8 * 200; 4]) else {
+            unreachable!()
+        };
+        f.origin = RowOrder::BottomUp;
+        let gray = Frame::BGRA(f)
+            .to_gray8(1, 2, LumaWeights::default())
+            .unwrap();
+        assert_eq!(gray.data, [200, 0]);
+        assert_eq!(gray.origin, RowOrder::TopDown);
+
+        let yuv = YUVFrame {
+            display_time: 5,
+            width: 2,
+            height: 1,
+            luminance_bytes: vec![16, 235, 0, 0],
+            luminance_stride: 4,
+            chrominance_bytes: vec![128, 128],
+            chrominance_stride: 2,
+            color_matrix: ColorMatrix::BT709,
+            color_range: ColorRange::Limited,
+            chroma_subsampling: ChromaSubsampling::Yuv420,
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::SRGB,
+        };
+        let gray = Frame::YUVFrame(yuv.clone())
+            .to_gray8(2, 1, LumaWeights::default())
+            .unwrap();
+        assert_eq!(gray.data, [0, 255]);
+
+        let full = YUVFrame {
+            color_range: ColorRange::Full,
+            ..yuv
+        };
+        let gray = Frame::YUVFrame(full)
+            .to_gray8(1, 1, LumaWeights::default())
+            .unwrap();
+        assert_eq!(gray.data, [126]);
+    }
+}
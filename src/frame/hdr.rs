@@ -0,0 +1,144 @@
+/// SDR white in scRGB, where 1.0 is 80 nits
+const SCRGB_NITS: f32 = 80.0;
+
+// Decodes an IEEE 754 half precision float
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1F) as i32;
+    let mantissa = (bits & 0x3FF) as f32;
+
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        0x1F if mantissa == 0.0 => f32::INFINITY,
+        0x1F => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.003_130_8 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Maps half float scRGB pixels, as composited by Windows on HDR displays,
+/// to 8-bit sRGB the way an SDR screenshot of the same content looks.
+///
+/// Values are scaled so the display's SDR white level becomes full white.
+/// Highlights above it and colors outside the sRGB gamut are clipped.
+pub struct ScRgbToneMapper {
+    // Every half float bit pattern mapped to its 8-bit sRGB value
+    lut: Vec<u8>,
+}
+
+impl std::fmt::Debug for ScRgbToneMapper {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScRgbToneMapper").finish_non_exhaustive()
+    }
+}
+
+impl ScRgbToneMapper {
+    /// A tone mapper for a display with an SDR white level of `sdr_white_nits`.
+    /// Windows defaults to 80 nits, the scRGB reference white.
+    pub fn new(sdr_white_nits: f32) -> Self {
+        let scale = SCRGB_NITS / sdr_white_nits.max(1.0);
+        let lut = (0..=u16::MAX)
+            .map(|bits| {
+                let v = f16_to_f32(bits) * scale;
+                // NaN fails both comparisons and clamps to black
+                let v = if v > 0.0 { v.min(1.0) } else { 0.0 };
+                (linear_to_srgb(v) * 255.0).round() as u8
+            })
+            .collect();
+
+        ScRgbToneMapper { lut }
+    }
+
+    fn map(&self, data: &[u8], [r, g, b]: [usize; 3]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(data.len() / 2);
+        for pixel in data.chunks_exact(8) {
+            let channel = |i: usize| u16::from_le_bytes([pixel[i * 2], pixel[i * 2 + 1]]);
+            let mut mapped = [0; 4];
+            mapped[r] = self.lut[channel(0) as usize];
+            mapped[g] = self.lut[channel(1) as usize];
+            mapped[b] = self.lut[channel(2) as usize];
+            // Alpha is linear and never tone mapped
+            mapped[3] = (f16_to_f32(channel(3)).clamp(0.0, 1.0) * 255.0).round() as u8;
+            out.extend_from_slice(&mapped);
+        }
+        out
+    }
+
+    /// Converts tightly packed RGBA16F pixels to BGRA8
+    pub fn to_bgra(&self, data: &[u8]) -> Vec<u8> {
+        self.map(data, [2, 1, 0])
+    }
+
+    /// Converts tightly packed RGBA16F pixels to RGBA8
+    pub fn to_rgba(&self, data: &[u8]) -> Vec<u8> {
+        self.map(data, [0, 1, 2])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Encodes finite, normal or zero values as half floats
+    fn f32_to_f16(v: f32) -> u16 {
+        if v == 0.0 {
+            return 0;
+        }
+        let bits = v.to_bits();
+        let sign = ((bits >> 16) & 0x8000) as u16;
+        let exponent = ((bits >> 23) & 0xFF) as i32 - 127 + 15;
+        let mantissa = ((bits >> 13) & 0x3FF) as u16;
+        sign | ((exponent as u16) << 10) | mantissa
+    }
+
+    fn pixel(r: f32, g: f32, b: f32) -> Vec<u8> {
+        [r, g, b, 1.0]
+            .iter()
+            .flat_map(|v| f32_to_f16(*v).to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn test_f16_decoding() {
+        assert_eq!(f16_to_f32(0x3C00), 1.0);
+        assert_eq!(f16_to_f32(0xC000), -2.0);
+        assert_eq!(f16_to_f32(0x3800), 0.5);
+        assert_eq!(f16_to_f32(0x0001), 2f32.powi(-24));
+        assert!(f16_to_f32(0x7E00).is_nan());
+        for v in [0.25, 1.5, 3.0, 12.5] {
+            assert_eq!(f16_to_f32(f32_to_f16(v)), v);
+        }
+    }
+
+    #[test]
+    fn test_sdr_white_maps_to_white() {
+        // With a 240 nit SDR white level, SDR white is composited at 3.0
+        let mapper = ScRgbToneMapper::new(240.0);
+        assert_eq!(mapper.to_bgra(&pixel(3.0, 3.0, 3.0)), vec![255; 4]);
+        assert_eq!(mapper.to_bgra(&pixel(0.0, 0.0, 0.0)), vec![0, 0, 0, 255]);
+
+        // Mid gray: 18% of SDR white is sRGB 118
+        let gray = mapper.to_bgra(&pixel(0.54, 0.54, 0.54));
+        assert!((gray[0] as i32 - 118).abs() <= 1, "{gray:?}");
+    }
+
+    #[test]
+    fn test_out_of_range_values_are_clipped() {
+        let mapper = ScRgbToneMapper::new(80.0);
+        // HDR highlight red with a negative (wide gamut) blue component
+        let mapped = mapper.to_bgra(&pixel(6.0, 0.5, -0.2));
+        assert_eq!(mapped[2], 255);
+        assert_eq!(mapped[0], 0);
+        assert_eq!(mapped[1], 188);
+
+        let rgba = mapper.to_rgba(&pixel(6.0, 0.5, -0.2));
+        assert_eq!(rgba, vec![255, 188, 0, 255]);
+    }
+}
@@ -0,0 +1,274 @@
+use std::io::{self, Read, Write};
+
+use super::{ChecksumAlgorithm, FrameChecksum};
+
+// The index format, all integers little endian:
+//
+//   header   b"SCAPIDX1"
+//   entries  display_time: u64, offset: u64, length: u64, one per frame
+//   trailer  b"SCAPEND\0", entry count: u64
+//
+// Indexes with checksums start with b"SCAPIDX2" and the algorithm: u8,
+// 0 XXH64 and 1 CRC-32, padded to 8 bytes. Their entries end with the
+// frame's checksum: u64.
+//
+// The trailer is only written by finish, an index cut short by a crash
+// still holds every entry written before it.
+const HEADER: &[u8; 8] = b"SCAPIDX1";
+const CHECKSUM_HEADER: &[u8; 8] = b"SCAPIDX2";
+const TRAILER: &[u8; 8] = b"SCAPEND\0";
+
+/// Where a frame is in a raw capture, see [FrameIndexWriter]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexEntry {
+    pub display_time: u64,
+    /// Byte offset of the frame in the capture
+    pub offset: u64,
+    /// Length of the frame in bytes
+    pub length: u64,
+    /// The frame's checksum, in indexes written
+    /// [with checksums](FrameIndexWriter::with_checksums)
+    pub checksum: Option<FrameChecksum>,
+}
+
+/// Writes a sidecar index of a raw capture, mapping each frame's display time
+/// to where its bytes are, so players can seek without reading the capture
+/// from the start. Entries are written as frames are recorded and the index
+/// is completed by [FrameIndexWriter::finish], read it with [read_index].
+pub struct FrameIndexWriter<W: Write> {
+    writer: W,
+    entries: u64,
+    checksums: Option<ChecksumAlgorithm>,
+}
+
+impl<W: Write> FrameIndexWriter<W> {
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(HEADER)?;
+        Ok(FrameIndexWriter {
+            writer,
+            entries: 0,
+            checksums: None,
+        })
+    }
+
+    /// An index that holds a checksum of every frame, so readers can check
+    /// the frames of the capture for corruption with
+    /// [verify_frame_checksum](super::verify_frame_checksum). Frames are
+    /// recorded with [FrameIndexWriter::record_checksum].
+    pub fn with_checksums(mut writer: W, algorithm: ChecksumAlgorithm) -> io::Result<Self> {
+        writer.write_all(CHECKSUM_HEADER)?;
+        writer.write_all(&[get_algorithm_id(algorithm), 0, 0, 0, 0, 0, 0, 0])?;
+        Ok(FrameIndexWriter {
+            writer,
+            entries: 0,
+            checksums: Some(algorithm),
+        })
+    }
+
+    /// Record a frame written to the capture at `offset` with `length` bytes.
+    /// Fails for indexes with checksums.
+    pub fn record(&mut self, display_time: u64, offset: u64, length: u64) -> io::Result<()> {
+        if self.checksums.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "frames of an index with checksums need one",
+            ));
+        }
+        self.write_entry(display_time, offset, length, None)
+    }
+
+    /// Record a frame written to the capture at `offset` with `length` bytes
+    /// and its checksum, e.g. from
+    /// [Capturer::last_frame_checksum](crate::capturer::Capturer::last_frame_checksum).
+    /// Fails for indexes without checksums or with another algorithm.
+    pub fn record_checksum(
+        &mut self,
+        display_time: u64,
+        offset: u64,
+        length: u64,
+        checksum: &FrameChecksum,
+    ) -> io::Result<()> {
+        if self.checksums != Some(checksum.algorithm) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the index doesn't hold checksums of this algorithm",
+            ));
+        }
+        self.write_entry(display_time, offset, length, Some(checksum.value))
+    }
+
+    fn write_entry(
+        &mut self,
+        display_time: u64,
+        offset: u64,
+        length: u64,
+        checksum: Option<u64>,
+    ) -> io::Result<()> {
+        let mut entry = [0; 32];
+        entry[..8].copy_from_slice(&display_time.to_le_bytes());
+        entry[8..16].copy_from_slice(&offset.to_le_bytes());
+        entry[16..24].copy_from_slice(&length.to_le_bytes());
+        let size = match checksum {
+            Some(checksum) => {
+                entry[24..].copy_from_slice(&checksum.to_le_bytes());
+                32
+            }
+            None => 24,
+        };
+        self.writer.write_all(&entry[..size])?;
+        self.entries += 1;
+        Ok(())
+    }
+
+    /// Write the trailer and flush, returning the writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.write_all(TRAILER)?;
+        self.writer.write_all(&self.entries.to_le_bytes())?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Read the entries of an index written by [FrameIndexWriter], in the order
+/// they were recorded. Unfinished indexes give the entries that were written
+/// completely.
+pub fn read_index(mut reader: impl Read) -> io::Result<Vec<IndexEntry>> {
+    let invalid = |reason| io::Error::new(io::ErrorKind::InvalidData, reason);
+
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let checksums = match data.get(..HEADER.len()) {
+        Some(header) if header == HEADER => None,
+        Some(header) if header == CHECKSUM_HEADER => match data.get(HEADER.len()) {
+            Some(0) => Some(ChecksumAlgorithm::XxHash64),
+            Some(1) => Some(ChecksumAlgorithm::Crc32),
+            _ => return Err(invalid("unknown frame index checksum")),
+        },
+        _ => return Err(invalid("not a frame index")),
+    };
+    let (mut start, entry_size) = match checksums {
+        Some(_) => (HEADER.len() + 8, 32),
+        None => (HEADER.len(), 24),
+    };
+
+    let u64_at = |start: usize| u64::from_le_bytes(data[start..start + 8].try_into().unwrap());
+    let mut entries = Vec::new();
+    loop {
+        if start + 16 == data.len() && &data[start..start + 8] == TRAILER {
+            if u64_at(start + 8) != entries.len() as u64 {
+                return Err(invalid("frame index trailer doesn't match its entries"));
+            }
+            break;
+        }
+        if start + entry_size > data.len() {
+            break;
+        }
+        entries.push(IndexEntry {
+            display_time: u64_at(start),
+            offset: u64_at(start + 8),
+            length: u64_at(start + 16),
+            checksum: checksums.map(|algorithm| FrameChecksum {
+                algorithm,
+                value: u64_at(start + 24),
+            }),
+        });
+        start += entry_size;
+    }
+    Ok(entries)
+}
+
+fn get_algorithm_id(algorithm: ChecksumAlgorithm) -> u8 {
+    match algorithm {
+        ChecksumAlgorithm::XxHash64 => 0,
+        ChecksumAlgorithm::Crc32 => 1,
+    }
+}
+
+/// The entry of the last frame displayed at or before `time`, to seek to
+/// it. `entries` must be sorted by display time, as recorded.
+pub fn seek_index(entries: &[IndexEntry], time: u64) -> Option<&IndexEntry> {
+    let after = entries.partition_point(|entry| entry.display_time <= time);
+    after.checked_sub(1).map(|i| &entries[i])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(frames: u64) -> FrameIndexWriter<Vec<u8>> {
+        let mut index = FrameIndexWriter::new(Vec::new()).unwrap();
+        for i in 0..frames {
+            index.record(i * 1000, i * 4096, 4096).unwrap();
+        }
+        index
+    }
+
+    #[test]
+    fn test_round_trip_and_seek() {
+        let data = write(5).finish().unwrap();
+        let entries = read_index(&data[..]).unwrap();
+        assert_eq!(entries.len(), 5);
+        assert_eq!(
+            entries[3],
+            IndexEntry {
+                display_time: 3000,
+                offset: 3 * 4096,
+                length: 4096,
+                checksum: None,
+            }
+        );
+
+        assert_eq!(seek_index(&entries, 2500).unwrap().display_time, 2000);
+        assert_eq!(seek_index(&entries, 4000).unwrap().display_time, 4000);
+        assert_eq!(seek_index(&entries, 1_000_000).unwrap().display_time, 4000);
+        assert!(seek_index(&entries, 0).is_some());
+        assert!(seek_index(&[], 0).is_none());
+    }
+
+    #[test]
+    fn test_unfinished_index() {
+        // Cut off in the middle of the fourth entry
+        let mut data = write(4).writer;
+        data.truncate(data.len() - 5);
+        assert_eq!(read_index(&data[..]).unwrap().len(), 3);
+
+        assert!(read_index(&b"garbage"[..]).is_err());
+
+        // A trailer with the wrong count
+        let mut data = write(2).finish().unwrap();
+        let len = data.len();
+        data[len - 8] = 7;
+        assert!(read_index(&data[..]).is_err());
+    }
+
+    #[test]
+    fn test_checksums() {
+        let checksum = |value| FrameChecksum {
+            algorithm: ChecksumAlgorithm::Crc32,
+            value,
+        };
+        let mut index =
+            FrameIndexWriter::with_checksums(Vec::new(), ChecksumAlgorithm::Crc32).unwrap();
+        index.record_checksum(0, 0, 4096, &checksum(7)).unwrap();
+        index
+            .record_checksum(1000, 4096, 4096, &checksum(0xcbf43926))
+            .unwrap();
+        // Every entry needs a checksum of the index's algorithm
+        assert!(index.record(2000, 8192, 4096).is_err());
+        let xxhash = FrameChecksum {
+            algorithm: ChecksumAlgorithm::XxHash64,
+            value: 7,
+        };
+        assert!(index.record_checksum(2000, 8192, 4096, &xxhash).is_err());
+        assert!(write(0).record_checksum(0, 0, 1, &xxhash).is_err());
+
+        let data = index.finish().unwrap();
+        let entries = read_index(&data[..]).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].display_time, 1000);
+        assert_eq!(entries[1].checksum, Some(checksum(0xcbf43926)));
+
+        // Cut off in the middle of the second entry
+        assert_eq!(read_index(&data[..data.len() - 20]).unwrap().len(), 1);
+    }
+}
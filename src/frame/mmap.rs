@@ -0,0 +1,446 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::thread::{self, JoinHandle};
+
+use memmap2::{Mmap, MmapMut};
+
+use super::{
+    convert_yuv_to_bgra,
+    record::{from_record, record_length, to_record, RECORD_HEADER},
+    Frame,
+};
+
+// The segment format:
+//
+//   header   b"SCAPMAP1"
+//   records  see frame::record
+//
+// Segments are zero filled when created and a record's length is written
+// after its pixels, so the records end at the first zero length, also when
+// recording stopped halfway through one.
+const HEADER: &[u8; 8] = b"SCAPMAP1";
+
+// Segments are zero filled in chunks of this many bytes
+const FILL_CHUNK: usize = 1 << 20;
+
+/// How [MmapRecorder] splits a recording into segment files
+#[derive(Debug, Clone)]
+pub struct MmapRecorderOptions {
+    /// Size of each segment file in bytes, which is allocated on disk up
+    /// front. Frames larger than a segment can't be recorded.
+    pub segment_size: u64,
+    /// Delete the oldest segments beyond this many, to keep only the end of
+    /// an endless recording. None keeps every segment.
+    pub max_segments: Option<usize>,
+}
+
+impl Default for MmapRecorderOptions {
+    fn default() -> Self {
+        MmapRecorderOptions {
+            segment_size: 256 << 20,
+            max_segments: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RecordError {
+    Io(io::Error),
+    /// The disk filled up. The frame wasn't recorded and neither will any
+    /// after it, the frames recorded before stay readable.
+    DiskFull,
+    /// The frame doesn't fit in a segment
+    FrameTooLarge,
+    /// Delta, scanline patch and regions frames can't be recorded
+    Unsupported,
+}
+
+impl std::fmt::Display for RecordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecordError::Io(error) => write!(f, "Failed to record the frame: {error}"),
+            RecordError::DiskFull => write!(f, "The disk is full, recording stopped"),
+            RecordError::FrameTooLarge => write!(f, "The frame is larger than a segment"),
+            RecordError::Unsupported => write!(f, "Frames of this type can't be recorded"),
+        }
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+impl From<io::Error> for RecordError {
+    fn from(error: io::Error) -> Self {
+        match is_disk_full(&error) {
+            true => RecordError::DiskFull,
+            false => RecordError::Io(error),
+        }
+    }
+}
+
+// ErrorKind::StorageFull needs Rust 1.83
+fn is_disk_full(error: &io::Error) -> bool {
+    #[cfg(unix)]
+    const DISK_FULL: [i32; 1] = [28]; // ENOSPC
+    #[cfg(windows)]
+    const DISK_FULL: [i32; 2] = [39, 112]; // ERROR_HANDLE_DISK_FULL, ERROR_DISK_FULL
+    #[cfg(not(any(unix, windows)))]
+    const DISK_FULL: [i32; 0] = [];
+
+    error
+        .raw_os_error()
+        .is_some_and(|code| DISK_FULL.contains(&code))
+}
+
+struct Segment {
+    path: PathBuf,
+    file: File,
+    map: MmapMut,
+    // End of the last record
+    end: usize,
+}
+
+impl Segment {
+    fn create(path: PathBuf, size: u64) -> io::Result<Segment> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+
+        // Writing the zeros allocates the segment on disk, so a full disk
+        // fails here rather than faulting later when its pages are written
+        let zeros = vec![0; FILL_CHUNK];
+        let mut left = size.max(RECORD_HEADER as u64 + HEADER.len() as u64);
+        let filled = (|| {
+            file.write_all(HEADER)?;
+            left -= HEADER.len() as u64;
+            while left > 0 {
+                let chunk = left.min(FILL_CHUNK as u64) as usize;
+                file.write_all(&zeros[..chunk])?;
+                left -= chunk as u64;
+            }
+            file.flush()
+        })();
+        if let Err(error) = filled {
+            drop(file);
+            let _ = fs::remove_file(&path);
+            return Err(error);
+        }
+
+        let map = unsafe { MmapMut::map_mut(&file)? };
+        Ok(Segment {
+            path,
+            file,
+            map,
+            end: HEADER.len(),
+        })
+    }
+
+    fn fits(&self, length: usize) -> bool {
+        self.map.len() - self.end >= RECORD_HEADER + length
+    }
+
+    // Flushes the records and cuts off the unused zeros
+    fn close(self) -> io::Result<()> {
+        self.map.flush()?;
+        let Segment { file, map, end, .. } = self;
+        // Windows can't resize a mapped file
+        drop(map);
+        file.set_len(end as u64)
+    }
+}
+
+/// Records frames into memory-mapped segment files, for recordings too long
+/// to keep in memory. Recording a frame only copies it into the mapped
+/// pages, which the OS writes to disk in the background, so a slow disk
+/// doesn't hold up capturing the next frame. Read recordings back with
+/// [MmapReader].
+///
+/// Each segment is allocated when the previous one is opened, in the
+/// background. Frames are stored uncompressed, YUV frames are converted to
+/// BGRA first.
+pub struct MmapRecorder {
+    dir: PathBuf,
+    options: MmapRecorderOptions,
+    segment: Option<Segment>,
+    next: Option<JoinHandle<io::Result<Segment>>>,
+    // Index of the next segment to create
+    next_index: u64,
+    // Segments written so far, the oldest first
+    written: Vec<PathBuf>,
+    disk_full: bool,
+}
+
+impl MmapRecorder {
+    /// Start recording into segment files in `dir`, which is created if needed
+    pub fn create(dir: impl AsRef<Path>, options: MmapRecorderOptions) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let mut recorder = MmapRecorder {
+            dir,
+            options,
+            segment: None,
+            next: None,
+            next_index: 0,
+            written: Vec::new(),
+            disk_full: false,
+        };
+        recorder.prepare_next();
+        Ok(recorder)
+    }
+
+    /// Copy `frame` into the recording
+    pub fn record(&mut self, frame: &Frame) -> Result<(), RecordError> {
+        if self.disk_full {
+            return Err(RecordError::DiskFull);
+        }
+
+        let converted = match frame {
+            Frame::YUVFrame(yuv) => Some(Frame::BGRA(convert_yuv_to_bgra(yuv))),
+            _ => None,
+        };
+        let frame = converted.as_ref().unwrap_or(frame);
+        let record = to_record(frame).ok_or(RecordError::Unsupported)?;
+        let length = record.pixels.len();
+        if RECORD_HEADER + HEADER.len() + length > self.options.segment_size as usize {
+            return Err(RecordError::FrameTooLarge);
+        }
+
+        if !self.segment.as_ref().is_some_and(|s| s.fits(length)) {
+            if let Err(error) = self.rotate() {
+                let error = RecordError::from(error);
+                self.disk_full = matches!(error, RecordError::DiskFull);
+                return Err(error);
+            }
+        }
+        let segment = self.segment.as_mut().unwrap();
+
+        let start = segment.end;
+        let pixels = start + RECORD_HEADER;
+        segment.map[pixels..pixels + length].copy_from_slice(record.pixels);
+
+        segment.map[start + 8..pixels].copy_from_slice(&record.header[8..]);
+        // The length goes last, it completes the record
+        segment.map[start..start + 8].copy_from_slice(&record.header[..8]);
+
+        segment.end = pixels + length;
+        Ok(())
+    }
+
+    /// Flush the recording and trim its last segment
+    pub fn finish(mut self) -> io::Result<()> {
+        if let Some(next) = self.next.take() {
+            if let Ok(Ok(segment)) = next.join() {
+                drop(segment);
+                fs::remove_file(self.segment_path(self.next_index - 1))?;
+            }
+        }
+        match self.segment.take() {
+            Some(segment) => segment.close(),
+            None => Ok(()),
+        }
+    }
+
+    fn segment_path(&self, index: u64) -> PathBuf {
+        self.dir.join(format!("segment-{index:08}.scap"))
+    }
+
+    // Starts allocating the next segment in the background
+    fn prepare_next(&mut self) {
+        let path = self.segment_path(self.next_index);
+        let size = self.options.segment_size;
+        self.next_index += 1;
+        self.next = Some(thread::spawn(move || Segment::create(path, size)));
+    }
+
+    // Switches to the next segment once it's allocated
+    fn rotate(&mut self) -> io::Result<()> {
+        let next = self
+            .next
+            .take()
+            .expect("the next segment is always prepared");
+        let segment = next.join().unwrap_or_else(|_| {
+            Err(io::Error::new(
+                io::ErrorKind::Other,
+                "segment allocation panicked",
+            ))
+        });
+        let segment = match segment {
+            Ok(segment) => segment,
+            Err(error) => {
+                // Try again with the next frame, unless the disk is full
+                self.prepare_next();
+                return Err(error);
+            }
+        };
+
+        let full = self.segment.replace(segment);
+        self.prepare_next();
+        if let Some(full) = full {
+            self.written.push(full.path.clone());
+            full.close()?;
+        }
+        if let Some(max) = self.options.max_segments {
+            // The current segment counts too
+            while self.written.len() + 1 > max.max(1) {
+                fs::remove_file(self.written.remove(0))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads the frames of a recording made by [MmapRecorder], mapping its
+/// segments instead of reading them into memory
+pub struct MmapReader {
+    segments: Vec<Mmap>,
+}
+
+impl MmapReader {
+    /// Map the segments in `dir`. Recordings still being written can be
+    /// read too, up to their last complete frame.
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let name = path.file_name().and_then(|name| name.to_str());
+            if name.is_some_and(|name| name.starts_with("segment-") && name.ends_with(".scap")) {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        let mut segments = Vec::new();
+        for path in paths {
+            let map = unsafe { Mmap::map(&File::open(path)?)? };
+            if map.len() < HEADER.len() || &map[..HEADER.len()] != HEADER {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "not a recording segment",
+                ));
+            }
+            segments.push(map);
+        }
+        Ok(MmapReader { segments })
+    }
+
+    /// The recorded frames in the order they were recorded. Every frame is
+    /// copied out of the mapping as it's reached.
+    pub fn frames(&self) -> impl Iterator<Item = Frame> + '_ {
+        self.segments.iter().flat_map(|map| SegmentFrames {
+            map,
+            start: HEADER.len(),
+        })
+    }
+}
+
+struct SegmentFrames<'a> {
+    map: &'a [u8],
+    start: usize,
+}
+
+impl Iterator for SegmentFrames<'_> {
+    type Item = Frame;
+
+    fn next(&mut self) -> Option<Frame> {
+        let header = self.map.get(self.start..self.start + RECORD_HEADER)?;
+        let header: &[u8; RECORD_HEADER] = header.try_into().unwrap();
+        let length = record_length(header) as usize;
+        if length == 0 {
+            return None;
+        }
+        let pixels = self.start + RECORD_HEADER;
+        let data = self.map.get(pixels..pixels.checked_add(length)?)?.to_vec();
+        self.start = pixels + length;
+        from_record(header, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{BGRAFrame, ColorSpace, RowOrder};
+
+    fn frame(display_time: u64) -> Frame {
+        Frame::BGRA(BGRAFrame {
+            display_time,
+            width: 8,
+            height: 4,
+            data: vec![display_time as u8; 8 * 4 * 4],
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::SRGB,
+        })
+    }
+
+    fn recording(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("scap-mmap-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn read_times(dir: &Path) -> Vec<u64> {
+        let reader = MmapReader::open(dir).unwrap();
+        reader
+            .frames()
+            .map(|frame| match frame {
+                Frame::BGRA(f) => {
+                    assert!(f.data.iter().all(|&b| b == f.display_time as u8));
+                    assert_eq!((f.width, f.height), (8, 4));
+                    f.display_time
+                }
+                _ => panic!("expected a BGRA frame"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip_across_segments() {
+        let dir = recording("round-trip");
+        // Room for two frames per segment
+        let options = MmapRecorderOptions {
+            segment_size: 8 + 2 * (32 + 128) + 16,
+            max_segments: None,
+        };
+        let mut recorder = MmapRecorder::create(&dir, options).unwrap();
+        for i in 1..=5 {
+            recorder.record(&frame(i)).unwrap();
+        }
+
+        // Readable while recording
+        assert_eq!(read_times(&dir), [1, 2, 3, 4, 5]);
+
+        recorder.finish().unwrap();
+        assert_eq!(read_times(&dir), [1, 2, 3, 4, 5]);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 3);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rotation_keeps_the_newest_segments() {
+        let dir = recording("rotation");
+        // A frame per segment, keeping the last two
+        let options = MmapRecorderOptions {
+            segment_size: 8 + 32 + 128,
+            max_segments: Some(2),
+        };
+        let mut recorder = MmapRecorder::create(&dir, options).unwrap();
+        for i in 1..=5 {
+            recorder.record(&frame(i)).unwrap();
+        }
+        let Frame::BGRA(mut large) = frame(6) else {
+            unreachable!()
+        };
+        large.width *= 2;
+        large.data.extend_from_slice(&large.data.clone());
+        assert!(matches!(
+            recorder.record(&Frame::BGRA(large)),
+            Err(RecordError::FrameTooLarge)
+        ));
+        recorder.finish().unwrap();
+
+        assert_eq!(read_times(&dir), [4, 5]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,3 +1,100 @@
+use crate::capturer::Area;
+
+mod activity;
+mod border;
+mod checksum;
+mod color;
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+mod compress;
+mod convert;
+mod cursor;
+mod delta;
+mod gray;
+mod hdr;
+mod index;
+#[cfg(feature = "mmap")]
+mod mmap;
+mod motion;
+#[cfg(any(target_os = "windows", test))]
+mod pad;
+mod parts;
+mod phash;
+mod planar;
+mod pool;
+mod raw;
+mod record;
+mod scale;
+mod scanline;
+mod timestamp;
+mod watermark;
+mod yuv;
+
+pub use activity::ActivityFrame;
+pub(crate) use activity::ActivitySummarizer;
+pub(crate) use border::{detect_black_bars, detect_uniform_border};
+pub use checksum::{frame_checksum, verify_frame_checksum, ChecksumAlgorithm, FrameChecksum};
+pub use color::{convert_p3_to_srgb, ColorSpace};
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+pub use compress::{compress, decompress, Codec, CompressedFrame, CompressionError};
+pub use convert::{convert_bgra_to_rgb565, ConvertOptions, Dither};
+pub use cursor::{
+    composite_cursor, draw_cursor_highlight, CursorImage, CURSOR_HIGHLIGHT_COLOR,
+    CURSOR_HIGHLIGHT_RADIUS, CURSOR_HIGHLIGHT_THICKNESS,
+};
+pub(crate) use delta::DeltaEncoder;
+pub use delta::{apply_delta, DeltaError, DeltaFrame, DeltaRegion};
+pub use gray::LumaWeights;
+pub use hdr::ScRgbToneMapper;
+pub use index::{read_index, seek_index, FrameIndexWriter, IndexEntry};
+#[cfg(feature = "mmap")]
+pub use mmap::{MmapReader, MmapRecorder, MmapRecorderOptions, RecordError};
+pub use motion::FrameMotion;
+pub(crate) use motion::MotionEstimator;
+#[cfg(target_os = "windows")]
+pub(crate) use pad::pad_frame;
+pub(crate) use parts::extract_regions;
+pub use parts::RegionsFrame;
+pub use phash::{perceptual_hash, PHash};
+pub use planar::{Normalization, PlanarRgbFrame};
+pub(crate) use pool::BufferPool;
+pub use pool::PooledFrame;
+pub use raw::{RawFrame, RawPixelFormat};
+pub use record::read_frame_record;
+pub(crate) use record::to_record;
+pub(crate) use scale::{get_fitted_size, get_subsampled_size};
+pub(crate) use scanline::ScanlineEncoder;
+pub use scanline::{apply_scanline_patch, ScanlineBand, ScanlinePatchFrame};
+pub(crate) use timestamp::rescale_to_timebase;
+pub use timestamp::{rescale_timestamp, ClockCorrelation};
+pub use watermark::{
+    embed_watermark, read_watermark, Watermark, WatermarkEncoding, WatermarkOptions,
+    WatermarkPosition, WATERMARK_COLUMNS, WATERMARK_ROWS,
+};
+pub use yuv::{
+    convert_bgra_to_yuv, convert_yuv_to_bgra, ChromaSubsampling, ColorMatrix, ColorRange,
+    CustomMatrix, MatrixError,
+};
+
+/// Order of the rows in a frame's buffer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RowOrder {
+    /// The first row in the buffer is the top of the image. All engines normalize to this.
+    #[default]
+    TopDown,
+    /// The first row in the buffer is the bottom of the image, as in DIBs
+    BottomUp,
+}
+
+impl RowOrder {
+    // Index of the buffer row holding image row `y`
+    pub(crate) fn buffer_row(&self, y: usize, height: usize) -> usize {
+        match self {
+            RowOrder::TopDown => y,
+            RowOrder::BottomUp => height - 1 - y,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct YUVFrame {
     pub display_time: u64,
@@ -7,6 +104,11 @@ pub struct YUVFrame {
     pub luminance_stride: i32,
     pub chrominance_bytes: Vec<u8>,
     pub chrominance_stride: i32,
+    pub color_matrix: ColorMatrix,
+    pub color_range: ColorRange,
+    pub chroma_subsampling: ChromaSubsampling,
+    pub origin: RowOrder,
+    pub color_space: ColorSpace,
 }
 
 #[derive(Debug, Clone)]
@@ -15,6 +117,8 @@ pub struct RGBFrame {
     pub width: i32,
     pub height: i32,
     pub data: Vec<u8>,
+    pub origin: RowOrder,
+    pub color_space: ColorSpace,
 }
 
 #[derive(Debug, Clone)]
@@ -30,6 +134,8 @@ pub struct RGBxFrame {
     pub width: i32,
     pub height: i32,
     pub data: Vec<u8>,
+    pub origin: RowOrder,
+    pub color_space: ColorSpace,
 }
 
 #[derive(Debug, Clone)]
@@ -38,6 +144,8 @@ pub struct XBGRFrame {
     pub width: i32,
     pub height: i32,
     pub data: Vec<u8>,
+    pub origin: RowOrder,
+    pub color_space: ColorSpace,
 }
 
 #[derive(Debug, Clone)]
@@ -46,6 +154,8 @@ pub struct BGRxFrame {
     pub width: i32,
     pub height: i32,
     pub data: Vec<u8>,
+    pub origin: RowOrder,
+    pub color_space: ColorSpace,
 }
 
 #[derive(Debug, Clone)]
@@ -54,6 +164,8 @@ pub struct BGRFrame {
     pub width: i32,
     pub height: i32,
     pub data: Vec<u8>,
+    pub origin: RowOrder,
+    pub color_space: ColorSpace,
 }
 
 #[derive(Debug, Clone)]
@@ -62,9 +174,35 @@ pub struct BGRAFrame {
     pub width: i32,
     pub height: i32,
     pub data: Vec<u8>,
+    pub origin: RowOrder,
+    pub color_space: ColorSpace,
+}
+
+/// Single channel luma, see [Frame::to_gray8]
+#[derive(Debug, Clone)]
+pub struct Gray8Frame {
+    pub display_time: u64,
+    pub width: i32,
+    pub height: i32,
+    pub data: Vec<u8>,
+    pub origin: RowOrder,
+    pub color_space: ColorSpace,
+}
+
+/// A moment marked during capture, delivered among the frames, see
+/// [Capturer::insert_marker](crate::capturer::Capturer::insert_marker)
+#[derive(Debug, Clone)]
+pub struct MarkerFrame {
+    /// Display time of the frame returned right before the marker, 0 if
+    /// there was none
+    pub display_time: u64,
+    /// When the marker was inserted, relative to the start of capture
+    pub timestamp: std::time::Duration,
+    pub label: String,
 }
 
 #[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FrameType {
     #[default]
     YUVFrame,
@@ -82,6 +220,14 @@ pub enum Frame {
     BGRx(BGRxFrame),
     BGR0(BGRFrame),
     BGRA(BGRAFrame),
+    Gray8(Gray8Frame),
+    Delta(DeltaFrame),
+    ScanlinePatch(ScanlinePatchFrame),
+    Regions(RegionsFrame),
+    Marker(MarkerFrame),
+    Activity(ActivityFrame),
+    /// See [Options::native_format](crate::capturer::Options::native_format)
+    Raw(RawFrame),
 }
 
 pub enum FrameData<'a> {
@@ -89,6 +235,426 @@ pub enum FrameData<'a> {
     BGR0(&'a [u8]),
 }
 
+impl Frame {
+    // Width and height of the frame in pixels
+    pub(crate) fn size(&self) -> (i32, i32) {
+        match self {
+            Frame::YUVFrame(f) => (f.width, f.height),
+            Frame::RGB(f) => (f.width, f.height),
+            Frame::BGR0(f) => (f.width, f.height),
+            Frame::RGBx(f) => (f.width, f.height),
+            Frame::XBGR(f) => (f.width, f.height),
+            Frame::BGRx(f) => (f.width, f.height),
+            Frame::BGRA(f) => (f.width, f.height),
+            Frame::Gray8(f) => (f.width, f.height),
+            Frame::Delta(f) => (f.width, f.height),
+            Frame::ScanlinePatch(f) => (f.width, f.height),
+            Frame::Regions(f) => (f.width, f.height),
+            Frame::Raw(f) => (f.width, f.height),
+            Frame::Marker(_) | Frame::Activity(_) => (0, 0),
+        }
+    }
+
+    pub(crate) fn display_time(&self) -> u64 {
+        match self {
+            Frame::YUVFrame(f) => f.display_time,
+            Frame::RGB(f) => f.display_time,
+            Frame::BGR0(f) => f.display_time,
+            Frame::RGBx(f) => f.display_time,
+            Frame::XBGR(f) => f.display_time,
+            Frame::BGRx(f) => f.display_time,
+            Frame::BGRA(f) => f.display_time,
+            Frame::Gray8(f) => f.display_time,
+            Frame::Delta(f) => f.display_time,
+            Frame::ScanlinePatch(f) => f.display_time,
+            Frame::Regions(f) => f.display_time,
+            Frame::Marker(f) => f.display_time,
+            Frame::Activity(f) => f.display_time,
+            Frame::Raw(f) => f.display_time,
+        }
+    }
+
+    pub(crate) fn display_time_mut(&mut self) -> &mut u64 {
+        match self {
+            Frame::YUVFrame(f) => &mut f.display_time,
+            Frame::RGB(f) => &mut f.display_time,
+            Frame::BGR0(f) => &mut f.display_time,
+            Frame::RGBx(f) => &mut f.display_time,
+            Frame::XBGR(f) => &mut f.display_time,
+            Frame::BGRx(f) => &mut f.display_time,
+            Frame::BGRA(f) => &mut f.display_time,
+            Frame::Gray8(f) => &mut f.display_time,
+            Frame::Delta(f) => &mut f.display_time,
+            Frame::ScanlinePatch(f) => &mut f.display_time,
+            Frame::Regions(f) => &mut f.display_time,
+            Frame::Marker(f) => &mut f.display_time,
+            Frame::Activity(f) => &mut f.display_time,
+            Frame::Raw(f) => &mut f.display_time,
+        }
+    }
+
+    pub(crate) fn color_space(&self) -> ColorSpace {
+        match self {
+            Frame::YUVFrame(f) => f.color_space,
+            Frame::RGB(f) => f.color_space,
+            Frame::BGR0(f) => f.color_space,
+            Frame::RGBx(f) => f.color_space,
+            Frame::XBGR(f) => f.color_space,
+            Frame::BGRx(f) => f.color_space,
+            Frame::BGRA(f) => f.color_space,
+            Frame::Gray8(f) => f.color_space,
+            Frame::Delta(f) => f.color_space,
+            Frame::ScanlinePatch(f) => f.color_space,
+            Frame::Regions(f) => f.color_space,
+            Frame::Raw(f) => f.color_space,
+            Frame::Marker(_) | Frame::Activity(_) => ColorSpace::Unknown,
+        }
+    }
+
+    /// Whether no pixel of the frame is brighter than `tolerance` in any color
+    /// channel, like the frames some platforms deliver before the first
+    /// composited one. YUV frames are judged by their luma above the range's
+    /// black level. Frames without pixels count as black, patches and
+    /// markers never do.
+    pub fn is_black(&self, tolerance: u8) -> bool {
+        if let Frame::YUVFrame(f) = self {
+            let black = match f.color_range {
+                ColorRange::Limited => 16,
+                ColorRange::Full => 0,
+            };
+            let limit = tolerance.saturating_add(black);
+            let (width, stride) = (f.width.max(0) as usize, f.luminance_stride.max(0) as usize);
+            return f
+                .luminance_bytes
+                .chunks(stride.max(1))
+                .take(f.height.max(0) as usize)
+                .all(|row| row.iter().take(width).all(|&luma| luma <= limit));
+        }
+
+        if matches!(
+            self,
+            Frame::Delta(_)
+                | Frame::ScanlinePatch(_)
+                | Frame::Regions(_)
+                | Frame::Marker(_)
+                | Frame::Activity(_)
+                | Frame::Raw(_)
+        ) {
+            return false;
+        }
+        let ignored = self.ignored_byte();
+        let Some(packed) = self.packed_data() else {
+            return true;
+        };
+        let stride = packed.data.len() / packed.height;
+        packed.data.chunks(stride.max(1)).all(|row| {
+            row[..(packed.width * packed.bytes_per_pixel).min(row.len())]
+                .chunks_exact(packed.bytes_per_pixel)
+                .all(|pixel| {
+                    pixel
+                        .iter()
+                        .enumerate()
+                        .all(|(i, &value)| Some(i) == ignored || value <= tolerance)
+                })
+        })
+    }
+
+    // The byte of packed pixels carrying alpha or padding, which says nothing
+    // about the color
+    fn ignored_byte(&self) -> Option<usize> {
+        match self {
+            Frame::XBGR(_) => Some(0),
+            Frame::RGBx(_) | Frame::BGRx(_) | Frame::BGRA(_) => Some(3),
+            _ => None,
+        }
+    }
+
+    // Returns the pixel data, layout and bytes per pixel of packed frames.
+    // Planar frames (YUV) and patches have no single packed buffer and return None.
+    fn packed_data(&self) -> Option<PackedData<'_>> {
+        let (data, width, height, origin, bytes_per_pixel) = match self {
+            Frame::YUVFrame(_)
+            | Frame::Delta(_)
+            | Frame::ScanlinePatch(_)
+            | Frame::Regions(_)
+            | Frame::Marker(_)
+            | Frame::Activity(_)
+            | Frame::Raw(_) => return None,
+            Frame::RGB(f) => (&f.data, f.width, f.height, f.origin, 3),
+            Frame::BGR0(f) => (&f.data, f.width, f.height, f.origin, 3),
+            Frame::RGBx(f) => (&f.data, f.width, f.height, f.origin, 4),
+            Frame::XBGR(f) => (&f.data, f.width, f.height, f.origin, 4),
+            Frame::BGRx(f) => (&f.data, f.width, f.height, f.origin, 4),
+            Frame::BGRA(f) => (&f.data, f.width, f.height, f.origin, 4),
+            Frame::Gray8(f) => (&f.data, f.width, f.height, f.origin, 1),
+        };
+
+        if width <= 0 || height <= 0 {
+            return None;
+        }
+
+        Some(PackedData {
+            data,
+            width: width as usize,
+            height: height as usize,
+            origin,
+            bytes_per_pixel,
+        })
+    }
+
+    /// Lazily computed, borrowed views of `areas` within this frame.
+    ///
+    /// Nothing is copied: every [RegionView] borrows this frame's buffer and
+    /// therefore cannot outlive it.
+    pub fn regions<'a>(&'a self, areas: &'a [Area]) -> Regions<'a> {
+        Regions {
+            frame: self.packed_data(),
+            areas,
+        }
+    }
+
+    /// Copies `area`, clamped to the frame, into a new top-down frame of the
+    /// same format. Returns None for planar frames or areas fully outside the frame.
+    pub fn cropped(&self, area: &Area) -> Option<Frame> {
+        let region = self.regions(std::slice::from_ref(area)).get(0)?;
+        let (width, height) = (region.width() as i32, region.height() as i32);
+        self.with_top_down_data(width, height, region.to_vec())
+    }
+
+    // A frame of the same packed format and metadata with other top-down
+    // pixels. None for planar frames and patches.
+    fn with_top_down_data(&self, width: i32, height: i32, data: Vec<u8>) -> Option<Frame> {
+        Some(match self {
+            Frame::YUVFrame(_)
+            | Frame::Delta(_)
+            | Frame::ScanlinePatch(_)
+            | Frame::Regions(_)
+            | Frame::Marker(_)
+            | Frame::Activity(_)
+            | Frame::Raw(_) => return None,
+            Frame::RGB(f) => Frame::RGB(RGBFrame {
+                display_time: f.display_time,
+                width,
+                height,
+                data,
+                origin: RowOrder::TopDown,
+                color_space: f.color_space,
+            }),
+            Frame::BGR0(f) => Frame::BGR0(BGRFrame {
+                display_time: f.display_time,
+                width,
+                height,
+                data,
+                origin: RowOrder::TopDown,
+                color_space: f.color_space,
+            }),
+            Frame::RGBx(f) => Frame::RGBx(RGBxFrame {
+                display_time: f.display_time,
+                width,
+                height,
+                data,
+                origin: RowOrder::TopDown,
+                color_space: f.color_space,
+            }),
+            Frame::XBGR(f) => Frame::XBGR(XBGRFrame {
+                display_time: f.display_time,
+                width,
+                height,
+                data,
+                origin: RowOrder::TopDown,
+                color_space: f.color_space,
+            }),
+            Frame::BGRx(f) => Frame::BGRx(BGRxFrame {
+                display_time: f.display_time,
+                width,
+                height,
+                data,
+                origin: RowOrder::TopDown,
+                color_space: f.color_space,
+            }),
+            Frame::BGRA(f) => Frame::BGRA(BGRAFrame {
+                display_time: f.display_time,
+                width,
+                height,
+                data,
+                origin: RowOrder::TopDown,
+                color_space: f.color_space,
+            }),
+            Frame::Gray8(f) => Frame::Gray8(Gray8Frame {
+                display_time: f.display_time,
+                width,
+                height,
+                data,
+                origin: RowOrder::TopDown,
+                color_space: f.color_space,
+            }),
+        })
+    }
+}
+
+#[derive(Clone, Copy)]
+struct PackedData<'a> {
+    data: &'a [u8],
+    width: usize,
+    height: usize,
+    origin: RowOrder,
+    bytes_per_pixel: usize,
+}
+
+/// Borrowed sub-regions of a single frame, see [Frame::regions]
+pub struct Regions<'a> {
+    frame: Option<PackedData<'a>>,
+    areas: &'a [Area],
+}
+
+impl<'a> Regions<'a> {
+    pub fn len(&self) -> usize {
+        self.areas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.areas.is_empty()
+    }
+
+    /// View of the `index`-th area, clamped to the frame bounds.
+    /// Returns None for planar frames or areas lying fully outside the frame.
+    pub fn get(&self, index: usize) -> Option<RegionView<'a>> {
+        let frame = self.frame?;
+        let [x, y, width, height] =
+            get_clamped_bounds(self.areas.get(index)?, frame.width, frame.height)?;
+
+        // Rows may be padded, so derive the stride from the buffer itself
+        let stride = frame.data.len() / frame.height;
+        if stride < frame.width * frame.bytes_per_pixel {
+            return None;
+        }
+
+        Some(RegionView {
+            data: frame.data,
+            stride,
+            bytes_per_pixel: frame.bytes_per_pixel,
+            frame_height: frame.height,
+            origin: frame.origin,
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Option<RegionView<'a>>> + '_ {
+        (0..self.len()).map(|i| self.get(i))
+    }
+}
+
+/// A zero-copy view into a rectangular region of a packed frame buffer.
+/// Rows are always addressed top to bottom, whatever the frame's [RowOrder].
+#[derive(Debug, Clone, Copy)]
+pub struct RegionView<'a> {
+    data: &'a [u8],
+    stride: usize,
+    bytes_per_pixel: usize,
+    frame_height: usize,
+    origin: RowOrder,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+impl<'a> RegionView<'a> {
+    /// Position of the region's top-left corner within the frame, in pixels
+    pub fn origin(&self) -> (usize, usize) {
+        (self.x, self.y)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn bytes_per_pixel(&self) -> usize {
+        self.bytes_per_pixel
+    }
+
+    /// Pixel bytes of row `y` (relative to the region) without padding
+    pub fn row(&self, y: usize) -> &'a [u8] {
+        assert!(y < self.height, "row {y} out of bounds");
+        let row = self.origin.buffer_row(self.y + y, self.frame_height);
+        let start = row * self.stride + self.x * self.bytes_per_pixel;
+        &self.data[start..start + self.width * self.bytes_per_pixel]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &'a [u8]> + '_ {
+        (0..self.height).map(|y| self.row(y))
+    }
+
+    /// Bytes of the pixel at (`x`, `y`) relative to the region
+    pub fn pixel(&self, x: usize, y: usize) -> &'a [u8] {
+        assert!(x < self.width, "column {x} out of bounds");
+        let start = x * self.bytes_per_pixel;
+        &self.row(y)[start..start + self.bytes_per_pixel]
+    }
+
+    /// Copies the region into a tightly packed buffer
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(self.width * self.height * self.bytes_per_pixel);
+        for row in self.rows() {
+            data.extend_from_slice(row);
+        }
+        data
+    }
+}
+
+// Clamps `area` to a `width` x `height` frame, returning [x, y, width, height]
+// in whole pixels, or None if nothing of the area remains.
+pub(crate) fn get_clamped_bounds(area: &Area, width: usize, height: usize) -> Option<[usize; 4]> {
+    let start_x = area.origin.x.max(0.0).floor() as usize;
+    let start_y = area.origin.y.max(0.0).floor() as usize;
+    let end_x = ((area.origin.x + area.size.width).max(0.0).floor() as usize).min(width);
+    let end_y = ((area.origin.y + area.size.height).max(0.0).floor() as usize).min(height);
+
+    if start_x >= end_x || start_y >= end_y {
+        return None;
+    }
+
+    Some([start_x, start_y, end_x - start_x, end_y - start_y])
+}
+
+/// Reverses the order of the `height` rows in `frame_data` in place,
+/// converting between top-down and bottom-up buffers
+pub fn flip_vertical(frame_data: &mut [u8], height: usize) {
+    if height == 0 {
+        return;
+    }
+
+    let stride = frame_data.len() / height;
+    let (mut top, mut bottom) = (0, height - 1);
+    while top < bottom {
+        let (upper, lower) = frame_data.split_at_mut(bottom * stride);
+        upper[top * stride..(top + 1) * stride].swap_with_slice(&mut lower[..stride]);
+        top += 1;
+        bottom -= 1;
+    }
+}
+
+/// Copies `height` rows of `row_bytes` each out of a buffer whose rows are
+/// `stride` bytes apart, dropping the padding at the end of every row
+pub fn remove_row_padding(data: &[u8], stride: usize, row_bytes: usize, height: usize) -> Vec<u8> {
+    if stride == row_bytes {
+        return data[..row_bytes * height].to_vec();
+    }
+
+    let mut packed = Vec::with_capacity(row_bytes * height);
+    for row in data.chunks(stride).take(height) {
+        packed.extend_from_slice(&row[..row_bytes]);
+    }
+    packed
+}
+
 pub fn remove_alpha_channel(frame_data: Vec<u8>) -> Vec<u8> {
     let width = frame_data.len();
     let width_without_alpha = (width / 4) * 3;
@@ -141,6 +707,43 @@ pub fn get_cropped_data(data: Vec<u8>, cur_width: i32, height: i32, width: i32)
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_is_black() {
+        let bgra = |data: Vec<u8>| {
+            Frame::BGRA(BGRAFrame {
+                display_time: 0,
+                width: 2,
+                height: 1,
+                data,
+                origin: RowOrder::TopDown,
+                color_space: ColorSpace::Unknown,
+            })
+        };
+        assert!(bgra(vec![0, 0, 0, 255, 3, 2, 1, 255]).is_black(3));
+        assert!(!bgra(vec![0, 0, 0, 255, 3, 2, 1, 255]).is_black(2));
+        assert!(bgra(vec![0, 0, 0, 255, 0, 0, 0, 255]).is_black(0));
+
+        let yuv = |luma: u8, color_range| {
+            Frame::YUVFrame(YUVFrame {
+                display_time: 0,
+                width: 2,
+                height: 2,
+                luminance_bytes: vec![luma, 16, 16, 16],
+                luminance_stride: 2,
+                chrominance_bytes: vec![128, 128],
+                chrominance_stride: 2,
+                color_matrix: ColorMatrix::BT709,
+                color_range,
+                chroma_subsampling: ChromaSubsampling::Yuv420,
+                origin: RowOrder::TopDown,
+                color_space: ColorSpace::Unknown,
+            })
+        };
+        assert!(yuv(20, ColorRange::Limited).is_black(4));
+        assert!(!yuv(21, ColorRange::Limited).is_black(4));
+        assert!(!yuv(16, ColorRange::Full).is_black(4));
+    }
+
     #[test]
     fn test_remove_alpha_channel() {
         assert_eq!(remove_alpha_channel(vec![1, 2, 3, 0]), vec![1, 2, 3]);
@@ -180,4 +783,128 @@ mod tests {
         expected.append(rgba!(8));
         assert_eq!(get_cropped_data(data, 3, 3, 2), expected)
     }
+
+    fn area(x: f64, y: f64, width: f64, height: f64) -> Area {
+        Area {
+            origin: crate::capturer::Point { x, y },
+            size: crate::capturer::Size { width, height },
+        }
+    }
+
+    #[test]
+    fn test_regions() {
+        let mut data: Vec<u8> = Vec::new();
+        for i in 1..=9 {
+            data.append(rgba!(i));
+        }
+        let frame = Frame::BGRA(BGRAFrame {
+            display_time: 0,
+            width: 3,
+            height: 3,
+            data,
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::Unknown,
+        });
+
+        let areas = [
+            area(1.0, 1.0, 2.0, 2.0),
+            area(2.0, 0.0, 5.0, 1.0),
+            area(5.0, 5.0, 1.0, 1.0),
+        ];
+        let regions = frame.regions(&areas);
+
+        let view = regions.get(0).unwrap();
+        assert_eq!((view.width(), view.height()), (2, 2));
+        assert_eq!(view.pixel(1, 1), &[9, 9, 9, 9]);
+        let mut expected: Vec<u8> = Vec::new();
+        expected.append(rgba!(5));
+        expected.append(rgba!(6));
+        expected.append(rgba!(8));
+        expected.append(rgba!(9));
+        assert_eq!(view.to_vec(), expected);
+
+        // Clamped to the right edge of the frame
+        let view = regions.get(1).unwrap();
+        assert_eq!((view.origin(), view.width(), view.height()), ((2, 0), 1, 1));
+        assert_eq!(view.row(0), &[3, 3, 3, 3]);
+
+        assert!(regions.get(2).is_none());
+        assert!(regions.get(3).is_none());
+    }
+
+    #[test]
+    fn test_remove_row_padding() {
+        // 2x3 BGRA rows padded to a 12 byte stride
+        let mut data: Vec<u8> = Vec::new();
+        for i in 1..=3 {
+            data.append(rgba!(i));
+            data.append(rgba!(i));
+            data.extend_from_slice(&[0xAA; 4]);
+        }
+
+        let packed = remove_row_padding(&data, 12, 8, 3);
+        assert_eq!(packed.len(), 2 * 3 * 4);
+        assert!(!packed.contains(&0xAA));
+        assert_eq!(&packed[16..], &[3; 8]);
+
+        // Tight buffers are copied as is
+        assert_eq!(remove_row_padding(&packed, 8, 8, 3), packed);
+    }
+
+    #[test]
+    fn test_flip_vertical() {
+        let mut data = vec![1, 1, 2, 2, 3, 3];
+        flip_vertical(&mut data, 3);
+        assert_eq!(data, vec![3, 3, 2, 2, 1, 1]);
+        flip_vertical(&mut data, 3);
+        assert_eq!(data, vec![1, 1, 2, 2, 3, 3]);
+    }
+
+    #[test]
+    fn test_bottom_up_regions() {
+        // Image rows 1, 2, 3 stored bottom row first
+        let mut data: Vec<u8> = Vec::new();
+        for i in [3, 2, 1] {
+            data.append(rgba!(i));
+        }
+        let frame = Frame::BGRA(BGRAFrame {
+            display_time: 0,
+            width: 1,
+            height: 3,
+            data,
+            origin: RowOrder::BottomUp,
+            color_space: ColorSpace::Unknown,
+        });
+
+        let areas = [area(0.0, 0.0, 1.0, 2.0)];
+        let view = frame.regions(&areas).get(0).unwrap();
+        assert_eq!(view.row(0), &[1, 1, 1, 1]);
+        assert_eq!(view.row(1), &[2, 2, 2, 2]);
+    }
+
+    #[test]
+    fn test_cropped() {
+        let frame = Frame::RGB(RGBFrame {
+            display_time: 7,
+            width: 3,
+            height: 2,
+            data: (1..=18).collect(),
+            origin: RowOrder::BottomUp,
+            color_space: ColorSpace::SRGB,
+        });
+
+        let Some(Frame::RGB(cropped)) = frame.cropped(&area(1.0, 0.0, 5.0, 1.0)) else {
+            panic!("expected an RGB frame");
+        };
+        assert_eq!((cropped.width, cropped.height), (2, 1));
+        // The top image row is the last buffer row
+        assert_eq!(cropped.data, vec![13, 14, 15, 16, 17, 18]);
+        assert_eq!(cropped.origin, RowOrder::TopDown);
+        assert_eq!(
+            (cropped.display_time, cropped.color_space),
+            (7, ColorSpace::SRGB)
+        );
+
+        assert!(frame.cropped(&area(3.0, 0.0, 1.0, 1.0)).is_none());
+    }
 }
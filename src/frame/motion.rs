@@ -0,0 +1,147 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use super::activity::{compare_grids, get_luma_grid};
+use super::Frame;
+
+// How far back frames count towards FrameMotion::active
+const ACTIVE_WINDOW: Duration = Duration::from_secs(1);
+// A frame moves once this part of it changed, more than a keystroke or a
+// blinking caret does
+const MOVING_AREA: f32 = 0.02;
+// Content is active while at least this many of the frames of the last
+// second moved, and no fewer than a third of them. Video captured faster
+// than it plays repeats frames, so not every one moves.
+const MIN_MOVING_FRAMES: usize = 3;
+
+/// How much a delivered frame changed from the one before, see
+/// [Capturer::last_frame_motion](crate::capturer::Capturer::last_frame_motion)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameMotion {
+    /// The mean absolute difference of the frames' luma from 0 to 1, compared
+    /// on a coarse grid like [ActivityFrame::motion](super::ActivityFrame::motion).
+    /// 0 for the first frame.
+    pub score: f32,
+    /// The part of the frame that changed, from 0 to 1
+    pub changed_area: f32,
+    /// Whether the content kept moving over the last second, like while a
+    /// video plays or a page scrolls, rather than changing in small steps
+    /// like while typing
+    pub active: bool,
+}
+
+// Compares every delivered frame with the one before, see
+// Options::estimate_motion
+#[derive(Debug, Default)]
+pub(crate) struct MotionEstimator {
+    // Luma grid of the last frame
+    previous: Option<Vec<u8>>,
+    // When the frames of the last second arrived, and whether they moved
+    recent: VecDeque<(Instant, bool)>,
+    last: Option<FrameMotion>,
+}
+
+impl MotionEstimator {
+    pub fn reset(&mut self) {
+        *self = MotionEstimator::default();
+    }
+
+    // The motion of the last frame, None if it had no pixels
+    pub fn last(&self) -> Option<FrameMotion> {
+        self.last
+    }
+
+    // Compares a frame delivered at `now` with the one before
+    pub fn estimate(&mut self, frame: &Frame, now: Instant) -> Option<FrameMotion> {
+        let Some(grid) = get_luma_grid(frame) else {
+            self.last = None;
+            return None;
+        };
+        let (score, changed) = match &self.previous {
+            Some(previous) => compare_grids(&grid, previous),
+            None => (0.0, 0),
+        };
+        let changed_area = changed as f32 / grid.len() as f32;
+        self.previous = Some(grid);
+
+        while self
+            .recent
+            .front()
+            .is_some_and(|(at, _)| now.saturating_duration_since(*at) >= ACTIVE_WINDOW)
+        {
+            self.recent.pop_front();
+        }
+        self.recent.push_back((now, changed_area >= MOVING_AREA));
+        let moving = self.recent.iter().filter(|(_, moved)| *moved).count();
+
+        self.last = Some(FrameMotion {
+            score: score as f32,
+            changed_area,
+            active: moving >= MIN_MOVING_FRAMES && moving * 3 >= self.recent.len(),
+        });
+        self.last
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{ColorSpace, Gray8Frame, MarkerFrame, RowOrder};
+
+    // A 64x36 frame, black but for `lit` white columns on the left
+    fn frame(lit: usize) -> Frame {
+        let row: Vec<u8> = (0..64).map(|x| if x < lit { 255 } else { 0 }).collect();
+        Frame::Gray8(Gray8Frame {
+            display_time: 0,
+            width: 64,
+            height: 36,
+            data: row.repeat(36),
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::SRGB,
+        })
+    }
+
+    #[test]
+    fn test_motion() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut motion = MotionEstimator::default();
+        assert!(motion.last().is_none());
+
+        let first = motion.estimate(&frame(0), at(0)).unwrap();
+        assert_eq!((first.score, first.changed_area), (0.0, 0.0));
+
+        // Half the frame turning white
+        let half = motion.estimate(&frame(32), at(100)).unwrap();
+        assert!((half.score - 0.5).abs() < 1e-6, "{}", half.score);
+        assert!((half.changed_area - 0.5).abs() < 1e-6);
+        assert!(!half.active);
+
+        // Video: every other frame moves
+        for i in 1..6 {
+            let lit = if i % 2 == 0 { 32 } else { 0 };
+            motion.estimate(&frame(lit), at(100 + i * 100));
+            motion.estimate(&frame(lit), at(150 + i * 100));
+        }
+        assert!(motion.last().unwrap().active);
+
+        // One column changing is too small to move, and motion stops
+        // counting once it's a second old
+        motion.estimate(&frame(1), at(1_700));
+        let still = motion.estimate(&frame(1), at(1_800)).unwrap();
+        assert_eq!(still.score, 0.0);
+        assert!(!still.active);
+
+        motion.estimate(
+            &Frame::Marker(MarkerFrame {
+                display_time: 0,
+                timestamp: Duration::ZERO,
+                label: String::new(),
+            }),
+            at(1_900),
+        );
+        assert!(motion.last().is_none());
+        motion.reset();
+        assert_eq!(motion.estimate(&frame(64), at(2_000)).unwrap().score, 0.0);
+    }
+}
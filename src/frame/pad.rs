@@ -0,0 +1,114 @@
+use super::Frame;
+
+/// Places `frame` at `x`, `y` on a `width` x `height` canvas of `color`, an
+/// RGB color, cutting off what doesn't fit. Only packed RGB frames, like
+/// BGRA, are padded, others are left as they are.
+pub(crate) fn pad_frame(
+    frame: &mut Frame,
+    [x, y]: [usize; 2],
+    [width, height]: [usize; 2],
+    color: [u8; 3],
+) {
+    let [r, g, b] = color;
+    let (data, frame_width, frame_height, origin, fill) = match frame {
+        Frame::RGB(f) => (
+            &mut f.data,
+            &mut f.width,
+            &mut f.height,
+            f.origin,
+            [r, g, b, 255],
+        ),
+        Frame::RGBx(f) => (
+            &mut f.data,
+            &mut f.width,
+            &mut f.height,
+            f.origin,
+            [r, g, b, 255],
+        ),
+        Frame::XBGR(f) => (
+            &mut f.data,
+            &mut f.width,
+            &mut f.height,
+            f.origin,
+            [255, b, g, r],
+        ),
+        Frame::BGRx(f) => (
+            &mut f.data,
+            &mut f.width,
+            &mut f.height,
+            f.origin,
+            [b, g, r, 255],
+        ),
+        Frame::BGRA(f) => (
+            &mut f.data,
+            &mut f.width,
+            &mut f.height,
+            f.origin,
+            [b, g, r, 255],
+        ),
+        _ => return,
+    };
+    if *frame_width <= 0 || *frame_height <= 0 || width == 0 || height == 0 {
+        return;
+    }
+    let (source_width, source_height) = (*frame_width as usize, *frame_height as usize);
+    let stride = data.len() / source_height;
+    // RGB frames are packed into 3 or 4 bytes
+    let bytes_per_pixel = stride / source_width;
+    if !(3..=4).contains(&bytes_per_pixel) {
+        return;
+    }
+
+    let pixel = &fill[..bytes_per_pixel];
+    let mut canvas: Vec<u8> = pixel
+        .iter()
+        .copied()
+        .cycle()
+        .take(width * height * bytes_per_pixel)
+        .collect();
+    let row_bytes = source_width.min(width.saturating_sub(x)) * bytes_per_pixel;
+    for row in 0..source_height.min(height.saturating_sub(y)) {
+        let source = origin.buffer_row(row, source_height) * stride;
+        let target = (origin.buffer_row(y + row, height) * width + x) * bytes_per_pixel;
+        canvas[target..target + row_bytes].copy_from_slice(&data[source..source + row_bytes]);
+    }
+
+    *data = canvas;
+    *frame_width = width as i32;
+    *frame_height = height as i32;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{BGRAFrame, ColorSpace, RowOrder};
+
+    #[test]
+    fn test_pad_frame() {
+        for origin in [RowOrder::TopDown, RowOrder::BottomUp] {
+            let mut frame = Frame::BGRA(BGRAFrame {
+                display_time: 0,
+                width: 1,
+                height: 1,
+                data: vec![1, 2, 3, 4],
+                origin,
+                color_space: ColorSpace::SRGB,
+            });
+            pad_frame(&mut frame, [1, 0], [2, 2], [10, 20, 30]);
+
+            let Frame::BGRA(f) = frame else {
+                unreachable!()
+            };
+            assert_eq!((f.width, f.height), (2, 2));
+            let top = match origin {
+                RowOrder::TopDown => 0,
+                RowOrder::BottomUp => 8,
+            };
+            assert_eq!(f.data[top..top + 8], [30, 20, 10, 255, 1, 2, 3, 4]);
+            assert_eq!(
+                f.data[8 - top..16 - top],
+                [30, 20, 10, 255, 30, 20, 10, 255]
+            );
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use super::{convert_yuv_to_bgra, ColorSpace, Frame};
+use crate::capturer::Area;
+
+/// Named regions of a frame, see [Capturer::set_regions](crate::capturer::Capturer::set_regions)
+#[derive(Debug, Clone)]
+pub struct RegionsFrame {
+    pub display_time: u64,
+    /// Index of the source frame among the frames returned since capture
+    /// started, counting from 0
+    pub source_sequence: u64,
+    /// Size of the source frame
+    pub width: i32,
+    pub height: i32,
+    /// The pixels of each region in the order the regions were set, top-down
+    /// and in the source's format. YUV sources are converted to BGRA first.
+    /// Regions outside the frame are left out.
+    pub parts: Vec<(String, Frame)>,
+    /// The whole source frame, if it's kept
+    pub source: Option<Box<Frame>>,
+    pub color_space: ColorSpace,
+}
+
+// Crops every region out of `frame`. Patches are returned as they are.
+pub(crate) fn extract_regions(
+    frame: Frame,
+    regions: &[(String, Area)],
+    source_sequence: u64,
+    keep_source: bool,
+) -> Frame {
+    let source = match &frame {
+        Frame::Delta(_)
+        | Frame::ScanlinePatch(_)
+        | Frame::Regions(_)
+        | Frame::Marker(_)
+        | Frame::Activity(_)
+        | Frame::Raw(_) => return frame,
+        Frame::YUVFrame(yuv) => Some(Frame::BGRA(convert_yuv_to_bgra(yuv))),
+        _ => None,
+    };
+    let packed = source.as_ref().unwrap_or(&frame);
+
+    let parts = regions
+        .iter()
+        .filter_map(|(name, area)| Some((name.clone(), packed.cropped(area)?)))
+        .collect();
+    let (width, height) = frame.size();
+
+    Frame::Regions(RegionsFrame {
+        display_time: frame.display_time(),
+        source_sequence,
+        width,
+        height,
+        parts,
+        color_space: frame.color_space(),
+        source: keep_source.then(|| Box::new(frame)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capturer::{Point, Size};
+    use crate::frame::{BGRAFrame, RowOrder};
+
+    fn area(x: f64, y: f64, width: f64, height: f64) -> Area {
+        Area {
+            origin: Point { x, y },
+            size: Size { width, height },
+        }
+    }
+
+    #[test]
+    fn test_extract_regions() {
+        // Every pixel holds its own coordinates
+        let frame = Frame::BGRA(BGRAFrame {
+            display_time: 3,
+            width: 16,
+            height: 8,
+            data: (0..8u8)
+                .flat_map(|y| (0..16u8).flat_map(move |x| [x, y, 0, 255]))
+                .collect(),
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::SRGB,
+        });
+        let regions = [
+            ("counter".to_string(), area(1.0, 2.0, 2.0, 1.0)),
+            ("outside".to_string(), area(20.0, 0.0, 4.0, 4.0)),
+            ("light".to_string(), area(14.0, 6.0, 4.0, 4.0)),
+        ];
+
+        let Frame::Regions(result) = extract_regions(frame, &regions, 7, true) else {
+            panic!("wrong frame type");
+        };
+        assert_eq!(result.source_sequence, 7);
+        assert_eq!(
+            (result.width, result.height, result.display_time),
+            (16, 8, 3)
+        );
+        assert!(matches!(result.source.as_deref(), Some(Frame::BGRA(_))));
+
+        let names: Vec<_> = result.parts.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["counter", "light"]);
+        let Frame::BGRA(counter) = &result.parts[0].1 else {
+            panic!("wrong frame type");
+        };
+        assert_eq!(counter.data, [1, 2, 0, 255, 2, 2, 0, 255]);
+        // Clamped to the frame
+        let Frame::BGRA(light) = &result.parts[1].1 else {
+            panic!("wrong frame type");
+        };
+        assert_eq!((light.width, light.height), (2, 2));
+        assert_eq!(&light.data[..4], [14, 6, 0, 255]);
+    }
+}
@@ -0,0 +1,89 @@
+use super::{Frame, LumaWeights};
+
+/// Hash of what a frame looks like rather than its exact pixels, see
+/// [perceptual_hash]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PHash(pub u64);
+
+impl PHash {
+    /// Number of the 64 bits that differ. Up to about 10 means the same
+    /// picture with noise, compression artifacts or small changes such as a
+    /// ticking clock, over 20 a different picture.
+    pub fn distance(&self, other: &PHash) -> u32 {
+        (self.0 ^ other.0).count_ones()
+    }
+}
+
+/// Hash the frame's luma on a 9x8 grid, one bit for whether each cell is
+/// brighter than its right neighbour (a dHash). Similar frames get hashes a
+/// small [PHash::distance] apart. It reads every pixel once, which takes a
+/// few milliseconds at 1080p, well within the time between frames at 60 fps.
+///
+/// Returns None for frames [Frame::to_gray8] can't convert.
+pub fn perceptual_hash(frame: &Frame) -> Option<PHash> {
+    let gray = frame.to_gray8(9, 8, LumaWeights::default())?;
+    let hash = gray.data.chunks_exact(9).fold(0u64, |hash, row| {
+        row.windows(2)
+            .fold(hash, |hash, pair| hash << 1 | (pair[0] > pair[1]) as u64)
+    });
+    Some(PHash(hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{BGRAFrame, ColorSpace, RowOrder};
+
+    fn frame(pixel: impl Fn(usize, usize) -> f64) -> Frame {
+        let (width, height) = (320, 180);
+        Frame::BGRA(BGRAFrame {
+            display_time: 0,
+            width,
+            height,
+            data: (0..height as usize)
+                .flat_map(|y| (0..width as usize).map(move |x| (x, y)))
+                .flat_map(|(x, y)| {
+                    let v = pixel(x, y).round().clamp(0.0, 255.0) as u8;
+                    [v, v, v, 255]
+                })
+                .collect(),
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::SRGB,
+        })
+    }
+
+    fn scene(x: usize, y: usize) -> f64 {
+        128.0 + 100.0 * (x as f64 / 30.0).sin() * (y as f64 / 20.0).cos()
+    }
+
+    // Noise in -12..=12 from a small LCG, like heavy compression artifacts
+    fn noise(x: usize, y: usize) -> f64 {
+        let seed = (y * 320 + x) as u32;
+        let n = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345) >> 16;
+        (n % 25) as f64 - 12.0
+    }
+
+    #[test]
+    fn test_similar_frames_are_close() {
+        let hash = perceptual_hash(&frame(scene)).unwrap();
+        let noisy = perceptual_hash(&frame(|x, y| scene(x, y) + noise(x, y))).unwrap();
+        assert!(hash.distance(&noisy) <= 4);
+
+        // A small clock in the corner
+        let clock = frame(|x, y| match x > 300 && y < 10 {
+            true => 255.0,
+            false => scene(x, y),
+        });
+        assert!(hash.distance(&perceptual_hash(&clock).unwrap()) <= 4);
+    }
+
+    #[test]
+    fn test_different_frames_are_far() {
+        let hash = perceptual_hash(&frame(scene)).unwrap();
+        let other = frame(|x, y| 128.0 + 100.0 * ((x + 2 * y) as f64 / 15.0).cos());
+        assert!(hash.distance(&perceptual_hash(&other).unwrap()) > 20);
+
+        let mirrored = frame(|x, y| scene(319 - x, y));
+        assert!(hash.distance(&perceptual_hash(&mirrored).unwrap()) > 20);
+    }
+}
@@ -0,0 +1,280 @@
+use super::BGRAFrame;
+
+/// How channel values are scaled by [BGRAFrame::to_planar_rgb_f32]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Normalization {
+    /// Values stay in [0, 255]
+    #[default]
+    None,
+    /// Values are divided by 255 into [0, 1]
+    UnitRange,
+    /// Values are mapped to [0, 1] and then standardized as `(v - mean) / std`,
+    /// with `mean` and `std` in RGB order. ImageNet models for example use a
+    /// mean of [0.485, 0.456, 0.406] and a std of [0.229, 0.224, 0.225].
+    MeanStd { mean: [f32; 3], std: [f32; 3] },
+}
+
+impl Normalization {
+    // Per channel scale and bias in RGB order, so a byte maps to `v * scale + bias`
+    fn affine(&self) -> ([f32; 3], [f32; 3]) {
+        match *self {
+            Normalization::None => ([1.0; 3], [0.0; 3]),
+            Normalization::UnitRange => ([1.0 / 255.0; 3], [0.0; 3]),
+            Normalization::MeanStd { mean, std } => (
+                [0, 1, 2].map(|c| 1.0 / (255.0 * std[c])),
+                [0, 1, 2].map(|c| -mean[c] / std[c]),
+            ),
+        }
+    }
+}
+
+/// An RGB frame with one `f32` plane per channel, the CHW layout most vision
+/// models take as input
+#[derive(Debug, Clone)]
+pub struct PlanarRgbFrame {
+    pub display_time: u64,
+    pub width: i32,
+    pub height: i32,
+    /// The red, green and blue planes one after another, each `width * height`
+    /// values stored top row first
+    pub data: Vec<f32>,
+}
+
+impl PlanarRgbFrame {
+    fn plane(&self, index: usize) -> &[f32] {
+        let len = self.width as usize * self.height as usize;
+        &self.data[index * len..(index + 1) * len]
+    }
+
+    pub fn red(&self) -> &[f32] {
+        self.plane(0)
+    }
+
+    pub fn green(&self) -> &[f32] {
+        self.plane(1)
+    }
+
+    pub fn blue(&self) -> &[f32] {
+        self.plane(2)
+    }
+}
+
+impl BGRAFrame {
+    /// Converts the frame to planar RGB floats, dropping alpha. Rows are
+    /// written top to bottom, whatever the frame's row order.
+    pub fn to_planar_rgb_f32(&self, normalize: Normalization) -> PlanarRgbFrame {
+        let (width, height) = (self.width.max(0) as usize, self.height.max(0) as usize);
+        let len = width * height;
+        let mut data = vec![0.0; len * 3];
+
+        if len > 0 {
+            let (scale, bias) = normalize.affine();
+            // Rows may be padded, so derive the stride from the buffer itself
+            let stride = self.data.len() / height;
+            let (r, rest) = data.split_at_mut(len);
+            let (g, b) = rest.split_at_mut(len);
+
+            for y in 0..height {
+                let start = self.origin.buffer_row(y, height) * stride;
+                let out = y * width..(y + 1) * width;
+                convert_row(
+                    &self.data[start..start + width * 4],
+                    [&mut r[out.clone()], &mut g[out.clone()], &mut b[out]],
+                    scale,
+                    bias,
+                );
+            }
+        }
+
+        PlanarRgbFrame {
+            display_time: self.display_time,
+            width: width as i32,
+            height: height as i32,
+            data,
+        }
+    }
+}
+
+// Converts a row of BGRA pixels into the R, G and B planes
+fn convert_row(row: &[u8], [r, g, b]: [&mut [f32]; 3], scale: [f32; 3], bias: [f32; 3]) {
+    // SSE2 is part of the x86_64 baseline and NEON of aarch64's, so neither
+    // needs runtime detection
+    #[cfg(target_arch = "x86_64")]
+    let done = unsafe { convert_row_sse2(row, [r, g, b], scale, bias) };
+    #[cfg(target_arch = "aarch64")]
+    let done = unsafe { convert_row_neon(row, [r, g, b], scale, bias) };
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    let done = 0;
+
+    for (i, pixel) in row.chunks_exact(4).enumerate().skip(done) {
+        r[i] = pixel[2] as f32 * scale[0] + bias[0];
+        g[i] = pixel[1] as f32 * scale[1] + bias[1];
+        b[i] = pixel[0] as f32 * scale[2] + bias[2];
+    }
+}
+
+// Converts 4 pixels at a time and returns how many pixels were converted
+#[cfg(target_arch = "x86_64")]
+unsafe fn convert_row_sse2(
+    row: &[u8],
+    [r, g, b]: [&mut [f32]; 3],
+    scale: [f32; 3],
+    bias: [f32; 3],
+) -> usize {
+    use std::arch::x86_64::*;
+
+    let zero = _mm_setzero_si128();
+    let scale = scale.map(|v| _mm_set1_ps(v));
+    let bias = bias.map(|v| _mm_set1_ps(v));
+    let pixels = row.len() / 16 * 4;
+
+    for i in (0..pixels).step_by(4) {
+        let bytes = _mm_loadu_si128(row.as_ptr().add(i * 4) as *const __m128i);
+
+        // Widen to one vector of [b, g, r, a] floats per pixel
+        let low = _mm_unpacklo_epi8(bytes, zero);
+        let high = _mm_unpackhi_epi8(bytes, zero);
+        let p0 = _mm_cvtepi32_ps(_mm_unpacklo_epi16(low, zero));
+        let p1 = _mm_cvtepi32_ps(_mm_unpackhi_epi16(low, zero));
+        let p2 = _mm_cvtepi32_ps(_mm_unpacklo_epi16(high, zero));
+        let p3 = _mm_cvtepi32_ps(_mm_unpackhi_epi16(high, zero));
+
+        // Transpose into one vector per channel
+        let bg01 = _mm_unpacklo_ps(p0, p1);
+        let bg23 = _mm_unpacklo_ps(p2, p3);
+        let ra01 = _mm_unpackhi_ps(p0, p1);
+        let ra23 = _mm_unpackhi_ps(p2, p3);
+        let blue = _mm_movelh_ps(bg01, bg23);
+        let green = _mm_movehl_ps(bg23, bg01);
+        let red = _mm_movelh_ps(ra01, ra23);
+
+        for (plane, value, c) in [(&mut *r, red, 0), (&mut *g, green, 1), (&mut *b, blue, 2)] {
+            let value = _mm_add_ps(_mm_mul_ps(value, scale[c]), bias[c]);
+            _mm_storeu_ps(plane.as_mut_ptr().add(i), value);
+        }
+    }
+
+    pixels
+}
+
+// Converts 16 pixels at a time and returns how many pixels were converted
+#[cfg(target_arch = "aarch64")]
+unsafe fn convert_row_neon(
+    row: &[u8],
+    [r, g, b]: [&mut [f32]; 3],
+    scale: [f32; 3],
+    bias: [f32; 3],
+) -> usize {
+    use std::arch::aarch64::*;
+
+    // Widens 16 channel values to floats and stores them scaled
+    unsafe fn store(values: uint8x16_t, out: *mut f32, scale: float32x4_t, bias: float32x4_t) {
+        let low = vmovl_u8(vget_low_u8(values));
+        let high = vmovl_high_u8(values);
+        let parts = [
+            vmovl_u16(vget_low_u16(low)),
+            vmovl_high_u16(low),
+            vmovl_u16(vget_low_u16(high)),
+            vmovl_high_u16(high),
+        ];
+        for (j, part) in parts.into_iter().enumerate() {
+            vst1q_f32(out.add(j * 4), vfmaq_f32(bias, vcvtq_f32_u32(part), scale));
+        }
+    }
+
+    let scale = scale.map(|v| vdupq_n_f32(v));
+    let bias = bias.map(|v| vdupq_n_f32(v));
+    let pixels = row.len() / 64 * 16;
+
+    for i in (0..pixels).step_by(16) {
+        // Deinterleaves into one vector each of b, g, r and a
+        let bytes = vld4q_u8(row.as_ptr().add(i * 4));
+        store(bytes.2, r.as_mut_ptr().add(i), scale[0], bias[0]);
+        store(bytes.1, g.as_mut_ptr().add(i), scale[1], bias[1]);
+        store(bytes.0, b.as_mut_ptr().add(i), scale[2], bias[2]);
+    }
+
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{ColorSpace, RowOrder};
+
+    // A frame whose pixels all differ, with 8 bytes of padding per row
+    fn test_frame(width: usize, height: usize, origin: RowOrder) -> BGRAFrame {
+        let stride = width * 4 + 8;
+        BGRAFrame {
+            display_time: 3,
+            width: width as i32,
+            height: height as i32,
+            data: (0..stride * height).map(|i| (i * 7 % 256) as u8).collect(),
+            origin,
+            color_space: ColorSpace::SRGB,
+        }
+    }
+
+    // The value of `channel` (in BGRA order) at image row `y`, column `x`
+    fn byte(frame: &BGRAFrame, x: usize, y: usize, channel: usize) -> f32 {
+        let height = frame.height as usize;
+        let stride = frame.data.len() / height;
+        frame.data[frame.origin.buffer_row(y, height) * stride + x * 4 + channel] as f32
+    }
+
+    #[test]
+    fn test_planar_matches_reference() {
+        // Widths that exercise both the vector loops and the scalar remainder
+        for width in [1, 5, 16, 37] {
+            for origin in [RowOrder::TopDown, RowOrder::BottomUp] {
+                let frame = test_frame(width, 3, origin);
+                let planar = frame.to_planar_rgb_f32(Normalization::None);
+                assert_eq!((planar.width, planar.height), (width as i32, 3));
+                assert_eq!(planar.display_time, 3);
+
+                for y in 0..3 {
+                    for x in 0..width {
+                        let i = y * width + x;
+                        assert_eq!(planar.red()[i], byte(&frame, x, y, 2));
+                        assert_eq!(planar.green()[i], byte(&frame, x, y, 1));
+                        assert_eq!(planar.blue()[i], byte(&frame, x, y, 0));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_planar_normalization() {
+        let frame = test_frame(21, 2, RowOrder::TopDown);
+
+        let unit = frame.to_planar_rgb_f32(Normalization::UnitRange);
+        let mean = [0.485, 0.456, 0.406];
+        let std = [0.229, 0.224, 0.225];
+        let standardized = frame.to_planar_rgb_f32(Normalization::MeanStd { mean, std });
+
+        for y in 0..2 {
+            for x in 0..21 {
+                let i = y * 21 + x;
+                for (c, (unit, standardized)) in [
+                    (unit.red(), standardized.red()),
+                    (unit.green(), standardized.green()),
+                    (unit.blue(), standardized.blue()),
+                ]
+                .into_iter()
+                .enumerate()
+                {
+                    let v = byte(&frame, x, y, 2 - c) / 255.0;
+                    assert!((unit[i] - v).abs() < 1e-6);
+                    assert!((standardized[i] - (v - mean[c]) / std[c]).abs() < 1e-5);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_planar_empty_frame() {
+        let frame = test_frame(0, 0, RowOrder::TopDown);
+        assert!(frame.to_planar_rgb_f32(Normalization::None).data.is_empty());
+    }
+}
@@ -0,0 +1,173 @@
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex},
+};
+
+use super::Frame;
+
+// Frame buffers kept for reuse, shared by the capture thread that fills them
+// and the PooledFrames that hand them back
+#[derive(Debug, Clone)]
+pub(crate) struct BufferPool {
+    buffers: Arc<Mutex<Vec<Vec<u8>>>>,
+    capacity: usize,
+}
+
+impl BufferPool {
+    // A pool keeping up to `capacity` buffers, 0 keeps none
+    pub fn new(capacity: usize) -> Self {
+        BufferPool {
+            buffers: Arc::new(Mutex::new(Vec::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    // Buffers waiting to be reused
+    #[cfg(test)]
+    pub fn available(&self) -> usize {
+        self.buffers.lock().unwrap().len()
+    }
+
+    // A copy of `data` in a buffer from the pool, or a new one if none is
+    // large enough
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+    pub fn copy_from(&self, data: &[u8]) -> Vec<u8> {
+        let mut buffers = self.buffers.lock().unwrap();
+        let Some(index) = buffers.iter().position(|b| b.capacity() >= data.len()) else {
+            return data.to_vec();
+        };
+        let mut buffer = buffers.swap_remove(index);
+        drop(buffers);
+
+        buffer.clear();
+        buffer.extend_from_slice(data);
+        buffer
+    }
+
+    // Keeps the buffers of `frame` for reuse while there's room
+    pub fn recycle(&self, frame: Frame) {
+        let mut buffers = self.buffers.lock().unwrap();
+        for buffer in into_buffers(frame) {
+            if buffers.len() >= self.capacity {
+                break;
+            }
+            if buffer.capacity() > 0 {
+                buffers.push(buffer);
+            }
+        }
+    }
+}
+
+// The pixel buffers a frame owns. Patches and regions are small and
+// irregular, so they aren't worth keeping.
+fn into_buffers(frame: Frame) -> Vec<Vec<u8>> {
+    match frame {
+        Frame::YUVFrame(f) => vec![f.luminance_bytes, f.chrominance_bytes],
+        Frame::RGB(f) => vec![f.data],
+        Frame::RGBx(f) => vec![f.data],
+        Frame::XBGR(f) => vec![f.data],
+        Frame::BGRx(f) => vec![f.data],
+        Frame::BGR0(f) => vec![f.data],
+        Frame::BGRA(f) => vec![f.data],
+        Frame::Gray8(f) => vec![f.data],
+        Frame::Raw(f) => vec![f.data],
+        Frame::Delta(_)
+        | Frame::ScanlinePatch(_)
+        | Frame::Regions(_)
+        | Frame::Marker(_)
+        | Frame::Activity(_) => Vec::new(),
+    }
+}
+
+/// A frame whose buffers go back to the capturer's pool when it's dropped,
+/// from a [FrameReceiver](crate::capturer::FrameReceiver).
+///
+/// It derefs to the [Frame], which can be read and changed in place. The
+/// buffers are only borrowed from the pool meanwhile: keep a copy, or take
+/// the frame with [PooledFrame::into_inner], to hold on to the pixels past
+/// the frame's lifetime.
+#[derive(Debug)]
+pub struct PooledFrame {
+    // Only None once taken by into_inner
+    frame: Option<Frame>,
+    pool: BufferPool,
+}
+
+impl PooledFrame {
+    pub(crate) fn new(frame: Frame, pool: BufferPool) -> Self {
+        PooledFrame {
+            frame: Some(frame),
+            pool,
+        }
+    }
+
+    /// Take the frame out of the pool's care. Its buffers are freed when it's
+    /// dropped, and the pool allocates new ones in their place.
+    pub fn into_inner(mut self) -> Frame {
+        self.frame.take().expect("frame was taken")
+    }
+}
+
+impl Deref for PooledFrame {
+    type Target = Frame;
+
+    fn deref(&self) -> &Frame {
+        self.frame.as_ref().expect("frame was taken")
+    }
+}
+
+impl DerefMut for PooledFrame {
+    fn deref_mut(&mut self) -> &mut Frame {
+        self.frame.as_mut().expect("frame was taken")
+    }
+}
+
+impl Drop for PooledFrame {
+    fn drop(&mut self) {
+        if let Some(frame) = self.frame.take() {
+            self.pool.recycle(frame);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{BGRAFrame, ColorSpace, RowOrder};
+
+    fn frame(data: Vec<u8>) -> Frame {
+        Frame::BGRA(BGRAFrame {
+            display_time: 0,
+            width: 1,
+            height: 1,
+            data,
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::Unknown,
+        })
+    }
+
+    #[test]
+    fn test_buffers_are_reused() {
+        let pool = BufferPool::new(1);
+        let data = pool.copy_from(&[1, 2, 3, 4]);
+        let address = data.as_ptr();
+
+        // Dropping the frame returns its buffer, whose allocation is reused
+        drop(PooledFrame::new(frame(data), pool.clone()));
+        assert_eq!(pool.available(), 1);
+        let data = pool.copy_from(&[5, 6, 7]);
+        assert_eq!((data.as_ptr(), &data[..]), (address, &[5, 6, 7][..]));
+        assert_eq!(pool.available(), 0);
+
+        // Frames taken out of the pool don't come back, and it stays bounded
+        let taken = PooledFrame::new(frame(data), pool.clone()).into_inner();
+        assert_eq!(pool.available(), 0);
+        drop(PooledFrame::new(taken, pool.clone()));
+        drop(PooledFrame::new(frame(vec![0; 4]), pool.clone()));
+        assert_eq!(pool.available(), 1);
+
+        // Too small buffers are left for later
+        assert_eq!(pool.copy_from(&[0; 8]).len(), 8);
+        assert_eq!(pool.available(), 1);
+    }
+}
@@ -0,0 +1,91 @@
+use super::ColorSpace;
+
+/// How the pixels of a [RawFrame] are encoded. More formats are added as
+/// platforms hand them out, so consumers should be ready for ones they
+/// don't know and skip those frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RawPixelFormat {
+    /// Red, green, blue and alpha as little-endian half floats. Linear
+    /// scRGB, where 1.0 is 80 nits and HDR highlights go past it, and
+    /// negative values are colors outside the sRGB gamut. What Windows
+    /// composes HDR displays in.
+    Rgba16Float,
+    /// Red, green, blue and alpha bytes
+    Rgba8,
+    /// Blue, green, red and alpha bytes
+    Bgra8,
+    /// A little-endian 32-bit word per pixel holding 2 bits of alpha at the
+    /// top, then 10 bits each of red, green and blue, full range.
+    /// ScreenCaptureKit's 'l10r'.
+    Argb2101010,
+}
+
+impl RawPixelFormat {
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            RawPixelFormat::Rgba16Float => 8,
+            RawPixelFormat::Rgba8 | RawPixelFormat::Bgra8 | RawPixelFormat::Argb2101010 => 4,
+        }
+    }
+
+    /// Bits of each color component
+    pub fn bit_depth(&self) -> u32 {
+        match self {
+            RawPixelFormat::Rgba16Float => 16,
+            RawPixelFormat::Rgba8 | RawPixelFormat::Bgra8 => 8,
+            RawPixelFormat::Argb2101010 => 10,
+        }
+    }
+}
+
+/// A frame in the format and bit depth the compositor delivered it in,
+/// with nothing converted, see
+/// [Options::native_format](crate::capturer::Options::native_format).
+/// Decoding `data` is up to the consumer, by `format`.
+#[derive(Debug, Clone)]
+pub struct RawFrame {
+    pub display_time: u64,
+    pub width: i32,
+    pub height: i32,
+    pub format: RawPixelFormat,
+    /// Rows top-down, [RawFrame::row_stride] bytes apart, which can be more
+    /// than `width` pixels as the platform aligns them
+    pub data: Vec<u8>,
+    /// For `format`s encoded in a color space they don't name themselves
+    pub color_space: ColorSpace,
+}
+
+impl RawFrame {
+    /// The bytes from the start of a row to the next
+    pub fn row_stride(&self) -> usize {
+        match self.height > 0 {
+            true => self.data.len() / self.height as usize,
+            false => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_row_stride() {
+        // 3 half float pixels in rows aligned to 32 bytes
+        let frame = RawFrame {
+            display_time: 0,
+            width: 3,
+            height: 2,
+            format: RawPixelFormat::Rgba16Float,
+            data: vec![0; 64],
+            color_space: ColorSpace::Unknown,
+        };
+        assert_eq!(frame.row_stride(), 32);
+        assert!(frame.row_stride() >= frame.width as usize * frame.format.bytes_per_pixel());
+        assert_eq!(RawPixelFormat::Argb2101010.bit_depth(), 10);
+
+        let empty = RawFrame { height: 0, ..frame };
+        assert_eq!(empty.row_stride(), 0);
+    }
+}
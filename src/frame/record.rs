@@ -0,0 +1,188 @@
+use std::io::{self, Read};
+
+use super::{
+    BGRAFrame, BGRFrame, BGRxFrame, ColorSpace, Frame, Gray8Frame, RGBFrame, RGBxFrame, RowOrder,
+    XBGRFrame,
+};
+
+// The record format, all integers little endian:
+//
+//   header   length: u64, display_time: u64, format: u8, origin: u8,
+//            color_space: u8, 0: u8, width: i32, height: i32, 0: u32
+//   pixels   `length` bytes, the frame's buffer as it is
+//
+// Formats are 0 RGB, 1 RGBx, 2 XBGR, 3 BGRx, 4 BGR0, 5 BGRA and 6 Gray8.
+// A zero length ends a sequence of records.
+pub(crate) const RECORD_HEADER: usize = 32;
+
+// A packed frame laid out as a record, borrowing its pixels
+pub(crate) struct Record<'a> {
+    pub header: [u8; RECORD_HEADER],
+    pub pixels: &'a [u8],
+}
+
+// The record of a packed frame. None for frames that have to be converted
+// first, like YUV frames, and for empty frames.
+pub(crate) fn to_record(frame: &Frame) -> Option<Record<'_>> {
+    let format = match frame {
+        Frame::RGB(_) => 0,
+        Frame::RGBx(_) => 1,
+        Frame::XBGR(_) => 2,
+        Frame::BGRx(_) => 3,
+        Frame::BGR0(_) => 4,
+        Frame::BGRA(_) => 5,
+        Frame::Gray8(_) => 6,
+        _ => return None,
+    };
+    let packed = frame.packed_data()?;
+
+    let mut header = [0; RECORD_HEADER];
+    header[..8].copy_from_slice(&(packed.data.len() as u64).to_le_bytes());
+    header[8..16].copy_from_slice(&frame.display_time().to_le_bytes());
+    header[16] = format;
+    header[17] = match packed.origin {
+        RowOrder::TopDown => 0,
+        RowOrder::BottomUp => 1,
+    };
+    header[18] = match frame.color_space() {
+        ColorSpace::Unknown => 0,
+        ColorSpace::SRGB => 1,
+        ColorSpace::DisplayP3 => 2,
+    };
+    header[20..24].copy_from_slice(&(packed.width as i32).to_le_bytes());
+    header[24..28].copy_from_slice(&(packed.height as i32).to_le_bytes());
+
+    Some(Record {
+        header,
+        pixels: packed.data,
+    })
+}
+
+// The length of the pixels following a record header, 0 at the end
+pub(crate) fn record_length(header: &[u8; RECORD_HEADER]) -> u64 {
+    u64::from_le_bytes(header[..8].try_into().unwrap())
+}
+
+// The frame of a record, None for an unknown format
+pub(crate) fn from_record(header: &[u8; RECORD_HEADER], data: Vec<u8>) -> Option<Frame> {
+    let display_time = u64::from_le_bytes(header[8..16].try_into().unwrap());
+    let origin = match header[17] {
+        0 => RowOrder::TopDown,
+        _ => RowOrder::BottomUp,
+    };
+    let color_space = match header[18] {
+        1 => ColorSpace::SRGB,
+        2 => ColorSpace::DisplayP3,
+        _ => ColorSpace::Unknown,
+    };
+    let width = i32::from_le_bytes(header[20..24].try_into().unwrap());
+    let height = i32::from_le_bytes(header[24..28].try_into().unwrap());
+
+    macro_rules! packed {
+        ($variant:ident, $frame:ident) => {
+            Frame::$variant($frame {
+                display_time,
+                width,
+                height,
+                data,
+                origin,
+                color_space,
+            })
+        };
+    }
+    Some(match header[16] {
+        0 => packed!(RGB, RGBFrame),
+        1 => packed!(RGBx, RGBxFrame),
+        2 => packed!(XBGR, XBGRFrame),
+        3 => packed!(BGRx, BGRxFrame),
+        4 => packed!(BGR0, BGRFrame),
+        5 => packed!(BGRA, BGRAFrame),
+        6 => packed!(Gray8, Gray8Frame),
+        _ => return None,
+    })
+}
+
+/// Read the next frame of a stream of frame records, like the one a
+/// [FrameReader](crate::capturer::FrameReader) with headers yields. Returns
+/// None at the end of the stream.
+pub fn read_frame_record(reader: &mut impl Read) -> io::Result<Option<Frame>> {
+    let mut header = [0; RECORD_HEADER];
+    // A stream that ends between records ends cleanly
+    let mut filled = 0;
+    while filled < RECORD_HEADER {
+        match reader.read(&mut header[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(read) => filled += read,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
+        }
+    }
+
+    let length = record_length(&header);
+    if length == 0 {
+        return Ok(None);
+    }
+    let mut data = Vec::new();
+    reader.take(length).read_to_end(&mut data)?;
+    if data.len() as u64 != length {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    from_record(&header, data)
+        .map(Some)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "unknown frame format"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_records_round_trip() {
+        let frames = [
+            Frame::BGRA(BGRAFrame {
+                display_time: 7,
+                width: 2,
+                height: 1,
+                data: vec![1, 2, 3, 4, 5, 6, 7, 8],
+                origin: RowOrder::BottomUp,
+                color_space: ColorSpace::DisplayP3,
+            }),
+            Frame::Gray8(Gray8Frame {
+                display_time: 9,
+                width: 3,
+                height: 1,
+                data: vec![10, 20, 30],
+                origin: RowOrder::TopDown,
+                color_space: ColorSpace::Unknown,
+            }),
+        ];
+        let mut stream = Vec::new();
+        for frame in &frames {
+            let record = to_record(frame).unwrap();
+            stream.extend_from_slice(&record.header);
+            stream.extend_from_slice(record.pixels);
+        }
+
+        let mut reader = &stream[..];
+        match read_frame_record(&mut reader).unwrap() {
+            Some(Frame::BGRA(f)) => {
+                assert_eq!((f.display_time, f.width, f.height), (7, 2, 1));
+                assert_eq!(f.data, [1, 2, 3, 4, 5, 6, 7, 8]);
+                assert_eq!(f.origin, RowOrder::BottomUp);
+                assert_eq!(f.color_space, ColorSpace::DisplayP3);
+            }
+            _ => panic!("expected a BGRA frame"),
+        }
+        assert!(matches!(
+            read_frame_record(&mut reader).unwrap(),
+            Some(Frame::Gray8(f)) if f.data == [10, 20, 30]
+        ));
+        assert!(read_frame_record(&mut reader).unwrap().is_none());
+
+        // Cut off in the middle of a frame
+        let mut cut = &stream[..RECORD_HEADER + 3];
+        let error = read_frame_record(&mut cut).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
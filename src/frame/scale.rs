@@ -0,0 +1,241 @@
+use super::{convert_yuv_to_bgra, gray::spans, yuv::subsample_yuv, Frame};
+
+// The size `width` x `height` is downscaled to, to fit in `max_width` x
+// `max_height` with the same aspect ratio
+pub(crate) fn get_fitted_size(
+    width: usize,
+    height: usize,
+    max_width: u32,
+    max_height: u32,
+) -> (usize, usize) {
+    let scale = (max_width as f64 / width as f64)
+        .min(max_height as f64 / height as f64)
+        .min(1.0);
+    (
+        ((width as f64 * scale).round() as usize).max(1),
+        ((height as f64 * scale).round() as usize).max(1),
+    )
+}
+
+// The size `width` x `height` is subsampled to taking every `stride`th
+// pixel of every `stride`th row
+pub(crate) fn get_subsampled_size(width: usize, height: usize, stride: u32) -> (usize, usize) {
+    let stride = (stride as usize).max(1);
+    (
+        (width + stride - 1) / stride,
+        (height + stride - 1) / stride,
+    )
+}
+
+impl Frame {
+    /// Box filters the frame down to fit in `max_width` x `max_height`,
+    /// keeping its aspect ratio. Frames that already fit are copied as they
+    /// are. Packed frames keep their format and YUV frames become BGRA, both
+    /// top-down.
+    ///
+    /// Returns None for patches, empty frames and a zero maximum size.
+    pub fn downscaled(&self, max_width: u32, max_height: u32) -> Option<Frame> {
+        if let Frame::YUVFrame(yuv) = self {
+            return Frame::BGRA(convert_yuv_to_bgra(yuv)).downscaled(max_width, max_height);
+        }
+
+        let source = self.packed_data()?;
+        let (src_width, src_height) = (source.width, source.height);
+        let stride = source.data.len() / src_height;
+        let bytes_per_pixel = source.bytes_per_pixel;
+        if max_width == 0 || max_height == 0 || stride < src_width * bytes_per_pixel {
+            return None;
+        }
+
+        let (width, height) = get_fitted_size(src_width, src_height, max_width, max_height);
+
+        let columns = spans(src_width, width);
+        let mut sums = vec![0u32; width * bytes_per_pixel];
+        let mut data = Vec::with_capacity(width * height * bytes_per_pixel);
+
+        for (top, bottom) in spans(src_height, height) {
+            sums.fill(0);
+            for y in top..bottom {
+                let start = source.origin.buffer_row(y, src_height) * stride;
+                let row = &source.data[start..start + src_width * bytes_per_pixel];
+                for (sum, &(left, right)) in sums.chunks_exact_mut(bytes_per_pixel).zip(&columns) {
+                    for pixel in row[left * bytes_per_pixel..right * bytes_per_pixel]
+                        .chunks_exact(bytes_per_pixel)
+                    {
+                        for (channel, &value) in sum.iter_mut().zip(pixel) {
+                            *channel += value as u32;
+                        }
+                    }
+                }
+            }
+
+            for (sum, &(left, right)) in sums.chunks_exact(bytes_per_pixel).zip(&columns) {
+                let count = ((bottom - top) * (right - left)) as u32;
+                data.extend(sum.iter().map(|sum| ((sum + count / 2) / count) as u8));
+            }
+        }
+
+        self.with_top_down_data(width as i32, height as i32, data)
+    }
+
+    /// Takes every `stride`th pixel of every `stride`th row, starting at the
+    /// top left one, without filtering. The cheapest way to make a frame
+    /// smaller, for tiny live previews: nothing is computed, but thin lines
+    /// and text can vanish or flicker between frames. A 1920x1080 frame
+    /// with a stride of 8 becomes 240x135, odd sizes are rounded up. Packed
+    /// frames keep their format and YUV frames become BGRA, converting only
+    /// the sampled pixels, both top-down.
+    ///
+    /// Returns None for patches, empty frames and a zero stride.
+    pub fn subsampled(&self, stride: u32) -> Option<Frame> {
+        if stride == 0 {
+            return None;
+        }
+        if let Frame::YUVFrame(yuv) = self {
+            if yuv.width <= 0 || yuv.height <= 0 {
+                return None;
+            }
+            let sampled = subsample_yuv(yuv, stride as usize);
+            return Some(Frame::BGRA(convert_yuv_to_bgra(&sampled)));
+        }
+
+        let source = self.packed_data()?;
+        let (src_width, src_height) = (source.width, source.height);
+        let row_stride = source.data.len() / src_height;
+        let bytes_per_pixel = source.bytes_per_pixel;
+        if row_stride < src_width * bytes_per_pixel {
+            return None;
+        }
+
+        let (width, height) = get_subsampled_size(src_width, src_height, stride);
+        let mut data = Vec::with_capacity(width * height * bytes_per_pixel);
+        for y in (0..src_height).step_by(stride as usize) {
+            let start = source.origin.buffer_row(y, src_height) * row_stride;
+            let row = &source.data[start..start + src_width * bytes_per_pixel];
+            for pixel in row.chunks_exact(bytes_per_pixel).step_by(stride as usize) {
+                data.extend_from_slice(pixel);
+            }
+        }
+
+        self.with_top_down_data(width as i32, height as i32, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{
+        convert_bgra_to_yuv, BGRAFrame, ChromaSubsampling, ColorMatrix, ColorRange, ColorSpace,
+        Gray8Frame, RGBFrame, RowOrder,
+    };
+
+    #[test]
+    fn test_downscaled_keeps_aspect_ratio() {
+        // Left half black, right half white
+        let frame = Frame::RGB(RGBFrame {
+            display_time: 9,
+            width: 8,
+            height: 4,
+            data: (0..4)
+                .flat_map(|_| (0..8).flat_map(|x| [if x < 4 { 0 } else { 255 }; 3]))
+                .collect(),
+            origin: RowOrder::BottomUp,
+            color_space: ColorSpace::SRGB,
+        });
+
+        let Some(Frame::RGB(small)) = frame.downscaled(4, 4) else {
+            panic!("wrong frame type");
+        };
+        assert_eq!((small.width, small.height), (4, 2));
+        assert_eq!(small.display_time, 9);
+        assert_eq!(small.origin, RowOrder::TopDown);
+        assert_eq!(
+            &small.data[..12],
+            [0, 0, 0, 0, 0, 0, 255, 255, 255, 255, 255, 255]
+        );
+
+        // Never upscaled
+        let Some(Frame::RGB(same)) = frame.downscaled(100, 100) else {
+            panic!("wrong frame type");
+        };
+        assert_eq!((same.width, same.height), (8, 4));
+        assert!(frame.downscaled(0, 10).is_none());
+    }
+
+    #[test]
+    fn test_fitted_size() {
+        // A 5K display for an encoder limited to 4096 pixels a side
+        assert_eq!(get_fitted_size(5120, 2880, 4096, 4096), (4096, 2304));
+        assert_eq!(get_fitted_size(2880, 5120, 4096, 4096), (2304, 4096));
+        assert_eq!(get_fitted_size(1920, 1080, 4096, 4096), (1920, 1080));
+        assert_eq!(get_fitted_size(1920, 1080, 1000, 100), (178, 100));
+    }
+
+    #[test]
+    fn test_downscaled_averages_channels() {
+        let frame = Frame::BGRA(BGRAFrame {
+            display_time: 0,
+            width: 2,
+            height: 2,
+            data: vec![
+                0, 10, 100, 255, 10, 20, 200, 255, //
+                20, 30, 100, 255, 30, 40, 200, 0,
+            ],
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::SRGB,
+        });
+        let Some(Frame::BGRA(pixel)) = frame.downscaled(1, 1) else {
+            panic!("wrong frame type");
+        };
+        assert_eq!(pixel.data, [15, 25, 150, 191]);
+    }
+
+    #[test]
+    fn test_subsampled() {
+        // Pixels numbered in reading order, stored bottom up
+        let frame = Frame::Gray8(Gray8Frame {
+            display_time: 3,
+            width: 5,
+            height: 3,
+            data: (0..15).map(|i| (14 - i) / 5 * 5 + i % 5).collect(),
+            origin: RowOrder::BottomUp,
+            color_space: ColorSpace::SRGB,
+        });
+        let Some(Frame::Gray8(small)) = frame.subsampled(2) else {
+            panic!("wrong frame type");
+        };
+        assert_eq!((small.width, small.height), (3, 2));
+        assert_eq!(small.data, [0, 2, 4, 10, 12, 14]);
+        assert_eq!(small.origin, RowOrder::TopDown);
+        assert_eq!(small.display_time, 3);
+        assert_eq!(get_subsampled_size(5, 3, 2), (3, 2));
+        assert!(frame.subsampled(0).is_none());
+
+        // Every pixel of a YUV frame with the chroma it was encoded with
+        let mut bgra = BGRAFrame {
+            display_time: 0,
+            width: 4,
+            height: 2,
+            data: vec![0; 4 * 2 * 4],
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::SRGB,
+        };
+        bgra.data[8..16].copy_from_slice(&[255, 0, 0, 255, 255, 0, 0, 255]);
+        let yuv = Frame::YUVFrame(convert_bgra_to_yuv(
+            &bgra,
+            ColorMatrix::BT709,
+            ColorRange::Full,
+            ChromaSubsampling::Yuv420,
+        ));
+        let Some(Frame::BGRA(small)) = yuv.subsampled(2) else {
+            panic!("wrong frame type");
+        };
+        assert_eq!((small.width, small.height), (2, 1));
+        let Frame::YUVFrame(yuv) = yuv else {
+            unreachable!()
+        };
+        let whole = convert_yuv_to_bgra(&yuv);
+        assert_eq!(small.data[..4], whole.data[..4]);
+        assert_eq!(small.data[4..], whole.data[8..12]);
+    }
+}
@@ -0,0 +1,280 @@
+use super::{delta::copy_top_down, BGRAFrame, ColorSpace, DeltaError, RowOrder};
+
+/// Consecutive changed rows of a frame, in BGRA without padding
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanlineBand {
+    pub y: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+/// A BGRA frame as the rows that changed since the frame before it, see
+/// [Options::scanline_patches](crate::capturer::Options::scanline_patches)
+#[derive(Debug, Clone)]
+pub struct ScanlinePatchFrame {
+    pub display_time: u64,
+    /// Counts up by one with every frame of the stream
+    pub sequence: u64,
+    pub width: i32,
+    pub height: i32,
+    /// The changed rows, top to bottom. Full frames are a single band of
+    /// every row.
+    pub bands: Vec<ScanlineBand>,
+    pub color_space: ColorSpace,
+}
+
+impl ScanlinePatchFrame {
+    /// Whether the patch holds the whole frame, which doesn't depend on any
+    /// frame before it
+    pub fn is_full(&self) -> bool {
+        matches!(self.bands.as_slice(), [band] if band.y == 0 && band.height as i32 == self.height)
+    }
+}
+
+/// Reconstructs the full frame on the receiving side by applying `patch` to
+/// `frame`, which must hold the result of the patch with sequence
+/// `patch.sequence - 1`. Full patches replace `frame` entirely.
+///
+/// When a patch's sequence doesn't follow the last applied one, patches got
+/// lost. Skip patches until the next full one, which can be requested with
+/// [Capturer::force_keyframe](crate::capturer::Capturer::force_keyframe).
+pub fn apply_scanline_patch(
+    frame: &mut BGRAFrame,
+    patch: &ScanlinePatchFrame,
+) -> Result<(), DeltaError> {
+    let (width, height) = (patch.width.max(0) as usize, patch.height.max(0) as usize);
+    let fits = |band: &ScanlineBand| {
+        (band.y + band.height) as usize <= height
+            && band.data.len() == band.height as usize * width * 4
+    };
+    if !patch.bands.iter().all(fits) {
+        return Err(DeltaError::InvalidRegion);
+    }
+
+    if patch.is_full() {
+        frame.data.clear();
+        frame.data.resize(width * height * 4, 0);
+        frame.width = patch.width;
+        frame.height = patch.height;
+        frame.origin = RowOrder::TopDown;
+    } else if (frame.width, frame.height) != (patch.width, patch.height)
+        || frame.data.len() != width * height * 4
+        || frame.origin != RowOrder::TopDown
+    {
+        return Err(DeltaError::SizeMismatch);
+    }
+
+    for band in &patch.bands {
+        let start = band.y as usize * width * 4;
+        frame.data[start..start + band.data.len()].copy_from_slice(&band.data);
+    }
+
+    frame.display_time = patch.display_time;
+    frame.color_space = patch.color_space;
+    Ok(())
+}
+
+// Turns BGRA frames into patches of the rows that differ from the frame before
+#[derive(Debug)]
+pub(crate) struct ScanlineEncoder {
+    max_changed_fraction: f32,
+    full_frame_interval: u32,
+    // The last frame without row padding, empty before the first one
+    previous: Vec<u8>,
+    current: Vec<u8>,
+    size: (usize, usize),
+    sequence: u64,
+    since_full: u32,
+    force_full: bool,
+}
+
+impl ScanlineEncoder {
+    // Sends the whole frame when more than `max_changed_fraction` of its rows
+    // changed, and every `full_frame_interval` frames unless it's 0
+    pub fn new(max_changed_fraction: f32, full_frame_interval: u32) -> Self {
+        ScanlineEncoder {
+            max_changed_fraction,
+            full_frame_interval,
+            previous: Vec::new(),
+            current: Vec::new(),
+            size: (0, 0),
+            sequence: 0,
+            since_full: 0,
+            force_full: false,
+        }
+    }
+
+    // Makes the next frame a full one
+    pub fn force_full(&mut self) {
+        self.force_full = true;
+    }
+
+    // Returns None only for empty frames before the first full one. Empty
+    // frames after it are patches without bands.
+    pub fn encode(&mut self, frame: &BGRAFrame) -> Option<ScanlinePatchFrame> {
+        let size = copy_top_down(frame, &mut self.current);
+        if size.is_none() && self.previous.is_empty() {
+            return None;
+        }
+
+        let (width, height) = size.unwrap_or(self.size);
+        let mut patch = ScanlinePatchFrame {
+            display_time: frame.display_time,
+            sequence: self.sequence,
+            width: width as i32,
+            height: height as i32,
+            bands: Vec::new(),
+            color_space: frame.color_space,
+        };
+        self.sequence = self.sequence.wrapping_add(1);
+
+        if size.is_none() {
+            self.since_full += 1;
+            return Some(patch);
+        }
+
+        let row_bytes = width * 4;
+        let full = self.force_full
+            || self.previous.is_empty()
+            || self.size != (width, height)
+            || (self.full_frame_interval > 0 && self.since_full >= self.full_frame_interval);
+
+        if !full {
+            let mut changed = 0;
+            let mut y = 0;
+            while y < height {
+                let row = |y: usize| y * row_bytes..(y + 1) * row_bytes;
+                if self.current[row(y)] == self.previous[row(y)] {
+                    y += 1;
+                    continue;
+                }
+
+                let top = y;
+                while y < height && self.current[row(y)] != self.previous[row(y)] {
+                    y += 1;
+                }
+                changed += y - top;
+                patch.bands.push(ScanlineBand {
+                    y: top as u32,
+                    height: (y - top) as u32,
+                    data: self.current[top * row_bytes..y * row_bytes].to_vec(),
+                });
+            }
+
+            // Past this point the bands aren't worth their bookkeeping
+            if changed as f32 <= self.max_changed_fraction * height as f32 {
+                self.since_full += 1;
+                std::mem::swap(&mut self.previous, &mut self.current);
+                return Some(patch);
+            }
+        }
+
+        patch.bands = vec![ScanlineBand {
+            y: 0,
+            height: height as u32,
+            data: self.current.clone(),
+        }];
+        self.force_full = false;
+        self.since_full = 0;
+        self.size = (width, height);
+        std::mem::swap(&mut self.previous, &mut self.current);
+        Some(patch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terminal(width: i32, height: i32) -> BGRAFrame {
+        BGRAFrame {
+            display_time: 0,
+            width,
+            height,
+            data: vec![30; (width * height * 4) as usize],
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::SRGB,
+        }
+    }
+
+    // Types on a line of text, 10 rows high
+    fn type_on_line(frame: &mut BGRAFrame, line: usize, value: u8) {
+        let row_bytes = frame.width as usize * 4;
+        let start = line * 10 * row_bytes;
+        frame.data[start..start + 10 * row_bytes]
+            .iter_mut()
+            .step_by(7)
+            .for_each(|byte| *byte = value);
+    }
+
+    #[test]
+    fn test_only_changed_lines_are_sent() {
+        let mut encoder = ScanlineEncoder::new(0.5, 0);
+        let mut frame = terminal(64, 100);
+        let mut received = terminal(0, 0);
+
+        let first = encoder.encode(&frame).unwrap();
+        assert!(first.is_full());
+        apply_scanline_patch(&mut received, &first).unwrap();
+
+        // A prompt line and the status bar
+        type_on_line(&mut frame, 3, 200);
+        type_on_line(&mut frame, 9, 100);
+        frame.display_time = 1;
+        let patch = encoder.encode(&frame).unwrap();
+        assert_eq!(patch.sequence, 1);
+        assert!(!patch.is_full());
+        let bands: Vec<_> = patch.bands.iter().map(|b| (b.y, b.height)).collect();
+        assert_eq!(bands, [(30, 10), (90, 10)]);
+        apply_scanline_patch(&mut received, &patch).unwrap();
+        assert_eq!(received.data, frame.data);
+        assert_eq!(received.display_time, 1);
+
+        // Nothing changed
+        let patch = encoder.encode(&frame).unwrap();
+        assert!(patch.bands.is_empty());
+        apply_scanline_patch(&mut received, &patch).unwrap();
+        assert_eq!(received.data, frame.data);
+    }
+
+    #[test]
+    fn test_full_frames() {
+        let mut encoder = ScanlineEncoder::new(0.5, 4);
+        let mut frame = terminal(16, 100);
+        let mut received = terminal(0, 0);
+
+        for i in 0..12u64 {
+            // Scrolling changes every line
+            let scrolled = i == 6;
+            for line in 0..10 {
+                if scrolled || line == i as usize % 10 {
+                    type_on_line(&mut frame, line, i as u8 * 10 + line as u8);
+                }
+            }
+
+            let patch = encoder.encode(&frame).unwrap();
+            let expected_full = i == 0 || i == 5 || scrolled || i == 11;
+            assert_eq!(patch.is_full(), expected_full, "frame {i}");
+            apply_scanline_patch(&mut received, &patch).unwrap();
+            assert_eq!(received.data, frame.data, "frame {i}");
+        }
+
+        encoder.force_full();
+        assert!(encoder.encode(&frame).unwrap().is_full());
+
+        // A resize needs a full frame, patches can't apply to the old size
+        let resized = terminal(16, 50);
+        let patch = encoder.encode(&resized).unwrap();
+        assert!(patch.is_full());
+        let stale = ScanlinePatchFrame {
+            bands: vec![],
+            ..patch.clone()
+        };
+        assert_eq!(
+            apply_scanline_patch(&mut received, &stale),
+            Err(DeltaError::SizeMismatch)
+        );
+        apply_scanline_patch(&mut received, &patch).unwrap();
+        assert_eq!(received.data, resized.data);
+    }
+}
@@ -0,0 +1,178 @@
+use std::time::{Duration, SystemTime};
+
+use super::Frame;
+
+const NANOSECONDS_PER_SECOND: u128 = 1_000_000_000;
+
+/// Converts a frame's display time from nanoseconds to ticks of `timebase`
+/// per second, e.g. 90000 for MPEG-TS and RTP video.
+///
+/// This is `time * timebase / 1e9` rounded to the nearest tick, computed in
+/// 128 bits so it can't overflow. Converting every frame from its own time
+/// rather than adding up frame durations means rounding errors don't build
+/// up. Rounding keeps timestamps in order, but frames closer together than
+/// half a tick can end up with the same one. A zero timebase leaves the time
+/// in nanoseconds.
+pub fn rescale_timestamp(time: u64, timebase: u32) -> u64 {
+    if timebase == 0 {
+        return time;
+    }
+
+    let ticks =
+        (time as u128 * timebase as u128 + NANOSECONDS_PER_SECOND / 2) / NANOSECONDS_PER_SECOND;
+    ticks as u64
+}
+
+// Nanoseconds as ticks of `timebase`, or left as they are without one, for
+// display times computed from other display times
+pub(crate) fn rescale_to_timebase(time: u64, timebase: Option<u32>) -> u64 {
+    match timebase {
+        Some(timebase) => rescale_timestamp(time, timebase),
+        None => time,
+    }
+}
+
+impl Frame {
+    // Replaces the display time with ticks of `timebase`, see [rescale_timestamp]
+    pub(crate) fn rescale_display_time(&mut self, timebase: u32) {
+        let time = self.display_time_mut();
+        *time = rescale_timestamp(*time, timebase);
+    }
+}
+
+/// The frames' clock and the wall clock read at the same moment when
+/// capture started, see [Capturer::clock_correlation](crate::capturer::Capturer::clock_correlation).
+///
+/// Frames are timed with a clock that suits syncing them, the host clock on
+/// macOS and the wall clock as they arrive on Windows. Converting through
+/// the correlation gives wall clock times as that clock ran when capture
+/// started, so it being adjusted later, e.g. by NTP, doesn't make frames
+/// jump or run backwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockCorrelation {
+    /// The frames' clock, in the units of their display time
+    pub frame_time: u64,
+    pub wall_time: SystemTime,
+    /// Ticks per second of `frame_time`, 0 for nanoseconds, see
+    /// [Options::timestamp_base](crate::capturer::Options::timestamp_base)
+    pub timebase: u32,
+}
+
+impl ClockCorrelation {
+    // Nanoseconds on the frames' clock
+    fn to_nanoseconds(self, frame_time: u64) -> i128 {
+        match self.timebase {
+            0 => frame_time as i128,
+            timebase => frame_time as i128 * NANOSECONDS_PER_SECOND as i128 / timebase as i128,
+        }
+    }
+
+    /// The wall clock time of `frame_time`, a display time on the frames' clock
+    pub fn to_wall_clock(self, frame_time: u64) -> SystemTime {
+        let offset = self.to_nanoseconds(frame_time) - self.to_nanoseconds(self.frame_time);
+        let duration = Duration::from_nanos(offset.unsigned_abs() as u64);
+        match offset >= 0 {
+            true => self.wall_time + duration,
+            false => self.wall_time - duration,
+        }
+    }
+
+    /// The display time of a frame captured at `wall_time`, None if that's
+    /// before the frames' clock started
+    pub fn to_frame_time(self, wall_time: SystemTime) -> Option<u64> {
+        let offset = match wall_time.duration_since(self.wall_time) {
+            Ok(after) => after.as_nanos() as i128,
+            Err(before) => -(before.duration().as_nanos() as i128),
+        };
+        let time = self.to_nanoseconds(self.frame_time) + offset;
+        (time >= 0).then(|| rescale_timestamp(time as u64, self.timebase))
+    }
+}
+
+impl Frame {
+    /// When the frame was captured on the wall clock, e.g. to show that it
+    /// was captured at 14:32:05.123, see [ClockCorrelation]
+    pub fn wall_clock_time(&self, correlation: &ClockCorrelation) -> SystemTime {
+        correlation.to_wall_clock(self.display_time())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rescale_timestamp() {
+        assert_eq!(rescale_timestamp(1_000_000_000, 90_000), 90_000);
+        assert_eq!(rescale_timestamp(1_000_000_000, 1_000_000), 1_000_000);
+        // A 60 fps frame duration is 1500 ticks at 90kHz
+        assert_eq!(rescale_timestamp(16_666_667, 90_000), 1500);
+        // Half a tick rounds up
+        assert_eq!(rescale_timestamp(5_556, 90_000), 1);
+        assert_eq!(rescale_timestamp(5_555, 90_000), 0);
+        assert_eq!(rescale_timestamp(123, 0), 123);
+
+        // Times since the Unix epoch don't overflow
+        let time = 1_760_000_000_000_000_000;
+        assert_eq!(rescale_timestamp(time, 90_000), 158_400_000_000_000);
+        assert_eq!(rescale_timestamp(u64::MAX, 1_000_000_000), u64::MAX);
+    }
+
+    #[test]
+    fn test_rescaled_timestamps_stay_in_order() {
+        // Two hours of jittery 60 fps frames, plus a few bursts closer than a tick
+        let mut time = 1_760_000_000_000_000_000u64;
+        let mut last = rescale_timestamp(time, 90_000);
+        for i in 0..432_000u64 {
+            time += match i % 7 {
+                0 => 3_000,
+                _ => 16_666_667 + (i * 7919 % 2_000_000) - 1_000_000,
+            };
+            let ticks = rescale_timestamp(time, 90_000);
+            assert!(ticks >= last, "frame {i}");
+            last = ticks;
+        }
+
+        // No drift: the last frame's ticks match its own time, within rounding
+        let expected = time as f64 * 90_000.0 / 1e9;
+        assert!((last as f64 - expected).abs() <= 0.5 + expected * f64::EPSILON);
+    }
+
+    #[test]
+    fn test_clock_correlation() {
+        let started = SystemTime::UNIX_EPOCH + Duration::from_secs(1_760_000_000);
+        // The host clock had run for 5 s, timestamps are at 90 kHz
+        let correlation = ClockCorrelation {
+            frame_time: 450_000,
+            wall_time: started,
+            timebase: 90_000,
+        };
+        assert_eq!(
+            correlation.to_wall_clock(675_000),
+            started + Duration::from_millis(2500)
+        );
+        assert_eq!(
+            correlation.to_wall_clock(0),
+            started - Duration::from_secs(5)
+        );
+        assert_eq!(
+            correlation.to_frame_time(started + Duration::from_millis(2500)),
+            Some(675_000)
+        );
+        assert_eq!(
+            correlation.to_frame_time(started - Duration::from_secs(6)),
+            None
+        );
+
+        // The wall clock is set back an hour 10 s in. Reading it then would
+        // place the frame before capture started, the correlation keeps
+        // frames at the times they were captured at, in order.
+        let adjusted = started + Duration::from_secs(10) - Duration::from_secs(3600);
+        assert!(adjusted < started);
+        let times: Vec<SystemTime> = (0..20u64)
+            .map(|i| correlation.to_wall_clock(450_000 + i * 90_000))
+            .collect();
+        assert!(times.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(times[10], started + Duration::from_secs(10));
+    }
+}
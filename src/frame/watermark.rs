@@ -0,0 +1,313 @@
+use super::{Frame, RowOrder};
+
+/// Cells in each row of a watermark and its rows, each cell holding a bit
+pub const WATERMARK_COLUMNS: usize = 16;
+pub const WATERMARK_ROWS: usize = 10;
+
+/// How a watermark is drawn, see [WatermarkOptions]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WatermarkEncoding {
+    /// Black and white cells, which survive scaling and recompression as
+    /// long as the cells stay a few pixels wide
+    #[default]
+    Visible,
+    /// The lowest bit of the color channels of the cells, which changes
+    /// them imperceptibly. Best effort: lossy recompression and scaling wipe
+    /// it out, so it only holds up in lossless recordings.
+    Invisible,
+}
+
+/// The corner a watermark is drawn in, a cell away from the frame's edges
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WatermarkPosition {
+    TopLeft,
+    TopRight,
+    #[default]
+    BottomLeft,
+    BottomRight,
+}
+
+/// Embeds each frame's sequence number and display time into it, so single
+/// frames can later be verified and ordered with [read_watermark]. See
+/// [Options::watermark](crate::capturer::Options::watermark)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WatermarkOptions {
+    pub encoding: WatermarkEncoding,
+    pub position: WatermarkPosition,
+    /// Width and height of a cell in pixels
+    pub cell_size: u32,
+}
+
+impl Default for WatermarkOptions {
+    fn default() -> Self {
+        WatermarkOptions {
+            encoding: WatermarkEncoding::default(),
+            position: WatermarkPosition::default(),
+            cell_size: 4,
+        }
+    }
+}
+
+/// What a watermark holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watermark {
+    /// The frame's place among the delivered frames, from 0
+    pub sequence: u64,
+    /// The frame's display time when it was watermarked
+    pub display_time: u64,
+}
+
+impl Watermark {
+    // The sequence number, the display time and a checksum of both, most
+    // significant bit first
+    fn to_bits(self) -> Vec<bool> {
+        let mut bytes = [self.sequence.to_be_bytes(), self.display_time.to_be_bytes()].concat();
+        bytes.extend(checksum(&bytes).to_be_bytes());
+        bytes
+            .iter()
+            .flat_map(|byte| (0..8).rev().map(move |bit| byte >> bit & 1 == 1))
+            .collect()
+    }
+
+    fn from_bits(bits: &[bool]) -> Option<Self> {
+        let bytes: Vec<u8> = bits
+            .chunks_exact(8)
+            .map(|bits| bits.iter().fold(0, |byte, &bit| byte << 1 | bit as u8))
+            .collect();
+        let (payload, sum) = bytes.split_at(16);
+        if checksum(payload).to_be_bytes() != sum {
+            return None;
+        }
+        Some(Watermark {
+            sequence: u64::from_be_bytes(payload[..8].try_into().unwrap()),
+            display_time: u64::from_be_bytes(payload[8..].try_into().unwrap()),
+        })
+    }
+}
+
+// 32 bit FNV-1a, which is enough to tell damaged watermarks apart
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c9dc5, |hash, byte| {
+        (hash ^ *byte as u32).wrapping_mul(0x01000193)
+    })
+}
+
+// A packed frame with 4 bytes per pixel, its size, row order, stride and
+// the offset of the three color bytes in a pixel
+struct Pixels {
+    width: usize,
+    height: usize,
+    origin: RowOrder,
+    stride: usize,
+    color: usize,
+}
+
+impl Pixels {
+    fn new(frame: &Frame) -> Option<(&[u8], Self)> {
+        let (data, width, height, origin, color) = match frame {
+            Frame::RGBx(f) => (&f.data, f.width, f.height, f.origin, 0),
+            Frame::XBGR(f) => (&f.data, f.width, f.height, f.origin, 1),
+            Frame::BGRx(f) => (&f.data, f.width, f.height, f.origin, 0),
+            Frame::BGRA(f) => (&f.data, f.width, f.height, f.origin, 0),
+            Frame::YUVFrame(_)
+            | Frame::RGB(_)
+            | Frame::BGR0(_)
+            | Frame::Gray8(_)
+            | Frame::Delta(_)
+            | Frame::ScanlinePatch(_)
+            | Frame::Regions(_)
+            | Frame::Marker(_)
+            | Frame::Activity(_)
+            | Frame::Raw(_) => return None,
+        };
+        if width <= 0 || height <= 0 {
+            return None;
+        }
+        let (width, height) = (width as usize, height as usize);
+        let stride = data.len() / height;
+        if stride < width * 4 {
+            return None;
+        }
+        let pixels = Pixels {
+            width,
+            height,
+            origin,
+            stride,
+            color,
+        };
+        Some((data, pixels))
+    }
+
+    // The top left pixel of the watermark, None if it doesn't fit
+    fn get_origin(&self, options: &WatermarkOptions) -> Option<(usize, usize)> {
+        let cell = options.cell_size as usize;
+        let (width, height) = (WATERMARK_COLUMNS * cell, WATERMARK_ROWS * cell);
+        if cell == 0 || width + 2 * cell > self.width || height + 2 * cell > self.height {
+            return None;
+        }
+        let left = match options.position {
+            WatermarkPosition::TopLeft | WatermarkPosition::BottomLeft => cell,
+            WatermarkPosition::TopRight | WatermarkPosition::BottomRight => {
+                self.width - cell - width
+            }
+        };
+        let top = match options.position {
+            WatermarkPosition::TopLeft | WatermarkPosition::TopRight => cell,
+            WatermarkPosition::BottomLeft | WatermarkPosition::BottomRight => {
+                self.height - cell - height
+            }
+        };
+        Some((left, top))
+    }
+
+    // The index of the first color byte of every pixel of each cell, row by row
+    fn cells(&self, options: &WatermarkOptions) -> Option<Vec<Vec<usize>>> {
+        let (left, top) = self.get_origin(options)?;
+        let cell = options.cell_size as usize;
+        let cells = (0..WATERMARK_COLUMNS * WATERMARK_ROWS).map(|i| {
+            let (x, y) = (
+                left + i % WATERMARK_COLUMNS * cell,
+                top + i / WATERMARK_COLUMNS * cell,
+            );
+            (y..y + cell)
+                .flat_map(|y| {
+                    let row = self.origin.buffer_row(y, self.height) * self.stride;
+                    (x..x + cell).map(move |x| row + x * 4 + self.color)
+                })
+                .collect()
+        });
+        Some(cells.collect())
+    }
+}
+
+/// Draws `watermark` into a frame with 4 bytes per pixel, like BGRA.
+/// Other frames and ones too small to hold it are left as they are.
+pub fn embed_watermark(frame: &mut Frame, watermark: &Watermark, options: &WatermarkOptions) {
+    let Some(cells) = Pixels::new(frame).and_then(|(_, pixels)| pixels.cells(options)) else {
+        return;
+    };
+    let data = match frame {
+        Frame::RGBx(f) => &mut f.data,
+        Frame::XBGR(f) => &mut f.data,
+        Frame::BGRx(f) => &mut f.data,
+        Frame::BGRA(f) => &mut f.data,
+        _ => return,
+    };
+
+    for (pixels, bit) in cells.iter().zip(watermark.to_bits()) {
+        for &i in pixels {
+            for value in &mut data[i..i + 3] {
+                *value = match options.encoding {
+                    WatermarkEncoding::Visible => bit as u8 * 255,
+                    WatermarkEncoding::Invisible => *value & !1 | bit as u8,
+                };
+            }
+        }
+    }
+}
+
+/// The watermark embedded into `frame` with `options`, None if it holds
+/// none or it was damaged
+pub fn read_watermark(frame: &Frame, options: &WatermarkOptions) -> Option<Watermark> {
+    let (data, pixels) = Pixels::new(frame)?;
+    let bits: Vec<bool> = pixels
+        .cells(options)?
+        .iter()
+        .map(|pixels| {
+            // The majority of the cell decides, which evens out some noise
+            let values = pixels.iter().flat_map(|&i| &data[i..i + 3]);
+            let ones = match options.encoding {
+                WatermarkEncoding::Visible => values.filter(|value| **value >= 128).count(),
+                WatermarkEncoding::Invisible => values.filter(|value| **value & 1 == 1).count(),
+            };
+            ones * 2 > pixels.len() * 3
+        })
+        .collect();
+    Watermark::from_bits(&bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{BGRAFrame, ColorSpace};
+
+    fn frame(origin: RowOrder) -> Frame {
+        let (width, height) = (100, 60);
+        Frame::BGRA(BGRAFrame {
+            display_time: 0,
+            width,
+            height,
+            data: (0..width * height * 4).map(|i| (i % 251) as u8).collect(),
+            origin,
+            color_space: ColorSpace::SRGB,
+        })
+    }
+
+    fn data(frame: &Frame) -> &[u8] {
+        match frame {
+            Frame::BGRA(f) => &f.data,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_embed_and_read() {
+        let watermark = Watermark {
+            sequence: 42,
+            display_time: 1_700_000_000_123_456_789,
+        };
+        for encoding in [WatermarkEncoding::Visible, WatermarkEncoding::Invisible] {
+            for position in [WatermarkPosition::TopLeft, WatermarkPosition::BottomRight] {
+                let options = WatermarkOptions {
+                    encoding,
+                    position,
+                    cell_size: 3,
+                };
+                let mut frame = frame(RowOrder::BottomUp);
+                assert_eq!(read_watermark(&frame, &options), None);
+                embed_watermark(&mut frame, &watermark, &options);
+                assert_eq!(read_watermark(&frame, &options), Some(watermark));
+            }
+        }
+
+        // Too large to fit, so the frame is left alone
+        let options = WatermarkOptions {
+            cell_size: 10,
+            ..Default::default()
+        };
+        let mut unchanged = frame(RowOrder::TopDown);
+        embed_watermark(&mut unchanged, &watermark, &options);
+        assert_eq!(data(&unchanged), data(&frame(RowOrder::TopDown)));
+    }
+
+    #[test]
+    fn test_tampering_is_detected() {
+        let options = WatermarkOptions {
+            encoding: WatermarkEncoding::Invisible,
+            ..Default::default()
+        };
+        let mut frame = frame(RowOrder::TopDown);
+        let watermark = Watermark {
+            sequence: 7,
+            display_time: 99,
+        };
+        embed_watermark(&mut frame, &watermark, &options);
+
+        // Flip the bit of the first cell
+        let Frame::BGRA(f) = &mut frame else {
+            unreachable!()
+        };
+        let row = (60 - 4 - WATERMARK_ROWS * 4) * 400;
+        for y in 0..4 {
+            for x in 4..8 {
+                for c in 0..3 {
+                    f.data[row + y * 400 + x * 4 + c] ^= 1;
+                }
+            }
+        }
+        assert_eq!(read_watermark(&frame, &options), None);
+    }
+}
@@ -0,0 +1,668 @@
+use super::{BGRAFrame, RowOrder, YUVFrame};
+
+/// The RGB <-> YCbCr matrix a [YUVFrame] is encoded with
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ColorMatrix {
+    /// ITU-R BT.601, used by SD content and JPEG
+    BT601,
+    /// ITU-R BT.709, used by HD content. This is what ScreenCaptureKit delivers by default.
+    #[default]
+    BT709,
+    /// ITU-R BT.2020, used by UHD and HDR content
+    BT2020,
+    /// A matrix of a pipeline's own, see [ColorMatrix::custom]
+    Custom(Box<CustomMatrix>),
+}
+
+/// The rows of an RGB -> YCbCr matrix for full range values, with Y in
+/// [0, 1] and Cb and Cr in [-0.5, 0.5]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(try_from = "[[f64; 3]; 3]", into = "[[f64; 3]; 3]")
+)]
+pub struct CustomMatrix {
+    rows: [[f64; 3]; 3],
+}
+
+impl CustomMatrix {
+    pub fn rows(&self) -> [[f64; 3]; 3] {
+        self.rows
+    }
+}
+
+impl TryFrom<[[f64; 3]; 3]> for CustomMatrix {
+    type Error = MatrixError;
+
+    fn try_from(rows: [[f64; 3]; 3]) -> Result<Self, Self::Error> {
+        match ColorMatrix::custom(rows)? {
+            ColorMatrix::Custom(matrix) => Ok(*matrix),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl From<CustomMatrix> for [[f64; 3]; 3] {
+    fn from(matrix: CustomMatrix) -> Self {
+        matrix.rows
+    }
+}
+
+/// Why [ColorMatrix::custom] rejected a matrix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatrixError {
+    /// A coefficient is NaN or infinite
+    NotFinite,
+    /// The Y row doesn't add up to 1, so white wouldn't be full luma
+    LumaNotNormalized,
+    /// The Cb or Cr row doesn't add up to 0, so grays would be tinted
+    ChromaNotNeutral,
+    /// The matrix has no inverse, so frames couldn't be decoded
+    Singular,
+}
+
+impl std::fmt::Display for MatrixError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MatrixError::NotFinite => write!(f, "The matrix has a coefficient that isn't finite"),
+            MatrixError::LumaNotNormalized => write!(f, "The Y row doesn't add up to 1"),
+            MatrixError::ChromaNotNeutral => write!(f, "A chroma row doesn't add up to 0"),
+            MatrixError::Singular => write!(f, "The matrix isn't invertible"),
+        }
+    }
+}
+
+impl std::error::Error for MatrixError {}
+
+// How far row sums may be off, enough for coefficients rounded to 4 digits
+const ROW_SUM_TOLERANCE: f64 = 1e-3;
+
+impl ColorMatrix {
+    /// A custom matrix from its Y, Cb and Cr `rows`, each applied to
+    /// [R, G, B]. See [CustomMatrix] for the value ranges.
+    pub fn custom(rows: [[f64; 3]; 3]) -> Result<ColorMatrix, MatrixError> {
+        if rows.iter().flatten().any(|v| !v.is_finite()) {
+            return Err(MatrixError::NotFinite);
+        }
+        let sum = |row: [f64; 3]| row.iter().sum::<f64>();
+        if (sum(rows[0]) - 1.0).abs() > ROW_SUM_TOLERANCE {
+            return Err(MatrixError::LumaNotNormalized);
+        }
+        if sum(rows[1]).abs() > ROW_SUM_TOLERANCE || sum(rows[2]).abs() > ROW_SUM_TOLERANCE {
+            return Err(MatrixError::ChromaNotNeutral);
+        }
+        if invert(&rows).is_none() {
+            return Err(MatrixError::Singular);
+        }
+        Ok(ColorMatrix::Custom(Box::new(CustomMatrix { rows })))
+    }
+
+    /// The Y, Cb and Cr rows of the matrix, see [CustomMatrix]
+    pub fn rows(&self) -> [[f64; 3]; 3] {
+        // Luma weights (Kr, Kb) of the standard matrices. Kg = 1 - Kr - Kb.
+        let (kr, kb) = match self {
+            ColorMatrix::BT601 => (0.299, 0.114),
+            ColorMatrix::BT709 => (0.2126, 0.0722),
+            ColorMatrix::BT2020 => (0.2627, 0.0593),
+            ColorMatrix::Custom(matrix) => return matrix.rows,
+        };
+        let kg = 1.0 - kr - kb;
+        let cb_div = 2.0 * (1.0 - kb);
+        let cr_div = 2.0 * (1.0 - kr);
+        [
+            [kr, kg, kb],
+            [-kr / cb_div, -kg / cb_div, 0.5],
+            [0.5, -kg / cr_div, -kb / cr_div],
+        ]
+    }
+}
+
+// The inverse of `m`, None if it's singular
+fn invert(m: &[[f64; 3]; 3]) -> Option<[[f64; 3]; 3]> {
+    let cofactor = |r: usize, c: usize| {
+        let (r0, r1) = ((r + 1) % 3, (r + 2) % 3);
+        let (c0, c1) = ((c + 1) % 3, (c + 2) % 3);
+        m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+    };
+    let determinant: f64 = (0..3).map(|c| m[0][c] * cofactor(0, c)).sum();
+    if determinant.abs() < 1e-9 {
+        return None;
+    }
+
+    let mut inverse = [[0.0; 3]; 3];
+    for (r, row) in inverse.iter_mut().enumerate() {
+        for (c, value) in row.iter_mut().enumerate() {
+            *value = cofactor(c, r) / determinant;
+        }
+    }
+    Some(inverse)
+}
+
+/// The quantization range of a [YUVFrame]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorRange {
+    /// Y in [16, 235] and CbCr in [16, 240] ("video" range)
+    #[default]
+    Limited,
+    /// Y and CbCr in [0, 255] ("JPEG" range)
+    Full,
+}
+
+impl ColorRange {
+    // (luma scale, chroma scale, luma offset) applied to 8-bit full range values
+    fn scale(&self) -> (f64, f64, f64) {
+        match self {
+            ColorRange::Limited => (219.0 / 255.0, 224.0 / 255.0, 16.0),
+            ColorRange::Full => (1.0, 1.0, 0.0),
+        }
+    }
+}
+
+/// How many luma samples share a chroma sample in a [YUVFrame]. Chroma is
+/// always one interleaved CbCr plane, as in NV12 (4:2:0), NV16 (4:2:2) and
+/// NV24 (4:4:4).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ChromaSubsampling {
+    /// Half the width and height, the smallest, what video is encoded with
+    #[default]
+    Yuv420,
+    /// Half the width and full height
+    Yuv422,
+    /// No subsampling, which keeps colored text sharp
+    Yuv444,
+}
+
+impl ChromaSubsampling {
+    // log2 of the (horizontal, vertical) luma samples per chroma sample
+    fn shifts(&self) -> (u32, u32) {
+        match self {
+            ChromaSubsampling::Yuv420 => (1, 1),
+            ChromaSubsampling::Yuv422 => (1, 0),
+            ChromaSubsampling::Yuv444 => (0, 0),
+        }
+    }
+
+    /// The width and height of the chroma plane, in CbCr pairs, of a frame
+    /// `width` by `height`. Odd sizes are rounded up.
+    pub fn chroma_size(&self, width: usize, height: usize) -> (usize, usize) {
+        let (x, y) = self.shifts();
+        ((width + (1 << x) - 1) >> x, (height + (1 << y) - 1) >> y)
+    }
+}
+
+// Fractional bits of the fixed point coefficients
+const SHIFT: u32 = 16;
+
+// Fixed point forward transform for one matrix/range pair. Every row is
+// applied to [R, G, B] and offset by the row's bias.
+struct Coefficients {
+    y: [i32; 3],
+    cb: [i32; 3],
+    cr: [i32; 3],
+    y_offset: i32,
+}
+
+impl Coefficients {
+    fn new(matrix: &ColorMatrix, range: ColorRange) -> Self {
+        let [y, cb, cr] = matrix.rows();
+        let (y_scale, c_scale, y_offset) = range.scale();
+
+        let fixed = |row: [f64; 3], scale: f64| {
+            row.map(|v| (v * scale * (1 << SHIFT) as f64).round() as i32)
+        };
+
+        Coefficients {
+            y: fixed(y, y_scale),
+            cb: fixed(cb, c_scale),
+            cr: fixed(cr, c_scale),
+            y_offset: y_offset as i32,
+        }
+    }
+
+    // `rgb` holds the sum of `1 << extra` samples, which is folded
+    // into the final shift so averaging costs no extra rounding step.
+    fn apply(row: &[i32; 3], rgb: [i32; 3], offset: i32, extra: u32) -> u8 {
+        let shift = SHIFT + extra;
+        let sum = row[0] * rgb[0] + row[1] * rgb[1] + row[2] * rgb[2];
+        let value = (sum + (offset << shift) + (1 << (shift - 1))) >> shift;
+        value.clamp(0, 255) as u8
+    }
+}
+
+/// Converts a BGRA frame into a bi-planar [YUVFrame] with the given chroma
+/// subsampling.
+///
+/// Chroma is the average of each block of pixels sharing a sample, like 2x2
+/// for 4:2:0 (edge pixels are repeated for odd dimensions), and all values
+/// are rounded to nearest. The output is always top-down.
+pub fn convert_bgra_to_yuv(
+    frame: &BGRAFrame,
+    matrix: ColorMatrix,
+    range: ColorRange,
+    subsampling: ChromaSubsampling,
+) -> YUVFrame {
+    let width = frame.width.max(0) as usize;
+    let height = frame.height.max(0) as usize;
+    let stride = frame.data.len().checked_div(height).unwrap_or(0);
+    let coefficients = Coefficients::new(&matrix, range);
+
+    let rgb_at = |x: usize, y: usize| {
+        let i = frame.origin.buffer_row(y, height) * stride + x * 4;
+        [
+            frame.data[i + 2] as i32,
+            frame.data[i + 1] as i32,
+            frame.data[i] as i32,
+        ]
+    };
+
+    let mut luminance_bytes = vec![0; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            luminance_bytes[y * width + x] =
+                Coefficients::apply(&coefficients.y, rgb_at(x, y), coefficients.y_offset, 0);
+        }
+    }
+
+    let (x_shift, y_shift) = subsampling.shifts();
+    let (chroma_width, chroma_height) = subsampling.chroma_size(width, height);
+    let mut chrominance_bytes = vec![0; chroma_width * chroma_height * 2];
+    for cy in 0..chroma_height {
+        for cx in 0..chroma_width {
+            let mut sum = [0; 3];
+            for dy in 0..1 << y_shift {
+                for dx in 0..1 << x_shift {
+                    let x = ((cx << x_shift) + dx).min(width - 1);
+                    let y = ((cy << y_shift) + dy).min(height - 1);
+                    let rgb = rgb_at(x, y);
+                    for c in 0..3 {
+                        sum[c] += rgb[c];
+                    }
+                }
+            }
+
+            let extra = x_shift + y_shift;
+            let i = (cy * chroma_width + cx) * 2;
+            chrominance_bytes[i] = Coefficients::apply(&coefficients.cb, sum, 128, extra);
+            chrominance_bytes[i + 1] = Coefficients::apply(&coefficients.cr, sum, 128, extra);
+        }
+    }
+
+    YUVFrame {
+        display_time: frame.display_time,
+        width: width as i32,
+        height: height as i32,
+        luminance_bytes,
+        luminance_stride: width as i32,
+        chrominance_bytes,
+        chrominance_stride: (chroma_width * 2) as i32,
+        color_matrix: matrix,
+        color_range: range,
+        chroma_subsampling: subsampling,
+        origin: RowOrder::TopDown,
+        color_space: frame.color_space,
+    }
+}
+
+/// Converts a bi-planar [YUVFrame] back to top-down BGRA using the matrix,
+/// range, subsampling and row order the frame is tagged with.
+pub fn convert_yuv_to_bgra(frame: &YUVFrame) -> BGRAFrame {
+    let width = frame.width.max(0) as usize;
+    let height = frame.height.max(0) as usize;
+    // Custom matrices are checked to be invertible when they're made
+    let inverse = invert(&frame.color_matrix.rows()).expect("The color matrix isn't invertible");
+    let (y_scale, c_scale, y_offset) = frame.color_range.scale();
+    let (x_shift, y_shift) = frame.chroma_subsampling.shifts();
+    let (_, chroma_height) = frame.chroma_subsampling.chroma_size(width, height);
+
+    let mut data = vec![0; width * height * 4];
+    for y in 0..height {
+        for x in 0..width {
+            let luma_row = frame.origin.buffer_row(y, height);
+            let chroma_row = frame.origin.buffer_row(y >> y_shift, chroma_height);
+            let luma = frame.luminance_bytes[luma_row * frame.luminance_stride as usize + x] as f64;
+            let i = chroma_row * frame.chrominance_stride as usize + (x >> x_shift) * 2;
+            let cb = frame.chrominance_bytes[i] as f64;
+            let cr = frame.chrominance_bytes[i + 1] as f64;
+
+            let luma = (luma - y_offset) / y_scale;
+            let cb = (cb - 128.0) / c_scale;
+            let cr = (cr - 128.0) / c_scale;
+
+            let [r, g, b] = inverse.map(|row| row[0] * luma + row[1] * cb + row[2] * cr);
+
+            let o = (y * width + x) * 4;
+            data[o] = b.round().clamp(0.0, 255.0) as u8;
+            data[o + 1] = g.round().clamp(0.0, 255.0) as u8;
+            data[o + 2] = r.round().clamp(0.0, 255.0) as u8;
+            data[o + 3] = 255;
+        }
+    }
+
+    BGRAFrame {
+        display_time: frame.display_time,
+        width: width as i32,
+        height: height as i32,
+        data,
+        origin: RowOrder::TopDown,
+        color_space: frame.color_space,
+    }
+}
+
+// Every `stride`th pixel of every `stride`th row, top-down, each with the
+// chroma sample it uses in `frame`
+pub(crate) fn subsample_yuv(frame: &YUVFrame, stride: usize) -> YUVFrame {
+    let width = frame.width.max(0) as usize;
+    let height = frame.height.max(0) as usize;
+    let (x_shift, y_shift) = frame.chroma_subsampling.shifts();
+    let (_, chroma_height) = frame.chroma_subsampling.chroma_size(width, height);
+
+    let mut luminance_bytes = Vec::new();
+    let mut chrominance_bytes = Vec::new();
+    for y in (0..height).step_by(stride) {
+        let luma_row = frame.origin.buffer_row(y, height) * frame.luminance_stride as usize;
+        let chroma_row = frame.origin.buffer_row(y >> y_shift, chroma_height)
+            * frame.chrominance_stride as usize;
+        for x in (0..width).step_by(stride) {
+            luminance_bytes.push(frame.luminance_bytes[luma_row + x]);
+            let i = chroma_row + (x >> x_shift) * 2;
+            chrominance_bytes.extend_from_slice(&frame.chrominance_bytes[i..i + 2]);
+        }
+    }
+
+    let sampled_width = (width + stride - 1) / stride;
+    YUVFrame {
+        display_time: frame.display_time,
+        width: sampled_width as i32,
+        height: ((height + stride - 1) / stride) as i32,
+        luminance_bytes,
+        luminance_stride: sampled_width as i32,
+        chrominance_bytes,
+        chrominance_stride: (sampled_width * 2) as i32,
+        color_matrix: frame.color_matrix.clone(),
+        color_range: frame.color_range,
+        chroma_subsampling: ChromaSubsampling::Yuv444,
+        origin: RowOrder::TopDown,
+        color_space: frame.color_space,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::ColorSpace;
+
+    fn solid(b: u8, g: u8, r: u8) -> BGRAFrame {
+        BGRAFrame {
+            display_time: 0,
+            width: 2,
+            height: 2,
+            data: [b, g, r, 255].repeat(4),
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::Unknown,
+        }
+    }
+
+    fn yuv_of(rgb: [u8; 3], matrix: ColorMatrix, range: ColorRange) -> [u8; 3] {
+        let yuv = convert_bgra_to_yuv(
+            &solid(rgb[2], rgb[1], rgb[0]),
+            matrix,
+            range,
+            ChromaSubsampling::Yuv420,
+        );
+        [
+            yuv.luminance_bytes[0],
+            yuv.chrominance_bytes[0],
+            yuv.chrominance_bytes[1],
+        ]
+    }
+
+    #[test]
+    fn test_reference_vectors() {
+        use ColorMatrix::*;
+        use ColorRange::*;
+
+        #[rustfmt::skip]
+        let vectors = [
+            // (matrix, range, RGB, expected YCbCr)
+            (BT601, Limited, [255, 255, 255], [235, 128, 128]),
+            (BT601, Limited, [0, 0, 0], [16, 128, 128]),
+            (BT601, Limited, [255, 0, 0], [81, 90, 240]),
+            (BT601, Limited, [0, 255, 0], [145, 54, 34]),
+            (BT601, Limited, [0, 0, 255], [41, 240, 110]),
+            (BT709, Limited, [255, 255, 255], [235, 128, 128]),
+            (BT709, Limited, [255, 0, 0], [63, 102, 240]),
+            (BT709, Limited, [0, 255, 0], [173, 42, 26]),
+            (BT709, Limited, [0, 0, 255], [32, 240, 118]),
+            (BT601, Full, [255, 255, 255], [255, 128, 128]),
+            (BT601, Full, [255, 0, 0], [76, 85, 255]),
+            (BT601, Full, [128, 128, 128], [128, 128, 128]),
+            (BT709, Full, [255, 0, 0], [54, 99, 255]),
+            (BT709, Full, [0, 0, 255], [18, 255, 116]),
+            (BT2020, Limited, [255, 255, 255], [235, 128, 128]),
+            (BT2020, Limited, [255, 0, 0], [74, 97, 240]),
+            (BT2020, Limited, [0, 255, 0], [164, 47, 25]),
+            (BT2020, Limited, [0, 0, 255], [29, 240, 119]),
+            (BT2020, Full, [255, 0, 0], [67, 92, 255]),
+        ];
+
+        for (matrix, range, rgb, expected) in vectors {
+            assert_eq!(
+                yuv_of(rgb, matrix.clone(), range),
+                expected,
+                "{matrix:?} {range:?} {rgb:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_custom_matrix() {
+        // A custom copy of a preset encodes and decodes like it
+        let custom = ColorMatrix::custom(ColorMatrix::BT2020.rows()).unwrap();
+        for rgb in [[255, 0, 0], [12, 200, 90], [128, 128, 128]] {
+            assert_eq!(
+                yuv_of(rgb, custom.clone(), ColorRange::Limited),
+                yuv_of(rgb, ColorMatrix::BT2020, ColorRange::Limited)
+            );
+        }
+        let yuv = convert_bgra_to_yuv(
+            &solid(90, 200, 12),
+            custom.clone(),
+            ColorRange::Full,
+            ChromaSubsampling::Yuv420,
+        );
+        assert_eq!(yuv.color_matrix, custom);
+        for pixel in convert_yuv_to_bgra(&yuv).data.chunks_exact(4) {
+            for (value, expected) in pixel.iter().zip([90, 200, 12, 255]) {
+                assert!((*value as i32 - expected).abs() <= 2, "{pixel:?}");
+            }
+        }
+
+        let identity_rows = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        assert_eq!(
+            ColorMatrix::custom(identity_rows),
+            Err(MatrixError::ChromaNotNeutral)
+        );
+        let mut rows = ColorMatrix::BT709.rows();
+        rows[0][1] = f64::NAN;
+        assert_eq!(ColorMatrix::custom(rows), Err(MatrixError::NotFinite));
+        rows[0] = [0.5, 0.2, 0.2];
+        assert_eq!(
+            ColorMatrix::custom(rows),
+            Err(MatrixError::LumaNotNormalized)
+        );
+        let singular = [[0.3, 0.6, 0.1], [-0.1, -0.2, 0.3], [-0.2, -0.4, 0.6]];
+        assert_eq!(ColorMatrix::custom(singular), Err(MatrixError::Singular));
+    }
+
+    #[test]
+    fn test_chroma_is_box_averaged() {
+        // Left column red, right column blue: chroma is the mean of both
+        let frame = BGRAFrame {
+            display_time: 0,
+            width: 2,
+            height: 2,
+            data: vec![
+                0, 0, 255, 255, 255, 0, 0, 255, 0, 0, 255, 255, 255, 0, 0, 255,
+            ],
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::Unknown,
+        };
+        let yuv = convert_bgra_to_yuv(
+            &frame,
+            ColorMatrix::BT709,
+            ColorRange::Limited,
+            ChromaSubsampling::Yuv420,
+        );
+        let purple = yuv_of([128, 0, 128], ColorMatrix::BT709, ColorRange::Limited);
+        assert!((yuv.chrominance_bytes[0] as i32 - purple[1] as i32).abs() <= 1);
+        assert!((yuv.chrominance_bytes[1] as i32 - purple[2] as i32).abs() <= 1);
+    }
+
+    #[test]
+    fn test_chroma_subsampling() {
+        use ChromaSubsampling::*;
+
+        // 3x3 so 4:2:0 and 4:2:2 repeat the edge pixels
+        let colors: Vec<[u8; 3]> = (0..9u8)
+            .map(|i| [i * 28, 255 - i * 20, (i % 3) * 120])
+            .collect();
+        let frame = BGRAFrame {
+            display_time: 0,
+            width: 3,
+            height: 3,
+            data: colors
+                .iter()
+                .flat_map(|&[r, g, b]| [b, g, r, 255])
+                .collect(),
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::Unknown,
+        };
+
+        for (subsampling, size, block) in [
+            (Yuv420, (2, 2), (2, 2)),
+            (Yuv422, (2, 3), (2, 1)),
+            (Yuv444, (3, 3), (1, 1)),
+        ] {
+            let yuv =
+                convert_bgra_to_yuv(&frame, ColorMatrix::BT709, ColorRange::Full, subsampling);
+            assert_eq!(yuv.chroma_subsampling, subsampling);
+            assert_eq!(subsampling.chroma_size(3, 3), size);
+            assert_eq!(yuv.luminance_bytes.len(), 9);
+            assert_eq!(yuv.chrominance_bytes.len(), size.0 * size.1 * 2);
+            assert_eq!(yuv.chrominance_stride, size.0 as i32 * 2);
+
+            // Every sample is the chroma of its block's mean color
+            for cy in 0..size.1 {
+                for cx in 0..size.0 {
+                    let mut sum = [0u32; 3];
+                    for dy in 0..block.1 {
+                        for dx in 0..block.0 {
+                            let x = (cx * block.0 + dx).min(2);
+                            let y = (cy * block.1 + dy).min(2);
+                            for c in 0..3 {
+                                sum[c] += colors[y * 3 + x][c] as u32;
+                            }
+                        }
+                    }
+                    let mean = sum.map(|v| (v as f64 / (block.0 * block.1) as f64).round() as u8);
+                    let expected = yuv_of(mean, ColorMatrix::BT709, ColorRange::Full);
+                    let i = (cy * size.0 + cx) * 2;
+                    for (value, expected) in
+                        yuv.chrominance_bytes[i..i + 2].iter().zip(&expected[1..])
+                    {
+                        assert!(
+                            (*value as i32 - *expected as i32).abs() <= 1,
+                            "{subsampling:?} ({cx}, {cy})"
+                        );
+                    }
+                }
+            }
+        }
+
+        // Full chroma decodes every pixel's own color
+        let yuv = convert_bgra_to_yuv(&frame, ColorMatrix::BT709, ColorRange::Full, Yuv444);
+        for (pixel, original) in convert_yuv_to_bgra(&yuv)
+            .data
+            .chunks_exact(4)
+            .zip(frame.data.chunks_exact(4))
+        {
+            for (value, expected) in pixel.iter().zip(original) {
+                assert!((*value as i32 - *expected as i32).abs() <= 2, "{pixel:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trip_psnr() {
+        let (width, height) = (64, 48);
+        let mut data = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                data.extend_from_slice(&[(x * 4) as u8, (y * 5) as u8, ((x + y) * 2) as u8, 255]);
+            }
+        }
+        let frame = BGRAFrame {
+            display_time: 0,
+            width,
+            height,
+            data,
+            origin: RowOrder::TopDown,
+            color_space: ColorSpace::Unknown,
+        };
+
+        for matrix in [ColorMatrix::BT601, ColorMatrix::BT709] {
+            for range in [ColorRange::Limited, ColorRange::Full] {
+                let yuv =
+                    convert_bgra_to_yuv(&frame, matrix.clone(), range, ChromaSubsampling::Yuv420);
+                assert_eq!(yuv.luminance_bytes.len(), 64 * 48);
+                assert_eq!(yuv.chrominance_bytes.len(), 64 * 48 / 2);
+
+                let decoded = convert_yuv_to_bgra(&yuv);
+                let mse = frame
+                    .data
+                    .iter()
+                    .zip(decoded.data.iter())
+                    .map(|(a, b)| (*a as f64 - *b as f64).powi(2))
+                    .sum::<f64>()
+                    / frame.data.len() as f64;
+                let psnr = 10.0 * (255.0 * 255.0 / mse).log10();
+                assert!(psnr > 38.0, "{matrix:?} {range:?} psnr {psnr}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_bottom_up_input() {
+        // Red over blue, stored bottom row first
+        let mut data = [255, 0, 0, 255].repeat(2);
+        data.extend([0, 0, 255, 255].repeat(2));
+        let frame = BGRAFrame {
+            display_time: 0,
+            width: 2,
+            height: 2,
+            data,
+            origin: RowOrder::BottomUp,
+            color_space: ColorSpace::Unknown,
+        };
+
+        let yuv = convert_bgra_to_yuv(
+            &frame,
+            ColorMatrix::BT709,
+            ColorRange::Full,
+            ChromaSubsampling::Yuv420,
+        );
+        assert_eq!(yuv.origin, RowOrder::TopDown);
+        assert_eq!(yuv.luminance_bytes, vec![54, 54, 18, 18]);
+
+        let mut flipped = yuv.clone();
+        flipped.origin = RowOrder::BottomUp;
+        flipped.luminance_bytes = vec![18, 18, 54, 54];
+        assert_eq!(
+            convert_yuv_to_bgra(&flipped).data,
+            convert_yuv_to_bgra(&yuv).data
+        );
+    }
+}
@@ -1,15 +1,22 @@
 //! Cross Platform, Performant and High Quality screen recordings
 
+mod audio;
 pub mod capturer;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 pub mod frame;
 mod targets;
 mod utils;
 
 // Helper Methods
+pub use audio::{audio_devices, AudioDevice, AudioDeviceKind};
 pub use targets::*;
 pub use utils::has_permission;
 pub use utils::is_supported;
 pub use utils::request_permission;
+pub use utils::watch_permission;
+pub use utils::PermissionWatcher;
+pub use utils::PERMISSION_POLL_INTERVAL;
 
 #[cfg(target_os = "macos")]
 pub mod engine {
@@ -3,8 +3,7 @@
 
 use scap::{
     capturer::{Area, Capturer, Options, Point, Size},
-    frame::Frame
-    ,
+    frame::Frame,
 };
 use std::process;
 
@@ -115,6 +114,48 @@ fn main() {
                     frame.display_time - start_time
                 );
             }
+            Frame::Gray8(frame) => {
+                println!(
+                    "Recieved Gray8 frame of width {} and height {}",
+                    frame.width, frame.height
+                );
+            }
+            Frame::Delta(frame) => {
+                println!(
+                    "Recieved delta frame {} with {} changed regions",
+                    frame.sequence,
+                    frame.regions.len()
+                );
+            }
+            Frame::ScanlinePatch(frame) => {
+                println!(
+                    "Recieved scanline patch {} with {} changed bands",
+                    frame.sequence,
+                    frame.bands.len()
+                );
+            }
+            Frame::Regions(frame) => {
+                println!(
+                    "Recieved {} regions of frame {}",
+                    frame.parts.len(),
+                    frame.source_sequence
+                );
+            }
+            Frame::Marker(frame) => {
+                println!("Recieved marker {:?} at {:?}", frame.label, frame.timestamp);
+            }
+            Frame::Activity(frame) => {
+                println!(
+                    "Recieved activity of {} frames, {} changed, motion {:.3}",
+                    frame.frames, frame.changed_frames, frame.motion
+                );
+            }
+            Frame::Raw(frame) => {
+                println!(
+                    "Recieved {:?} frame of width {} and height {}",
+                    frame.format, frame.width, frame.height
+                );
+            }
         }
     }
 
@@ -0,0 +1,287 @@
+/// Which windows [get_filtered_targets](crate::get_filtered_targets) leaves
+/// out. The default skips everything that can't be captured in a useful way,
+/// and is what [get_all_targets](crate::get_all_targets) uses.
+///
+/// Only Windows lists windows that need filtering, the filter is ignored on
+/// other platforms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TargetFilter {
+    /// Windows cloaked by DWM: on other virtual desktops, suspended UWP apps
+    /// and the hidden duplicate of every UWP window
+    pub skip_cloaked: bool,
+    /// Tool windows like tooltips and floating palettes, unless they also
+    /// ask for a taskbar button
+    pub skip_tool_windows: bool,
+    /// ApplicationFrameHost frames that don't host a UWP app (anymore)
+    pub skip_empty_app_frames: bool,
+    /// Windows without area, or entirely outside the virtual desktop.
+    /// Minimized windows are kept.
+    pub skip_offscreen: bool,
+    /// Windows of the current process. Off by default, so apps can still
+    /// find their own windows to capture them.
+    pub skip_current_process: bool,
+}
+
+impl Default for TargetFilter {
+    fn default() -> Self {
+        TargetFilter {
+            skip_cloaked: true,
+            skip_tool_windows: true,
+            skip_empty_app_frames: true,
+            skip_offscreen: true,
+            skip_current_process: false,
+        }
+    }
+}
+
+pub(crate) const WS_EX_TOOLWINDOW: u32 = 0x0000_0080;
+pub(crate) const WS_EX_APPWINDOW: u32 = 0x0004_0000;
+
+// What the filter looks at for a top level window
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct WindowTraits {
+    pub visible: bool,
+    pub cloaked: bool,
+    pub ex_style: u32,
+    // An ApplicationFrameWindow, and whether it hosts a UWP CoreWindow
+    pub app_frame: Option<bool>,
+    pub width: i32,
+    pub height: i32,
+    pub minimized: bool,
+    pub on_virtual_desktop: bool,
+    pub current_process: bool,
+}
+
+// Whether a window should be listed as a target
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+pub(crate) fn is_listed(window: &WindowTraits, filter: &TargetFilter) -> bool {
+    // Hidden windows can never be captured
+    if !window.visible {
+        return false;
+    }
+
+    if filter.skip_cloaked && window.cloaked {
+        return false;
+    }
+
+    let tool_window = window.ex_style & WS_EX_TOOLWINDOW != 0;
+    let app_window = window.ex_style & WS_EX_APPWINDOW != 0;
+    if filter.skip_tool_windows && tool_window && !app_window {
+        return false;
+    }
+
+    if filter.skip_empty_app_frames && window.app_frame == Some(false) {
+        return false;
+    }
+
+    let offscreen = window.width <= 0 || window.height <= 0 || !window.on_virtual_desktop;
+    if filter.skip_offscreen && offscreen && !window.minimized {
+        return false;
+    }
+
+    !(filter.skip_current_process && window.current_process)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An ordinary application window
+    const APP: WindowTraits = WindowTraits {
+        visible: true,
+        cloaked: false,
+        // WS_EX_WINDOWEDGE | WS_EX_ACCEPTFILES
+        ex_style: 0x0000_0110,
+        app_frame: None,
+        width: 1280,
+        height: 720,
+        minimized: false,
+        on_virtual_desktop: true,
+        current_process: false,
+    };
+
+    #[test]
+    fn test_default_filter() {
+        let fixtures = [
+            ("notepad", APP, true),
+            (
+                "hidden electron helper",
+                WindowTraits {
+                    visible: false,
+                    ..APP
+                },
+                false,
+            ),
+            (
+                "settings, cloaked CoreWindow duplicate",
+                WindowTraits {
+                    cloaked: true,
+                    // WS_EX_NOREDIRECTIONBITMAP | WS_EX_WINDOWEDGE
+                    ex_style: 0x0020_0100,
+                    ..APP
+                },
+                false,
+            ),
+            (
+                "settings, ApplicationFrameWindow",
+                WindowTraits {
+                    // WS_EX_NOREDIRECTIONBITMAP | WS_EX_WINDOWEDGE
+                    ex_style: 0x0020_0100,
+                    app_frame: Some(true),
+                    ..APP
+                },
+                true,
+            ),
+            (
+                "suspended ApplicationFrameWindow shell",
+                WindowTraits {
+                    app_frame: Some(false),
+                    ..APP
+                },
+                false,
+            ),
+            (
+                "tooltip",
+                WindowTraits {
+                    // WS_EX_TOOLWINDOW | WS_EX_TOPMOST | WS_EX_LAYERED
+                    ex_style: 0x0008_0088,
+                    width: 120,
+                    height: 24,
+                    ..APP
+                },
+                false,
+            ),
+            (
+                "tool window with a taskbar button",
+                WindowTraits {
+                    // WS_EX_TOOLWINDOW | WS_EX_APPWINDOW
+                    ex_style: 0x0004_0080,
+                    ..APP
+                },
+                true,
+            ),
+            (
+                "zero area message sink",
+                WindowTraits {
+                    width: 0,
+                    height: 0,
+                    ..APP
+                },
+                false,
+            ),
+            (
+                "parked outside the desktop",
+                WindowTraits {
+                    on_virtual_desktop: false,
+                    ..APP
+                },
+                false,
+            ),
+            (
+                "minimized",
+                WindowTraits {
+                    minimized: true,
+                    on_virtual_desktop: false,
+                    width: 160,
+                    height: 28,
+                    ..APP
+                },
+                true,
+            ),
+            (
+                "own window",
+                WindowTraits {
+                    current_process: true,
+                    ..APP
+                },
+                true,
+            ),
+        ];
+
+        for (name, window, listed) in fixtures {
+            assert_eq!(
+                is_listed(&window, &TargetFilter::default()),
+                listed,
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_filters_can_be_disabled() {
+        let cloaked = WindowTraits {
+            cloaked: true,
+            ..APP
+        };
+        let tooltip = WindowTraits {
+            ex_style: WS_EX_TOOLWINDOW,
+            ..APP
+        };
+        let shell = WindowTraits {
+            app_frame: Some(false),
+            ..APP
+        };
+        let empty = WindowTraits { width: 0, ..APP };
+
+        let cases = [
+            (
+                cloaked,
+                TargetFilter {
+                    skip_cloaked: false,
+                    ..Default::default()
+                },
+            ),
+            (
+                tooltip,
+                TargetFilter {
+                    skip_tool_windows: false,
+                    ..Default::default()
+                },
+            ),
+            (
+                shell,
+                TargetFilter {
+                    skip_empty_app_frames: false,
+                    ..Default::default()
+                },
+            ),
+            (
+                empty,
+                TargetFilter {
+                    skip_offscreen: false,
+                    ..Default::default()
+                },
+            ),
+        ];
+
+        for (window, filter) in cases {
+            assert!(!is_listed(&window, &TargetFilter::default()), "{window:?}");
+            assert!(is_listed(&window, &filter), "{window:?}");
+        }
+
+        // Invisible windows are never listed
+        let hidden = WindowTraits {
+            visible: false,
+            ..APP
+        };
+        let nothing_skipped = TargetFilter {
+            skip_cloaked: false,
+            skip_tool_windows: false,
+            skip_empty_app_frames: false,
+            skip_offscreen: false,
+            skip_current_process: false,
+        };
+        assert!(!is_listed(&hidden, &nothing_skipped));
+
+        // Windows of the current process are only skipped when asked to
+        let own = WindowTraits {
+            current_process: true,
+            ..APP
+        };
+        let own_skipped = TargetFilter {
+            skip_current_process: true,
+            ..Default::default()
+        };
+        assert!(!is_listed(&own, &own_skipped));
+    }
+}
@@ -1,36 +1,83 @@
+use std::collections::HashMap;
+
 use cocoa::appkit::{NSApp, NSScreen};
-use cocoa::base::{id, nil};
+use cocoa::base::{id, nil, BOOL, NO};
 use cocoa::foundation::{NSRect, NSString, NSUInteger};
 use core_graphics_helmer_fork::display::{CGDirectDisplayID, CGDisplay, CGMainDisplayID};
-use core_graphics_helmer_fork::window::CGWindowID;
-use objc::{msg_send, sel, sel_impl};
+use core_graphics_helmer_fork::geometry::CGRect;
+use core_graphics_helmer_fork::window::{
+    copy_window_info, kCGNullWindowID, kCGWindowAlpha, kCGWindowListExcludeDesktopElements,
+    kCGWindowListOptionOnScreenOnly, kCGWindowNumber, CGWindowID,
+};
+use objc::{class, msg_send, sel, sel_impl};
 use screencapturekit::sc_shareable_content::SCShareableContent;
 
-use super::{Display, Target};
+use super::occlusion::{get_largest_overlap, visible_fractions, Rect};
+use super::system_bar::get_edge_strip;
+use super::{Display, ScreenEdge, StackedWindow, SystemBar, Target, Window};
+use crate::capturer::engine::mac::{
+    apple_sys::{
+        CFDictionaryGetValue, CFDictionaryRef, CFNumberGetValue, CFNumberType,
+        CFNumberType_kCFNumberFloat64Type, CFNumberType_kCFNumberSInt64Type,
+    },
+    get_description_bounds,
+};
+use crate::capturer::Size;
 
-fn get_display_name(display_id: CGDirectDisplayID) -> String {
+// The NSScreen of a display and its position in the screen list, where the
+// first one has the menu bar
+fn get_screen(display_id: CGDirectDisplayID) -> Option<(id, u64)> {
     unsafe {
         // Get all screens
         let screens: id = NSScreen::screens(nil);
         let count: u64 = msg_send![screens, count];
 
-        for i in 0..count {
+        (0..count).find_map(|i| {
             let screen: id = msg_send![screens, objectAtIndex: i];
             let device_description: id = msg_send![screen, deviceDescription];
             let display_id_number: id = msg_send![device_description, objectForKey: NSString::alloc(nil).init_str("NSScreenNumber")];
             let display_id_number: u32 = msg_send![display_id_number, unsignedIntValue];
+            (display_id_number == display_id).then_some((screen, i))
+        })
+    }
+}
+
+fn get_display_name(display_id: CGDirectDisplayID) -> String {
+    match get_screen(display_id) {
+        Some((screen, _)) => unsafe {
+            let localized_name: id = msg_send![screen, localizedName];
+            let name: *const i8 = msg_send![localized_name, UTF8String];
+            std::ffi::CStr::from_ptr(name)
+                .to_string_lossy()
+                .into_owned()
+        },
+        None => format!("Unknown Display {}", display_id),
+    }
+}
 
-            if display_id_number == display_id {
-                let localized_name: id = msg_send![screen, localizedName];
-                let name: *const i8 = msg_send![localized_name, UTF8String];
-                return std::ffi::CStr::from_ptr(name)
-                    .to_string_lossy()
-                    .into_owned();
-            }
+// The visible frame leaves out the menu bar at the top, and the Dock at
+// whichever edge it's on
+pub fn get_menu_bar(display: &Display) -> Option<SystemBar> {
+    let (screen, index) = get_screen(display.id)?;
+    let (frame, visible): (NSRect, NSRect) = unsafe {
+        let separate_spaces: BOOL = msg_send![class!(NSScreen), screensHaveSeparateSpaces];
+        if separate_spaces == NO && index > 0 {
+            return None;
         }
+        (msg_send![screen, frame], msg_send![screen, visibleFrame])
+    };
 
-        format!("Unknown Display {}", display_id)
-    }
+    // Frames are bottom up, the inset at the top is the menu bar
+    let thickness = (frame.origin.y + frame.size.height) - (visible.origin.y + visible.size.height);
+    let size = Size {
+        width: frame.size.width,
+        height: frame.size.height,
+    };
+    Some(SystemBar {
+        area: get_edge_strip(&size, ScreenEdge::Top, thickness),
+        edge: ScreenEdge::Top,
+        auto_hide: thickness <= 0.0,
+    })
 }
 
 pub fn get_all_targets() -> Vec<Target> {
@@ -48,6 +95,8 @@ pub fn get_all_targets() -> Vec<Target> {
             id,
             title,
             raw_handle,
+            is_virtual: false,
+            span: None,
         });
 
         targets.push(target);
@@ -72,6 +121,162 @@ pub fn get_all_targets() -> Vec<Target> {
     targets
 }
 
+// Reads a number from a window list entry
+fn get_description_number<T: Default>(
+    description: CFDictionaryRef,
+    key: *const std::ffi::c_void,
+    number_type: CFNumberType,
+) -> Option<T> {
+    unsafe {
+        let number = CFDictionaryGetValue(description, key);
+        let mut value = T::default();
+        (!number.is_null()
+            && CFNumberGetValue(number as _, number_type, &mut value as *mut T as _) != 0)
+            .then_some(value)
+    }
+}
+
+pub fn get_stacked_windows() -> Vec<StackedWindow> {
+    let mut capturable: HashMap<CGWindowID, String> = SCShareableContent::current()
+        .windows
+        .into_iter()
+        .filter_map(|window| Some((window.window_id, window.title?)))
+        .collect();
+
+    // The window list is ordered front to back
+    let Some(info) = copy_window_info(
+        kCGWindowListOptionOnScreenOnly | kCGWindowListExcludeDesktopElements,
+        kCGNullWindowID,
+    ) else {
+        return Vec::new();
+    };
+
+    let mut windows = Vec::new();
+    let mut rects = Vec::new();
+    for description in info.get_all_values() {
+        let description = description as CFDictionaryRef;
+        let id: Option<i64> = get_description_number(
+            description,
+            unsafe { kCGWindowNumber } as _,
+            CFNumberType_kCFNumberSInt64Type,
+        );
+        let alpha: f64 = get_description_number(
+            description,
+            unsafe { kCGWindowAlpha } as _,
+            CFNumberType_kCFNumberFloat64Type,
+        )
+        .unwrap_or(1.0);
+
+        // Fully transparent windows, like some overlays, hide nothing
+        let rect = get_description_bounds(description)
+            .filter(|_| alpha > 0.0)
+            .map(to_rect);
+        windows.push(id.map(|id| id as CGWindowID));
+        rects.push(rect);
+    }
+
+    let fractions = visible_fractions(&rects);
+    let mut stacked: Vec<(CGWindowID, String, f64)> = windows
+        .into_iter()
+        .zip(fractions)
+        .filter_map(|(id, fraction)| {
+            let id = id?;
+            Some((id, capturable.remove(&id)?, fraction))
+        })
+        .collect();
+
+    // Minimized and hidden windows aren't in the on-screen list
+    let mut hidden: Vec<_> = capturable.into_iter().collect();
+    hidden.sort_by_key(|(id, _)| *id);
+    stacked.extend(hidden.into_iter().map(|(id, title)| (id, title, 0.0)));
+
+    stacked
+        .into_iter()
+        .enumerate()
+        .map(|(z_index, (id, title, visible_fraction))| StackedWindow {
+            window: super::Window {
+                id,
+                title,
+                raw_handle: id,
+            },
+            z_index,
+            visible_fraction,
+            occluded: visible_fraction == 0.0,
+        })
+        .collect()
+}
+
+fn to_rect(bounds: CGRect) -> Rect {
+    Rect {
+        left: bounds.origin.x,
+        top: bounds.origin.y,
+        right: bounds.origin.x + bounds.size.width,
+        bottom: bounds.origin.y + bounds.size.height,
+    }
+}
+
+pub fn get_windows_on_display(display_id: CGDirectDisplayID) -> Vec<Window> {
+    let displays = CGDisplay::active_displays().unwrap_or_default();
+    let rects: Vec<Rect> = displays
+        .iter()
+        .map(|display| to_rect(CGDisplay::new(*display).bounds()))
+        .collect();
+
+    // Window and display bounds are both in points of the global display space
+    let Some(info) = copy_window_info(kCGWindowListExcludeDesktopElements, kCGNullWindowID) else {
+        return Vec::new();
+    };
+    let mut on_display = HashMap::new();
+    for description in info.get_all_values() {
+        let description = description as CFDictionaryRef;
+        let id: Option<i64> = get_description_number(
+            description,
+            unsafe { kCGWindowNumber } as _,
+            CFNumberType_kCFNumberSInt64Type,
+        );
+        let overlap = get_description_bounds(description)
+            .and_then(|bounds| get_largest_overlap(&to_rect(bounds), &rects));
+        if let (Some(id), Some(i)) = (id, overlap) {
+            on_display.insert(id as CGWindowID, displays[i] == display_id);
+        }
+    }
+
+    SCShareableContent::current()
+        .windows
+        .into_iter()
+        .filter(|window| on_display.get(&window.window_id) == Some(&true))
+        .filter_map(|window| {
+            Some(Window {
+                id: window.window_id,
+                title: window.title?,
+                raw_handle: window.window_id,
+            })
+        })
+        .collect()
+}
+
+// Every display with the name of its monitor and its top left corner on
+// the desktop
+pub fn get_named_displays() -> Vec<(Display, String, [f64; 2])> {
+    CGDisplay::active_displays()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|id| {
+            let raw_handle = CGDisplay::new(id);
+            let origin = raw_handle.bounds().origin;
+            let title = get_display_name(id);
+            let display = Display {
+                id,
+                title: title.clone(),
+                raw_handle,
+                is_virtual: false,
+                span: None,
+            };
+            (display, title, [origin.x, origin.y])
+        })
+        .collect()
+}
+
 pub fn get_main_display() -> Display {
     let id = unsafe { CGMainDisplayID() };
     let title = get_display_name(id);
@@ -80,6 +285,8 @@ pub fn get_main_display() -> Display {
         id,
         title,
         raw_handle: CGDisplay::new(id),
+        is_virtual: false,
+        span: None,
     }
 }
 
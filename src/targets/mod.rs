@@ -7,6 +7,23 @@ mod win;
 #[cfg(target_os = "linux")]
 mod linux;
 
+#[cfg(any(target_os = "windows", target_os = "macos", test))]
+mod occlusion;
+
+#[cfg(any(target_os = "windows", test))]
+mod span;
+
+#[cfg(any(target_os = "windows", target_os = "macos", test))]
+mod names;
+
+#[cfg(any(target_os = "windows", target_os = "macos", test))]
+mod system_bar;
+
+mod filter;
+pub use filter::TargetFilter;
+
+use crate::capturer::Area;
+
 #[derive(Debug, Clone)]
 pub struct Window {
     pub id: u32,
@@ -29,6 +46,69 @@ pub struct Display {
 
     #[cfg(target_os = "macos")]
     pub raw_handle: core_graphics_helmer_fork::display::CGDisplay,
+
+    /// Backed by virtual display software or a virtual machine's video
+    /// adapter instead of a physical output. These are captured like any
+    /// other display. Only detected on Windows, always false elsewhere.
+    pub is_virtual: bool,
+
+    /// The monitors behind this display if the GPU spans several of them as
+    /// one, like NVIDIA Surround or AMD Eyefinity. None for single monitors
+    /// and whenever the layout is unknown, in which case the whole display
+    /// is captured as usual.
+    pub span: Option<SpanLayout>,
+}
+
+impl Display {
+    /// Finds the connected display with `name`, as listed by
+    /// [get_display_names], ignoring case. Names are more readable than ids
+    /// in config files, and stay the same while ids change across reboots.
+    /// A name that several displays share only matches with its number. None
+    /// on Linux.
+    pub fn by_name(name: &str) -> Option<Display> {
+        get_display_names()
+            .into_iter()
+            .find(|(_, display_name)| display_name.to_lowercase() == name.to_lowercase())
+            .map(|(display, _)| display)
+    }
+}
+
+/// The monitors of a spanned display, see [Display::span]
+///
+/// Only guessed from the resolution on Windows, for three to five monitors
+/// side by side, and never detected elsewhere.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpanLayout {
+    /// The part of the display shown on each monitor, left to right, in
+    /// pixels of the display. Pass one as [Options::crop_area](crate::capturer::Options::crop_area)
+    /// to capture a single monitor.
+    pub segments: Vec<Area>,
+    /// Pixels hidden behind the bezels between two monitors by bezel
+    /// correction, 0 without it
+    pub bezel: u32,
+}
+
+/// The taskbar or menu bar of a display, see [get_system_bar]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SystemBar {
+    /// The part of the display the bar covers, in points of the display. Pass
+    /// it as [Options::crop_area](crate::capturer::Options::crop_area) to
+    /// capture just the bar, like notifications and tray icons. Zero thick
+    /// at `edge` while the bar is auto-hidden.
+    pub area: Area,
+    pub edge: ScreenEdge,
+    /// The bar hides until the pointer touches `edge`, and covers the
+    /// windows under it while shown instead of keeping them out of `area`
+    pub auto_hide: bool,
+}
+
+/// An edge of a display
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenEdge {
+    Top,
+    Bottom,
+    Left,
+    Right,
 }
 
 #[derive(Debug, Clone)]
@@ -37,16 +117,150 @@ pub enum Target {
     Display(Display),
 }
 
+/// How a window's app handles the DPI of the display it's on, see [get_dpi_awareness]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DpiAwareness {
+    /// Drawn at 96 DPI and bitmap-stretched by Windows to the DPI of the
+    /// display, so on scaled displays it looks blurry on screen and in the
+    /// frames alike. Crop areas are scaled by the display's DPI, matching
+    /// what is shown, and the frames have the stretched size.
+    Unaware,
+    /// Drawn at the DPI of the primary display when the user signed in, and
+    /// stretched like [DpiAwareness::Unaware] on displays of another DPI
+    SystemAware,
+    /// Drawn at the DPI of the display it's on, sharp and unscaled. Crop
+    /// areas are scaled by that DPI.
+    PerMonitorAware,
+}
+
+/// A window with its place in the on-screen stacking order, see [get_stacked_windows]
+#[derive(Debug, Clone)]
+pub struct StackedWindow {
+    pub window: Window,
+    /// Position among the returned windows, 0 is the frontmost
+    pub z_index: usize,
+    /// The part of the window not covered by windows above it, from 0 to 1.
+    /// This is an approximation that treats every window as an opaque
+    /// rectangle, ignoring transparency, rounded corners and shadows.
+    pub visible_fraction: f64,
+    /// Nothing of the window is visible. Also true for minimized windows and,
+    /// on Windows, windows cloaked on other virtual desktops.
+    pub occluded: bool,
+}
+
 /// Returns a list of targets that can be captured
 pub fn get_all_targets() -> Vec<Target> {
+    get_filtered_targets(&TargetFilter::default())
+}
+
+/// Returns a list of targets that can be captured, leaving out the windows
+/// `filter` skips. Displays are always listed.
+pub fn get_filtered_targets(filter: &TargetFilter) -> Vec<Target> {
     #[cfg(target_os = "macos")]
-    return mac::get_all_targets();
+    return {
+        let _ = filter;
+        mac::get_all_targets()
+    };
 
     #[cfg(target_os = "windows")]
-    return win::get_all_targets();
+    return win::get_all_targets(filter);
 
     #[cfg(target_os = "linux")]
-    return linux::get_all_targets();
+    return {
+        let _ = filter;
+        linux::get_all_targets()
+    };
+}
+
+/// Returns the windows that can be captured, frontmost first, with an
+/// estimate of how much of each is visible. Every window on screen counts
+/// as an occluder, including ones that can't be captured like the taskbar.
+///
+/// The estimate is rectangle based to stay cheap with a couple hundred
+/// windows. Always empty on Linux, where windows are picked through the portal.
+pub fn get_stacked_windows() -> Vec<StackedWindow> {
+    #[cfg(target_os = "macos")]
+    return mac::get_stacked_windows();
+
+    #[cfg(target_os = "windows")]
+    return win::get_stacked_windows();
+
+    #[cfg(target_os = "linux")]
+    return Vec::new();
+}
+
+/// Returns the windows that can be captured and are mostly on the display
+/// with `display_id`, for pickers that list windows per display. Windows
+/// spanning several displays are listed for the one they overlap the most.
+///
+/// Minimized windows belong to the display they're restored to on Windows.
+/// Windows outside of every display aren't listed. Always empty on Linux,
+/// where windows are picked through the portal.
+pub fn get_windows_on_display(display_id: u32) -> Vec<Window> {
+    #[cfg(target_os = "macos")]
+    return mac::get_windows_on_display(display_id);
+
+    #[cfg(target_os = "windows")]
+    return win::get_windows_on_display(display_id);
+
+    #[cfg(target_os = "linux")]
+    return {
+        let _ = display_id;
+        Vec::new()
+    };
+}
+
+/// Returns every connected display with the name of its monitor, like "DELL
+/// U2720Q", for [Display::by_name]. Identical monitors are numbered left to
+/// right, then top to bottom, as in "DELL U2720Q (2)".
+///
+/// Windows reads the name from the monitor's EDID and falls back to its
+/// driver's, macOS uses the localized name it shows in its settings. Always
+/// empty on Linux, where displays are picked through the portal.
+pub fn get_display_names() -> Vec<(Display, String)> {
+    #[cfg(target_os = "macos")]
+    let displays = mac::get_named_displays();
+
+    #[cfg(target_os = "windows")]
+    let displays = win::get_named_displays();
+
+    #[cfg(target_os = "linux")]
+    return Vec::new();
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let named: Vec<(String, [f64; 2])> = displays
+            .iter()
+            .map(|(_, name, origin)| (name.clone(), *origin))
+            .collect();
+        displays
+            .into_iter()
+            .zip(names::number_duplicates(&named))
+            .map(|((display, _, _), name)| (display, name))
+            .collect()
+    }
+}
+
+/// Returns where the taskbar is on `display` on Windows, or the menu bar on
+/// macOS, as the difference between the display and its work area: the part
+/// maximized windows fill.
+///
+/// Auto-hiding bars take no space out of the work area, so their area is
+/// zero thick at their edge. None for displays without a bar, like ones the
+/// taskbar isn't shown on or secondary displays when macOS doesn't give each
+/// display its own Spaces, and always on Linux.
+pub fn get_system_bar(display: &Display) -> Option<SystemBar> {
+    #[cfg(target_os = "macos")]
+    return mac::get_menu_bar(display);
+
+    #[cfg(target_os = "windows")]
+    return win::get_taskbar(display);
+
+    #[cfg(target_os = "linux")]
+    return {
+        let _ = display;
+        None
+    };
 }
 
 pub fn get_scale_factor(target: &Target) -> f64 {
@@ -60,6 +274,63 @@ pub fn get_scale_factor(target: &Target) -> f64 {
     return 1.0;
 }
 
+/// Returns the title `target` has now. Windows can retitle themselves after
+/// they're listed, like browsers switching tabs, while display names stay
+/// the same. None once the window is closed.
+///
+/// On macOS window titles need the screen recording permission.
+pub fn get_target_title(target: &Target) -> Option<String> {
+    let window = match target {
+        Target::Display(display) => return Some(display.title.clone()),
+        Target::Window(window) => window,
+    };
+
+    #[cfg(target_os = "macos")]
+    return crate::capturer::engine::mac::get_window_title(window.raw_handle);
+
+    #[cfg(target_os = "windows")]
+    return win::get_current_window_title(window.raw_handle);
+
+    #[cfg(target_os = "linux")]
+    return Some(window.title.clone());
+}
+
+/// Returns how the app of a window target scales it for its display, which
+/// decides how crop areas map to the frames, see [DpiAwareness]. None for
+/// displays, for windows whose awareness can't be read, and outside Windows,
+/// where windows are always captured as shown.
+pub fn get_dpi_awareness(target: &Target) -> Option<DpiAwareness> {
+    let Target::Window(window) = target else {
+        return None;
+    };
+
+    #[cfg(target_os = "windows")]
+    return win::get_dpi_awareness(window.raw_handle);
+
+    #[cfg(not(target_os = "windows"))]
+    return {
+        let _ = window;
+        None
+    };
+}
+
+/// Returns whether `window` is on the virtual desktop being shown. Windows
+/// cloaks windows on other desktops, which are then neither listed by
+/// default nor captured: no new frames arrive until their desktop is shown
+/// again, see [CapturerEvent::WindowDesktopChanged](crate::capturer::CapturerEvent::WindowDesktopChanged).
+///
+/// None when Windows can't tell, and on macOS and Linux.
+pub fn is_on_current_desktop(window: &Window) -> Option<bool> {
+    #[cfg(target_os = "windows")]
+    return win::is_on_current_desktop(window.raw_handle);
+
+    #[cfg(not(target_os = "windows"))]
+    return {
+        let _ = window;
+        None
+    };
+}
+
 pub fn get_main_display() -> Display {
     #[cfg(target_os = "macos")]
     return mac::get_main_display();
@@ -0,0 +1,40 @@
+/// Numbers the names several displays share with " (1)", " (2)" and so on,
+/// left to right and then top to bottom by where the displays are on the
+/// desktop, so identical monitors can be told apart. Unique names stay as
+/// they are.
+pub(crate) fn number_duplicates(displays: &[(String, [f64; 2])]) -> Vec<String> {
+    let mut names: Vec<String> = displays.iter().map(|(name, _)| name.clone()).collect();
+    for (i, (name, _)) in displays.iter().enumerate() {
+        let mut shared: Vec<usize> = (0..displays.len())
+            .filter(|&j| displays[j].0 == *name)
+            .collect();
+        if shared.len() < 2 {
+            continue;
+        }
+        shared.sort_by(|&a, &b| {
+            let ([ax, ay], [bx, by]) = (displays[a].1, displays[b].1);
+            ax.total_cmp(&bx).then(ay.total_cmp(&by))
+        });
+        let number = shared.iter().position(|&j| j == i).unwrap() + 1;
+        names[i] = format!("{name} ({number})");
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_number_duplicates() {
+        let displays = [
+            ("DELL U2720Q".to_string(), [2560.0, 0.0]),
+            ("Built-in Display".to_string(), [0.0, 0.0]),
+            ("DELL U2720Q".to_string(), [-3840.0, 0.0]),
+        ];
+        assert_eq!(
+            number_duplicates(&displays),
+            ["DELL U2720Q (2)", "Built-in Display", "DELL U2720Q (1)"]
+        );
+    }
+}
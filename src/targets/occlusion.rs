@@ -0,0 +1,186 @@
+// Rectangle based occlusion estimates for window stacking, and placement
+// of windows on displays
+
+/// An axis aligned rectangle in screen coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Rect {
+    pub left: f64,
+    pub top: f64,
+    pub right: f64,
+    pub bottom: f64,
+}
+
+impl Rect {
+    fn area(&self) -> f64 {
+        (self.right - self.left).max(0.0) * (self.bottom - self.top).max(0.0)
+    }
+
+    fn intersection(&self, other: &Rect) -> Option<Rect> {
+        let rect = Rect {
+            left: self.left.max(other.left),
+            top: self.top.max(other.top),
+            right: self.right.min(other.right),
+            bottom: self.bottom.min(other.bottom),
+        };
+        (rect.left < rect.right && rect.top < rect.bottom).then_some(rect)
+    }
+}
+
+// Area covered by the union of `rects`. Sweeps the distinct x coordinates and
+// merges the y intervals of every strip, O(n² log n) for n rectangles.
+fn union_area(rects: &[Rect]) -> f64 {
+    let mut xs: Vec<f64> = rects.iter().flat_map(|r| [r.left, r.right]).collect();
+    xs.sort_by(f64::total_cmp);
+    xs.dedup();
+
+    let mut area = 0.0;
+    let mut intervals = Vec::with_capacity(rects.len());
+    for strip in xs.windows(2) {
+        let (left, right) = (strip[0], strip[1]);
+
+        intervals.clear();
+        intervals.extend(
+            rects
+                .iter()
+                .filter(|r| r.left <= left && r.right >= right)
+                .map(|r| (r.top, r.bottom)),
+        );
+        intervals.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut covered = 0.0;
+        let mut current: Option<(f64, f64)> = None;
+        for &(top, bottom) in &intervals {
+            current = match current {
+                Some((start, end)) if top <= end => Some((start, end.max(bottom))),
+                Some((start, end)) => {
+                    covered += end - start;
+                    Some((top, bottom))
+                }
+                None => Some((top, bottom)),
+            };
+        }
+        if let Some((start, end)) = current {
+            covered += end - start;
+        }
+
+        area += covered * (right - left);
+    }
+
+    area
+}
+
+/// The fraction of each rectangle that's not covered by the rectangles before
+/// it, for rectangles ordered front to back. `None` entries are windows that
+/// aren't shown at all: they're fully hidden and cover nothing.
+pub(crate) fn visible_fractions(rects: &[Option<Rect>]) -> Vec<f64> {
+    let mut above: Vec<Rect> = Vec::with_capacity(rects.len());
+    let mut clipped = Vec::with_capacity(rects.len());
+
+    rects
+        .iter()
+        .map(|rect| {
+            let Some(rect) = rect else {
+                return 0.0;
+            };
+            let area = rect.area();
+            if area <= 0.0 {
+                return 0.0;
+            }
+
+            clipped.clear();
+            clipped.extend(above.iter().filter_map(|a| a.intersection(rect)));
+            above.push(*rect);
+
+            (1.0 - union_area(&clipped) / area).clamp(0.0, 1.0)
+        })
+        .collect()
+}
+
+// The display `rect` overlaps the most, as an index into `displays`, or
+// None if it's outside of all of them. Ties go to the first display.
+pub(crate) fn get_largest_overlap(rect: &Rect, displays: &[Rect]) -> Option<usize> {
+    displays
+        .iter()
+        .enumerate()
+        .filter_map(|(i, display)| Some((i, display.intersection(rect)?.area())))
+        .fold(None, |best: Option<(usize, f64)>, (i, area)| match best {
+            Some((_, most)) if most >= area => best,
+            _ => Some((i, area)),
+        })
+        .map(|(i, _)| i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(left: f64, top: f64, width: f64, height: f64) -> Option<Rect> {
+        Some(Rect {
+            left,
+            top,
+            right: left + width,
+            bottom: top + height,
+        })
+    }
+
+    #[test]
+    fn test_visible_fractions() {
+        let fractions = visible_fractions(&[
+            rect(0.0, 0.0, 10.0, 10.0),
+            // Left half covered by the first window
+            rect(5.0, 0.0, 10.0, 10.0),
+            // Fully covered by the first two
+            rect(1.0, 1.0, 12.0, 8.0),
+            None,
+            // Overlaps of the windows above are only subtracted once
+            rect(0.0, 0.0, 20.0, 20.0),
+            rect(100.0, 100.0, 10.0, 10.0),
+        ]);
+
+        assert_eq!(fractions[0], 1.0);
+        assert_eq!(fractions[1], 0.5);
+        assert_eq!(fractions[2], 0.0);
+        assert_eq!(fractions[3], 0.0);
+        assert_eq!(fractions[4], 1.0 - 150.0 / 400.0);
+        assert_eq!(fractions[5], 1.0);
+    }
+
+    #[test]
+    fn test_largest_overlap() {
+        let displays: Vec<Rect> = [
+            rect(0.0, 0.0, 1920.0, 1080.0),
+            rect(1920.0, 0.0, 2560.0, 1440.0),
+            rect(-1920.0, 0.0, 1920.0, 1080.0),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        let overlap = |left, top, width, height| {
+            get_largest_overlap(&rect(left, top, width, height).unwrap(), &displays)
+        };
+
+        assert_eq!(overlap(100.0, 100.0, 800.0, 600.0), Some(0));
+        // Mostly on the second display
+        assert_eq!(overlap(1800.0, 100.0, 800.0, 600.0), Some(1));
+        // Spanning three displays, with the most of it on the first
+        assert_eq!(overlap(-100.0, 0.0, 2200.0, 1080.0), Some(0));
+        // Split evenly, so the first display wins
+        assert_eq!(overlap(1820.0, 100.0, 200.0, 200.0), Some(0));
+        assert_eq!(overlap(-32000.0, -32000.0, 160.0, 28.0), None);
+    }
+
+    #[test]
+    fn test_many_windows() {
+        // A cascade of 200 windows, each shifted by 10 from the one above
+        let rects: Vec<_> = (0..200)
+            .map(|i| rect(i as f64 * 10.0, i as f64 * 10.0, 400.0, 300.0))
+            .collect();
+        let fractions = visible_fractions(&rects);
+
+        assert_eq!(fractions[0], 1.0);
+        let expected = 1.0 - (390.0 * 290.0) / (400.0 * 300.0);
+        for fraction in &fractions[1..] {
+            assert!((fraction - expected).abs() < 1e-9, "{fraction}");
+        }
+    }
+}
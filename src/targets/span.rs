@@ -0,0 +1,82 @@
+use super::SpanLayout;
+use crate::capturer::{Area, Point, Size};
+
+// Aspect ratios of the panels a span may be made of, most common first since
+// a span like 7680x1440 fits both three 16:9 and four 4:3 panels
+const PANEL_RATIOS: [(u32, u32); 5] = [(16, 9), (16, 10), (21, 9), (4, 3), (5, 4)];
+
+// Drivers only span three or more panels side by side. Two panels would also
+// match single 32:9 monitors.
+const PANEL_COUNTS: std::ops::RangeInclusive<u32> = 3..=5;
+
+// Bezel correction hides at most an eighth of a panel's width per gap
+const MAX_BEZEL_FRACTION: u32 = 8;
+
+/// Guesses the panels behind a display of `width` by `height` pixels from its
+/// resolution alone, for GPUs that present several monitors as one display.
+/// Returns None if the resolution doesn't look like a span.
+pub(crate) fn detect_span(width: u32, height: u32) -> Option<SpanLayout> {
+    if height == 0 {
+        return None;
+    }
+
+    PANEL_RATIOS.iter().find_map(|&(w, h)| {
+        if (height * w) % h != 0 {
+            return None;
+        }
+        let panel_width = height * w / h;
+
+        PANEL_COUNTS.clone().find_map(|count| {
+            let hidden = width.checked_sub(panel_width * count)?;
+            let gaps = count - 1;
+            if hidden % gaps != 0 || hidden / gaps > panel_width / MAX_BEZEL_FRACTION {
+                return None;
+            }
+            let bezel = hidden / gaps;
+
+            let segments = (0..count)
+                .map(|i| Area {
+                    origin: Point {
+                        x: (i * (panel_width + bezel)) as f64,
+                        y: 0.0,
+                    },
+                    size: Size {
+                        width: panel_width as f64,
+                        height: height as f64,
+                    },
+                })
+                .collect();
+            Some(SpanLayout { segments, bezel })
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn origins(span: &SpanLayout) -> Vec<f64> {
+        span.segments.iter().map(|area| area.origin.x).collect()
+    }
+
+    #[test]
+    fn test_detect_span() {
+        let span = detect_span(7680, 1440).unwrap();
+        assert_eq!(span.bezel, 0);
+        assert_eq!(origins(&span), [0.0, 2560.0, 5120.0]);
+        assert_eq!(span.segments[0].size.width, 2560.0);
+
+        // Bezel corrected triple 1080p, 60 pixels hidden per gap
+        let span = detect_span(5880, 1080).unwrap();
+        assert_eq!(span.bezel, 60);
+        assert_eq!(origins(&span), [0.0, 1980.0, 3960.0]);
+
+        let span = detect_span(5760, 1200).unwrap();
+        assert_eq!((span.segments.len(), span.bezel), (3, 0));
+
+        // Single monitors, including ultrawides
+        for (width, height) in [(1920, 1080), (3440, 1440), (5120, 1440), (3840, 2160)] {
+            assert!(detect_span(width, height).is_none(), "{width}x{height}");
+        }
+    }
+}
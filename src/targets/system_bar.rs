@@ -0,0 +1,88 @@
+#[cfg(any(target_os = "windows", test))]
+use super::occlusion::Rect;
+use super::ScreenEdge;
+use crate::capturer::{Area, Point, Size};
+
+// The edge of a monitor with the widest bar docked to it, and the bar's
+// thickness, from how far the work area is inset from each edge. Apps can
+// dock bars of their own next to the taskbar, the widest is taken for it.
+// None if the work area covers the whole monitor.
+#[cfg(any(target_os = "windows", test))]
+pub(crate) fn get_docked_edge(monitor: &Rect, work_area: &Rect) -> Option<(ScreenEdge, f64)> {
+    [
+        (ScreenEdge::Bottom, monitor.bottom - work_area.bottom),
+        (ScreenEdge::Top, work_area.top - monitor.top),
+        (ScreenEdge::Left, work_area.left - monitor.left),
+        (ScreenEdge::Right, monitor.right - work_area.right),
+    ]
+    .into_iter()
+    .filter(|(_, inset)| *inset > 0.0)
+    .max_by(|(_, a), (_, b)| a.total_cmp(b))
+}
+
+// The strip `thickness` wide along `edge` of a display `size` large, in its
+// coordinates with the origin at the top left
+pub(crate) fn get_edge_strip(size: &Size, edge: ScreenEdge, thickness: f64) -> Area {
+    let thickness = thickness.clamp(
+        0.0,
+        match edge {
+            ScreenEdge::Top | ScreenEdge::Bottom => size.height,
+            ScreenEdge::Left | ScreenEdge::Right => size.width,
+        },
+    );
+    let (x, y, width, height) = match edge {
+        ScreenEdge::Top => (0.0, 0.0, size.width, thickness),
+        ScreenEdge::Bottom => (0.0, size.height - thickness, size.width, thickness),
+        ScreenEdge::Left => (0.0, 0.0, thickness, size.height),
+        ScreenEdge::Right => (size.width - thickness, 0.0, thickness, size.height),
+    };
+    Area {
+        origin: Point { x, y },
+        size: Size { width, height },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(left: f64, top: f64, right: f64, bottom: f64) -> Rect {
+        Rect {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    #[test]
+    fn test_docked_edge() {
+        // A second monitor right of the first with the taskbar at its bottom
+        let monitor = rect(1920.0, 0.0, 3840.0, 1080.0);
+        let docked = get_docked_edge(&monitor, &rect(1920.0, 0.0, 3840.0, 1032.0));
+        assert_eq!(docked, Some((ScreenEdge::Bottom, 48.0)));
+        // On the left, next to a narrower bar an app docked to the top
+        let docked = get_docked_edge(&monitor, &rect(1982.0, 30.0, 3840.0, 1080.0));
+        assert_eq!(docked, Some((ScreenEdge::Left, 62.0)));
+        // Auto-hiding taskbars leave the work area alone
+        assert_eq!(get_docked_edge(&monitor, &monitor), None);
+    }
+
+    #[test]
+    fn test_edge_strip() {
+        let size = Size {
+            width: 1920.0,
+            height: 1080.0,
+        };
+        let strip = get_edge_strip(&size, ScreenEdge::Bottom, 48.0);
+        assert_eq!((strip.origin.x, strip.origin.y), (0.0, 1032.0));
+        assert_eq!((strip.size.width, strip.size.height), (1920.0, 48.0));
+        let strip = get_edge_strip(&size, ScreenEdge::Right, 62.0);
+        assert_eq!((strip.origin.x, strip.origin.y), (1858.0, 0.0));
+        assert_eq!((strip.size.width, strip.size.height), (62.0, 1080.0));
+
+        // Hidden bars are zero thick at their edge
+        let strip = get_edge_strip(&size, ScreenEdge::Bottom, 0.0);
+        assert_eq!((strip.origin.y, strip.size.height), (1080.0, 0.0));
+    }
+}
@@ -1,79 +1,440 @@
-use super::{Display, Target};
-use windows::Win32::UI::HiDpi::{GetDpiForMonitor, GetDpiForWindow, MDT_EFFECTIVE_DPI};
+use std::collections::HashMap;
+
+use super::filter::{is_listed, TargetFilter, WindowTraits};
+use super::occlusion::{get_largest_overlap, visible_fractions, Rect};
+use super::span::detect_span;
+use super::system_bar::{get_docked_edge, get_edge_strip};
+use super::{Display, DpiAwareness, ScreenEdge, SpanLayout, StackedWindow, SystemBar, Target};
+use crate::capturer::Size;
+use windows::core::{w, PCWSTR};
+use windows::Win32::UI::HiDpi::{
+    GetAwarenessFromDpiAwarenessContext, GetDpiForMonitor, GetDpiForWindow,
+    GetWindowDpiAwarenessContext, DPI_AWARENESS_PER_MONITOR_AWARE, DPI_AWARENESS_SYSTEM_AWARE,
+    DPI_AWARENESS_UNAWARE, MDT_EFFECTIVE_DPI,
+};
+use windows::Win32::UI::Shell::{
+    SHAppBarMessage, ABE_BOTTOM, ABE_LEFT, ABE_RIGHT, ABE_TOP, ABM_GETAUTOHIDEBAREX, APPBARDATA,
+};
 use windows::Win32::{
-    Foundation::{HWND, RECT},
-    Graphics::Gdi::HMONITOR,
+    Foundation::{BOOL, HWND, LPARAM, RECT},
+    Graphics::{
+        Dwm::{DwmGetWindowAttribute, DWMWA_CLOAKED, DWMWA_EXTENDED_FRAME_BOUNDS},
+        Gdi::{
+            GetMonitorInfoW, MonitorFromWindow, HMONITOR, MONITORINFO, MONITOR_DEFAULTTONEAREST,
+        },
+    },
+    UI::WindowsAndMessaging::{
+        EnumWindows, FindWindowExW, GetClassNameW, GetSystemMetrics, GetWindowLongW, GetWindowRect,
+        GetWindowThreadProcessId, IsIconic, IsWindow, IsWindowVisible, GWL_EXSTYLE, GWL_STYLE,
+        SM_CXVIRTUALSCREEN, SM_CYVIRTUALSCREEN, SM_XVIRTUALSCREEN, SM_YVIRTUALSCREEN, WS_CHILD,
+    },
 };
 use windows_capture::{monitor::Monitor, window::Window};
 
-pub fn get_all_targets() -> Vec<Target> {
+mod monitor_name;
+mod virtual_desktop;
+mod virtual_display;
+
+pub use virtual_desktop::is_on_current_desktop;
+
+pub fn get_all_targets(filter: &TargetFilter) -> Vec<Target> {
     let mut targets: Vec<Target> = Vec::new();
 
     // Add displays to targets
     let displays = Monitor::enumerate().expect("Failed to enumerate monitors");
-    for display in displays {
-        let id = display.as_raw_hmonitor() as u32;
-        let title = display.device_name().expect("Failed to get monitor name");
-
-        let target = Target::Display(super::Display {
-            id,
-            title,
-            raw_handle: HMONITOR(display.as_raw_hmonitor()),
-        });
-        targets.push(target);
-    }
+    targets.extend(
+        displays
+            .iter()
+            .map(|display| Target::Display(get_display(display))),
+    );
 
     // Add windows to targets
-    let windows = Window::enumerate().expect("Failed to enumerate windows");
-    for window in windows {
-        let id = window.as_raw_hwnd() as u32;
-        let title = window.title().unwrap().to_string();
-
-        let target = Target::Window(super::Window {
-            id,
-            title,
-            raw_handle: HWND(window.as_raw_hwnd()),
-        });
-        targets.push(target);
-    }
+    targets.extend(get_windows(filter).into_iter().map(Target::Window));
 
     targets
 }
 
-pub fn get_main_display() -> Display {
-    let display = Monitor::primary().expect("Failed to get primary monitor");
-    let id = display.as_raw_hmonitor() as u32;
+// Every top level window, in z-order with the topmost first
+fn get_top_level_windows() -> Vec<HWND> {
+    unsafe extern "system" fn collect(hwnd: HWND, windows: LPARAM) -> BOOL {
+        let windows = &mut *(windows.0 as *mut Vec<HWND>);
+        windows.push(hwnd);
+        true.into()
+    }
+
+    let mut windows: Vec<HWND> = Vec::new();
+    unsafe {
+        let _ = EnumWindows(Some(collect), LPARAM(&mut windows as *mut _ as isize));
+    }
+    windows
+}
+
+// The windows `filter` lists, topmost first
+fn get_windows(filter: &TargetFilter) -> Vec<super::Window> {
+    let desktop = get_virtual_desktop();
+
+    get_top_level_windows()
+        .into_iter()
+        .filter_map(|hwnd| {
+            let (traits, content) = get_window_traits(hwnd, &desktop)?;
+            if !is_listed(&traits, filter) {
+                return None;
+            }
+
+            // UWP frames are usually titled after the app, fall back to the
+            // hosted window for the ones that aren't
+            let mut title = get_window_title(hwnd);
+            if let (true, Some(content)) = (title.is_empty(), content) {
+                title = get_window_title(content);
+            }
+
+            Some(super::Window {
+                id: hwnd.0 as u32,
+                title,
+                raw_handle: hwnd,
+            })
+        })
+        .collect()
+}
+
+fn get_window_title(hwnd: HWND) -> String {
+    Window::from_raw_hwnd(hwnd.0).title().unwrap_or_default()
+}
+
+// The title a window has now, None once it's destroyed
+pub fn get_current_window_title(hwnd: HWND) -> Option<String> {
+    unsafe { IsWindow(hwnd) }
+        .as_bool()
+        .then(|| get_window_title(hwnd))
+}
+
+// The bounding rectangle of all monitors
+fn get_virtual_desktop() -> RECT {
+    unsafe {
+        let left = GetSystemMetrics(SM_XVIRTUALSCREEN);
+        let top = GetSystemMetrics(SM_YVIRTUALSCREEN);
+        RECT {
+            left,
+            top,
+            right: left + GetSystemMetrics(SM_CXVIRTUALSCREEN),
+            bottom: top + GetSystemMetrics(SM_CYVIRTUALSCREEN),
+        }
+    }
+}
+
+fn is_cloaked(hwnd: HWND) -> bool {
+    let mut cloaked = 0u32;
+    unsafe {
+        DwmGetWindowAttribute(
+            hwnd,
+            DWMWA_CLOAKED,
+            &mut cloaked as *mut _ as _,
+            std::mem::size_of::<u32>() as u32,
+        )
+        .is_ok()
+            && cloaked != 0
+    }
+}
+
+// For an ApplicationFrameHost frame, the UWP CoreWindow it hosts if any.
+// Frames are kept around for suspended apps and outlive closed ones for a
+// while, without a CoreWindow inside they capture as an empty title bar.
+fn get_app_frame_content(hwnd: HWND) -> Option<Option<HWND>> {
+    unsafe {
+        let mut class = [0u16; 64];
+        let len = GetClassNameW(hwnd, &mut class).max(0) as usize;
+        if String::from_utf16_lossy(&class[..len]) != "ApplicationFrameWindow" {
+            return None;
+        }
+
+        let content = FindWindowExW(
+            hwnd,
+            HWND::default(),
+            w!("Windows.UI.Core.CoreWindow"),
+            PCWSTR::null(),
+        );
+        Some(content.ok())
+    }
+}
+
+// What the target filter looks at, and the hosted UWP window for frames.
+// None for windows that are never targets.
+fn get_window_traits(hwnd: HWND, desktop: &RECT) -> Option<(WindowTraits, Option<HWND>)> {
+    unsafe {
+        let style = GetWindowLongW(hwnd, GWL_STYLE) as u32;
+        if style & WS_CHILD.0 != 0 {
+            return None;
+        }
+
+        let mut rect = RECT::default();
+        GetWindowRect(hwnd, &mut rect).ok()?;
+        let on_virtual_desktop = rect.left < desktop.right
+            && rect.right > desktop.left
+            && rect.top < desktop.bottom
+            && rect.bottom > desktop.top;
+
+        let mut process_id = 0;
+        GetWindowThreadProcessId(hwnd, Some(&mut process_id));
+
+        let app_frame = get_app_frame_content(hwnd);
+        let traits = WindowTraits {
+            visible: IsWindowVisible(hwnd).as_bool(),
+            cloaked: is_cloaked(hwnd),
+            ex_style: GetWindowLongW(hwnd, GWL_EXSTYLE) as u32,
+            app_frame: app_frame.map(|content| content.is_some()),
+            width: rect.right - rect.left,
+            height: rect.bottom - rect.top,
+            minimized: IsIconic(hwnd).as_bool(),
+            on_virtual_desktop,
+            current_process: process_id == std::process::id(),
+        };
+        Some((traits, app_frame.flatten()))
+    }
+}
+
+// Where a window is on screen in physical pixels. The extended frame bounds
+// leave out the invisible resize borders and, unlike GetWindowRect, aren't
+// virtualized for processes that aren't DPI aware.
+fn get_frame_bounds(hwnd: HWND) -> Option<RECT> {
+    let mut rect = RECT::default();
+    unsafe {
+        if DwmGetWindowAttribute(
+            hwnd,
+            DWMWA_EXTENDED_FRAME_BOUNDS,
+            &mut rect as *mut _ as _,
+            std::mem::size_of::<RECT>() as u32,
+        )
+        .is_err()
+        {
+            GetWindowRect(hwnd, &mut rect).ok()?;
+        }
+    }
+    Some(rect)
+}
+
+fn to_rect(rect: RECT) -> Rect {
+    Rect {
+        left: rect.left as f64,
+        top: rect.top as f64,
+        right: rect.right as f64,
+        bottom: rect.bottom as f64,
+    }
+}
+
+// Where a top level window is drawn, or None if it isn't shown at all
+fn get_shown_rect(hwnd: HWND) -> Option<Rect> {
+    unsafe {
+        if !IsWindowVisible(hwnd).as_bool() || IsIconic(hwnd).as_bool() {
+            return None;
+        }
+
+        // Cloaked windows are on other virtual desktops or suspended
+        if is_cloaked(hwnd) {
+            return None;
+        }
+
+        get_frame_bounds(hwnd).map(to_rect)
+    }
+}
+
+fn get_monitor_rect(monitor: HMONITOR) -> Option<Rect> {
+    let mut info = MONITORINFO {
+        cbSize: std::mem::size_of::<MONITORINFO>() as u32,
+        ..Default::default()
+    };
+    unsafe { GetMonitorInfoW(monitor, &mut info) }
+        .as_bool()
+        .then(|| to_rect(info.rcMonitor))
+}
+
+// Whether an auto-hiding app bar, which is the taskbar unless some app
+// registered its own, is docked to `edge` of the monitor at `monitor`
+fn has_auto_hide_bar(monitor: RECT, edge: ScreenEdge) -> bool {
+    let mut data = APPBARDATA {
+        cbSize: std::mem::size_of::<APPBARDATA>() as u32,
+        uEdge: match edge {
+            ScreenEdge::Top => ABE_TOP,
+            ScreenEdge::Bottom => ABE_BOTTOM,
+            ScreenEdge::Left => ABE_LEFT,
+            ScreenEdge::Right => ABE_RIGHT,
+        },
+        rc: monitor,
+        ..Default::default()
+    };
+    unsafe { SHAppBarMessage(ABM_GETAUTOHIDEBAREX, &mut data) != 0 }
+}
+
+pub fn get_taskbar(display: &Display) -> Option<SystemBar> {
+    let mut info = MONITORINFO {
+        cbSize: std::mem::size_of::<MONITORINFO>() as u32,
+        ..Default::default()
+    };
+    if !unsafe { GetMonitorInfoW(display.raw_handle, &mut info) }.as_bool() {
+        return None;
+    }
+
+    // Auto-hiding taskbars keep their place in the work area free
+    let monitor = to_rect(info.rcMonitor);
+    let auto_hide = [
+        ScreenEdge::Bottom,
+        ScreenEdge::Top,
+        ScreenEdge::Left,
+        ScreenEdge::Right,
+    ]
+    .into_iter()
+    .find(|edge| has_auto_hide_bar(info.rcMonitor, *edge));
+    let (edge, thickness) = match auto_hide {
+        Some(edge) => (edge, 0.0),
+        None => get_docked_edge(&monitor, &to_rect(info.rcWork))?,
+    };
+
+    // In points, like crop areas
+    let scale_factor = get_monitor_dpi(display.raw_handle) as f64 / BASE_DPI as f64;
+    let size = Size {
+        width: (monitor.right - monitor.left) / scale_factor,
+        height: (monitor.bottom - monitor.top) / scale_factor,
+    };
+    Some(SystemBar {
+        area: get_edge_strip(&size, edge, thickness / scale_factor),
+        edge,
+        auto_hide: auto_hide.is_some(),
+    })
+}
+
+pub fn get_windows_on_display(display_id: u32) -> Vec<super::Window> {
+    let monitors: Vec<(u32, Rect)> = Monitor::enumerate()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|monitor| {
+            let raw_handle = HMONITOR(monitor.as_raw_hmonitor());
+            Some((raw_handle.0 as u32, get_monitor_rect(raw_handle)?))
+        })
+        .collect();
+    let rects: Vec<Rect> = monitors.iter().map(|(_, rect)| *rect).collect();
+
+    get_windows(&TargetFilter::default())
+        .into_iter()
+        .filter(|window| {
+            let hwnd = window.raw_handle;
+            let overlap = match unsafe { IsIconic(hwnd) }.as_bool() {
+                true => None,
+                false => {
+                    get_frame_bounds(hwnd).and_then(|r| get_largest_overlap(&to_rect(r), &rects))
+                }
+            };
+            // Minimized windows are placed where they're restored to
+            let id = match overlap {
+                Some(i) => monitors[i].0,
+                None => unsafe { MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST) }.0 as u32,
+            };
+            id == display_id
+        })
+        .collect()
+}
+
+pub fn get_stacked_windows() -> Vec<StackedWindow> {
+    let windows = get_top_level_windows();
+    let mut capturable: HashMap<isize, super::Window> = get_windows(&TargetFilter::default())
+        .into_iter()
+        .map(|window| (window.raw_handle.0 as isize, window))
+        .collect();
+
+    let rects: Vec<Option<Rect>> = windows.iter().map(|hwnd| get_shown_rect(*hwnd)).collect();
+    let fractions = visible_fractions(&rects);
+
+    windows
+        .into_iter()
+        .zip(fractions)
+        .filter_map(|(hwnd, fraction)| {
+            let window = capturable.remove(&(hwnd.0 as isize))?;
+            Some((window, fraction))
+        })
+        .enumerate()
+        .map(|(z_index, (window, visible_fraction))| StackedWindow {
+            window,
+            z_index,
+            visible_fraction,
+            occluded: visible_fraction == 0.0,
+        })
+        .collect()
+}
+
+fn get_display(display: &Monitor) -> Display {
+    let raw_handle = HMONITOR(display.as_raw_hmonitor());
 
     Display {
-        id,
+        id: display.as_raw_hmonitor() as u32,
         title: display.device_name().expect("Failed to get monitor name"),
-        raw_handle: HMONITOR(display.as_raw_hmonitor()),
+        raw_handle,
+        is_virtual: virtual_display::is_virtual_monitor(raw_handle),
+        span: get_span(display),
     }
 }
 
-// Referred to: https://github.com/tauri-apps/tao/blob/ab792dbd6c5f0a708c818b20eaff1d9a7534c7c1/src/platform_impl/windows/dpi.rs#L50
-pub fn get_scale_factor(target: &Target) -> f64 {
-    const BASE_DPI: u32 = 96;
+pub fn get_main_display() -> Display {
+    get_display(&Monitor::primary().expect("Failed to get primary monitor"))
+}
+
+// Every display with the name of its monitor and its top left corner on
+// the desktop
+pub fn get_named_displays() -> Vec<(Display, String, [f64; 2])> {
+    Monitor::enumerate()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|monitor| {
+            let display = get_display(monitor);
+            let rect = get_monitor_rect(display.raw_handle)?;
+            let name = monitor_name::get_monitor_name(display.raw_handle)
+                .unwrap_or_else(|| display.title.clone());
+            Some((display, name, [rect.left, rect.top]))
+        })
+        .collect()
+}
+
+// Spanned displays are reported as one monitor, so the panels can only be
+// told apart by the resolution
+fn get_span(display: &Monitor) -> Option<SpanLayout> {
+    detect_span(display.width().ok()?, display.height().ok()?)
+}
+
+const BASE_DPI: u32 = 96;
+
+pub fn get_dpi_awareness(hwnd: HWND) -> Option<DpiAwareness> {
+    let awareness =
+        unsafe { GetAwarenessFromDpiAwarenessContext(GetWindowDpiAwarenessContext(hwnd)) };
+    match awareness {
+        DPI_AWARENESS_UNAWARE => Some(DpiAwareness::Unaware),
+        DPI_AWARENESS_SYSTEM_AWARE => Some(DpiAwareness::SystemAware),
+        DPI_AWARENESS_PER_MONITOR_AWARE => Some(DpiAwareness::PerMonitorAware),
+        _ => None,
+    }
+}
 
+fn get_monitor_dpi(monitor: HMONITOR) -> u32 {
     let mut dpi_x = 0;
     let mut dpi_y = 0;
+    unsafe {
+        if GetDpiForMonitor(monitor, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y).is_ok() {
+            dpi_x
+        } else {
+            BASE_DPI
+        }
+    }
+}
 
+// Referred to: https://github.com/tauri-apps/tao/blob/ab792dbd6c5f0a708c818b20eaff1d9a7534c7c1/src/platform_impl/windows/dpi.rs#L50
+pub fn get_scale_factor(target: &Target) -> f64 {
     let dpi = match target {
-        Target::Window(window) => unsafe { GetDpiForWindow(window.raw_handle) },
-        Target::Display(display) => unsafe {
-            if GetDpiForMonitor(
-                display.raw_handle,
-                MDT_EFFECTIVE_DPI,
-                &mut dpi_x,
-                &mut dpi_y,
-            )
-            .is_ok()
-            {
-                dpi_x.into()
-            } else {
-                BASE_DPI
-            }
+        // Windows stretches these to the DPI of their monitor, which is what
+        // shows on screen and ends up in the frames. The window's own DPI is
+        // 96 or the system's.
+        Target::Window(window) => match get_dpi_awareness(window.raw_handle) {
+            Some(DpiAwareness::Unaware | DpiAwareness::SystemAware) => get_monitor_dpi(unsafe {
+                MonitorFromWindow(window.raw_handle, MONITOR_DEFAULTTONEAREST)
+            }),
+            _ => unsafe { GetDpiForWindow(window.raw_handle) },
         },
+        Target::Display(display) => get_monitor_dpi(display.raw_handle),
     };
 
     let scale_factor = dpi as f64 / BASE_DPI as f64;
@@ -82,17 +443,15 @@ pub fn get_scale_factor(target: &Target) -> f64 {
 
 pub fn get_target_dimensions(target: &Target) -> (u64, u64) {
     match target {
-        Target::Window(window) => unsafe {
-            let hwnd = window.raw_handle;
-
-            // get width and height of the window
-            let mut rect = RECT::default();
-            let _ = windows::Win32::UI::WindowsAndMessaging::GetWindowRect(hwnd, &mut rect);
-            let width = rect.right - rect.left;
-            let height = rect.bottom - rect.top;
+        // What window capture covers, the stretched size for windows that
+        // aren't DPI aware
+        Target::Window(window) => {
+            let rect = get_frame_bounds(window.raw_handle).unwrap_or_default();
+            let width = (rect.right - rect.left).max(0);
+            let height = (rect.bottom - rect.top).max(0);
 
             (width as u64, height as u64)
-        },
+        }
         Target::Display(display) => {
             let monitor = Monitor::from_raw_hmonitor(display.raw_handle.0);
 
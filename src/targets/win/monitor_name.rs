@@ -0,0 +1,49 @@
+use windows::core::PCWSTR;
+use windows::Win32::{
+    Devices::Display::{
+        DisplayConfigGetDeviceInfo, DISPLAYCONFIG_DEVICE_INFO_GET_TARGET_NAME,
+        DISPLAYCONFIG_DEVICE_INFO_HEADER, DISPLAYCONFIG_TARGET_DEVICE_NAME,
+    },
+    Graphics::Gdi::{EnumDisplayDevicesW, DISPLAY_DEVICEW, HMONITOR},
+};
+
+use super::virtual_display::{get_device_name, get_display_paths};
+
+fn from_wide(chars: &[u16]) -> String {
+    let length = chars.iter().position(|&c| c == 0).unwrap_or(chars.len());
+    String::from_utf16_lossy(&chars[..length])
+}
+
+/// The name of the monitor showing `monitor` from its EDID, like
+/// "DELL U2720Q", or else the name of its driver, like "Generic PnP
+/// Monitor". None if the monitor can't be read.
+pub fn get_monitor_name(monitor: HMONITOR) -> Option<String> {
+    let device_name = get_device_name(monitor)?;
+    let edid_name = get_display_paths(&device_name).iter().find_map(|path| {
+        let mut target = DISPLAYCONFIG_TARGET_DEVICE_NAME {
+            header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
+                r#type: DISPLAYCONFIG_DEVICE_INFO_GET_TARGET_NAME,
+                size: std::mem::size_of::<DISPLAYCONFIG_TARGET_DEVICE_NAME>() as u32,
+                adapterId: path.targetInfo.adapterId,
+                id: path.targetInfo.id,
+            },
+            ..Default::default()
+        };
+        if unsafe { DisplayConfigGetDeviceInfo(&mut target.header) } != 0 {
+            return None;
+        }
+        Some(from_wide(&target.monitorFriendlyDeviceName)).filter(|name| !name.is_empty())
+    });
+
+    edid_name.or_else(|| {
+        // The first monitor of the GDI device
+        let mut device = DISPLAY_DEVICEW {
+            cb: std::mem::size_of::<DISPLAY_DEVICEW>() as u32,
+            ..Default::default()
+        };
+        unsafe { EnumDisplayDevicesW(PCWSTR(device_name.as_ptr()), 0, &mut device, 0) }
+            .as_bool()
+            .then(|| from_wide(&device.DeviceString))
+            .filter(|name| !name.is_empty())
+    })
+}
@@ -0,0 +1,22 @@
+use windows::Win32::{
+    Foundation::HWND,
+    System::Com::{
+        CoCreateInstance, CoInitializeEx, CoUninitialize, CLSCTX_ALL, COINIT_MULTITHREADED,
+    },
+    UI::Shell::{IVirtualDesktopManager, VirtualDesktopManager},
+};
+
+// Whether `hwnd` is on the virtual desktop being shown. None when the shell
+// can't tell, like for closed windows or without Explorer running.
+pub fn is_on_current_desktop(hwnd: HWND) -> Option<bool> {
+    // COM may already be initialized on this thread, in either apartment
+    let initialized = unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }.is_ok();
+    let on_current_desktop = unsafe {
+        CoCreateInstance::<_, IVirtualDesktopManager>(&VirtualDesktopManager, None, CLSCTX_ALL)
+            .and_then(|manager| manager.IsWindowOnCurrentVirtualDesktop(hwnd))
+    };
+    if initialized {
+        unsafe { CoUninitialize() };
+    }
+    on_current_desktop.ok().map(|on| on.as_bool())
+}
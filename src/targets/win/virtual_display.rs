@@ -0,0 +1,122 @@
+use windows::core::PCWSTR;
+use windows::Win32::{
+    Devices::Display::{
+        DisplayConfigGetDeviceInfo, GetDisplayConfigBufferSizes, QueryDisplayConfig,
+        DISPLAYCONFIG_DEVICE_INFO_GET_SOURCE_NAME, DISPLAYCONFIG_DEVICE_INFO_HEADER,
+        DISPLAYCONFIG_MODE_INFO, DISPLAYCONFIG_OUTPUT_TECHNOLOGY_INDIRECT_VIRTUAL,
+        DISPLAYCONFIG_PATH_INFO, DISPLAYCONFIG_SOURCE_DEVICE_NAME, QDC_ONLY_ACTIVE_PATHS,
+    },
+    Foundation::ERROR_SUCCESS,
+    Graphics::Gdi::{
+        EnumDisplayDevicesW, GetMonitorInfoW, DISPLAY_DEVICEW, HMONITOR, MONITORINFOEXW,
+    },
+};
+
+// Device id prefixes of the video adapters of Hyper-V, VMware, QEMU,
+// VirtualBox and Parallels. Microsoft's PCI vendor id is only used by
+// Hyper-V's emulated adapters.
+const HYPERVISOR_ADAPTERS: [&str; 6] = [
+    "PCI\\VEN_1414",
+    "VMBUS\\",
+    "PCI\\VEN_15AD",
+    "PCI\\VEN_1234",
+    "PCI\\VEN_80EE",
+    "PCI\\VEN_1AB8",
+];
+
+/// Whether `monitor` is driven by an indirect display driver, like most
+/// virtual display software, or by a hypervisor's emulated video adapter.
+///
+/// Dummy plugs are indistinguishable from real monitors and count as physical.
+pub fn is_virtual_monitor(monitor: HMONITOR) -> bool {
+    let Some(device_name) = get_device_name(monitor) else {
+        return false;
+    };
+
+    is_indirect_virtual(&device_name) || is_hypervisor_adapter(&device_name)
+}
+
+// The GDI device name of a monitor, like \\.\DISPLAY1
+pub(super) fn get_device_name(monitor: HMONITOR) -> Option<[u16; 32]> {
+    let mut info = MONITORINFOEXW::default();
+    info.monitorInfo.cbSize = std::mem::size_of::<MONITORINFOEXW>() as u32;
+    unsafe { GetMonitorInfoW(monitor, &mut info.monitorInfo) }
+        .as_bool()
+        .then_some(info.szDevice)
+}
+
+// Whether a display path of the GDI `device_name` ends at a virtual output
+fn is_indirect_virtual(device_name: &[u16; 32]) -> bool {
+    get_display_paths(device_name).iter().any(|path| {
+        path.targetInfo.outputTechnology == DISPLAYCONFIG_OUTPUT_TECHNOLOGY_INDIRECT_VIRTUAL
+    })
+}
+
+// The active display paths from the GDI `device_name` to its monitors,
+// several when the display is duplicated
+pub(super) fn get_display_paths(device_name: &[u16; 32]) -> Vec<DISPLAYCONFIG_PATH_INFO> {
+    unsafe {
+        let (mut path_count, mut mode_count) = (0, 0);
+        if GetDisplayConfigBufferSizes(QDC_ONLY_ACTIVE_PATHS, &mut path_count, &mut mode_count)
+            != ERROR_SUCCESS
+        {
+            return Vec::new();
+        }
+
+        let mut paths = vec![DISPLAYCONFIG_PATH_INFO::default(); path_count as usize];
+        let mut modes = vec![DISPLAYCONFIG_MODE_INFO::default(); mode_count as usize];
+        if QueryDisplayConfig(
+            QDC_ONLY_ACTIVE_PATHS,
+            &mut path_count,
+            paths.as_mut_ptr(),
+            &mut mode_count,
+            modes.as_mut_ptr(),
+            None,
+        ) != ERROR_SUCCESS
+        {
+            return Vec::new();
+        }
+        paths.truncate(path_count as usize);
+
+        paths.retain(|path| {
+            let mut source = DISPLAYCONFIG_SOURCE_DEVICE_NAME {
+                header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
+                    r#type: DISPLAYCONFIG_DEVICE_INFO_GET_SOURCE_NAME,
+                    size: std::mem::size_of::<DISPLAYCONFIG_SOURCE_DEVICE_NAME>() as u32,
+                    adapterId: path.sourceInfo.adapterId,
+                    id: path.sourceInfo.id,
+                },
+                ..Default::default()
+            };
+
+            DisplayConfigGetDeviceInfo(&mut source.header) == 0
+                && source.viewGdiDeviceName == *device_name
+        });
+        paths
+    }
+}
+
+// Whether the GDI `device_name` belongs to an emulated video adapter
+fn is_hypervisor_adapter(device_name: &[u16; 32]) -> bool {
+    let mut index = 0;
+    loop {
+        let mut device = DISPLAY_DEVICEW {
+            cb: std::mem::size_of::<DISPLAY_DEVICEW>() as u32,
+            ..Default::default()
+        };
+        if !unsafe { EnumDisplayDevicesW(PCWSTR::null(), index, &mut device, 0) }.as_bool() {
+            return false;
+        }
+        index += 1;
+
+        if device.DeviceName != *device_name {
+            continue;
+        }
+
+        let length = device.DeviceID.iter().position(|&c| c == 0);
+        let id = String::from_utf16_lossy(&device.DeviceID[..length.unwrap_or(128)]);
+        return HYPERVISOR_ADAPTERS
+            .iter()
+            .any(|prefix| id.to_uppercase().starts_with(prefix));
+    }
+}
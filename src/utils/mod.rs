@@ -1,3 +1,12 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
 #[cfg(target_os = "macos")]
 mod mac;
 
@@ -44,3 +53,67 @@ pub fn is_supported() -> bool {
     #[cfg(target_os = "linux")]
     return linux::is_supported();
 }
+
+/// How often [watch_permission] checks the permission state. macOS has no
+/// public notification for screen recording permission changes, so it's polled.
+pub const PERMISSION_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A running [watch_permission] subscription. Watching stops when this is dropped.
+pub struct PermissionWatcher {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PermissionWatcher {
+    /// Stop watching. Same as dropping the watcher.
+    pub fn stop(self) {}
+}
+
+impl Drop for PermissionWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Calls `callback` with the new state whenever screen capturing permission
+/// is granted or revoked, e.g. by the user in System Settings while the app
+/// is running. The callback runs on a background thread.
+///
+/// On macOS the state is polled every [PERMISSION_POLL_INTERVAL]. Windows and
+/// Linux have no permission to revoke, so the callback never fires there.
+pub fn watch_permission(mut callback: impl FnMut(bool) + Send + 'static) -> PermissionWatcher {
+    let stop = Arc::new(AtomicBool::new(false));
+
+    // Permission is constant on platforms without a permission system
+    if cfg!(not(target_os = "macos")) {
+        return PermissionWatcher { stop, thread: None };
+    }
+
+    let thread = thread::spawn({
+        let stop = stop.clone();
+        move || {
+            let mut granted = has_permission();
+            while !stop.load(Ordering::Relaxed) {
+                thread::park_timeout(PERMISSION_POLL_INTERVAL);
+                if stop.load(Ordering::Relaxed) {
+                    break;
+                }
+
+                let current = has_permission();
+                if current != granted {
+                    granted = current;
+                    callback(granted);
+                }
+            }
+        }
+    });
+
+    PermissionWatcher {
+        stop,
+        thread: Some(thread),
+    }
+}
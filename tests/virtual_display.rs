@@ -0,0 +1,63 @@
+//! End to end checks of virtual and headless displays on Windows. They need a
+//! machine set up for them, so they're ignored by default:
+//!
+//! - `virtual_display_is_captured` on a virtual machine, e.g. Hyper-V with
+//!   the Microsoft Basic Display Adapter, or with one of the virtual display
+//!   drivers listed in the README installed
+//! - `headless_session_has_no_target` in a session without displays, e.g.
+//!   over SSH or started from a service
+//!
+//! Run them with `cargo test --test virtual_display -- --ignored <name>`
+#![cfg(target_os = "windows")]
+
+use scap::{
+    capturer::{Capturer, CapturerBuildError, Options},
+    frame::{Frame, FrameType},
+    get_all_targets, Display, Target,
+};
+
+fn displays() -> Vec<Display> {
+    get_all_targets()
+        .into_iter()
+        .filter_map(|target| match target {
+            Target::Display(display) => Some(display),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+#[ignore = "needs a virtual display"]
+fn virtual_display_is_captured() {
+    let display = displays()
+        .into_iter()
+        .find(|display| display.is_virtual)
+        .expect("no display is flagged virtual");
+
+    let mut capturer = Capturer::build(Options {
+        target: Some(Target::Display(display)),
+        output_type: FrameType::BGRAFrame,
+        ..Default::default()
+    })
+    .expect("the virtual display can't be captured");
+    let [width, height] = capturer.get_output_frame_size();
+    assert!(width > 0 && height > 0);
+
+    capturer.start_capture();
+    let frame = capturer.get_next_frame().expect("no frame arrived");
+    capturer.stop_capture();
+
+    let Frame::BGRA(frame) = frame else {
+        panic!("not a BGRA frame");
+    };
+    assert_eq!((frame.width as u32, frame.height as u32), (width, height));
+}
+
+#[test]
+#[ignore = "needs a session without displays"]
+fn headless_session_has_no_target() {
+    assert!(displays().is_empty(), "the session has displays");
+
+    let result = Capturer::build(Options::default());
+    assert!(matches!(result, Err(CapturerBuildError::InvalidTarget(_))));
+}